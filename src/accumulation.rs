@@ -0,0 +1,134 @@
+use cgmath::Vector2;
+
+use crate::blit::{Blitter, RenderTarget};
+
+/// format used to store the accumulated frames
+pub const ACCUMULATION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Temporal anti-aliasing for the volume rendering.
+/// While the view does not change (identified by a key of type `K`),
+/// frames with jittered sub-pixel positions and ray offsets are averaged.
+pub struct TemporalAccumulation<K: PartialEq> {
+    pub enabled: bool,
+    pub max_samples: u32,
+    samples: u32,
+    key: Option<K>,
+    frame: Option<RenderTarget>,
+    accumulator: Blitter,
+}
+
+impl<K: PartialEq> TemporalAccumulation<K> {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let accumulator = Blitter::with_blend(
+            device,
+            ACCUMULATION_FORMAT,
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Constant,
+                    dst_factor: wgpu::BlendFactor::OneMinusConstant,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Constant,
+                    dst_factor: wgpu::BlendFactor::OneMinusConstant,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+        );
+        Self {
+            enabled: false,
+            max_samples: 32,
+            samples: 0,
+            key: None,
+            frame: None,
+            accumulator,
+        }
+    }
+
+    /// Returns the index of the sample that should be rendered next
+    /// or `None` if all samples for the current view are accumulated.
+    pub fn next_sample(&mut self, key: K) -> Option<u32> {
+        if self.key.as_ref() != Some(&key) {
+            self.samples = 0;
+            self.key = Some(key);
+        }
+        if self.samples >= self.max_samples {
+            return None;
+        }
+        self.samples += 1;
+        Some(self.samples - 1)
+    }
+
+    /// number of frames accumulated for the current view
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn reset(&mut self) {
+        self.key = None;
+        self.samples = 0;
+    }
+
+    /// target for the current (jittered) frame
+    pub fn frame_target(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> &RenderTarget {
+        let up_to_date = self
+            .frame
+            .as_ref()
+            .is_some_and(|f| f.size() == (width, height) && f.format() == format);
+        if !up_to_date {
+            self.frame = Some(RenderTarget::new(
+                device,
+                &self.accumulator,
+                format,
+                width,
+                height,
+            ));
+        }
+        self.frame.as_ref().unwrap()
+    }
+
+    /// blends the current frame into the render pass target (the history)
+    pub fn accumulate<'rpass>(
+        &'rpass self,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+        sample: u32,
+    ) {
+        if let Some(frame) = &self.frame {
+            let weight = 1. / (sample + 1) as f64;
+            render_pass.set_blend_constant(wgpu::Color {
+                r: weight,
+                g: weight,
+                b: weight,
+                a: weight,
+            });
+            self.accumulator.render(render_pass, &frame.bind_group);
+        }
+    }
+}
+
+/// sub-pixel offset (in pixels, within [-0.5,0.5]) and ray start offset (fraction of a step) for a sample
+pub fn jitter(sample: u32) -> (Vector2<f32>, f32) {
+    if sample == 0 {
+        return (Vector2::new(0., 0.), 0.);
+    }
+    let offset = Vector2::new(halton(sample, 2) - 0.5, halton(sample, 3) - 0.5);
+    let ray_offset = halton(sample, 5);
+    (offset, ray_offset)
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut f = 1.;
+    let mut r = 0.;
+    while index > 0 {
+        f /= base as f32;
+        r += f * (index % base) as f32;
+        index /= base;
+    }
+    r
+}
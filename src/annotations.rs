@@ -0,0 +1,612 @@
+use std::path::Path;
+
+use cgmath::{EuclideanSpace, MetricSpace, Point3, SquareMatrix, Vector3, Vector4, Zero};
+use egui::{pos2, vec2, Align2, Color32, FontId, Mesh, Painter, Pos2, Rect, Stroke};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::{Camera, Projection},
+    renderer::{Normalization, RenderSettings},
+    volume::Volume,
+};
+
+/// physical scale annotations (using the voxel spacing of the volume) drawn on top of the rendering
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotations {
+    /// bar with a round physical length in the lower right corner
+    pub scale_bar: bool,
+    /// labeled tick marks along the axes of the bounding box
+    pub ticks: bool,
+    /// timestep in the upper left corner
+    pub time_label: bool,
+    /// unit of the voxel spacing, only used for labels
+    pub unit: String,
+    pub color: Color32,
+    /// size of lines and labels (1 = sizes in points on a 96 dpi screen)
+    pub scale: f32,
+    /// text labels anchored to points of the volume
+    pub labels: Vec<Label>,
+}
+
+/// text anchored to a point of the volume, drawn facing the camera
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub text: String,
+    /// anchor in world space
+    pub position: [f32; 3],
+}
+
+pub fn read_labels(path: &Path) -> anyhow::Result<Vec<Label>> {
+    let file = std::fs::File::open(path)?;
+    Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+}
+
+pub fn write_labels(path: &Path, labels: &[Label]) -> anyhow::Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(labels)?)?;
+    Ok(())
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Self {
+            scale_bar: false,
+            ticks: false,
+            time_label: false,
+            unit: String::new(),
+            color: Color32::from_gray(230),
+            scale: 1.,
+            labels: Vec::new(),
+        }
+    }
+}
+
+impl Annotations {
+    pub fn any(&self) -> bool {
+        self.scale_bar || self.ticks || self.time_label || !self.labels.is_empty()
+    }
+
+    /// paints the annotations of a volume rendered with `camera` at `time` into `rect` (in points)
+    pub fn paint<P: Projection>(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        camera: &Camera<P>,
+        volume: &Volume,
+        time: f32,
+    ) {
+        let view_proj = camera.view_proj_matrix();
+        let project = |p: Point3<f32>| {
+            let clip = view_proj * p.to_homogeneous();
+            pos2(
+                rect.left() + (clip.x / clip.w + 1.) / 2. * rect.width(),
+                rect.top() + (1. - clip.y / clip.w) / 2. * rect.height(),
+            )
+        };
+        let painter = painter.with_clip_rect(rect);
+        if self.ticks {
+            self.paint_ticks(&painter, &project, volume);
+        }
+        if !self.labels.is_empty() {
+            self.paint_labels(&painter, &project);
+        }
+        if self.time_label {
+            let s = self.scale;
+            painter.text(
+                rect.left_top() + vec2(20., 20.) * s,
+                Align2::LEFT_TOP,
+                time_label(time, volume.timesteps),
+                FontId::proportional(13. * s),
+                self.color,
+            );
+        }
+        if self.scale_bar {
+            if let Some(inv_view_proj) = view_proj.invert() {
+                // width of the view at the depth of the volume center
+                let center = view_proj * volume.aabb.center().to_homogeneous();
+                let unproject = |x: f32| {
+                    let w = inv_view_proj * Vector4::new(x, 0., center.z / center.w, 1.);
+                    Point3::from_vec(w.truncate() / w.w)
+                };
+                let view_width = unproject(-1.).distance(unproject(1.)) * volume.physical_scale();
+                self.paint_scale_bar(&painter, rect, view_width);
+            }
+        }
+    }
+
+    fn paint_ticks(
+        &self,
+        painter: &Painter,
+        project: &impl Fn(Point3<f32>) -> Pos2,
+        volume: &Volume,
+    ) {
+        let s = self.scale;
+        let aabb = &volume.aabb;
+        let scale = volume.physical_scale();
+        let stroke = Stroke::new(s, self.color);
+        let font = FontId::proportional(11. * s);
+        let center = project(aabb.center());
+        let mut origin_labeled = false;
+        for axis in 0..3 {
+            let mut dir = Vector3::zero();
+            dir[axis] = 1.;
+            let length = aabb.max[axis] - aabb.min[axis];
+            let start = project(aabb.min);
+            let end = project(aabb.min + dir * length);
+            let screen = end - start;
+            let screen_length = screen.length();
+            // axis (almost) pointing at the camera
+            if screen_length < 20. * s {
+                continue;
+            }
+            painter.line_segment([start, end], stroke);
+
+            let physical_length = length * scale;
+            // at least 50 points between ticks, but always a tick after the origin
+            let step = nice_number(
+                (physical_length / 10.).max(physical_length * 50. * s / screen_length),
+                true,
+            )
+            .min(nice_number(physical_length, false));
+            // ticks point away from the volume
+            let mut normal = vec2(-screen.y, screen.x) / screen_length;
+            if normal.dot(start + screen / 2. - center) < 0. {
+                normal = -normal;
+            }
+            let anchor = Align2([align(normal.x), align(normal.y)]);
+            let num_ticks = (physical_length / step + 1e-3).floor() as usize;
+            for i in 0..=num_ticks {
+                let value = i as f32 * step;
+                let p = project(aabb.min + dir * (value / scale));
+                painter.line_segment([p, p + normal * 5. * s], stroke);
+                // all axes share the origin
+                if i == 0 && origin_labeled {
+                    continue;
+                }
+                origin_labeled = true;
+                let mut label = format_value(value, step);
+                if i == num_ticks && !self.unit.is_empty() {
+                    label = format!("{label} {}", self.unit);
+                }
+                painter.text(p + normal * 8. * s, anchor, label, font.clone(), self.color);
+            }
+        }
+    }
+
+    /// draws each label next to its anchor with a line pointing at it
+    fn paint_labels(&self, painter: &Painter, project: &impl Fn(Point3<f32>) -> Pos2) {
+        let s = self.scale;
+        let font = FontId::proportional(13. * s);
+        for label in &self.labels {
+            let anchor = project(Point3::from(label.position));
+            if !painter.clip_rect().contains(anchor) {
+                continue;
+            }
+            let galley = painter.layout_no_wrap(label.text.clone(), font.clone(), self.color);
+            // above the anchor, or below it at the top of the view
+            let mut pos = anchor + vec2(12., -12.) * s;
+            let mut text_rect = Align2::LEFT_BOTTOM.anchor_size(pos, galley.size());
+            if text_rect.top() < painter.clip_rect().top() {
+                pos = anchor + vec2(12., 12.) * s;
+                text_rect = Align2::LEFT_TOP.anchor_size(pos, galley.size());
+            }
+            painter.line_segment([anchor, pos], Stroke::new(s, self.color));
+            painter.circle(anchor, 3. * s, self.color, Stroke::new(1., Color32::BLACK));
+            painter.rect_filled(
+                text_rect.expand(3. * s),
+                3. * s,
+                Color32::from_black_alpha(160),
+            );
+            painter.galley(text_rect.min, galley, self.color);
+        }
+    }
+
+    /// `view_width` is the physical width of the view
+    fn paint_scale_bar(&self, painter: &Painter, rect: Rect, view_width: f32) {
+        if !view_width.is_finite() || view_width <= 0. {
+            return;
+        }
+        let s = self.scale;
+        let length = nice_number(view_width / 5., false);
+        let width = length / view_width * rect.width();
+        let right = rect.right_bottom() + vec2(-20., -20.) * s;
+        let left = right - vec2(width, 0.);
+        let stroke = Stroke::new(2. * s, self.color);
+        painter.line_segment([left, right], stroke);
+        for p in [left, right] {
+            painter.line_segment([p - vec2(0., 4.) * s, p + vec2(0., 4.) * s], stroke);
+        }
+        let label = format!("{} {}", format_value(length, length), self.unit);
+        painter.text(
+            pos2((left.x + right.x) / 2., left.y - 6. * s),
+            Align2::CENTER_BOTTOM,
+            label.trim_end(),
+            FontId::proportional(13. * s),
+            self.color,
+        );
+    }
+}
+
+/// side of the frame the colorbar is placed at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorbarPosition {
+    #[default]
+    Right,
+    Left,
+    Top,
+    Bottom,
+}
+
+impl ColorbarPosition {
+    pub const ALL: [Self; 4] = [Self::Right, Self::Left, Self::Top, Self::Bottom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Right => "Right",
+            Self::Left => "Left",
+            Self::Top => "Top",
+            Self::Bottom => "Bottom",
+        }
+    }
+}
+
+impl std::str::FromStr for ColorbarPosition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "unknown colorbar position '{s}' (expected right, left, top or bottom)"
+                )
+            })
+    }
+}
+
+/// format of the colorbar tick labels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelFormat {
+    /// as many decimals as needed to tell the ticks apart
+    #[default]
+    Auto,
+    /// fixed number of decimals
+    Fixed(usize),
+    /// scientific notation with a fixed number of decimals
+    Scientific(usize),
+}
+
+impl LabelFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LabelFormat::Auto => "Auto",
+            LabelFormat::Fixed(_) => "Fixed",
+            LabelFormat::Scientific(_) => "Scientific",
+        }
+    }
+
+    /// label of `value`, `step` is the distance between the ticks
+    pub fn format(&self, value: f32, step: f32) -> String {
+        match *self {
+            LabelFormat::Auto => format_value(value, step),
+            LabelFormat::Fixed(decimals) => format!("{value:.decimals$}"),
+            LabelFormat::Scientific(decimals) => format!("{value:.decimals$e}"),
+        }
+    }
+}
+
+/// legend of the colormap with value ticks, painted into the frame (see [RenderSettings])
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colorbar {
+    pub position: ColorbarPosition,
+    /// length as fraction of the side of the frame it is placed at
+    pub length: f32,
+    /// thickness in points (scaled with the annotations)
+    pub thickness: f32,
+    /// number of labeled ticks, vmin and vmax are always labeled
+    pub ticks: usize,
+    pub format: LabelFormat,
+}
+
+impl Default for Colorbar {
+    fn default() -> Self {
+        Self {
+            position: ColorbarPosition::Right,
+            length: 0.5,
+            thickness: 12.,
+            ticks: 5,
+            format: LabelFormat::Auto,
+        }
+    }
+}
+
+impl Annotations {
+    /// paints the colorbar with the `colors` of the colormap, whose ends are at
+    /// `range` (vmin, vmax), into `rect` (in points)
+    pub fn paint_colorbar(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        colorbar: &Colorbar,
+        colors: &[Vector4<u8>],
+        (vmin, vmax): (f32, f32),
+        normalization: Normalization,
+    ) {
+        let s = self.scale;
+        let margin = 20. * s;
+        let thickness = colorbar.thickness * s;
+        let vertical = matches!(
+            colorbar.position,
+            ColorbarPosition::Left | ColorbarPosition::Right
+        );
+        let side = if vertical {
+            rect.height()
+        } else {
+            rect.width()
+        };
+        let length = colorbar.length.clamp(0.05, 1.) * (side - 2. * margin).max(0.);
+        let size = if vertical {
+            vec2(thickness, length)
+        } else {
+            vec2(length, thickness)
+        };
+        // the labels are on the side facing the center of the frame
+        let (bar, normal) = match colorbar.position {
+            ColorbarPosition::Right => (
+                Rect::from_center_size(
+                    pos2(rect.right() - margin - thickness / 2., rect.center().y),
+                    size,
+                ),
+                vec2(-1., 0.),
+            ),
+            ColorbarPosition::Left => (
+                Rect::from_center_size(
+                    pos2(rect.left() + margin + thickness / 2., rect.center().y),
+                    size,
+                ),
+                vec2(1., 0.),
+            ),
+            ColorbarPosition::Top => (
+                Rect::from_center_size(
+                    pos2(rect.center().x, rect.top() + margin + thickness / 2.),
+                    size,
+                ),
+                vec2(0., 1.),
+            ),
+            ColorbarPosition::Bottom => (
+                Rect::from_center_size(
+                    pos2(rect.center().x, rect.bottom() - margin - thickness / 2.),
+                    size,
+                ),
+                vec2(0., -1.),
+            ),
+        };
+        // point on the inner edge of the bar at the fraction `f` of the colormap
+        let edge = |f: f32| {
+            let inner = bar.center() + normal * thickness / 2.;
+            if vertical {
+                pos2(inner.x, bar.bottom() - f * length)
+            } else {
+                pos2(bar.left() + f * length, inner.y)
+            }
+        };
+
+        let mut mesh = Mesh::default();
+        for (i, c) in colors.iter().enumerate() {
+            let f = i as f32 / (colors.len() - 1).max(1) as f32;
+            let color = Color32::from_rgb(c.x, c.y, c.z);
+            let p = edge(f);
+            mesh.colored_vertex(p, color);
+            mesh.colored_vertex(p - normal * thickness, color);
+            if i > 0 {
+                let v = 2 * i as u32;
+                mesh.add_triangle(v - 2, v - 1, v);
+                mesh.add_triangle(v - 1, v, v + 1);
+            }
+        }
+        painter.add(mesh);
+        let stroke = Stroke::new(s, self.color);
+        painter.rect_stroke(bar, 0., stroke);
+
+        let num_ticks = colorbar.ticks.max(2);
+        let ticks: Vec<(f32, f32)> = (0..num_ticks)
+            .map(|i| {
+                let f = i as f32 / (num_ticks - 1) as f32;
+                (f, normalization.denormalize(f, vmin, vmax))
+            })
+            .collect();
+        let step = ticks
+            .windows(2)
+            .map(|w| (w[1].1 - w[0].1).abs())
+            .fold(f32::INFINITY, f32::min);
+        // vmin == vmax would ask for infinitely many decimals
+        let step = if step > 0. && step.is_finite() {
+            step
+        } else {
+            1.
+        };
+        let anchor = Align2([align(normal.x), align(normal.y)]);
+        let font = FontId::proportional(11. * s);
+        for (f, value) in ticks {
+            let p = edge(f);
+            painter.line_segment([p, p + normal * 4. * s], stroke);
+            painter.text(
+                p + normal * 6. * s,
+                anchor,
+                colorbar.format.format(value, step),
+                font.clone(),
+                self.color,
+            );
+        }
+    }
+}
+
+/// rounds to the next larger (`up`) or smaller number of the form 1, 2 or 5 times a power of ten
+/// named value of interest (e.g. a melting point or an alert threshold),
+/// marked on the colormap legend and optionally drawn as a surface in the volume
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct ReferenceValue {
+    pub name: String,
+    pub value: f32,
+    /// sRGB color and opacity of the marker and surface
+    #[serde(default = "ReferenceValue::default_color")]
+    pub color: [u8; 4],
+    /// draws the surface where the volume crosses the value
+    #[serde(default)]
+    pub surface: bool,
+}
+
+impl ReferenceValue {
+    pub fn new(name: impl Into<String>, value: f32) -> Self {
+        Self {
+            name: name.into(),
+            value,
+            color: Self::default_color(),
+            surface: false,
+        }
+    }
+
+    fn default_color() -> [u8; 4] {
+        [255, 255, 255, 128]
+    }
+
+    pub fn color32(&self) -> Color32 {
+        let [r, g, b, _] = self.color;
+        Color32::from_rgb(r, g, b)
+    }
+
+    /// value and color of the surface, see [RenderSettings](crate::renderer::RenderSettings)
+    pub fn contour(&self) -> (f32, Vector4<f32>) {
+        (
+            self.value,
+            Vector4::from(self.color).map(|c| c as f32 / 255.),
+        )
+    }
+
+    /// reads a list of reference values from a json file
+    pub fn read_json<R: std::io::Read>(reader: R) -> anyhow::Result<Vec<Self>> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// writes a list of reference values to a json file
+    pub fn write_json<W: std::io::Write>(values: &[Self], writer: W) -> anyhow::Result<()> {
+        Ok(serde_json::to_writer_pretty(writer, values)?)
+    }
+}
+
+fn nice_number(x: f32, up: bool) -> f32 {
+    let exp = 10f32.powf(x.log10().floor());
+    let f = x / exp;
+    let mantissa = if up {
+        [1., 2., 5., 10.]
+            .into_iter()
+            .find(|m| *m >= f - 1e-4)
+            .unwrap_or(10.)
+    } else {
+        [10., 5., 2., 1.]
+            .into_iter()
+            .find(|m| *m <= f + 1e-4)
+            .unwrap_or(1.)
+    };
+    mantissa * exp
+}
+
+/// timestep at the normalized `time`, with decimals between timesteps
+fn time_label(time: f32, timesteps: u32) -> String {
+    let step = time * timesteps.saturating_sub(1) as f32;
+    if (step - step.round()).abs() < 1e-3 {
+        format!("t = {}", step.round())
+    } else {
+        format!("t = {step:.2}")
+    }
+}
+
+/// formats a value with as many decimals as needed to distinguish multiples of `step`
+fn format_value(value: f32, step: f32) -> String {
+    let decimals = (-step.log10().floor()).max(0.) as usize;
+    format!("{value:.decimals$}")
+}
+
+/// anchor of a label placed next to a point in direction `d` (-1 left/up, 1 right/down)
+fn align(d: f32) -> egui::Align {
+    if d > 0.5 {
+        egui::Align::Min
+    } else if d < -0.5 {
+        egui::Align::Max
+    } else {
+        egui::Align::Center
+    }
+}
+
+/// renders the annotations (with their pixels per point) and the colorbar of the settings
+/// (with the `colors` of the colormap) on top of an offline render of the volume in `target`
+pub(crate) fn render<P: Projection>(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    target: &wgpu::Texture,
+    (annotations, pixels_per_point, colors): (&Annotations, f32, &[Vector4<u8>]),
+    camera: &Camera<P>,
+    volume: &Volume,
+    settings: &RenderSettings,
+) {
+    let ctx = egui::Context::default();
+    ctx.set_zoom_factor(pixels_per_point);
+    let screen_rect = Rect::from_min_size(
+        Pos2::ZERO,
+        vec2(target.width() as f32, target.height() as f32) / pixels_per_point,
+    );
+    let input = egui::RawInput {
+        screen_rect: Some(screen_rect),
+        ..Default::default()
+    };
+    let output = ctx.run(input, |ctx| {
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        if annotations.any() {
+            annotations.paint(&painter, screen_rect, camera, volume, settings.time);
+        }
+        if let Some(colorbar) = &settings.colorbar {
+            annotations.paint_colorbar(
+                &painter,
+                screen_rect,
+                colorbar,
+                colors,
+                settings.value_range(volume),
+                settings.normalization,
+            );
+        }
+    });
+    let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+    let screen_descriptor = egui_wgpu::ScreenDescriptor {
+        size_in_pixels: [target.width(), target.height()],
+        pixels_per_point: output.pixels_per_point,
+    };
+
+    let mut renderer = egui_wgpu::Renderer::new(device, target.format(), None, 1);
+    for (id, delta) in &output.textures_delta.set {
+        renderer.update_texture(device, queue, *id, delta);
+    }
+    let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("annotation encoder"),
+    });
+    renderer.update_buffers(device, queue, &mut encoder, &primitives, &screen_descriptor);
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("annotation render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        renderer.render(&mut render_pass, &primitives, &screen_descriptor);
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+}
@@ -0,0 +1,277 @@
+use cgmath::{Matrix4, SquareMatrix, Vector4};
+use image::RgbaImage;
+use wgpu::util::DeviceExt;
+
+use crate::camera::{Camera, OrthographicProjection};
+
+/// what is drawn behind the volume
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Background {
+    /// the flat background color
+    #[default]
+    Solid,
+    /// vertical gradient from the background color (bottom) to `top`
+    Gradient { top: wgpu::Color },
+    /// checkerboard of the background color and `color`, useful to inspect transparency
+    Checkerboard { color: wgpu::Color, size: f32 },
+    /// equirectangular environment image looked up with the viewing direction
+    Environment,
+}
+
+impl Background {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Background::Solid => "Solid",
+            Background::Gradient { .. } => "Gradient",
+            Background::Checkerboard { .. } => "Checkerboard",
+            Background::Environment => "Environment",
+        }
+    }
+}
+
+/// draws the background (other than solid colors, which are cleared)
+pub struct BackgroundRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    environment: wgpu::TextureView,
+    environment_format: wgpu::TextureFormat,
+    has_environment: bool,
+}
+
+impl BackgroundRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("background bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("background pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/background.wgsl"));
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("background pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("environment sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // the image is displayed as is on non srgb targets
+        let environment_format = if color_format.is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let environment =
+            Self::create_environment(device, queue, environment_format, &RgbaImage::new(1, 1));
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            environment,
+            environment_format,
+            has_environment: false,
+        }
+    }
+
+    fn create_environment(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        image: &RgbaImage,
+    ) -> wgpu::TextureView {
+        device
+            .create_texture_with_data(
+                queue,
+                &wgpu::TextureDescriptor {
+                    label: Some("environment texture"),
+                    size: wgpu::Extent3d {
+                        width: image.width(),
+                        height: image.height(),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                },
+                wgpu::util::TextureDataOrder::LayerMajor,
+                image.as_raw(),
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// sets the equirectangular image used by `Background::Environment`
+    pub fn set_environment(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &RgbaImage,
+    ) -> anyhow::Result<()> {
+        let max_size = device.limits().max_texture_dimension_2d;
+        if image.width() > max_size || image.height() > max_size {
+            anyhow::bail!(
+                "environment image {}x{} exceeds the maximum texture size of {max_size}",
+                image.width(),
+                image.height()
+            );
+        }
+        self.environment = Self::create_environment(device, queue, self.environment_format, image);
+        self.has_environment = true;
+        Ok(())
+    }
+
+    pub fn has_environment(&self) -> bool {
+        self.has_environment
+    }
+
+    /// returns None if nothing has to be drawn (solid background)
+    pub fn prepare(
+        &self,
+        device: &wgpu::Device,
+        background: &Background,
+        color: wgpu::Color,
+        camera: &Camera<OrthographicProjection>,
+    ) -> Option<wgpu::BindGroup> {
+        let to_vec = |c: wgpu::Color| Vector4::new(c.r, c.g, c.b, c.a).cast::<f32>().unwrap();
+        let mut uniforms = BackgroundUniforms {
+            view_inv: camera.view_matrix().invert().unwrap(),
+            color_a: to_vec(color),
+            color_b: to_vec(color),
+            mode: 0,
+            checker_size: 1.,
+            aspect: camera.projection.viewport.x / camera.projection.viewport.y,
+            _pad: 0,
+        };
+        match *background {
+            Background::Solid => return None,
+            Background::Gradient { top } => {
+                uniforms.mode = 1;
+                uniforms.color_b = to_vec(top);
+            }
+            Background::Checkerboard { color, size } => {
+                uniforms.mode = 2;
+                uniforms.color_b = to_vec(color);
+                uniforms.checker_size = size.max(1.);
+            }
+            Background::Environment => uniforms.mode = 3,
+        }
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("background uniform buffer"),
+            contents: bytemuck::bytes_of(&uniforms),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("background bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&self.environment),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        }))
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+        bind_group: &'rpass wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BackgroundUniforms {
+    view_inv: Matrix4<f32>,
+    color_a: Vector4<f32>,
+    color_b: Vector4<f32>,
+    mode: u32,
+    checker_size: f32,
+    aspect: f32,
+    _pad: u32,
+}
@@ -0,0 +1,110 @@
+use std::path::{Path, PathBuf};
+
+use cgmath::{Point3, Quaternion, Vector2};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{Camera, OrthographicProjection};
+
+/// maximum number of bookmarks that can be recalled with Shift+1 to Shift+9
+pub const NUM_BOOKMARK_KEYS: usize = 9;
+
+/// named camera pose, saved together with the animation time and iso value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bookmark {
+    pub name: String,
+    pub position: [f32; 3],
+    /// camera rotation as quaternion (w, x, y, z)
+    pub rotation: [f32; 4],
+    /// point the camera rotates around
+    pub center: [f32; 3],
+    /// width and height of the view (orthographic projection)
+    pub viewport: [f32; 2],
+    /// normalized animation time
+    pub time: f32,
+    /// value of the first surface, if one is drawn
+    #[serde(default)]
+    pub iso_value: Option<f32>,
+}
+
+impl Bookmark {
+    pub fn new(
+        name: impl Into<String>,
+        camera: &Camera<OrthographicProjection>,
+        center: Point3<f32>,
+        time: f32,
+        iso_value: Option<f32>,
+    ) -> Self {
+        let r = camera.rotation;
+        Self {
+            name: name.into(),
+            position: camera.position.into(),
+            rotation: [r.s, r.v.x, r.v.y, r.v.z],
+            center: center.into(),
+            viewport: camera.projection.viewport.into(),
+            time,
+            iso_value,
+        }
+    }
+
+    /// moves `camera` to the saved pose, the view is adapted to the aspect ratio of `size`
+    pub fn apply(&self, camera: &mut Camera<OrthographicProjection>, size: (u32, u32)) {
+        let [w, x, y, z] = self.rotation;
+        camera.position = self.position.into();
+        camera.rotation = Quaternion::new(w, x, y, z);
+        camera.projection.viewport = Vector2::from(self.viewport);
+        camera.projection.resize(size.0, size.1);
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.center.into()
+    }
+}
+
+/// bookmarks of a session, stored as json list in `file` if set
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    pub list: Vec<Bookmark>,
+    /// file the bookmarks are saved to whenever they change
+    pub file: Option<PathBuf>,
+}
+
+impl Bookmarks {
+    /// bookmarks of `file`, empty if it does not exist yet
+    pub fn open(file: PathBuf) -> anyhow::Result<Self> {
+        let list = if file.exists() {
+            read_bookmarks(&file)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            list,
+            file: Some(file),
+        })
+    }
+
+    /// writes the bookmarks to the session's file
+    pub fn save(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        if let Err(err) = write_bookmarks(file, &self.list) {
+            log::error!("failed to save bookmarks to {}: {err}", file.display());
+        }
+    }
+}
+
+/// file the bookmarks of a session (spec or volume file) are stored in
+pub fn bookmarks_file(session: &Path) -> PathBuf {
+    session.with_extension("bookmarks.json")
+}
+
+pub fn read_bookmarks(path: &Path) -> anyhow::Result<Vec<Bookmark>> {
+    let file = std::fs::File::open(path)?;
+    Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+}
+
+pub fn write_bookmarks(path: &Path, bookmarks: &[Bookmark]) -> anyhow::Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(bookmarks)?)?;
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use cgmath::*;
+use serde::{Deserialize, Serialize};
 
 use crate::volume::Aabb;
 
@@ -21,14 +22,26 @@ impl<P: Projection> Camera<P> {
     }
 
     pub fn new_aabb_iso(aabb: Aabb<f32>, projection: P) -> Self {
-        let r = aabb.radius();
-        let corner = vec3(1., -1., 1.);
-        let view_dir = Quaternion::look_at(-corner, Vector3::unit_y());
-        Camera::new(
-            aabb.center() + corner.normalize() * r * 2.8,
-            view_dir,
-            projection,
-        )
+        Self::with_view(aabb, &CameraView::default(), projection)
+    }
+
+    /// camera looking at the target of `view` (the center of the aabb by default) from its
+    /// position or direction. The zoom is not applied, it belongs to the projection.
+    pub fn with_view(aabb: Aabb<f32>, view: &CameraView, projection: P) -> Self {
+        let target = view.target(&aabb);
+        let position = match view.position {
+            Some(position) => Point3::from(position),
+            None => {
+                let distance = match view.projection {
+                    ProjectionKind::Orthographic | ProjectionKind::Equirectangular => 2.8,
+                    // the bounding sphere touches the edges of the image
+                    ProjectionKind::Perspective => 1. / (Rad::from(Deg(view.fov)) / 2.).sin(),
+                };
+                target + view.direction() * aabb.radius() * distance
+            }
+        };
+        let rotation = Quaternion::look_at((target - position).normalize(), Vector3::from(view.up));
+        Camera::new(position, rotation, projection)
     }
 
     pub fn view_matrix(&self) -> Matrix4<f32> {
@@ -38,6 +51,152 @@ impl<P: Projection> Camera<P> {
     pub fn proj_matrix(&self) -> Matrix4<f32> {
         self.projection.projection_matrix()
     }
+
+    /// rotates the camera by `angle` around `axis` through `center`,
+    /// so that it keeps looking at the same point
+    pub fn orbit(&mut self, center: Point3<f32>, axis: Vector3<f32>, angle: Rad<f32>) {
+        let rotation = Quaternion::from_axis_angle(axis.normalize(), angle);
+        self.position = center + rotation.rotate_vector(self.position - center);
+        self.rotation = (self.rotation * rotation.invert()).normalize();
+    }
+
+    /// view projection matrix matching the image of the volume renderer.
+    /// Use it to rasterize geometry that is composited with the volume.
+    pub fn view_proj_matrix(&self) -> Matrix4<f32> {
+        VIEWPORT_Y_FLIP * self.proj_matrix() * self.view_matrix()
+    }
+}
+
+/// projection of a [CameraView]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectionKind {
+    #[default]
+    Orthographic,
+    Perspective,
+    /// 360° panorama around the camera position, looking along the view direction
+    /// in the center of the image (offline only)
+    Equirectangular,
+}
+
+impl std::str::FromStr for ProjectionKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "orthographic" | "ortho" => Ok(Self::Orthographic),
+            "perspective" => Ok(Self::Perspective),
+            "equirectangular" | "panorama" => Ok(Self::Equirectangular),
+            _ => Err(anyhow::anyhow!(
+                "unknown projection '{s}' (orthographic, perspective or equirectangular)"
+            )),
+        }
+    }
+}
+
+/// where a volume is looked at from, independent of the volume's size.
+/// The camera position is taken from `position`, `azimuth`/`elevation` or `direction`
+/// (in this order), the viewer only supports the orthographic projection
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraView {
+    /// direction from the target to the camera
+    pub direction: [f32; 3],
+    pub up: [f32; 3],
+    /// magnification, 1 fits the volume into the image
+    pub zoom: f32,
+    /// position of the camera in world space
+    pub position: Option<[f32; 3]>,
+    /// point in world space the camera looks at, the center of the volume by default
+    pub target: Option<[f32; 3]>,
+    /// angle in degrees around the up vector, 0 looks from +z (or +x if up is along z)
+    pub azimuth: Option<f32>,
+    /// angle in degrees above the plane perpendicular to the up vector
+    pub elevation: Option<f32>,
+    pub projection: ProjectionKind,
+    /// vertical field of view of the perspective projection in degrees
+    pub fov: f32,
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        Self {
+            direction: [1., -1., 1.],
+            up: [0., 1., 0.],
+            zoom: 1.,
+            position: None,
+            target: None,
+            azimuth: None,
+            elevation: None,
+            projection: ProjectionKind::Orthographic,
+            fov: 45.,
+        }
+    }
+}
+
+impl CameraView {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let up = Vector3::from(self.up);
+        anyhow::ensure!(up.magnitude2() > 0., "camera up must not be zero");
+        anyhow::ensure!(
+            self.elevation.unwrap_or(0.).abs() < 90.,
+            "camera elevation must be between -90 and 90 degrees"
+        );
+        let direction = match (self.position, self.target) {
+            (Some(position), Some(target)) => Some(Point3::from(position) - Point3::from(target)),
+            // the target defaults to the center of the volume, which is not known here
+            (Some(_), None) => None,
+            _ if self.azimuth.is_some() || self.elevation.is_some() => Some(self.direction()),
+            _ => Some(Vector3::from(self.direction)),
+        };
+        if let Some(direction) = direction {
+            anyhow::ensure!(
+                direction.magnitude2() > 0.,
+                "camera direction must not be zero"
+            );
+            anyhow::ensure!(
+                direction.normalize().cross(up.normalize()).magnitude2() > 1e-6,
+                "camera direction and up must not be parallel"
+            );
+        }
+        anyhow::ensure!(self.zoom > 0., "camera zoom must be positive");
+        anyhow::ensure!(
+            self.fov > 0. && self.fov < 180.,
+            "camera field of view must be between 0 and 180 degrees"
+        );
+        Ok(())
+    }
+
+    /// point the camera looks at
+    pub fn target(&self, aabb: &Aabb<f32>) -> Point3<f32> {
+        self.target.map(Point3::from).unwrap_or(aabb.center())
+    }
+
+    /// normalized direction from the target to the camera given by the azimuth and elevation,
+    /// or `direction`. The position is not taken into account
+    pub fn direction(&self) -> Vector3<f32> {
+        if self.azimuth.is_none() && self.elevation.is_none() {
+            return Vector3::from(self.direction).normalize();
+        }
+        let up = Vector3::from(self.up).normalize();
+        // the z axis (or x axis) projected onto the plane perpendicular to up
+        let front = [Vector3::unit_z(), Vector3::unit_x()]
+            .into_iter()
+            .map(|axis| axis - up * axis.dot(up))
+            .find(|v| v.magnitude2() > 1e-6)
+            .unwrap()
+            .normalize();
+        let side = up.cross(front);
+        let azimuth = Rad::from(Deg(self.azimuth.unwrap_or(0.)));
+        let elevation = Rad::from(Deg(self.elevation.unwrap_or(0.)));
+        (front * azimuth.cos() + side * azimuth.sin()) * elevation.cos() + up * elevation.sin()
+    }
+
+    /// field of view of the perspective projection, narrowed by the zoom
+    pub fn zoomed_fov(&self) -> Rad<f32> {
+        let half = Rad::from(Deg(self.fov)) / 2.;
+        Rad::atan(half.tan() / self.zoom) * 2.
+    }
 }
 
 impl Default for PerspectiveCamera {
@@ -107,6 +266,83 @@ pub trait Projection {
     fn projection_matrix(&self) -> Matrix4<f32>;
 }
 
+/// projection chosen at runtime, used for offline rendering
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewProjection {
+    Orthographic(OrthographicProjection),
+    Perspective(PerspectiveProjection),
+}
+
+impl Projection for ViewProjection {
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        match self {
+            Self::Orthographic(projection) => projection.projection_matrix(),
+            Self::Perspective(projection) => projection.projection_matrix(),
+        }
+    }
+}
+
+/// projection of a part (tile) of the image of another projection,
+/// used to render images larger than the maximum texture size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileProjection<P: Projection> {
+    pub projection: P,
+    /// corners of the tile in normalized device coordinates
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+impl<P: Projection> TileProjection<P> {
+    /// tile of `size` pixels at `offset` (from the upper left corner) of an image
+    /// with `resolution` pixels
+    pub fn new(
+        projection: P,
+        resolution: Vector2<u32>,
+        offset: Vector2<u32>,
+        size: Vector2<u32>,
+    ) -> Self {
+        // the rays of the upper row of the image are cast at y = -1 (see VIEWPORT_Y_FLIP)
+        let ndc = |offset: Vector2<u32>| {
+            offset
+                .cast::<f32>()
+                .unwrap()
+                .div_element_wise(resolution.cast().unwrap())
+                * 2.
+                - Vector2::new(1., 1.)
+        };
+        Self {
+            projection,
+            min: ndc(offset),
+            max: ndc(offset + size),
+        }
+    }
+}
+
+impl<P: Projection> Projection for TileProjection<P> {
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        // maps the tile to [-1, 1]
+        let scale = Vector2::new(
+            2. / (self.max.x - self.min.x),
+            2. / (self.max.y - self.min.y),
+        );
+        let center = (self.min + self.max) / 2.;
+        let tile =
+            Matrix4::from_translation(Vector3::new(-center.x * scale.x, -center.y * scale.y, 0.))
+                * Matrix4::from_nonuniform_scale(scale.x, scale.y, 1.);
+        tile * self.projection.projection_matrix()
+    }
+}
+
+impl From<OrthographicCamera> for Camera<ViewProjection> {
+    fn from(camera: OrthographicCamera) -> Self {
+        Camera::new(
+            camera.position,
+            camera.rotation,
+            ViewProjection::Orthographic(camera.projection),
+        )
+    }
+}
+
 pub fn world2view(r: impl Into<Matrix3<f32>>, t: Point3<f32>) -> Matrix4<f32> {
     let mut rt = Matrix4::from(r.into());
     rt[0].w = t.x;
@@ -161,6 +397,24 @@ impl OrthographicProjection {
             self.viewport.y = self.viewport.x / ratio;
         }
     }
+
+    /// smallest viewport with the current aspect ratio that contains `extent` (width, height)
+    pub fn fit(&mut self, extent: Vector2<f32>) {
+        let scale = (extent.x / self.viewport.x).max(extent.y / self.viewport.y);
+        if scale.is_finite() && scale > 0. {
+            self.viewport *= scale;
+        }
+    }
+
+    /// zooms so that a pixel of an image with `width` x `height` pixels covers `pixel_size`.
+    /// The image can also be a view derived with [Self::resize], as the same axis is kept.
+    pub fn set_pixel_size(&mut self, width: u32, height: u32, pixel_size: f32) {
+        self.viewport *= if width > height {
+            height as f32 * pixel_size / self.viewport.y
+        } else {
+            width as f32 * pixel_size / self.viewport.x
+        };
+    }
 }
 
 impl Projection for OrthographicProjection {
@@ -169,11 +423,12 @@ impl Projection for OrthographicProjection {
         let right = width / 2.;
         let top = self.viewport.y / 2.;
 
+        // maps view space depth [znear, zfar] to [0, 1] (same as the perspective projection)
         let mut p = Matrix4::zero();
         p[0][0] = 1. / right;
         p[1][1] = 1. / top;
-        p[2][2] = -2.0 / (self.zfar - self.znear);
-        p[2][3] = -(self.zfar + self.znear) / (self.zfar - self.znear);
+        p[2][2] = 1. / (self.zfar - self.znear);
+        p[2][3] = -self.znear / (self.zfar - self.znear);
         p[3][3] = 1.0;
         return p.transpose();
     }
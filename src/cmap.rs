@@ -54,6 +54,95 @@ pub static COLORMAPS: Lazy<HashMap<String, HashMap<String, GenericColorMap>>> =
     cmaps
 });
 
+/// names (group, name) of colormaps by category
+#[cfg(feature = "colormaps")]
+pub type CategorizedColorMaps = Vec<(ColorMapCategory, Vec<(String, String)>)>;
+
+/// predefined colormaps by category, sorted by name
+#[cfg(feature = "colormaps")]
+pub static COLORMAP_CATEGORIES: Lazy<CategorizedColorMaps> = Lazy::new(|| {
+    ColorMapCategory::ALL
+        .into_iter()
+        .map(|category| {
+            let mut cmaps: Vec<_> = COLORMAPS
+                .iter()
+                .flat_map(|(group, cmaps)| {
+                    cmaps
+                        .iter()
+                        .filter(|(_, cmap)| cmap.category() == category)
+                        .map(|(name, _)| (group.clone(), name.clone()))
+                })
+                .collect();
+            cmaps.sort_by_key(|(_, name)| name.to_lowercase());
+            (category, cmaps)
+        })
+        .collect()
+});
+
+/// kind of colormap, estimated from its colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorMapCategory {
+    /// colors change monotonically from low to high values
+    Sequential,
+    /// two colors diverge from a neutral color in the middle
+    Diverging,
+    /// a few distinct colors
+    Categorical,
+}
+
+impl ColorMapCategory {
+    pub const ALL: [Self; 3] = [Self::Sequential, Self::Diverging, Self::Categorical];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMapCategory::Sequential => "Sequential",
+            ColorMapCategory::Diverging => "Diverging",
+            ColorMapCategory::Categorical => "Categorical",
+        }
+    }
+}
+
+/// colormaps with at most this many distinct colors are categorical
+const MAX_CATEGORICAL_COLORS: usize = 20;
+
+/// estimates the category of the colormap:
+/// categorical colormaps have only a few distinct colors,
+/// diverging ones have their lightest or darkest color, which is unsaturated, in the middle
+fn category(cmap: impl ColorMap) -> ColorMapCategory {
+    let colors = cmap.rasterize(COLORMAP_RESOLUTION as usize);
+    let mut distinct = colors.clone();
+    distinct.sort_by_key(|c| (c.x, c.y, c.z, c.w));
+    distinct.dedup();
+    if distinct.len() <= MAX_CATEGORICAL_COLORS {
+        return ColorMapCategory::Categorical;
+    }
+    let rgb = |c: &Vector4<u8>| [c.x, c.y, c.z].map(|v| v as f32 / 255.);
+    let luminance: Vec<f32> = colors
+        .iter()
+        .map(|c| {
+            let [r, g, b] = rgb(c);
+            0.2126 * r + 0.7152 * g + 0.0722 * b
+        })
+        .collect();
+    let n = luminance.len();
+    let (first, last) = (luminance[0], luminance[n - 1]);
+    let by_luminance = |a: &usize, b: &usize| luminance[*a].total_cmp(&luminance[*b]);
+    let lightest = (0..n).max_by(by_luminance).unwrap();
+    let darkest = (0..n).min_by(by_luminance).unwrap();
+    let centered_neutral = |i: usize| {
+        let [r, g, b] = rgb(&colors[i]);
+        let saturation = r.max(g).max(b) - r.min(g).min(b);
+        i > n / 4 && i < n * 3 / 4 && saturation < 0.35
+    };
+    if (centered_neutral(lightest) && luminance[lightest] - first.max(last) > 0.15)
+        || (centered_neutral(darkest) && first.min(last) - luminance[darkest] > 0.15)
+    {
+        ColorMapCategory::Diverging
+    } else {
+        ColorMapCategory::Sequential
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ListedColorMap(Vec<Vector4<u8>>);
 
@@ -153,6 +242,8 @@ impl<'a> ColorMap for &'a ListedColorMap {
 pub struct ColorMapGPU {
     texture: wgpu::Texture,
     bindgroup: wgpu::BindGroup,
+    /// currently uploaded LUT (all rows), used to skip uploads if the colormap did not change
+    lut: Vec<Vector4<u8>>,
 }
 
 pub trait ColorMap {
@@ -170,8 +261,39 @@ pub trait ColorMap {
 
 impl ColorMapGPU {
     pub fn new(cmap: impl ColorMap, device: &wgpu::Device, queue: &wgpu::Queue, n: u32) -> Self {
-        let (texture, bindgroup) = Self::create(device, queue, cmap, n);
-        Self { texture, bindgroup }
+        Self::with_lut(cmap.rasterize(n as usize), device, queue)
+    }
+
+    /// creates the texture from an already rasterized colormap
+    pub fn with_lut(lut: Vec<Vector4<u8>>, device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::with_rows(lut, 1, device, queue)
+    }
+
+    /// creates the texture for a time-varying colormap with `n` colors per timestep
+    pub fn from_sequence(
+        cmap: &ColorMapSequence,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        n: u32,
+    ) -> Self {
+        let rows = cmap.rows();
+        Self::with_rows(cmap.rasterize(n as usize, rows), rows, device, queue)
+    }
+
+    /// creates the texture from `rows` rasterized colormaps evenly spaced in time, one after the other.
+    /// The sampler interpolates between the rows.
+    pub fn with_rows(
+        lut: Vec<Vector4<u8>>,
+        rows: usize,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        let (texture, bindgroup) = Self::create(device, queue, &lut, rows);
+        Self {
+            texture,
+            bindgroup,
+            lut,
+        }
     }
 
     pub fn texture(&self) -> &wgpu::Texture {
@@ -185,16 +307,16 @@ impl ColorMapGPU {
     fn create(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        cmap: impl ColorMap,
-        n: u32,
+        lut: &[Vector4<u8>],
+        rows: usize,
     ) -> (wgpu::Texture, wgpu::BindGroup) {
         let texture = device.create_texture_with_data(
             queue,
             &wgpu::TextureDescriptor {
                 label: Some("cmap texture"),
                 size: Extent3d {
-                    width: n,
-                    height: 1,
+                    width: (lut.len() / rows) as u32,
+                    height: rows as u32,
                     depth_or_array_layers: 1,
                 },
                 mip_level_count: 1,
@@ -205,7 +327,7 @@ impl ColorMapGPU {
                 view_formats: &[],
             },
             wgpu::util::TextureDataOrder::LayerMajor,
-            bytemuck::cast_slice(&cmap.rasterize(n as usize)),
+            bytemuck::cast_slice(lut),
         );
 
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -238,6 +360,11 @@ impl ColorMapGPU {
         return self.texture.size().width;
     }
 
+    /// number of timesteps the colormap is stored for
+    pub fn rows(&self) -> u32 {
+        self.texture.size().height
+    }
+
     pub(crate) fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("cmap bind group layout"),
@@ -262,7 +389,29 @@ impl ColorMapGPU {
         })
     }
 
-    pub fn update(&self, queue: &wgpu::Queue, cmap: impl ColorMap) {
+    /// uploads the colormap if it differs from the current one.
+    /// Returns true if the texture was updated.
+    pub fn update(&mut self, queue: &wgpu::Queue, cmap: impl ColorMap) -> bool {
+        // the same colormap for all timesteps
+        let lut = cmap
+            .rasterize(self.size() as usize)
+            .repeat(self.rows() as usize);
+        self.write(queue, lut)
+    }
+
+    /// uploads the time-varying colormap if it differs from the current one,
+    /// resampled to the rows of the texture.
+    /// Returns true if the texture was updated.
+    pub fn update_sequence(&mut self, queue: &wgpu::Queue, cmap: &ColorMapSequence) -> bool {
+        let lut = cmap.rasterize(self.size() as usize, self.rows() as usize);
+        self.write(queue, lut)
+    }
+
+    fn write(&mut self, queue: &wgpu::Queue, lut: Vec<Vector4<u8>>) -> bool {
+        if lut == self.lut {
+            return false;
+        }
+        self.lut = lut;
         queue.write_texture(
             wgpu::ImageCopyTexture {
                 texture: &self.texture,
@@ -270,22 +419,19 @@ impl ColorMapGPU {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            bytemuck::cast_slice(&cmap.rasterize(self.size() as usize)),
+            bytemuck::cast_slice(&self.lut),
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: None,
+                bytes_per_row: Some(self.size() * 4),
                 rows_per_image: None,
             },
-            wgpu::Extent3d {
-                width: self.size() as u32,
-                height: 1,
-                depth_or_array_layers: 1,
-            },
+            self.texture.size(),
         );
+        true
     }
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct LinearSegmentedColorMap {
     /// x, y0,y1
     #[serde(alias = "red")]
@@ -296,6 +442,20 @@ pub struct LinearSegmentedColorMap {
     pub b: Vec<(f32, f32, f32)>,
     #[serde(alias = "alpha")]
     pub a: Option<Vec<(f32, f32, f32)>>,
+    /// colors are looked up in reverse order (the opacity is not affected)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reversed: bool,
+    /// contrast curve of the color lookup: the colors at x^gamma are used for x
+    #[serde(default = "default_gamma", skip_serializing_if = "is_default_gamma")]
+    pub gamma: f32,
+}
+
+fn default_gamma() -> f32 {
+    1.
+}
+
+fn is_default_gamma(gamma: &f32) -> bool {
+    *gamma == default_gamma()
 }
 
 impl LinearSegmentedColorMap {
@@ -330,11 +490,174 @@ impl LinearSegmentedColorMap {
                 ));
             };
         }
-        Ok(Self { r, g, b, a })
+        Ok(Self {
+            r,
+            g,
+            b,
+            a,
+            reversed: false,
+            gamma: default_gamma(),
+        })
+    }
+
+    /// position of the color for the normalized value `x` after the gamma curve and reversal
+    pub fn lookup(&self, x: f32) -> f32 {
+        let x = x.clamp(0., 1.).powf(self.gamma);
+        if self.reversed {
+            1. - x
+        } else {
+            x
+        }
+    }
+
+    /// the colormap with the gamma curve and reversal applied to the color segments
+    pub fn baked(&self) -> Self {
+        if !self.reversed && is_default_gamma(&self.gamma) {
+            return self.clone();
+        }
+        let n = COLORMAP_RESOLUTION;
+        let channel = |values: &[(f32, f32, f32)]| {
+            let mut baked = (0..n)
+                .map(|i| {
+                    let x = i as f32 / (n - 1) as f32;
+                    let y = sample_channel(self.lookup(x), values);
+                    (x, y, y)
+                })
+                .collect();
+            merge_neighbours(&mut baked);
+            baked
+        };
+        Self {
+            r: channel(&self.r),
+            g: channel(&self.g),
+            b: channel(&self.b),
+            a: self.a.clone(),
+            reversed: false,
+            gamma: default_gamma(),
+        }
     }
 
+    /// evenly spaced colors from a comma-separated list of hex colors
+    /// (`#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`, the `#` is optional),
+    /// e.g. "#000000,#ff0000,#ffff00"
+    pub fn from_hex_list(colors: &str) -> anyhow::Result<Self> {
+        let mut colors = colors
+            .split(',')
+            .map(|c| parse_hex_color(c.trim()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if colors.len() == 1 {
+            // constant colormap
+            colors.push(colors[0]);
+        }
+        let n = colors.len();
+        let channel = |c: usize| {
+            colors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (i as f32 / (n - 1) as f32, v[c], v[c]))
+                .collect::<Vec<_>>()
+        };
+        let a = colors.iter().any(|c| c[3] < 1.).then(|| channel(3));
+        Self::new(channel(0), channel(1), channel(2), a)
+    }
+
+    /// reads a colormap in our own format or a ParaView preset file
     pub fn from_json<R: Read>(reader: R) -> anyhow::Result<Self> {
-        Ok(serde_json::from_reader(reader)?)
+        let value: serde_json::Value = serde_json::from_reader(reader)?;
+        if value.is_array() || value.get("RGBPoints").is_some() {
+            return Self::from_paraview(value);
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// reads the first transfer function of a ParaView preset file (json).
+    /// Colors are always interpolated in RGB, opacity midpoints and sharpness are ignored.
+    pub fn from_paraview_json<R: Read>(reader: R) -> anyhow::Result<Self> {
+        Self::from_paraview(serde_json::from_reader(reader)?)
+    }
+
+    fn from_paraview(value: serde_json::Value) -> anyhow::Result<Self> {
+        let tf: ParaViewTransferFunction = match value {
+            serde_json::Value::Array(mut presets) => {
+                anyhow::ensure!(!presets.is_empty(), "ParaView preset file is empty");
+                serde_json::from_value(presets.swap_remove(0))?
+            }
+            value => serde_json::from_value(value)?,
+        };
+        anyhow::ensure!(
+            !tf.rgb_points.is_empty() && tf.rgb_points.chunks_exact(4).remainder().is_empty(),
+            "RGBPoints must contain x, r, g, b of each point"
+        );
+        // ParaView uses data values, the range of the colors is mapped to [0, 1]
+        let min = tf.rgb_points[0];
+        let max = tf.rgb_points[tf.rgb_points.len() - 4];
+        let normalize = |x: f32| {
+            if max > min {
+                ((x - min) / (max - min)).clamp(0., 1.)
+            } else {
+                0.
+            }
+        };
+        let channel =
+            |c: usize| paraview_segments(tf.rgb_points.chunks(4).map(|p| (normalize(p[0]), p[c])));
+        let a = match &tf.points {
+            Some(points) => {
+                anyhow::ensure!(
+                    !points.is_empty() && points.chunks_exact(4).remainder().is_empty(),
+                    "Points must contain x, opacity, midpoint, sharpness of each point"
+                );
+                Some(paraview_segments(
+                    points.chunks(4).map(|p| (normalize(p[0]), p[1])),
+                ))
+            }
+            None => None,
+        };
+        Self::new(channel(1), channel(2), channel(3), a)
+    }
+
+    /// writes the colormap as ParaView preset file (json) with the given name
+    pub fn write_paraview_json<W: Write>(&self, writer: W, name: &str) -> anyhow::Result<()> {
+        let cmap = self.baked();
+        let mut xs: Vec<f32> = cmap
+            .r
+            .iter()
+            .chain(&cmap.g)
+            .chain(&cmap.b)
+            .map(|p| p.0)
+            .collect();
+        xs.sort_by(f32::total_cmp);
+        xs.dedup();
+        let mut rgb_points = Vec::new();
+        for x in xs {
+            let (r, g, b) = (
+                channel_at(&cmap.r, x),
+                channel_at(&cmap.g, x),
+                channel_at(&cmap.b, x),
+            );
+            rgb_points.extend([x, r.0, g.0, b.0]);
+            // jumps are two points with the same x
+            if (r.1, g.1, b.1) != (r.0, g.0, b.0) {
+                rgb_points.extend([x, r.1, g.1, b.1]);
+            }
+        }
+        let points = cmap.a.as_ref().map(|a| {
+            let mut points = Vec::new();
+            for (x, y0, y1) in a {
+                points.extend([*x, *y0, 0.5, 0.]);
+                if y0 != y1 {
+                    points.extend([*x, *y1, 0.5, 0.]);
+                }
+            }
+            points
+        });
+        let tf = ParaViewTransferFunction {
+            name: name.to_string(),
+            color_space: default_color_space(),
+            rgb_points,
+            points,
+        };
+        serde_json::to_writer_pretty(writer, &[tf])?;
+        Ok(())
     }
 
     fn check_values(v: &Vec<(f32, f32, f32)>) -> bool {
@@ -371,6 +694,84 @@ impl LinearSegmentedColorMap {
             g,
             b,
             a: Some(a),
+            reversed: false,
+            gamma: default_gamma(),
+        }
+    }
+}
+
+/// color and opacity transfer function of a ParaView preset file (which holds a list of them)
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ParaViewTransferFunction {
+    #[serde(default)]
+    name: String,
+    #[serde(default = "default_color_space")]
+    color_space: String,
+    /// x, r, g, b of each point
+    #[serde(rename = "RGBPoints")]
+    rgb_points: Vec<f32>,
+    /// x, opacity, midpoint, sharpness of each point
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    points: Option<Vec<f32>>,
+}
+
+fn default_color_space() -> String {
+    "RGB".to_string()
+}
+
+/// segments covering [0, 1] through the points (x, y).
+/// Two points with the same x are a jump
+fn paraview_segments(points: impl Iterator<Item = (f32, f32)>) -> Vec<(f32, f32, f32)> {
+    let mut segments: Vec<(f32, f32, f32)> = Vec::new();
+    for (x, y) in points {
+        match segments.last_mut() {
+            Some(last) if last.0 == x => last.2 = y,
+            _ => segments.push((x, y, y)),
+        }
+    }
+    if let Some(&(x, y, _)) = segments.first() {
+        if x > 0. {
+            segments.insert(0, (0., y, y));
+        }
+    }
+    if let Some(&(x, _, y)) = segments.last() {
+        if x < 1. {
+            segments.push((1., y, y));
+        }
+    }
+    segments
+}
+
+/// RGBA color in [0, 1] from `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`
+fn parse_hex_color(text: &str) -> anyhow::Result<[f32; 4]> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    let digits: Option<Vec<u8>> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| (d * 17) as u8))
+            .collect(),
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect(),
+        _ => None,
+    };
+    let digits = digits.ok_or_else(|| anyhow::anyhow!("invalid hex color '{text}'"))?;
+    let mut color = [1.; 4];
+    for (c, d) in color.iter_mut().zip(digits) {
+        *c = d as f32 / 255.;
+    }
+    Ok(color)
+}
+
+/// values left and right of x
+fn channel_at(values: &[(f32, f32, f32)], x: f32) -> (f32, f32) {
+    match values.iter().find(|p| p.0 == x) {
+        Some((_, y0, y1)) => (*y0, *y1),
+        None => {
+            let y = sample_channel(x, values);
+            (y, y)
         }
     }
 }
@@ -383,10 +784,11 @@ impl ColorMap for &LinearSegmentedColorMap {
             .as_ref()
             .map(|a| sample_channel(x, &a))
             .unwrap_or(1.0);
+        let c = self.lookup(x);
         Vector4::new(
-            (sample_channel(x, &self.r) * 255.) as u8,
-            (sample_channel(x, &self.g) * 255.) as u8,
-            (sample_channel(x, &self.b) * 255.) as u8,
+            (sample_channel(c, &self.r) * 255.) as u8,
+            (sample_channel(c, &self.g) * 255.) as u8,
+            (sample_channel(c, &self.b) * 255.) as u8,
             (a * 255.) as u8,
         )
     }
@@ -416,7 +818,14 @@ impl ColorMap for &LinearSegmentedColorMap {
         if let Some(a) = &mut a {
             a.reverse();
         }
-        LinearSegmentedColorMap { r, g, b, a }
+        LinearSegmentedColorMap {
+            r,
+            g,
+            b,
+            a,
+            reversed: self.reversed,
+            gamma: self.gamma,
+        }
     }
 }
 impl Hash for LinearSegmentedColorMap {
@@ -435,6 +844,80 @@ impl Hash for LinearSegmentedColorMap {
                 state.write_u32(c.to_bits())
             });
         }
+        self.reversed.hash(state);
+        state.write_u32(self.gamma.to_bits());
+    }
+}
+
+impl LinearSegmentedColorMap {
+    /// the colormap with its alpha channel replaced by `opacity`
+    pub fn with_opacity(&self, opacity: &TransferFunction) -> Self {
+        Self {
+            a: Some(opacity.points.clone()),
+            ..self.clone()
+        }
+    }
+}
+
+/// piecewise-linear opacity as a function of the normalized value.
+/// Kept separately from the colors so that structures can be isolated without changing the colormap.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct TransferFunction {
+    /// control points (x, y0, y1) with opacity y0 left and y1 right of x (same as the segments of a channel)
+    pub points: Vec<(f32, f32, f32)>,
+}
+
+impl Default for TransferFunction {
+    fn default() -> Self {
+        Self::constant(1.)
+    }
+}
+
+impl TransferFunction {
+    pub fn new(points: Vec<(f32, f32, f32)>) -> anyhow::Result<Self> {
+        if points.len() < 2 {
+            return Err(anyhow::anyhow!("opacity needs at least two control points"));
+        }
+        if !LinearSegmentedColorMap::check_values(&points) {
+            return Err(anyhow::anyhow!(
+                "x values for opacity are not in (0,1) or ascending"
+            ));
+        }
+        Ok(Self { points })
+    }
+
+    /// from control points (x, opacity) without discontinuities
+    pub fn from_points(points: &[(f32, f32)]) -> anyhow::Result<Self> {
+        Self::new(points.iter().map(|(x, y)| (*x, *y, *y)).collect())
+    }
+
+    pub fn constant(opacity: f32) -> Self {
+        Self {
+            points: vec![(0.0, opacity, opacity), (1.0, opacity, opacity)],
+        }
+    }
+
+    /// the alpha channel of a colormap, constant 1 if it has none
+    pub fn from_alpha(cmap: &LinearSegmentedColorMap) -> Self {
+        cmap.a
+            .clone()
+            .map(|points| Self { points })
+            .unwrap_or_default()
+    }
+
+    pub fn sample(&self, x: f32) -> f32 {
+        sample_channel(x, &self.points)
+    }
+}
+
+impl Hash for TransferFunction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.points.iter().for_each(|(a, b, c)| {
+            state.write_u32(a.to_bits());
+            state.write_u32(b.to_bits());
+            state.write_u32(c.to_bits())
+        });
     }
 }
 
@@ -523,6 +1006,10 @@ impl GenericColorMap {
         }
     }
 
+    pub fn category(&self) -> ColorMapCategory {
+        category(self)
+    }
+
     /// if all alpha values are 1.0, the alpha channel is considered boring
     #[allow(unused)]
     pub(crate) fn has_boring_alpha_channel(&self) -> bool {
@@ -537,6 +1024,142 @@ impl GenericColorMap {
     }
 }
 
+/// number of rows a colormap sequence with unevenly spaced keyframes is resampled to
+const SEQUENCE_ROWS: usize = 64;
+
+/// colormaps that change over time, e.g. one per timestep or a few keyframes,
+/// for quantities whose meaning changes over the phases of a simulation.
+/// Between two keyframes the colormaps are blended linearly.
+#[derive(Debug, Clone, Hash)]
+pub struct ColorMapSequence {
+    /// normalized time in [0, 1] (same as the render time) and the colormap used at that time,
+    /// ascending in time
+    keyframes: Vec<(Time, GenericColorMap)>,
+}
+
+/// f32 that can be hashed
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+struct Time(f32);
+
+impl Hash for Time {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u32(self.0.to_bits());
+    }
+}
+
+impl ColorMapSequence {
+    pub fn new(keyframes: Vec<(f32, GenericColorMap)>) -> anyhow::Result<Self> {
+        if keyframes.is_empty() {
+            return Err(anyhow::anyhow!(
+                "colormap sequence needs at least one colormap"
+            ));
+        }
+        if keyframes.iter().any(|(t, _)| !(0.0..=1.0).contains(t))
+            || keyframes.windows(2).any(|w| w[0].0 >= w[1].0)
+        {
+            return Err(anyhow::anyhow!(
+                "keyframe times are not in (0,1) or ascending"
+            ));
+        }
+        Ok(Self {
+            keyframes: keyframes.into_iter().map(|(t, c)| (Time(t), c)).collect(),
+        })
+    }
+
+    /// colormaps evenly spaced in time, e.g. one per timestep
+    pub fn uniform(cmaps: Vec<GenericColorMap>) -> anyhow::Result<Self> {
+        let n = cmaps.len();
+        Self::new(
+            cmaps
+                .into_iter()
+                .enumerate()
+                .map(|(i, c)| (i as f32 / (n - 1).max(1) as f32, c))
+                .collect(),
+        )
+    }
+
+    pub fn keyframes(&self) -> impl Iterator<Item = (f32, &GenericColorMap)> {
+        self.keyframes.iter().map(|(t, c)| (t.0, c))
+    }
+
+    /// applies `f` to the colormap of every keyframe, e.g. to replace the opacity
+    pub fn map(&self, f: impl Fn(&GenericColorMap) -> GenericColorMap) -> Self {
+        Self {
+            keyframes: self.keyframes.iter().map(|(t, c)| (*t, f(c))).collect(),
+        }
+    }
+
+    /// the sequence with the alpha channels of all colormaps replaced by `opacity`
+    pub fn with_opacity(&self, opacity: &TransferFunction) -> Self {
+        self.map(|c| {
+            GenericColorMap::LinearSegmented(
+                c.into_linear_segmented(COLORMAP_RESOLUTION)
+                    .with_opacity(opacity),
+            )
+        })
+    }
+
+    /// number of LUT rows needed to store the sequence.
+    /// One per keyframe if they are evenly spaced, otherwise the sequence is resampled.
+    pub fn rows(&self) -> usize {
+        let n = self.keyframes.len();
+        let evenly_spaced = self
+            .keyframes
+            .iter()
+            .enumerate()
+            .all(|(i, (t, _))| (t.0 - i as f32 / (n - 1).max(1) as f32).abs() < 1e-4);
+        if evenly_spaced {
+            n
+        } else {
+            SEQUENCE_ROWS
+        }
+    }
+
+    /// the colormap at `time` with `n` colors
+    pub fn at(&self, time: f32, n: u32) -> ListedColorMap {
+        ListedColorMap::new(self.blend(&self.rasterize_keyframes(n as usize), time))
+    }
+
+    /// rasterizes the colormaps at `rows` evenly spaced times, one after the other
+    pub fn rasterize(&self, n: usize, rows: usize) -> Vec<Vector4<u8>> {
+        let luts = self.rasterize_keyframes(n);
+        (0..rows)
+            .flat_map(|r| self.blend(&luts, r as f32 / (rows - 1).max(1) as f32))
+            .collect()
+    }
+
+    fn rasterize_keyframes(&self, n: usize) -> Vec<Vec<Vector4<u8>>> {
+        self.keyframes.iter().map(|(_, c)| c.rasterize(n)).collect()
+    }
+
+    /// linear interpolation between the rasterized keyframes `luts` before and after `time`
+    fn blend(&self, luts: &[Vec<Vector4<u8>>], time: f32) -> Vec<Vector4<u8>> {
+        let i = self.keyframes.partition_point(|(t, _)| t.0 <= time);
+        if i == 0 || i == self.keyframes.len() {
+            return luts[i.saturating_sub(1)].clone();
+        }
+        let Time(t0) = self.keyframes[i - 1].0;
+        let Time(t1) = self.keyframes[i].0;
+        let f = (time - t0) / (t1 - t0);
+        luts[i - 1]
+            .iter()
+            .zip(&luts[i])
+            .map(|(a, b)| {
+                (a.cast::<f32>().unwrap() * (1. - f) + b.cast::<f32>().unwrap() * f)
+                    .map(|v| v.round() as u8)
+            })
+            .collect()
+    }
+}
+
+impl From<GenericColorMap> for ColorMapSequence {
+    fn from(cmap: GenericColorMap) -> Self {
+        Self {
+            keyframes: vec![(Time(0.), cmap)],
+        }
+    }
+}
+
 impl<'a> ColorMap for &'a GenericColorMap {
     fn sample(&self, x: f32) -> Vector4<u8> {
         match self {
@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+/// second dataset (e.g. another simulation run) shown next to the opened one with the same
+/// camera and time. The views hold the channels of the first dataset, then the channels of
+/// the second one and, in difference mode, the differences (first minus second).
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub file: PathBuf,
+    /// number of channels of each dataset
+    pub channels: usize,
+    /// shows the differences of the datasets as additional views
+    pub difference: bool,
+}
+
+impl Comparison {
+    /// number of columns that puts each dataset into its own row (or column for one channel)
+    pub fn num_columns(&self, num_volumes: usize) -> u32 {
+        if self.channels == 1 {
+            num_volumes as u32
+        } else {
+            self.channels as u32
+        }
+    }
+
+    /// name of the view of volume `i`, `first` is the file of the first dataset
+    pub fn label(&self, first: Option<&Path>, i: usize) -> String {
+        let name = |file: Option<&Path>| {
+            file.and_then(|f| f.file_stem())
+                .map_or("volume".to_string(), |s| s.to_string_lossy().into_owned())
+        };
+        let mut label = match i / self.channels {
+            0 => name(first),
+            1 => name(Some(&self.file)),
+            _ => format!("{} − {}", name(first), name(Some(&self.file))),
+        };
+        if self.channels > 1 {
+            label += &format!(" [{}]", i % self.channels);
+        }
+        label
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// default size of body text in points
+pub const DEFAULT_FONT_SIZE: f32 = 12.5;
+
+/// user settings of the viewer, stored as `config.json` in [config_dir]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub ui: UiConfig,
+}
+
+/// color theme of the user interface
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// appearance of the user interface
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiConfig {
+    /// scale of the user interface on top of the scale factor of the display
+    pub scale: f32,
+    pub theme: Theme,
+    /// size of body text in points, the other text styles are scaled along
+    pub font_size: f32,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            theme: Theme::Dark,
+            font_size: DEFAULT_FONT_SIZE,
+        }
+    }
+}
+
+impl UiConfig {
+    /// applies scale, theme and font size to the egui context
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_zoom_factor(self.scale);
+        ctx.set_visuals(match self.theme {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        });
+        let factor = self.font_size / DEFAULT_FONT_SIZE;
+        ctx.style_mut(|style| {
+            style.text_styles = egui::Style::default()
+                .text_styles
+                .into_iter()
+                .map(|(text_style, font)| {
+                    (
+                        text_style,
+                        egui::FontId::new(font.size * factor, font.family),
+                    )
+                })
+                .collect();
+        });
+    }
+}
+
+/// directory with the user's configuration:
+/// `$VAPE4D_CONFIG_DIR` or `vape4d` in the user's config directory
+#[cfg(not(target_arch = "wasm32"))]
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("VAPE4D_CONFIG_DIR") {
+        return Some(dir.into());
+    }
+    let config_dir = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    config_dir.map(|dir| dir.join("vape4d"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Config {
+    fn file() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("config.json"))
+    }
+
+    /// reads the config file, the default config if there is none or it is invalid
+    pub fn load() -> Self {
+        let Some(file) = Self::file().filter(|f| f.exists()) else {
+            return Self::default();
+        };
+        match std::fs::read(&file)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| Ok(serde_json::from_slice(&data)?))
+        {
+            Ok(config) => config,
+            Err(err) => {
+                log::warn!("ignoring config file {}: {err}", file.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// writes the config file
+    pub fn save(&self) -> anyhow::Result<()> {
+        let file = Self::file()
+            .ok_or_else(|| anyhow::anyhow!("no config directory, set VAPE4D_CONFIG_DIR"))?;
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&file, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -1,18 +1,202 @@
 use cgmath::*;
 #[cfg(target_arch = "wasm32")]
 use instant::Duration;
-use num_traits::Float;
 use std::f32::consts::PI;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Duration;
 
-use winit::keyboard::KeyCode;
+use winit::{event::TouchPhase, keyboard::KeyCode};
 
-use crate::camera::{Camera, OrthographicProjection};
+use crate::{
+    camera::{Camera, OrthographicProjection},
+    volume::Aabb,
+};
+
+/// smoothing filter applied to the interactive camera input
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CameraSmoothing {
+    #[default]
+    Off,
+    /// exponential moving average, keeps `factor` of the previous input per 1/60s
+    Exponential { factor: f32 },
+    /// One-Euro filter (Casiez et al. 2012), adapts the cutoff frequency to the input speed
+    OneEuro { min_cutoff: f32, beta: f32 },
+}
+
+impl CameraSmoothing {
+    pub const EXPONENTIAL: Self = CameraSmoothing::Exponential { factor: 0.8 };
+    pub const ONE_EURO: Self = CameraSmoothing::OneEuro {
+        min_cutoff: 1.,
+        beta: 0.01,
+    };
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CameraSmoothing::Off => "Off",
+            CameraSmoothing::Exponential { .. } => "Exponential",
+            CameraSmoothing::OneEuro { .. } => "One-Euro",
+        }
+    }
+}
+
+/// default of [CameraInertia], the motion kept per 1/60s
+const DEFAULT_INERTIA: f32 = 0.8;
+
+/// fraction of the orbit, pan and zoom motion that is kept per 1/60s after the input,
+/// so the camera eases out. 0 stops the camera immediately.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraInertia {
+    pub orbit: f32,
+    pub pan: f32,
+    pub zoom: f32,
+}
+
+impl CameraInertia {
+    pub const NONE: Self = CameraInertia {
+        orbit: 0.,
+        pan: 0.,
+        zoom: 0.,
+    };
+}
+
+impl Default for CameraInertia {
+    fn default() -> Self {
+        Self {
+            orbit: DEFAULT_INERTIA,
+            pan: DEFAULT_INERTIA,
+            zoom: DEFAULT_INERTIA,
+        }
+    }
+}
+
+/// axes the up vector of the camera can be locked to, with their names
+pub const UP_AXES: [(&str, Vector3<f32>); 6] = [
+    ("+X", Vector3::new(1., 0., 0.)),
+    ("-X", Vector3::new(-1., 0., 0.)),
+    ("+Y", Vector3::new(0., 1., 0.)),
+    ("-Y", Vector3::new(0., -1., 0.)),
+    ("+Z", Vector3::new(0., 0., 1.)),
+    ("-Z", Vector3::new(0., 0., -1.)),
+];
+
+/// how the mouse and keyboard move the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NavigationMode {
+    /// the camera rotates around the center and scrolling zooms
+    #[default]
+    Orbit,
+    /// first-person navigation: WASD moves the camera, dragging turns it
+    /// and scrolling changes the speed
+    Fly,
+}
+
+impl NavigationMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NavigationMode::Orbit => "Orbit",
+            NavigationMode::Fly => "Fly",
+        }
+    }
+}
+
+/// axis aligned views as (direction from the center to the camera, up vector),
+/// in the order +X, -X, +Y, -Y, +Z, -Z
+pub const AXIS_VIEWS: [(Vector3<f32>, Vector3<f32>); 6] = [
+    (Vector3::new(1., 0., 0.), Vector3::new(0., 1., 0.)),
+    (Vector3::new(-1., 0., 0.), Vector3::new(0., 1., 0.)),
+    (Vector3::new(0., 1., 0.), Vector3::new(0., 0., -1.)),
+    (Vector3::new(0., -1., 0.), Vector3::new(0., 0., 1.)),
+    (Vector3::new(0., 0., 1.), Vector3::new(0., 1., 0.)),
+    (Vector3::new(0., 0., -1.), Vector3::new(0., 1., 0.)),
+];
+
+/// duration of the animation when snapping to a view
+const SNAP_DURATION: f32 = 0.3;
+
+/// animated rotation of the camera around the center towards a fixed view
+#[derive(Debug, Clone, Copy)]
+struct CameraSnap {
+    /// camera rotation when the animation started (set on the first update)
+    from: Option<Quaternion<f32>>,
+    to: Quaternion<f32>,
+    progress: f32,
+}
+
+/// maximum time in seconds between the two taps of a double tap
+const DOUBLE_TAP_TIME: f32 = 0.3;
+/// maximum distance in pixels a finger moves during a tap
+const TAP_DISTANCE: f32 = 10.;
+/// maximum time in seconds between the two clicks of a double click
+const DOUBLE_CLICK_TIME: f32 = 0.4;
+
+/// fingers on the touch screen, used for the touch gestures
+#[derive(Debug, Default)]
+struct TouchState {
+    /// current position of every finger by its id
+    fingers: Vec<(u64, Vector2<f32>)>,
+    /// start position of a single finger that has not moved yet
+    tap_start: Option<Vector2<f32>>,
+    /// time of the last tap (see [CameraController::clock])
+    last_tap: Option<f32>,
+}
+
+/// number of input signals (rotation xyz, shift xy, scroll)
+const INPUT_DIMS: usize = 6;
+
+#[derive(Debug, Default)]
+struct SmoothingState {
+    value: Option<[f32; INPUT_DIMS]>,
+    derivative: [f32; INPUT_DIMS],
+}
+
+impl SmoothingState {
+    fn filter(
+        &mut self,
+        smoothing: CameraSmoothing,
+        input: [f32; INPUT_DIMS],
+        dt: f32,
+    ) -> [f32; INPUT_DIMS] {
+        let prev = match self.value {
+            Some(prev) if dt > 0. => prev,
+            _ => {
+                self.value = Some(input);
+                self.derivative = [0.; INPUT_DIMS];
+                return input;
+            }
+        };
+        let mut out = input;
+        match smoothing {
+            CameraSmoothing::Off => {}
+            CameraSmoothing::Exponential { factor } => {
+                let keep = factor.clamp(0., 0.999).powf(dt * 60.);
+                for i in 0..INPUT_DIMS {
+                    out[i] = prev[i] * keep + input[i] * (1. - keep);
+                }
+            }
+            CameraSmoothing::OneEuro { min_cutoff, beta } => {
+                let alpha = |cutoff: f32| {
+                    let tau = 1. / (2. * PI * cutoff);
+                    1. / (1. + tau / dt)
+                };
+                for i in 0..INPUT_DIMS {
+                    let dx = (input[i] - prev[i]) / dt;
+                    let a_d = alpha(1.);
+                    self.derivative[i] = a_d * dx + (1. - a_d) * self.derivative[i];
+                    let cutoff = min_cutoff + beta * self.derivative[i].abs();
+                    let a = alpha(cutoff);
+                    out[i] = a * input[i] + (1. - a) * prev[i];
+                }
+            }
+        }
+        self.value = Some(out);
+        out
+    }
+}
 
 #[derive(Debug)]
 pub struct CameraController {
     pub center: Point3<f32>,
+    /// locked up vector of the camera, free trackball rotation if None (see [Self::lock_up])
     pub up: Option<Vector3<f32>>,
     amount: Vector3<f32>,
     shift: Vector2<f32>,
@@ -20,11 +204,34 @@ pub struct CameraController {
     scroll: f32,
     pub speed: f32,
     pub sensitivity: f32,
+    pub mode: NavigationMode,
+    /// distance the camera moves per second in fly mode
+    pub fly_speed: f32,
 
     pub left_mouse_pressed: bool,
     pub right_mouse_pressed: bool,
     pub alt_pressed: bool,
     pub user_inptut: bool,
+
+    pub smoothing: CameraSmoothing,
+    smoothing_state: SmoothingState,
+    pub inertia: CameraInertia,
+    /// disables the smoothing and the inertia while set, e.g. while a modifier key is held
+    pub precise: bool,
+    snap: Option<CameraSnap>,
+
+    touch: TouchState,
+    /// factor the view is scaled with in the next update (pinch gesture)
+    zoom: f32,
+    /// seconds since the controller was created, advanced in every update
+    clock: f32,
+    /// world space offset from the view center to the point the scroll zooms towards,
+    /// relative to the size of the viewport
+    zoom_anchor: Option<Vector3<f32>>,
+    /// cursor position when the left mouse button was pressed
+    click_start: Option<Vector2<f32>>,
+    /// time of the last click (see [CameraController::clock])
+    last_click: Option<f32>,
 }
 
 impl CameraController {
@@ -38,30 +245,44 @@ impl CameraController {
             scroll: 0.0,
             speed,
             sensitivity,
+            mode: NavigationMode::Orbit,
+            fly_speed: 0.5,
             left_mouse_pressed: false,
             right_mouse_pressed: false,
             alt_pressed: false,
             user_inptut: false,
+            smoothing: CameraSmoothing::Off,
+            smoothing_state: SmoothingState::default(),
+            inertia: CameraInertia::default(),
+            precise: false,
+            snap: None,
+            touch: TouchState::default(),
+            zoom: 1.,
+            clock: 0.,
+            zoom_anchor: None,
+            click_start: None,
+            last_click: None,
         }
     }
 
     pub fn process_keyboard(&mut self, key: KeyCode, pressed: bool) -> bool {
         let amount = if pressed { 1.0 } else { 0.0 };
         let processed = match key {
+            // assigned instead of added up, pressed keys repeat
             KeyCode::KeyW | KeyCode::ArrowUp => {
-                self.amount.z += amount;
+                self.amount.z = amount;
                 true
             }
             KeyCode::KeyS | KeyCode::ArrowDown => {
-                self.amount.z += -amount;
+                self.amount.z = -amount;
                 true
             }
             KeyCode::KeyA | KeyCode::ArrowLeft => {
-                self.amount.x += -amount;
+                self.amount.x = -amount;
                 true
             }
             KeyCode::KeyD | KeyCode::ArrowRight => {
-                self.amount.x += amount;
+                self.amount.x = amount;
                 true
             }
             KeyCode::KeyQ => {
@@ -73,11 +294,11 @@ impl CameraController {
                 true
             }
             KeyCode::Space => {
-                self.amount.y += amount;
+                self.amount.y = amount;
                 true
             }
             KeyCode::ShiftLeft => {
-                self.amount.y += -amount;
+                self.amount.y = -amount;
                 true
             }
             _ => false,
@@ -86,6 +307,77 @@ impl CameraController {
         return processed;
     }
 
+    /// rotates the camera around the center so that it looks at the center from `dir`.
+    /// The locked up vector is used instead of `up` unless `dir` points along it.
+    pub fn snap_to(&mut self, dir: Vector3<f32>, up: Vector3<f32>) {
+        let up = self
+            .up
+            .filter(|locked| angle_short(*locked, dir) > Rad(0.1))
+            .unwrap_or(up);
+        self.snap = Some(CameraSnap {
+            from: None,
+            to: Quaternion::look_at(-dir.normalize(), up),
+            progress: 0.,
+        });
+    }
+
+    /// locks the up vector of the camera to `up`, or frees it if None.
+    /// The camera turns upright if it is locked.
+    pub fn lock_up(&mut self, camera: &Camera<OrthographicProjection>, up: Option<Vector3<f32>>) {
+        self.up = up;
+        if up.is_some() {
+            let view_t: Matrix3<f32> = camera.rotation.invert().into();
+            self.snap_to(view_t.z, view_t.y);
+        }
+    }
+
+    /// forgets the pressed movement keys, e.g. when the window loses the focus
+    /// and does not receive their release
+    pub fn release_keys(&mut self) {
+        self.amount = Vector3::zero();
+    }
+
+    /// snaps to one of the [AXIS_VIEWS]
+    pub fn snap_to_axis(&mut self, index: usize) {
+        let (dir, up) = AXIS_VIEWS[index];
+        self.snap_to(dir, up);
+    }
+
+    /// moves the camera so that it orbits around `center` without rotating it
+    pub fn center_at(&mut self, camera: &mut Camera<OrthographicProjection>, center: Point3<f32>) {
+        camera.position += center - self.center;
+        self.center = center;
+    }
+
+    /// moves the center to the center of `aabb` and zooms so that it just fits into the view
+    pub fn zoom_to_aabb(&mut self, camera: &mut Camera<OrthographicProjection>, aabb: &Aabb<f32>) {
+        let center = aabb.center();
+        self.center_at(camera, center);
+        // flying through the volume takes a few seconds
+        self.fly_speed = aabb.radius() * 0.5;
+        let view = camera.view_matrix();
+        let view_center = view.transform_point(center);
+        let mut extent = Vector2::<f32>::zero();
+        for corner in aabb.corners() {
+            let p = view.transform_point(corner) - view_center;
+            extent.x = extent.x.max(2. * p.x.abs());
+            extent.y = extent.y.max(2. * p.y.abs());
+        }
+        camera.projection.fit(extent);
+        self.shift = Vector2::zero();
+        self.scroll = 0.;
+        self.smoothing_state = SmoothingState::default();
+    }
+
+    /// stops all ongoing camera movement, e.g. before the camera is moved to a saved pose
+    pub fn stop(&mut self) {
+        self.rotation = Vector3::zero();
+        self.shift = Vector2::zero();
+        self.scroll = 0.;
+        self.snap = None;
+        self.smoothing_state = SmoothingState::default();
+    }
+
     pub fn process_mouse(&mut self, mouse_dx: f32, mouse_dy: f32) {
         if self.left_mouse_pressed {
             self.rotation.x += mouse_dx as f32;
@@ -99,19 +391,194 @@ impl CameraController {
         }
     }
 
-    pub fn process_scroll(&mut self, dy: f32) {
+    /// one finger orbits, two fingers pan and pinch to zoom.
+    /// Returns true for a double tap, which resets the view.
+    pub fn process_touch(&mut self, id: u64, phase: TouchPhase, position: Vector2<f32>) -> bool {
+        let fingers = &mut self.touch.fingers;
+        match phase {
+            TouchPhase::Started => {
+                fingers.push((id, position));
+                self.touch.tap_start = (fingers.len() == 1).then_some(position);
+            }
+            TouchPhase::Moved => {
+                let Some(i) = fingers.iter().position(|(f, _)| *f == id) else {
+                    return false;
+                };
+                let last = fingers[i].1;
+                let delta = position - last;
+                if fingers.len() == 1 {
+                    self.rotation.x += delta.x;
+                    self.rotation.y += delta.y;
+                } else if i < 2 {
+                    let other = fingers[1 - i].1;
+                    // the center between the fingers moves by half of the delta
+                    self.shift.y += -delta.x / 2.;
+                    self.shift.x += delta.y / 2.;
+                    let (before, after) = (last.distance(other), position.distance(other));
+                    if before > 1. && after > 1. {
+                        self.zoom *= before / after;
+                    }
+                }
+                fingers[i].1 = position;
+                self.user_inptut = true;
+                if self
+                    .touch
+                    .tap_start
+                    .is_some_and(|start| start.distance(position) > TAP_DISTANCE)
+                {
+                    self.touch.tap_start = None;
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                fingers.retain(|(f, _)| *f != id);
+                if phase == TouchPhase::Cancelled || self.touch.tap_start.take().is_none() {
+                    return false;
+                }
+                if self
+                    .touch
+                    .last_tap
+                    .is_some_and(|t| self.clock - t < DOUBLE_TAP_TIME)
+                {
+                    self.touch.last_tap = None;
+                    return true;
+                }
+                self.touch.last_tap = Some(self.clock);
+            }
+        }
+        false
+    }
+
+    /// continuous input of a gamepad over `dt` seconds, `orbit` and `pan` are stick deflections
+    /// (y pointing down like the mouse) and `zoom` is positive for zooming out
+    #[cfg(feature = "gamepad")]
+    pub fn process_gamepad(&mut self, orbit: Vector2<f32>, pan: Vector2<f32>, zoom: f32, dt: f32) {
+        // per 1/60s, chosen to feel like a moderate mouse drag with the decay of the input
+        let frames = dt * 60.;
+        self.rotation.x += orbit.x * 5. * frames;
+        self.rotation.y += orbit.y * 5. * frames;
+        self.shift.y += -pan.x * 5. * frames;
+        self.shift.x += pan.y * 5. * frames;
+        self.scroll += zoom * 0.2 * frames;
+        if !orbit.is_zero() || !pan.is_zero() || zoom != 0. {
+            self.user_inptut = true;
+        }
+    }
+
+    /// zooms towards `anchor`, the world space offset from the view center to the point
+    /// under the cursor divided by the viewport size, or towards the center if None
+    pub fn process_scroll(&mut self, dy: f32, anchor: Option<Vector3<f32>>) {
         self.scroll += -dy;
+        self.zoom_anchor = anchor;
         self.user_inptut = true;
     }
 
+    /// presses or releases the left mouse button at the cursor `position`.
+    /// Returns true if the release completes a double click without dragging.
+    pub fn process_left_button(&mut self, pressed: bool, position: Option<Vector2<f32>>) -> bool {
+        self.left_mouse_pressed = pressed;
+        if pressed {
+            self.click_start = position;
+            return false;
+        }
+        let click = self
+            .click_start
+            .take()
+            .zip(position)
+            .is_some_and(|(start, end)| start.distance(end) <= TAP_DISTANCE);
+        if !click {
+            self.last_click = None;
+            return false;
+        }
+        if self
+            .last_click
+            .is_some_and(|t| self.clock - t < DOUBLE_CLICK_TIME)
+        {
+            self.last_click = None;
+            return true;
+        }
+        self.last_click = Some(self.clock);
+        false
+    }
+
+    /// true if the user is dragging or the camera is still moving
+    pub fn is_moving(&self) -> bool {
+        self.left_mouse_pressed
+            || self.right_mouse_pressed
+            || !self.touch.fingers.is_empty()
+            || !self.rotation.is_zero()
+            || !self.shift.is_zero()
+            || self.scroll != 0.
+            || (self.mode == NavigationMode::Fly && !self.amount.is_zero())
+            || self.snap.is_some()
+            || self
+                .smoothing_state
+                .value
+                .is_some_and(|v| v.iter().any(|x| x.abs() > 1e-4))
+    }
+
     pub fn update_camera(&mut self, camera: &mut Camera<OrthographicProjection>, dt: Duration) {
         let dt: f32 = dt.as_secs_f32();
+        self.clock += dt;
         let dir = camera.position - self.center;
         let distance = dir.magnitude();
 
+        let (smoothing, inertia) = if self.precise {
+            (CameraSmoothing::Off, CameraInertia::NONE)
+        } else {
+            (self.smoothing, self.inertia)
+        };
+        let decay = |inertia: f32| {
+            let decay = inertia.clamp(0., 0.99).powf(dt * 60.);
+            if decay < 1e-4 {
+                0.
+            } else {
+                decay
+            }
+        };
+        let (orbit_decay, pan_decay, zoom_decay) = (
+            decay(inertia.orbit),
+            decay(inertia.pan),
+            decay(inertia.zoom),
+        );
+        // scales the input so that the total motion does not depend on the inertia
+        let gain = |decay: f32| (1. - decay) / (1. - DEFAULT_INERTIA);
+
+        let [rot_x, rot_y, rot_z, shift_x, shift_y, scroll] = self.smoothing_state.filter(
+            smoothing,
+            [
+                self.rotation.x,
+                self.rotation.y,
+                self.rotation.z,
+                self.shift.x,
+                self.shift.y,
+                self.scroll,
+            ],
+            dt,
+        );
+        let rotation = Vector3::new(rot_x, rot_y, rot_z) * gain(orbit_decay);
+        let shift = Vector2::new(shift_x, shift_y) * gain(pan_decay);
+        let scroll = scroll * gain(zoom_decay);
+
         let scale = camera.projection.viewport.magnitude();
-        let new_scale = (scale.ln() + self.scroll * dt * 10. * self.speed).exp();
-        camera.projection.viewport = camera.projection.viewport.normalize() * new_scale;
+        let mut new_scale = scale;
+        match self.mode {
+            NavigationMode::Orbit => {
+                new_scale = (scale.ln() + scroll * dt * 10. * self.speed).exp()
+            }
+            // scrolling up flies faster
+            NavigationMode::Fly => self.fly_speed *= (-scroll * dt * 10. * self.speed).exp(),
+        }
+        camera.projection.viewport = camera.projection.viewport.normalize() * new_scale * self.zoom;
+        self.zoom = 1.;
+        // keeps the point under the cursor in place while zooming
+        if let Some(anchor) = self
+            .zoom_anchor
+            .filter(|_| self.mode == NavigationMode::Orbit)
+        {
+            let offset = anchor * (scale - new_scale);
+            self.center += offset;
+            camera.position += offset;
+        }
 
         let view_t: Matrix3<f32> = camera.rotation.invert().into();
 
@@ -119,50 +586,102 @@ impl CameraController {
         let y_axis = self.up.unwrap_or(view_t.y);
         let z_axis = view_t.z;
 
-        let offset =
-            (self.shift.y * x_axis - self.shift.x * y_axis) * dt * self.speed * 0.1 * distance;
+        let offset = (shift.y * x_axis - shift.x * y_axis) * dt * self.speed * 0.1 * distance;
         self.center += offset;
         camera.position += offset;
-        let mut theta = Rad((self.rotation.x) * dt * self.sensitivity);
-        let mut phi = Rad((-self.rotation.y) * dt * self.sensitivity);
-        let mut eta = Rad::zero();
+        let mut theta = Rad((rotation.x) * dt * self.sensitivity);
+        let mut phi = Rad((-rotation.y) * dt * self.sensitivity);
+        // Q/E roll the camera
+        let mut eta = Rad(rotation.z * dt * self.sensitivity);
 
         if self.alt_pressed {
-            eta = Rad(-self.rotation.y * dt * self.sensitivity);
+            eta += Rad(-rotation.y * dt * self.sensitivity);
             theta = Rad::zero();
             phi = Rad::zero();
         }
+        // a locked up vector cannot roll
+        if self.up.is_some() {
+            eta = Rad::zero();
+        }
 
         let rot_theta = Quaternion::from_axis_angle(y_axis, theta);
         let rot_phi = Quaternion::from_axis_angle(x_axis, phi);
         let rot_eta = Quaternion::from_axis_angle(z_axis, eta);
-        let rot = rot_theta * rot_phi * rot_eta;
-
-        let mut new_dir = rot.rotate_vector(dir);
 
-        if angle_short(y_axis, new_dir) < Rad(0.1) {
-            new_dir = dir;
-        }
+        let new_dir = match self.mode {
+            NavigationMode::Orbit => {
+                let rot = rot_theta * rot_phi * rot_eta;
+                let new_dir = rot.rotate_vector(dir);
+                // the camera must not turn towards the up axis, but it can turn away
+                // from it if it was snapped to look along it
+                if angle_short(y_axis, new_dir) < Rad(0.1)
+                    && angle_short(y_axis, new_dir) < angle_short(y_axis, dir)
+                {
+                    dir
+                } else {
+                    new_dir
+                }
+            }
+            NavigationMode::Fly => {
+                // mouse-look: the camera turns towards the drag around its position and
+                // the center stays in front of it. Only the pitch is dropped when looking
+                // along the up axis, so the camera can still turn sideways.
+                let turned = rot_theta.invert().rotate_vector(dir);
+                let pitched = rot_phi.rotate_vector(turned);
+                let new_dir = if angle_short(y_axis, pitched) < Rad(0.1)
+                    && angle_short(y_axis, pitched) < angle_short(y_axis, turned)
+                {
+                    turned
+                } else {
+                    pitched
+                };
+                let forward = -new_dir.normalize();
+                let velocity =
+                    forward * self.amount.z + x_axis * self.amount.x + y_axis * self.amount.y;
+                camera.position += velocity * self.fly_speed * dt;
+                self.center = camera.position - new_dir;
+                new_dir
+            }
+        };
         camera.position = self.center + new_dir;
 
         // update rotation
         // camera.rotation = (rot * camera.rotation.invert()).invert();
-        camera.rotation = Quaternion::look_at(-new_dir, y_axis);
+        let up = if angle_short(y_axis, new_dir) < Rad(1e-3) {
+            // looking along the locked up vector, keep the current orientation
+            view_t.y
+        } else {
+            rot_eta.rotate_vector(y_axis)
+        };
+        camera.rotation = Quaternion::look_at(-new_dir, up);
 
-        // decay based on fps
-        let mut decay = (0.8).powf(dt * 60.);
-        if decay < 1e-4 {
-            decay = 0.;
+        // dragging cancels the animation
+        if self.left_mouse_pressed || !self.touch.fingers.is_empty() {
+            self.snap = None;
+        }
+        if let Some(snap) = &mut self.snap {
+            let from = *snap.from.get_or_insert(camera.rotation);
+            snap.progress = (snap.progress + dt / SNAP_DURATION).min(1.);
+            // ease in and out
+            let t = snap.progress * snap.progress * (3. - 2. * snap.progress);
+            camera.rotation = from.slerp(snap.to, t).normalize();
+            let forward = camera.rotation.invert().rotate_vector(Vector3::unit_z());
+            camera.position = self.center - forward * distance;
+            if snap.progress >= 1. {
+                self.snap = None;
+            }
         }
-        self.rotation *= decay;
+
+        // decay based on fps
+        self.rotation *= orbit_decay;
         if self.rotation.magnitude() < 1e-4 {
             self.rotation = Vector3::zero();
         }
-        self.shift *= decay;
+        self.shift *= pan_decay;
         if self.shift.magnitude() < 1e-4 {
             self.shift = Vector2::zero();
         }
-        self.scroll *= decay;
+        self.scroll *= zoom_decay;
         if self.scroll.abs() < 1e-4 {
             self.scroll = 0.;
         }
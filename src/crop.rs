@@ -0,0 +1,56 @@
+use cgmath::{ElementWise, EuclideanSpace, Point3};
+
+use crate::volume::Aabb;
+
+/// smallest extent of the crop box along an axis (relative to the volume)
+const MIN_EXTENT: f32 = 0.01;
+
+/// box gizmo in the viewport that edits the clipping region of the render settings.
+/// Faces are numbered `2 * axis` for the lower and `2 * axis + 1` for the upper face.
+#[derive(Debug, Clone, Default)]
+pub struct CropBox {
+    /// draws the box with a handle on each face
+    pub show: bool,
+    /// face handle under the pointer, the camera is not rotated when clicking it
+    pub hovered: Option<usize>,
+    /// face that is dragged
+    pub dragged: Option<usize>,
+}
+
+/// clip region (relative to `aabb`) in world space
+pub fn world_aabb(clip: &Aabb<f32>, aabb: &Aabb<f32>) -> Aabb<f32> {
+    let size = aabb.max - aabb.min;
+    Aabb {
+        min: aabb.min + clip.min.to_vec().mul_element_wise(size),
+        max: aabb.min + clip.max.to_vec().mul_element_wise(size),
+    }
+}
+
+/// center of the face of `aabb`
+pub fn face_center(aabb: &Aabb<f32>, face: usize) -> Point3<f32> {
+    let mut center = aabb.center();
+    let axis = face / 2;
+    center[axis] = if face % 2 == 1 {
+        aabb.max[axis]
+    } else {
+        aabb.min[axis]
+    };
+    center
+}
+
+/// moves the face of the normalized clip region by `delta`, keeping it inside [0, 1]
+/// and the lower face below the upper one
+pub fn move_face(clip: &mut Aabb<f32>, face: usize, delta: f32) {
+    let axis = face / 2;
+    if face % 2 == 1 {
+        clip.max[axis] = (clip.max[axis] + delta).clamp(clip.min[axis] + MIN_EXTENT, 1.);
+    } else {
+        clip.min[axis] = (clip.min[axis] + delta).clamp(0., clip.max[axis] - MIN_EXTENT);
+    }
+}
+
+/// sets the lower and upper bound of the normalized clip region along `axis`
+pub fn set_range(clip: &mut Aabb<f32>, axis: usize, min: f32, max: f32) {
+    clip.min[axis] = min.clamp(0., 1. - MIN_EXTENT);
+    clip.max[axis] = max.clamp(clip.min[axis] + MIN_EXTENT, 1.);
+}
@@ -0,0 +1,146 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use npyz::{AutoSerialize, WriterBuilder};
+
+/// data type of the values in a raw volume file (little endian)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawType {
+    U8,
+    F32,
+}
+
+/// small public volume dataset that can be downloaded with [fetch_example]
+#[derive(Debug, Clone, Copy)]
+pub struct ExampleDataset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// raw file with the values in z, y, x order
+    pub url: &'static str,
+    /// size in array order (depth, height, width)
+    pub shape: [usize; 3],
+    pub dtype: RawType,
+}
+
+/// datasets from the Open SciVis Datasets collection (https://klacansky.com/open-scivis-datasets)
+pub const EXAMPLE_DATASETS: &[ExampleDataset] = &[
+    ExampleDataset {
+        name: "ct-head",
+        description: "CT scan of a human skull phantom (256x256x256, 16 MB)",
+        url: "https://klacansky.com/open-scivis-datasets/skull/skull_256x256x256_uint8.raw",
+        shape: [256, 256, 256],
+        dtype: RawType::U8,
+    },
+    ExampleDataset {
+        name: "turbulence",
+        description: "snapshot of an isotropic turbulence simulation (256x256x256, 64 MB)",
+        url: "https://klacansky.com/open-scivis-datasets/tacc_turbulence/tacc_turbulence_256x256x256_float32.raw",
+        shape: [256, 256, 256],
+        dtype: RawType::F32,
+    },
+    ExampleDataset {
+        name: "fuel",
+        description: "simulation of fuel injection into a combustion chamber (64x64x64, 256 KB)",
+        url: "https://klacansky.com/open-scivis-datasets/fuel/fuel_64x64x64_uint8.raw",
+        shape: [64, 64, 64],
+        dtype: RawType::U8,
+    },
+];
+
+impl ExampleDataset {
+    pub fn find(name: &str) -> Option<&'static Self> {
+        EXAMPLE_DATASETS
+            .iter()
+            .find(|d| d.name.eq_ignore_ascii_case(name))
+    }
+
+    /// file the dataset is stored in once it was fetched
+    pub fn cache_path(&self) -> Option<PathBuf> {
+        cache_dir().map(|dir| dir.join(format!("{}.npy", self.name)))
+    }
+}
+
+/// directory the example datasets are downloaded to:
+/// `$VAPE4D_CACHE_DIR` or `vape4d` in the user's cache directory
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("VAPE4D_CACHE_DIR") {
+        return Some(dir.into());
+    }
+    let cache_dir = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+    cache_dir.map(|dir| dir.join("vape4d"))
+}
+
+/// downloads the example dataset `name` (unless it is cached already) and returns the path of
+/// the npy file (shape [1, 1, depth, height, width]).
+/// Uses `curl`, which has to be installed.
+pub fn fetch_example(name: &str) -> anyhow::Result<PathBuf> {
+    let dataset = ExampleDataset::find(name).ok_or_else(|| {
+        let names: Vec<_> = EXAMPLE_DATASETS.iter().map(|d| d.name).collect();
+        anyhow::anyhow!(
+            "unknown example dataset '{name}' (available: {})",
+            names.join(", ")
+        )
+    })?;
+    let path = dataset
+        .cache_path()
+        .ok_or_else(|| anyhow::anyhow!("no cache directory, set VAPE4D_CACHE_DIR"))?;
+    if path.exists() {
+        return Ok(path);
+    }
+    std::fs::create_dir_all(path.parent().unwrap())?;
+
+    let raw_path = path.with_extension("raw.part");
+    log::info!("downloading {} to {}", dataset.url, raw_path.display());
+    let status = Command::new("curl")
+        .args(["--fail", "--location", "--retry", "2", "--output"])
+        .arg(&raw_path)
+        .arg(dataset.url)
+        .status()
+        .map_err(|err| anyhow::anyhow!("failed to run curl (is it installed?): {err}"))?;
+    anyhow::ensure!(status.success(), "failed to download {}", dataset.url);
+
+    let raw = std::fs::read(&raw_path)?;
+    std::fs::remove_file(&raw_path)?;
+    // the npy file is only created once it is complete, so that interrupted downloads are retried
+    let npy_path = path.with_extension("npy.part");
+    match dataset.dtype {
+        RawType::U8 => write_npy(&npy_path, dataset.shape, raw),
+        RawType::F32 => {
+            let values = raw
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+                .collect();
+            write_npy(&npy_path, dataset.shape, values)
+        }
+    }?;
+    std::fs::rename(&npy_path, &path)?;
+    Ok(path)
+}
+
+/// writes `data` with `shape` (depth, height, width) as single timestep and channel
+fn write_npy<T: AutoSerialize>(path: &Path, shape: [usize; 3], data: Vec<T>) -> anyhow::Result<()> {
+    let numel: usize = shape.iter().product();
+    anyhow::ensure!(
+        data.len() == numel,
+        "expected {numel} values, the download contains {}",
+        data.len()
+    );
+    let mut writer = npyz::WriteOptions::new()
+        .default_dtype()
+        .shape(&[1, 1, shape[0] as u64, shape[1] as u64, shape[2] as u64])
+        .writer(BufWriter::new(File::create(path)?))
+        .begin_nd()?;
+    writer.extend(data)?;
+    writer.finish()?;
+    Ok(())
+}
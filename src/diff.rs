@@ -0,0 +1,138 @@
+use image::{ImageBuffer, Rgba};
+
+/// side length of the box window used for SSIM
+const SSIM_WINDOW: u32 = 7;
+
+/// result of the comparison of two images
+pub struct ImageDiff {
+    /// peak signal-to-noise ratio in dB over all channels (infinite for identical images)
+    pub psnr: f64,
+    /// mean structural similarity of the luminance (1 = identical)
+    pub ssim: f64,
+    /// largest absolute difference of any channel
+    pub max_error: u8,
+    /// grayscale version of the first image with the per pixel error drawn on top
+    pub heatmap: ImageBuffer<Rgba<u8>, Vec<u8>>,
+}
+
+/// compares two images of the same size
+pub fn diff_images(
+    a: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    b: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+) -> anyhow::Result<ImageDiff> {
+    if a.dimensions() != b.dimensions() {
+        anyhow::bail!(
+            "image sizes do not match ({}x{} vs {}x{})",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        );
+    }
+    let (width, height) = a.dimensions();
+
+    let mse = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(x, y)| (*x as f64 - *y as f64).powi(2))
+        .sum::<f64>()
+        / a.as_raw().len().max(1) as f64;
+    let psnr = 10. * (255f64.powi(2) / mse).log10();
+
+    let errors: Vec<u8> = a
+        .pixels()
+        .zip(b.pixels())
+        .map(|(p, q)| {
+            p.0.iter()
+                .zip(q.0)
+                .map(|(x, y)| x.abs_diff(y))
+                .max()
+                .unwrap()
+        })
+        .collect();
+    let max_error = errors.iter().copied().max().unwrap_or(0);
+
+    let heatmap = ImageBuffer::from_fn(width, height, |x, y| {
+        let gray = luminance(a.get_pixel(x, y)) * 0.5;
+        let e = if max_error > 0 {
+            errors[(y * width + x) as usize] as f64 / max_error as f64
+        } else {
+            0.
+        };
+        let heat = [
+            (3. * e).clamp(0., 1.),
+            (3. * e - 1.).clamp(0., 1.),
+            (3. * e - 2.).clamp(0., 1.),
+        ];
+        let mut out = [255u8; 4];
+        for (o, h) in out.iter_mut().zip(heat) {
+            *o = ((gray * (1. - e) + h * e) * 255.).round() as u8;
+        }
+        Rgba(out)
+    });
+
+    Ok(ImageDiff {
+        psnr,
+        ssim: ssim(a, b),
+        max_error,
+        heatmap,
+    })
+}
+
+fn luminance(p: &Rgba<u8>) -> f64 {
+    (0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64) / 255.
+}
+
+/// mean SSIM over all windows that lie completely inside the image
+fn ssim(a: &ImageBuffer<Rgba<u8>, Vec<u8>>, b: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> f64 {
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+
+    let (width, height) = a.dimensions();
+    let win = SSIM_WINDOW.min(width).min(height);
+    if win == 0 {
+        return 1.;
+    }
+    let la: Vec<f64> = a.pixels().map(luminance).collect();
+    let lb: Vec<f64> = b.pixels().map(luminance).collect();
+
+    // summed area tables of x, y, x², y² and xy
+    let w = width as usize + 1;
+    let mut table = vec![[0f64; 5]; w * (height as usize + 1)];
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let (va, vb) = (la[y * width as usize + x], lb[y * width as usize + x]);
+            let v = [va, vb, va * va, vb * vb, va * vb];
+            let (above, left, diag) = (
+                table[y * w + x + 1],
+                table[(y + 1) * w + x],
+                table[y * w + x],
+            );
+            table[(y + 1) * w + x + 1] =
+                std::array::from_fn(|i| v[i] + above[i] + left[i] - diag[i]);
+        }
+    }
+    let box_sum = |x: usize, y: usize, i: usize| {
+        let s = win as usize;
+        table[(y + s) * w + x + s][i] - table[y * w + x + s][i] - table[(y + s) * w + x][i]
+            + table[y * w + x][i]
+    };
+
+    let n = (win * win) as f64;
+    let mut total = 0.;
+    let mut count = 0;
+    for y in 0..=(height - win) as usize {
+        for x in 0..=(width - win) as usize {
+            let mu_a = box_sum(x, y, 0) / n;
+            let mu_b = box_sum(x, y, 1) / n;
+            let var_a = box_sum(x, y, 2) / n - mu_a * mu_a;
+            let var_b = box_sum(x, y, 3) / n - mu_b * mu_b;
+            let cov = box_sum(x, y, 4) / n - mu_a * mu_b;
+            total += ((2. * mu_a * mu_b + C1) * (2. * cov + C2))
+                / ((mu_a * mu_a + mu_b * mu_b + C1) * (var_a + var_b + C2));
+            count += 1;
+        }
+    }
+    total / count as f64
+}
@@ -0,0 +1,90 @@
+use cgmath::{InnerSpace, Vector2, Zero};
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+
+use crate::{controller::CameraController, timeline::Timeline};
+
+/// stick deflections below this are ignored
+const DEADZONE: f32 = 0.15;
+/// seconds a held shoulder button takes to scrub through all timesteps
+const SCRUB_DURATION: f32 = 5.;
+
+/// gamepad input: the left stick orbits, the right stick pans, the triggers zoom,
+/// the shoulder buttons scrub through time and the D-pad steps single timesteps.
+/// South (A) plays or pauses the animation and North (Y) resets the view.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+    /// gamepad that sent the last event
+    active: Option<GamepadId>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = Gilrs::new()
+            .map_err(|err| log::warn!("gamepad input is not available: {err}"))
+            .ok();
+        Self {
+            gilrs,
+            active: None,
+        }
+    }
+
+    /// handles the button presses and applies the sticks, triggers and shoulder buttons
+    /// of the active gamepad. Returns true if the view should be reset.
+    pub fn update(
+        &mut self,
+        controller: &mut CameraController,
+        timeline: &mut Timeline,
+        time: &mut f32,
+        timesteps: u32,
+        dt: f32,
+    ) -> bool {
+        let Some(gilrs) = &mut self.gilrs else {
+            return false;
+        };
+        let mut reset = false;
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::Disconnected => {
+                    if self.active == Some(event.id) {
+                        self.active = None;
+                    }
+                    continue;
+                }
+                EventType::ButtonPressed(button, _) => match button {
+                    Button::South => timeline.toggle(time),
+                    Button::North => reset = true,
+                    Button::DPadLeft => timeline.step(time, -1, timesteps),
+                    Button::DPadRight => timeline.step(time, 1, timesteps),
+                    _ => {}
+                },
+                _ => {}
+            }
+            self.active = Some(event.id);
+        }
+        let Some(id) = self.active else {
+            return reset;
+        };
+        let gamepad = gilrs.gamepad(id);
+        let stick = |x: Axis, y: Axis| {
+            // stick up is positive, on screen it is negative
+            let v = Vector2::new(gamepad.value(x), -gamepad.value(y));
+            if v.magnitude() < DEADZONE {
+                Vector2::zero()
+            } else {
+                v
+            }
+        };
+        let button = |b: Button| gamepad.button_data(b).map_or(0., |d| d.value());
+
+        let orbit = stick(Axis::LeftStickX, Axis::LeftStickY);
+        let pan = stick(Axis::RightStickX, Axis::RightStickY);
+        let zoom = button(Button::LeftTrigger2) - button(Button::RightTrigger2);
+        controller.process_gamepad(orbit, pan, zoom, dt);
+
+        let scrub = button(Button::RightTrigger) - button(Button::LeftTrigger);
+        if scrub != 0. && timesteps > 1 {
+            timeline.scrub(time, scrub * dt / SCRUB_DURATION);
+        }
+        reset
+    }
+}
@@ -0,0 +1,347 @@
+use wgpu::util::DeviceExt;
+
+use crate::{
+    readback::BufferMapping,
+    renderer::{Normalization, RenderSettings},
+    volume::{Volume, VolumeGPU},
+};
+
+/// number of bins (must match BINS in histogram.wgsl)
+pub const HISTOGRAM_BINS: u32 = 128;
+
+/// number of values in evenly sized bins between `min` and `max`,
+/// after the values are mapped to [0, 1] with `normalization`.
+/// Values outside of the range are not counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub bins: Vec<u32>,
+    pub min: f32,
+    pub max: f32,
+    pub normalization: Normalization,
+}
+
+impl Histogram {
+    /// computes the histogram of all timesteps (or only `timestep`) on the CPU
+    pub fn compute(
+        volume: &Volume,
+        timestep: Option<usize>,
+        min: f32,
+        max: f32,
+        normalization: Normalization,
+    ) -> Self {
+        let mut bins = vec![0; HISTOGRAM_BINS as usize];
+        let steps = match timestep {
+            Some(t) => t..t + 1,
+            None => 0..volume.timesteps as usize,
+        };
+        for t in steps {
+            for v in volume.timestep(t) {
+                let v = v.to_f32();
+                if (min..=max).contains(&v) {
+                    let x = normalization.normalize(v, min, max);
+                    let bin = (x * HISTOGRAM_BINS as f32) as usize;
+                    bins[bin.min(HISTOGRAM_BINS as usize - 1)] += 1;
+                }
+            }
+        }
+        Self {
+            bins,
+            min,
+            max,
+            normalization,
+        }
+    }
+
+    /// bin heights in [0, 1] on a log scale, so that small peaks stay visible
+    pub fn heights(&self) -> Vec<f32> {
+        let max = self.bins.iter().copied().max().unwrap_or(0).max(1) as f32;
+        self.bins
+            .iter()
+            .map(|c| (*c as f32).ln_1p() / max.ln_1p())
+            .collect()
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct HistogramSettingsUniform {
+    vmin: f32,
+    vmax: f32,
+    normalization: u32,
+    exponent: f32,
+}
+
+/// computes histograms with a compute shader.
+/// The result is read back asynchronously, so the render loop never waits for it.
+pub struct HistogramGPU {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bins: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    /// value range and normalization of the histogram that is currently computed
+    pending: Option<(f32, f32, Normalization)>,
+    mapping: BufferMapping,
+}
+
+impl HistogramGPU {
+    /// returns None if compute shaders are not supported (e.g. WebGL)
+    pub fn new(device: &wgpu::Device, adapter: &wgpu::Adapter) -> Option<Self> {
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return None;
+        }
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/histogram.wgsl"));
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("histogram bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("histogram pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("histogram pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "main",
+            compilation_options: Default::default(),
+        });
+        let size = HISTOGRAM_BINS as u64 * std::mem::size_of::<u32>() as u64;
+        let bins = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("histogram bins"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("histogram readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Some(Self {
+            pipeline,
+            bind_group_layout,
+            bins,
+            readback,
+            pending: None,
+            mapping: BufferMapping::default(),
+        })
+    }
+
+    /// true while a histogram is computed or read back
+    pub fn busy(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// starts computing the histogram of all timesteps (or only `timestep`) of the volume
+    /// in the value `range` (min, max). Does nothing while the last one is not finished.
+    pub fn dispatch(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        volume: &VolumeGPU,
+        timestep: Option<usize>,
+        range: (f32, f32),
+        normalization: Normalization,
+    ) {
+        if self.busy() {
+            return;
+        }
+        let (min, max) = range;
+        let (normalization_id, exponent) = normalization.uniform();
+        let settings = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("histogram settings"),
+            contents: bytemuck::bytes_of(&HistogramSettingsUniform {
+                vmin: min,
+                vmax: max,
+                normalization: normalization_id,
+                exponent,
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let textures = match timestep {
+            Some(t) => &volume.textures[t..t + 1],
+            None => &volume.textures[..],
+        };
+        let bind_groups: Vec<_> = textures
+            .iter()
+            .map(|texture| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("histogram bind group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(
+                                &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                            ),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: self.bins.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: settings.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("histogram encoder"),
+        });
+        encoder.clear_buffer(&self.bins, 0, None);
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("histogram pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            for (texture, bind_group) in textures.iter().zip(&bind_groups) {
+                pass.set_bind_group(0, bind_group, &[]);
+                let size = texture.size();
+                pass.dispatch_workgroups(size.width.div_ceil(8), size.height.div_ceil(8), 1);
+            }
+        }
+        encoder.copy_buffer_to_buffer(&self.bins, 0, &self.readback, 0, self.bins.size());
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.mapping.map(&self.readback);
+        self.pending = Some((min, max, normalization));
+    }
+
+    /// returns the histogram of the last dispatch once it is available
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<Histogram> {
+        let (min, max, normalization) = self.pending?;
+        let result = self.mapping.poll(device)?;
+        self.pending = None;
+        if let Err(err) = result {
+            log::error!("failed to read back histogram: {err}");
+            return None;
+        }
+        let bins = bytemuck::cast_slice(&self.readback.slice(..).get_mapped_range()).to_vec();
+        self.readback.unmap();
+        Some(Histogram {
+            bins,
+            min,
+            max,
+            normalization,
+        })
+    }
+}
+
+/// channel, timestep (None for all), value range and normalization of a histogram
+type HistogramKey = (usize, Option<usize>, f32, f32, Normalization);
+
+/// histogram shown behind the opacity editor.
+/// Recomputed (on the GPU if possible) when the channel, value range or timestep changes.
+pub struct VolumeHistogram {
+    pub enabled: bool,
+    /// only counts the current timestep instead of all
+    pub per_timestep: bool,
+    histogram: Option<(HistogramKey, Histogram)>,
+    /// key of the histogram currently computed on the GPU
+    requested: Option<HistogramKey>,
+    gpu: Option<HistogramGPU>,
+}
+
+impl VolumeHistogram {
+    pub fn new(device: &wgpu::Device, adapter: &wgpu::Adapter) -> Self {
+        Self {
+            enabled: true,
+            per_timestep: false,
+            histogram: None,
+            requested: None,
+            gpu: HistogramGPU::new(device, adapter),
+        }
+    }
+
+    /// the last computed histogram (may lag behind while a new one is computed)
+    pub fn histogram(&self) -> Option<&Histogram> {
+        self.histogram.as_ref().map(|(_, h)| h)
+    }
+
+    /// forgets the histogram, e.g. after a new volume was loaded
+    pub fn reset(&mut self) {
+        self.histogram = None;
+        self.requested = None;
+    }
+
+    /// makes sure the histogram matches the channel and the time, value range and
+    /// normalization of the render `settings`
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        volume: &VolumeGPU,
+        channel: usize,
+        settings: &RenderSettings,
+    ) {
+        let (min, max) = settings.value_range(&volume.volume);
+        let (time, normalization) = (settings.time, settings.normalization);
+        if !self.enabled {
+            return;
+        }
+        let timestep = self
+            .per_timestep
+            .then(|| ((volume.volume.timesteps - 1) as f32 * time) as usize);
+        let key = (channel, timestep, min, max, normalization);
+        match &mut self.gpu {
+            Some(gpu) => {
+                if let Some(histogram) = gpu.poll(device) {
+                    self.histogram = self.requested.take().map(|k| (k, histogram));
+                }
+                if self.histogram.as_ref().map(|(k, _)| *k) != Some(key) && !gpu.busy() {
+                    gpu.dispatch(device, queue, volume, timestep, (min, max), normalization);
+                    self.requested = Some(key);
+                }
+            }
+            None => {
+                if self.histogram.as_ref().map(|(k, _)| *k) != Some(key) {
+                    let histogram =
+                        Histogram::compute(&volume.volume, timestep, min, max, normalization);
+                    self.histogram = Some((key, histogram));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{
+    annotations::ReferenceValue,
+    cmap::{LinearSegmentedColorMap, TransferFunction},
+    volume::Aabb,
+    WindowContext,
+};
+
+/// maximum number of edits that can be undone
+const MAX_HISTORY: usize = 100;
+
+/// the settings covered by undo/redo: transfer function, value range, surfaces and clipping
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Edits {
+    cmap: LinearSegmentedColorMap,
+    opacity: TransferFunction,
+    vmin: Option<f32>,
+    vmax: Option<f32>,
+    reference_values: Vec<ReferenceValue>,
+    clipping_aabb: Option<Aabb<f32>>,
+}
+
+impl Edits {
+    pub fn of(state: &WindowContext) -> Self {
+        Self {
+            cmap: state.cmap.clone(),
+            opacity: state.opacity.clone(),
+            vmin: state.render_settings.vmin,
+            vmax: state.render_settings.vmax,
+            reference_values: state.reference_values.clone(),
+            clipping_aabb: state.render_settings.clipping_aabb,
+        }
+    }
+
+    pub fn apply(self, state: &mut WindowContext) {
+        state.cmap = self.cmap;
+        state.opacity = self.opacity;
+        state.render_settings.vmin = self.vmin;
+        state.render_settings.vmax = self.vmax;
+        state.reference_values = self.reference_values;
+        state.render_settings.clipping_aabb = self.clipping_aabb;
+    }
+}
+
+/// undo and redo stacks of the edits
+#[derive(Debug, Default)]
+pub(crate) struct EditHistory {
+    undo: Vec<Edits>,
+    redo: Vec<Edits>,
+    /// last recorded state
+    current: Option<Edits>,
+}
+
+impl EditHistory {
+    /// adds `edits` as a new entry if they differ from the last recorded state.
+    /// Called once per frame while nothing is dragged, so that a drag is a single entry.
+    pub fn record(&mut self, edits: Edits) {
+        match self.current.replace(edits) {
+            Some(last) if Some(&last) != self.current.as_ref() => {
+                if self.undo.len() >= MAX_HISTORY {
+                    self.undo.remove(0);
+                }
+                self.undo.push(last);
+                self.redo.clear();
+            }
+            _ => {}
+        }
+    }
+
+    /// the state before the last edit, `current` is the state that is replaced
+    pub fn undo(&mut self, current: Edits) -> Option<Edits> {
+        let edits = self.undo.pop()?;
+        self.redo.push(current);
+        self.current = Some(edits.clone());
+        Some(edits)
+    }
+
+    /// the state before the last undo, `current` is the state that is replaced
+    pub fn redo(&mut self, current: Edits) -> Option<Edits> {
+        let edits = self.redo.pop()?;
+        self.undo.push(current);
+        self.current = Some(edits.clone());
+        Some(edits)
+    }
+}
@@ -0,0 +1,299 @@
+use cgmath::{InnerSpace, Point3, Quaternion, Rotation, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::{Camera, OrthographicProjection},
+    renderer::RenderSettings,
+    volume::Aabb,
+};
+
+/// camera pose, volume time and settings at one point of a camera animation.
+/// The pose is relative to the volume, so keyframes can be reused with the offline renderer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keyframe {
+    /// position of the keyframe in the camera animation in seconds
+    pub at: f32,
+    /// direction from the center to the camera
+    pub direction: [f32; 3],
+    pub up: [f32; 3],
+    /// magnification, 1 fits the volume into the image
+    pub zoom: f32,
+    /// point the camera looks at, relative to the center of the volume
+    pub center: [f32; 3],
+    /// normalized time of the volume
+    pub time: f32,
+    pub vmin: Option<f32>,
+    pub vmax: Option<f32>,
+    pub distance_scale: f32,
+    /// value of the first surface, if one is drawn
+    pub iso_value: Option<f32>,
+}
+
+impl Default for Keyframe {
+    fn default() -> Self {
+        Self {
+            at: 0.,
+            direction: [1., -1., 1.],
+            up: [0., 1., 0.],
+            zoom: 1.,
+            center: [0.; 3],
+            time: 0.,
+            vmin: None,
+            vmax: None,
+            distance_scale: 1.,
+            iso_value: None,
+        }
+    }
+}
+
+impl Keyframe {
+    /// keyframe at `at` seconds with the pose of `camera`, which rotates around `center`
+    pub fn new(
+        at: f32,
+        camera: &Camera<OrthographicProjection>,
+        center: Point3<f32>,
+        aabb: &Aabb<f32>,
+        settings: &RenderSettings,
+        iso_value: Option<f32>,
+    ) -> Self {
+        let inverse = camera.rotation.invert();
+        let forward = inverse.rotate_vector(Vector3::unit_z());
+        let up = inverse.rotate_vector(Vector3::unit_y());
+        Self {
+            at,
+            direction: (-forward).into(),
+            up: up.into(),
+            zoom: 2. * aabb.radius() / camera.projection.viewport.y,
+            center: (center - aabb.center()).into(),
+            time: settings.time,
+            vmin: settings.vmin,
+            vmax: settings.vmax,
+            distance_scale: settings.distance_scale,
+            iso_value,
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let direction = Vector3::from(self.direction);
+        let up = Vector3::from(self.up);
+        anyhow::ensure!(
+            direction.magnitude2() > 0. && up.magnitude2() > 0.,
+            "keyframe direction and up must not be zero"
+        );
+        anyhow::ensure!(
+            direction.normalize().cross(up.normalize()).magnitude2() > 1e-6,
+            "keyframe direction and up must not be parallel"
+        );
+        anyhow::ensure!(self.zoom > 0., "keyframe zoom must be positive");
+        Ok(())
+    }
+
+    /// point the camera rotates around
+    pub fn center(&self, aabb: &Aabb<f32>) -> Point3<f32> {
+        aabb.center() + Vector3::from(self.center)
+    }
+
+    /// camera with the pose of the keyframe for an image of `resolution`
+    pub fn camera(
+        &self,
+        aabb: &Aabb<f32>,
+        resolution: Vector2<u32>,
+    ) -> Camera<OrthographicProjection> {
+        let radius = aabb.radius();
+        let direction = Vector3::from(self.direction).normalize();
+        let rotation = Quaternion::look_at(-direction, Vector3::from(self.up));
+        let ratio = resolution.x as f32 / resolution.y.max(1) as f32;
+        Camera::new(
+            self.center(aabb) + direction * radius * 2.8,
+            rotation,
+            OrthographicProjection::new(
+                Vector2::new(ratio, 1.) * radius * 2. / self.zoom,
+                0.01,
+                1000.,
+            ),
+        )
+    }
+
+    /// sets the time, value range and distance scale of `settings`
+    pub fn apply_settings(&self, settings: &mut RenderSettings) {
+        settings.time = self.time;
+        settings.vmin = self.vmin;
+        settings.vmax = self.vmax;
+        settings.distance_scale = self.distance_scale;
+    }
+}
+
+/// keyframe at `at` seconds, interpolated with a cubic spline through all keyframes.
+/// The keyframes have to be sorted by their position, None if there are none.
+pub fn sample(keyframes: &[Keyframe], at: f32) -> Option<Keyframe> {
+    let (first, last) = (keyframes.first()?, keyframes.last()?);
+    if at <= first.at {
+        return Some(Keyframe {
+            at,
+            ..first.clone()
+        });
+    }
+    if at >= last.at {
+        return Some(Keyframe { at, ..last.clone() });
+    }
+    let i = keyframes.partition_point(|k| k.at <= at) - 1;
+    let (k0, k1) = (&keyframes[i], &keyframes[i + 1]);
+    let s = if k1.at > k0.at {
+        (at - k0.at) / (k1.at - k0.at)
+    } else {
+        1.
+    };
+    let spline = |f: &dyn Fn(&Keyframe) -> f32| spline(keyframes, i, s, f);
+    // optional values are only interpolated if all keyframes have one
+    let optional = |f: &dyn Fn(&Keyframe) -> Option<f32>| {
+        if keyframes.iter().all(|k| f(k).is_some()) {
+            Some(spline(&|k| f(k).unwrap()))
+        } else {
+            f(k0)
+        }
+    };
+    let vector = |f: &dyn Fn(&Keyframe) -> [f32; 3]| {
+        Vector3::new(
+            spline(&|k| f(k)[0]),
+            spline(&|k| f(k)[1]),
+            spline(&|k| f(k)[2]),
+        )
+    };
+    // directions are interpolated componentwise and normalized again
+    let unit = |f: &dyn Fn(&Keyframe) -> [f32; 3]| {
+        let v = vector(f);
+        if v.magnitude2() > 1e-8 {
+            v.normalize().into()
+        } else {
+            f(k0)
+        }
+    };
+    Some(Keyframe {
+        at,
+        direction: unit(&|k| Vector3::from(k.direction).normalize().into()),
+        up: unit(&|k| Vector3::from(k.up).normalize().into()),
+        zoom: spline(&|k| k.zoom.ln()).exp(),
+        center: vector(&|k| k.center).into(),
+        time: spline(&|k| k.time).clamp(0., 1.),
+        vmin: optional(&|k| k.vmin),
+        vmax: optional(&|k| k.vmax),
+        distance_scale: spline(&|k| k.distance_scale).max(0.),
+        iso_value: optional(&|k| k.iso_value),
+    })
+}
+
+/// value of the cubic Hermite spline between keyframe `i` and `i + 1` at `s` in [0, 1].
+/// The tangents are estimated from the neighbouring keyframes (Catmull-Rom).
+fn spline(keyframes: &[Keyframe], i: usize, s: f32, f: impl Fn(&Keyframe) -> f32) -> f32 {
+    let tangent = |j: usize| {
+        let (a, b) = (
+            &keyframes[j.saturating_sub(1)],
+            &keyframes[(j + 1).min(keyframes.len() - 1)],
+        );
+        if b.at > a.at {
+            (f(b) - f(a)) / (b.at - a.at)
+        } else {
+            0.
+        }
+    };
+    let dt = keyframes[i + 1].at - keyframes[i].at;
+    let (p0, p1) = (f(&keyframes[i]), f(&keyframes[i + 1]));
+    let (m0, m1) = (tangent(i) * dt, tangent(i + 1) * dt);
+    let (s2, s3) = (s * s, s * s * s);
+    (2. * s3 - 3. * s2 + 1.) * p0
+        + (s3 - 2. * s2 + s) * m0
+        + (-2. * s3 + 3. * s2) * p1
+        + (s3 - s2) * m1
+}
+
+/// positions (in seconds) of the frames of a camera animation rendered at `fps`
+pub fn frame_positions(keyframes: &[Keyframe], fps: f32) -> Vec<f32> {
+    let (Some(first), Some(last)) = (keyframes.first(), keyframes.last()) else {
+        return Vec::new();
+    };
+    let num_frames = ((last.at - first.at) * fps.max(1e-3)).round() as usize + 1;
+    (0..num_frames)
+        .map(|i| first.at + i as f32 / fps.max(1e-3))
+        .collect()
+}
+
+/// camera animation of the viewer, played independently of the time animation
+#[derive(Debug, Default)]
+pub struct CameraAnimation {
+    /// sorted by their position
+    pub keyframes: Vec<Keyframe>,
+    pub playing: bool,
+    /// starts again at the first keyframe after the last one
+    pub looped: bool,
+    /// current position in seconds
+    pub position: f32,
+    /// index of the keyframe selected in the timeline
+    pub selected: Option<usize>,
+}
+
+impl CameraAnimation {
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        keyframes.sort_by(|a, b| a.at.total_cmp(&b.at));
+        Self {
+            keyframes,
+            ..Default::default()
+        }
+    }
+
+    /// start and end of the animation in seconds
+    pub fn range(&self) -> (f32, f32) {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => (first.at, last.at),
+            _ => (0., 0.),
+        }
+    }
+
+    /// adds the keyframe, a keyframe at the same position is replaced
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        self.keyframes.retain(|k| (k.at - keyframe.at).abs() > 1e-3);
+        let at = keyframe.at;
+        self.keyframes.push(keyframe);
+        self.sort();
+        self.selected = self.keyframes.iter().position(|k| k.at == at);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.keyframes.remove(index);
+        self.selected = None;
+    }
+
+    /// restores the order after positions were edited
+    pub fn sort(&mut self) {
+        self.keyframes.sort_by(|a, b| a.at.total_cmp(&b.at));
+    }
+
+    /// moves the playhead by `dt` seconds if the animation is playing and returns the
+    /// interpolated keyframe
+    pub fn advance(&mut self, dt: f32) -> Option<Keyframe> {
+        if !self.playing || self.keyframes.is_empty() {
+            return None;
+        }
+        let (start, end) = self.range();
+        self.position += dt;
+        if self.position >= end {
+            if self.looped && end > start {
+                self.position = start + (self.position - start) % (end - start);
+            } else {
+                self.position = end;
+                self.playing = false;
+            }
+        }
+        self.position = self.position.max(start);
+        sample(&self.keyframes, self.position)
+    }
+
+    /// starts or pauses playback, a finished animation starts again at the first keyframe
+    pub fn toggle(&mut self) {
+        self.playing = !self.playing;
+        let (start, end) = self.range();
+        if self.playing && self.position >= end {
+            self.position = start;
+        }
+    }
+}
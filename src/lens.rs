@@ -0,0 +1,191 @@
+use cgmath::{EuclideanSpace, Point3, SquareMatrix, Vector2, Vector4};
+
+use crate::{
+    blit::{Blitter, RenderTarget},
+    camera::{Camera, OrthographicProjection},
+    lines,
+    renderer::RenderSettings,
+};
+
+/// circular magnifier that re-renders the region under the cursor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lens {
+    pub enabled: bool,
+    /// radius in physical pixels
+    pub radius: f32,
+    pub zoom: f32,
+    /// samples per step of the main view
+    pub sampling_rate: f32,
+    /// value range used inside the lens (None uses the one of the main view)
+    pub vmin: Option<f32>,
+    pub vmax: Option<f32>,
+}
+
+impl Default for Lens {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 150.,
+            zoom: 2.,
+            sampling_rate: 2.,
+            vmin: None,
+            vmax: None,
+        }
+    }
+}
+
+/// what the lens shows in the current frame
+pub struct LensView {
+    /// index of the volume under the cursor
+    pub volume: usize,
+    pub camera: Camera<OrthographicProjection>,
+    /// center of the lens on the screen in physical pixels
+    pub center: Vector2<f32>,
+    pub radius: f32,
+}
+
+/// volume, size and upper left corner of the view at a cursor position (in physical pixels).
+/// The window is divided into a grid of `columns` with one volume per cell
+/// unless a single volume is `selected`.
+pub(crate) fn cell_at(
+    cursor: Vector2<f32>,
+    window_size: Vector2<f32>,
+    columns: usize,
+    num_volumes: usize,
+    selected: Option<usize>,
+) -> Option<(usize, Vector2<f32>, Vector2<f32>)> {
+    if cursor.x < 0. || cursor.y < 0. || cursor.x >= window_size.x || cursor.y >= window_size.y {
+        return None;
+    }
+    if let Some(selected) = selected {
+        return Some((selected, window_size, Vector2::new(0., 0.)));
+    }
+    let rows = num_volumes.div_ceil(columns);
+    let cell_size = Vector2::new(window_size.x / columns as f32, window_size.y / rows as f32);
+    let column = (cursor.x / cell_size.x) as usize;
+    let row = (cursor.y / cell_size.y) as usize;
+    let volume = row * columns + column;
+    if volume >= num_volumes {
+        return None;
+    }
+    Some((
+        volume,
+        cell_size,
+        Vector2::new(column as f32 * cell_size.x, row as f32 * cell_size.y),
+    ))
+}
+
+impl Lens {
+    /// computes the lens for a cursor position (in physical pixels).
+    /// The window is divided into a grid of `columns` with one volume per cell
+    /// unless a single volume is `selected`.
+    pub fn view(
+        &self,
+        camera: &Camera<OrthographicProjection>,
+        cursor: Vector2<f32>,
+        window_size: Vector2<f32>,
+        columns: usize,
+        num_volumes: usize,
+        selected: Option<usize>,
+    ) -> Option<LensView> {
+        let (volume, cell_size, cell_min) =
+            cell_at(cursor, window_size, columns, num_volumes, selected)?;
+        let radius = self
+            .radius
+            .min(window_size.x / 2.)
+            .min(window_size.y / 2.)
+            .floor();
+        if radius < 1. {
+            return None;
+        }
+
+        let mut cell_camera = *camera;
+        cell_camera
+            .projection
+            .resize(cell_size.x as u32, cell_size.y as u32);
+
+        // move the camera (orthographic) so that the point under the cursor is centered
+        let p = cursor - cell_min;
+        let ndc = Vector4::new(
+            p.x / cell_size.x * 2. - 1.,
+            1. - p.y / cell_size.y * 2.,
+            0.,
+            1.,
+        );
+        let inv_view_proj = cell_camera.view_proj_matrix().invert()?;
+        let unproject = |v: Vector4<f32>| {
+            let w = inv_view_proj * v;
+            Point3::from_vec(w.truncate() / w.w)
+        };
+        let offset = unproject(ndc) - unproject(Vector4::new(0., 0., 0., 1.));
+
+        let mut lens_camera = cell_camera;
+        lens_camera.position += offset;
+        let viewport = cell_camera.projection.viewport;
+        lens_camera.projection.viewport = Vector2::new(
+            viewport.x * 2. * radius / cell_size.x,
+            viewport.y * 2. * radius / cell_size.y,
+        ) / self.zoom.max(1.);
+
+        Some(LensView {
+            volume,
+            camera: lens_camera,
+            center: Vector2::new(
+                cursor.x.clamp(radius, window_size.x - radius),
+                cursor.y.clamp(radius, window_size.y - radius),
+            ),
+            radius,
+        })
+    }
+
+    /// render settings inside the lens, derived from the ones of the main view
+    pub fn render_settings(&self, settings: &RenderSettings) -> RenderSettings {
+        let mut settings = settings.clone();
+        settings.step_size /= self.sampling_rate.max(1.);
+        settings.vmin = self.vmin.or(settings.vmin);
+        settings.vmax = self.vmax.or(settings.vmax);
+        settings.jitter = Vector2::new(0., 0.);
+        settings.ray_offset = 0.;
+        settings
+    }
+}
+
+/// color and depth buffer the lens is rendered into
+pub struct LensTarget {
+    pub color: RenderTarget,
+    pub depth: wgpu::TextureView,
+}
+
+impl LensTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        blitter: &Blitter,
+        format: wgpu::TextureFormat,
+        size: u32,
+    ) -> Self {
+        let depth = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("lens depth buffer"),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: lines::DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            color: RenderTarget::new(device, blitter, format, size, size),
+            depth,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.color.size().0
+    }
+}
@@ -1,8 +1,30 @@
-use camera::{Camera, OrthographicProjection};
-use cmap::LinearSegmentedColorMap;
+use annotations::{Annotations, Colorbar, Label, ReferenceValue};
+use background::{Background, BackgroundRenderer};
+use bookmarks::{Bookmark, Bookmarks};
+use camera::{Camera, CameraView, OrthographicProjection};
+use cmap::{ColorMapSequence, GenericColorMap, LinearSegmentedColorMap, TransferFunction};
+use compare::Comparison;
 use controller::CameraController;
-use renderer::{RenderSettings, VolumeRenderer};
-use std::{path::PathBuf, sync::Arc};
+use crop::CropBox;
+use keyframes::{CameraAnimation, Keyframe};
+use lens::{Lens, LensTarget};
+use lines::{LineRenderer, OverlaySettings};
+use measure::Measurement;
+use pacing::FramePacing;
+use probe::Probe;
+use profiler::{FrameStats, GpuTimer};
+use renderer::{Interpolation, RenderSettings, VolumeRenderer};
+use resources::ResourceRegistry;
+use session::{Session, SessionRender};
+use std::{
+    cell::RefCell,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+};
+use stereo::{Eye, Stereo, StereoMode};
+use timeline::Timeline;
 use volume::VolumeGPU;
 
 #[cfg(target_arch = "wasm32")]
@@ -17,25 +39,70 @@ mod web;
 #[cfg(target_arch = "wasm32")]
 pub use web::*;
 
-use cgmath::{Vector2, Zero};
+use cgmath::{
+    ElementWise, InnerSpace, Rad, Rotation, SquareMatrix, Vector2, Vector3, Vector4, Zero,
+};
 use winit::{
     dpi::PhysicalSize,
     event::{DeviceEvent, ElementState, Event, WindowEvent},
-    event_loop::EventLoop,
+    event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget},
     keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowBuilder},
 };
 
 use crate::{
-    cmap::{ColorMapGPU, COLORMAP_RESOLUTION},
-    volume::Volume,
+    accumulation::{TemporalAccumulation, ACCUMULATION_FORMAT},
+    blit::{Blitter, RenderTarget},
+    cmap::{ColorMap, ColorMapGPU, COLORMAP_RESOLUTION},
+    config::UiConfig,
+    histogram::VolumeHistogram,
+    history::{EditHistory, Edits},
+    screenshot::{ScreenshotTarget, Screenshots},
+    statistics::VolumeStatistics,
+    volume::{Aabb, InvalidCounts, Mask, Volume},
 };
 
+mod accumulation;
+pub mod annotations;
+pub mod background;
+mod blit;
+pub mod bookmarks;
 pub mod camera;
 pub mod cmap;
+mod compare;
+pub mod config;
 mod controller;
+mod crop;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod datasets;
+pub mod diff;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+pub mod histogram;
+mod history;
+pub mod keyframes;
+mod lens;
+mod lines;
+mod measure;
 pub mod offline;
+mod pacing;
+mod panorama;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod presets;
+mod probe;
+mod profiler;
+mod readback;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod recording;
+pub mod reference;
 pub mod renderer;
+pub mod resources;
+mod screenshot;
+pub mod session;
+pub mod spec;
+pub mod statistics;
+pub mod stereo;
+mod timeline;
 mod ui;
 mod ui_renderer;
 mod viewer;
@@ -49,29 +116,183 @@ pub mod py;
 pub struct RenderConfig {
     pub no_vsync: bool,
     pub background_color: wgpu::Color,
+    pub background: Background,
+    /// equirectangular image used for `Background::Environment`
+    pub environment_map: Option<PathBuf>,
+    /// scale bar and axis ticks
+    pub annotations: Annotations,
+    pub stereo: Stereo,
+    /// time-varying colormap whose colors are used instead of the colormap's
+    pub cmap_sequence: Option<ColorMapSequence>,
+    /// named values marked on the legend
+    pub reference_values: Vec<ReferenceValue>,
+    /// legend of the colormap painted into the frame
+    pub colorbar: Option<Colorbar>,
     pub show_colormap_editor: bool,
     pub show_volume_info: bool,
     pub vmin: Option<f32>,
     pub vmax: Option<f32>,
     pub distance_scale: f32,
+    pub spatial_interpolation: Interpolation,
+    pub temporal_interpolation: Interpolation,
+    /// initial view of the volume
+    pub camera: CameraView,
+    /// fraction of the window resolution the volume is rendered at
+    pub render_scale: f32,
     #[cfg(feature = "colormaps")]
     pub show_cmap_select: bool,
+    /// time it takes to play from the first to the last timestep
     pub duration: Option<Duration>,
+    /// playback speed in timesteps per second, used instead of `duration`
+    pub fps: Option<f32>,
+    /// plays the animation from the last to the first timestep
+    pub reverse_playback: bool,
+    /// shows whole timesteps only instead of interpolating between them
+    pub snap_timesteps: bool,
+    /// json file the camera bookmarks are loaded from and saved to
+    pub bookmarks_file: Option<PathBuf>,
+    /// camera animation played in the viewer
+    pub keyframes: Vec<Keyframe>,
+    /// file the volume was loaded from, referenced by saved sessions
+    pub volume_file: Option<PathBuf>,
+    /// npy or npz file the mask applied to the volume was loaded from, referenced by saved sessions
+    pub mask_file: Option<PathBuf>,
+    /// the mask hides the masked voxels instead of the others
+    pub invert_mask: bool,
+    /// json file the transfer function (colormap with the opacity as alpha channel)
+    /// is written to when the window is closed
+    pub transfer_function_file: Option<PathBuf>,
+    /// render settings like the render section of a session, applied before `session`
+    pub render_settings: Option<SessionRender>,
+    /// viewer state restored once the window is open
+    pub session: Option<Session>,
+    /// second dataset shown next to the volume
+    pub compare_file: Option<PathBuf>,
+    /// shows the difference of the volume and `compare_file`
+    pub difference: bool,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            no_vsync: false,
+            background_color: wgpu::Color::BLACK,
+            background: Background::Solid,
+            environment_map: None,
+            annotations: Annotations::default(),
+            stereo: Stereo::default(),
+            cmap_sequence: None,
+            reference_values: Vec::new(),
+            colorbar: None,
+            show_colormap_editor: true,
+            show_volume_info: true,
+            vmin: None,
+            vmax: None,
+            distance_scale: 1.0,
+            spatial_interpolation: Interpolation::Linear,
+            temporal_interpolation: Interpolation::Linear,
+            camera: CameraView::default(),
+            render_scale: 1.0,
+            #[cfg(feature = "colormaps")]
+            show_cmap_select: true,
+            duration: None,
+            fps: None,
+            reverse_playback: false,
+            snap_timesteps: false,
+            bookmarks_file: None,
+            keyframes: Vec::new(),
+            volume_file: None,
+            mask_file: None,
+            invert_mask: false,
+            render_settings: None,
+            transfer_function_file: None,
+            session: None,
+            compare_file: None,
+            difference: false,
+        }
+    }
+}
+
+/// volumes, colormap and settings of a window opened with [open_windows]
+pub struct ViewerWindow {
+    pub volumes: Vec<Volume>,
+    pub cmap: LinearSegmentedColorMap,
+    pub config: RenderConfig,
+}
+
+impl ViewerWindow {
+    /// reads a session file (json) or a volume (npy or npz) that is shown with `cmap`
+    /// and the default settings
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &Path, cmap: LinearSegmentedColorMap) -> anyhow::Result<Self> {
+        let read = |file: &Path| {
+            let reader = std::io::BufReader::new(std::fs::File::open(file)?);
+            Volume::load_numpy(reader, true)
+        };
+        if path.extension().is_some_and(|ext| ext == "json") {
+            let session = Session::read(path)?;
+            let base_dir = path.parent().unwrap_or(Path::new("."));
+            let file = session
+                .volume_file(base_dir)
+                .ok_or_else(|| anyhow::anyhow!("the session has no volume file"))?;
+            let mut volumes = read(&file)?;
+            volumes.iter_mut().for_each(|v| session.apply_spacing(v));
+            session.load_mask(base_dir, &mut volumes)?;
+            let mut config = session.render_config(base_dir);
+            config.volume_file = Some(file);
+            return Ok(Self {
+                volumes,
+                cmap: session.colormap.clone(),
+                config,
+            });
+        }
+        Ok(Self {
+            volumes: read(path)?,
+            cmap,
+            config: RenderConfig {
+                bookmarks_file: Some(bookmarks::bookmarks_file(path)),
+                volume_file: Some(path.to_path_buf()),
+                ..Default::default()
+            },
+        })
+    }
 }
 
+/// render scale used for the preview while interacting
+const PREVIEW_RENDER_SCALE: f32 = 0.5;
+/// step size multiplier used for the preview while interacting
+const PREVIEW_STEP_SIZE_FACTOR: f32 = 2.;
+
+/// device and shared resources, shared by windows and offline renders as `Arc<WGPUContext>`
 pub struct WGPUContext {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub adapter: wgpu::Adapter,
+    /// colormaps and pipelines shared by all users of the context
+    pub resources: Arc<Mutex<ResourceRegistry>>,
+}
+
+thread_local! {
+    /// headless context of the offline renders on this thread while any of them is alive
+    static HEADLESS_CONTEXT: RefCell<Weak<WGPUContext>> = RefCell::new(Weak::new());
 }
 
 impl WGPUContext {
     pub async fn new(instance: &wgpu::Instance, surface: Option<&wgpu::Surface<'static>>) -> Self {
+        Self::try_new(instance, surface).await.unwrap()
+    }
+
+    /// like `new`, but returns an error if no adapter or device is available
+    pub async fn try_new(
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface<'static>>,
+    ) -> anyhow::Result<Self> {
         let adapter = wgpu::util::initialize_adapter_from_env_or_default(instance, surface)
             .await
-            .unwrap();
+            .ok_or_else(|| anyhow::anyhow!("no suitable GPU adapter found"))?;
 
-        let required_features = wgpu::Features::default();
+        // timestamps are used for the GPU timings of the performance overlay if supported
+        let required_features = adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
 
         let (device, queue) = adapter
             .request_device(
@@ -82,40 +303,167 @@ impl WGPUContext {
                 },
                 None,
             )
-            .await
-            .unwrap();
+            .await?;
 
-        Self {
+        Ok(Self {
             device,
             queue,
             adapter,
+            resources: Default::default(),
+        })
+    }
+
+    /// returns the headless context of this thread, creating it if there is none in use
+    pub async fn shared() -> anyhow::Result<Arc<Self>> {
+        if let Some(context) = HEADLESS_CONTEXT.with(|c| c.borrow().upgrade()) {
+            return Ok(context);
         }
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let context = Arc::new(Self::try_new(&instance, None).await?);
+        HEADLESS_CONTEXT.with(|c| *c.borrow_mut() = Arc::downgrade(&context));
+        Ok(context)
     }
 }
 
+/// everything that influences the volume rendering of a frame.
+/// Used to detect when accumulated frames become invalid.
+#[derive(PartialEq)]
+struct FrameState {
+    camera: Camera<OrthographicProjection>,
+    settings: RenderSettings,
+    cmap_hash: u64,
+    background: (wgpu::Color, Background),
+    selected_channel: Option<usize>,
+    num_columns: u32,
+    resolution: (u32, u32),
+    overlay: OverlaySettings,
+    stereo: Stereo,
+}
+
 pub struct WindowContext {
-    wgpu_context: WGPUContext,
+    wgpu_context: Arc<WGPUContext>,
     surface: wgpu::Surface<'static>,
     config: wgpu::SurfaceConfiguration,
+    /// sRGB (or linear float) format everything is rendered in
+    render_format: wgpu::TextureFormat,
+    /// copies the image to surfaces without sRGB formats or views
+    encode_blitter: Option<Blitter>,
+    encode_target: Option<RenderTarget>,
     window: Arc<Window>,
     scale_factor: f32,
+    pacing: FramePacing,
+    /// GPU time of the render passes, measured while the performance overlay is shown
+    gpu_timer: GpuTimer,
+    /// rays and samples of the last frame
+    frame_stats: FrameStats,
+    /// shows frame time, GPU timings, samples and memory usage (F3)
+    performance_overlay: bool,
 
     controller: CameraController,
+    #[cfg(feature = "gamepad")]
+    gamepads: gamepad::Gamepads,
     camera: Camera<OrthographicProjection>,
     ui_renderer: ui_renderer::EguiWGPU,
     ui_visible: bool,
+    /// scale, theme and font size of the user interface, saved in the config file
+    ui_config: UiConfig,
 
     background_color: wgpu::Color,
+    background: Background,
+    background_renderer: BackgroundRenderer,
 
     volumes: Vec<VolumeGPU>,
-    renderer: VolumeRenderer,
+    /// shared with other users of the context
+    renderer: Arc<Mutex<VolumeRenderer>>,
+    line_renderer: LineRenderer,
+    overlay: OverlaySettings,
+    annotations: Annotations,
+    /// first hit depth of the volume rendering, used to depth test the overlay lines
+    depth_buffer: Option<wgpu::Texture>,
+    blitter: Blitter,
+    render_target: Option<RenderTarget>,
+    lens: Lens,
+    lens_blitter: Blitter,
+    lens_target: Option<LensTarget>,
+    stereo: Stereo,
+    anaglyph_blitter: Blitter,
+    /// left eye of the anaglyph
+    stereo_target: Option<RenderTarget>,
+    /// last cursor position in physical pixels
+    cursor_position: Option<Vector2<f32>>,
+    /// clicks into the volume set the value of a surface instead of rotating the camera
+    pick_surface: bool,
+    /// clicks into the volume read the voxel there instead of rotating the camera
+    probe_mode: bool,
+    probe: Option<Probe>,
+    /// direction the volume is looked at from after a camera reset
+    initial_view: CameraView,
+    /// points picked in measurement mode
+    measure: Measurement,
+    /// clicks into the volume place a text label instead of rotating the camera
+    label_mode: bool,
+    crop_box: CropBox,
+    shift_pressed: bool,
+    control_pressed: bool,
+    screenshots: Screenshots,
+    #[cfg(not(target_arch = "wasm32"))]
+    recorder: recording::Recorder,
+    render_scale: f32,
+    taa: TemporalAccumulation<FrameState>,
+    /// shows the last volume rendering again while nothing changed instead of raymarching
+    reuse_frames: bool,
+    /// state of the volume rendering in the render target (without anti-aliasing)
+    last_frame: Option<FrameState>,
+    /// render with reduced quality while the camera is moving
+    interactive_preview: bool,
+    interacting: bool,
 
     render_settings: RenderSettings,
     cmap_gpu: cmap::ColorMapGPU,
+    /// colors of the colormap, its alpha channel is replaced by `opacity`
     cmap: LinearSegmentedColorMap,
+    /// time-varying colors used instead of `cmap`, its alpha channels are replaced by `opacity`
+    cmap_sequence: Option<ColorMapSequence>,
+    opacity: TransferFunction,
+    /// value distribution shown behind the opacity editor
+    histogram: VolumeHistogram,
+    /// value distribution over the whole value range, shown behind the vmin/vmax slider
+    range_histogram: VolumeHistogram,
+    /// logarithmic value axis of the vmin/vmax slider
+    range_log_scale: bool,
+    /// invalid values of the current timestep, shown while they are highlighted.
+    /// Computed for the channel, timestep, vmin and vmax in the key.
+    invalid_counts: Option<((usize, usize, f32, f32), InvalidCounts)>,
+    /// statistics of the channels shown in the volume info window
+    statistics: VolumeStatistics,
+    reference_values: Vec<ReferenceValue>,
+    /// undo/redo of transfer function, value range, surface and clipping edits
+    history: EditHistory,
+    /// user colormap presets by name
+    #[cfg(not(target_arch = "wasm32"))]
+    colormap_presets: Vec<(String, GenericColorMap)>,
 
-    playing: bool,
-    animation_duration: Duration,
+    timeline: Timeline,
+    /// saved camera poses, recalled with Shift+1 to Shift+9
+    bookmarks: Bookmarks,
+    /// file the volume was loaded from
+    volume_file: Option<PathBuf>,
+    /// file the mask applied to the volume was loaded from
+    mask_file: Option<PathBuf>,
+    invert_mask: bool,
+    /// file the environment image of the background was loaded from
+    environment_file: Option<PathBuf>,
+    /// file the transfer function is written to when the window is closed
+    #[cfg(not(target_arch = "wasm32"))]
+    transfer_function_file: Option<PathBuf>,
+    /// files to open in new windows, handled by the event loop
+    #[cfg(not(target_arch = "wasm32"))]
+    open_requests: Vec<PathBuf>,
+    /// volume files dropped onto the window, loaded together before the next frame
+    dropped_volumes: Vec<PathBuf>,
+    /// second dataset shown next to the volume
+    comparison: Option<Comparison>,
+    camera_animation: CameraAnimation,
     num_columns: u32,
     selected_channel: Option<usize>,
 
@@ -127,8 +475,12 @@ pub struct WindowContext {
 
 impl WindowContext {
     // Creating some of the wgpu types requires async code
+    /// creates the device for the window's surface unless `wgpu_context` is shared
+    /// with other windows
     async fn new(
         window: Window,
+        instance: &wgpu::Instance,
+        wgpu_context: Option<Arc<WGPUContext>>,
         volumes: Vec<Volume>,
         cmap: LinearSegmentedColorMap,
         render_config: &RenderConfig,
@@ -139,14 +491,12 @@ impl WindowContext {
         }
         let window = Arc::new(window);
 
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: Backends::all().symmetric_difference(Backends::BROWSER_WEBGPU),
-            ..Default::default()
-        });
-
         let surface: wgpu::Surface = instance.create_surface(window.clone())?;
 
-        let wgpu_context = WGPUContext::new(&instance, Some(&surface)).await;
+        let wgpu_context = match wgpu_context {
+            Some(wgpu_context) => wgpu_context,
+            None => Arc::new(WGPUContext::new(instance, Some(&surface)).await),
+        };
 
         log::info!("device: {:?}", wgpu_context.adapter.get_info().name);
 
@@ -165,10 +515,14 @@ impl WindowContext {
             .next()
             .unwrap_or(&surface_caps.formats[0])
             .clone();
-        let surface_format = surface_format;
+        let (render_format, view_formats) =
+            srgb_render_format(surface_format, &wgpu_context.adapter);
+        log::info!("surface format: {surface_format:?}, render format: {render_format:?}");
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // copying the frame is needed for screenshots
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -179,39 +533,98 @@ impl WindowContext {
                 wgpu::PresentMode::AutoVsync
             },
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            view_formats: vec![],
+            view_formats,
         };
         surface.configure(&device, &config);
 
-        let ui_renderer = ui_renderer::EguiWGPU::new(device, surface_format, &window);
+        // the image is encoded manually if the surface has no sRGB view
+        let encode_blitter = (render_format != surface_format
+            && !config.view_formats.contains(&render_format))
+        .then(|| Blitter::srgb_encode(device, surface_format));
+        let ui_format = if encode_blitter.is_some() {
+            surface_format
+        } else {
+            render_format
+        };
+        let ui_renderer = ui_renderer::EguiWGPU::new(device, ui_format, &window);
+        #[cfg(not(target_arch = "wasm32"))]
+        let ui_config = config::Config::load().ui;
+        #[cfg(target_arch = "wasm32")]
+        let ui_config = UiConfig::default();
+        ui_config.apply(ui_renderer.winit.egui_ctx());
+        let gpu_timer = GpuTimer::new(device, queue);
 
-        let renderer = VolumeRenderer::new(device, surface_format);
+        let renderer = wgpu_context.resources.lock().unwrap().volume_renderer(
+            device,
+            render_format,
+            Some(lines::DEPTH_FORMAT),
+        );
+        let line_renderer = LineRenderer::new(device, render_format);
+        let mut background_renderer =
+            BackgroundRenderer::new(device, queue, render_format, Some(lines::DEPTH_FORMAT));
+        if let Some(path) = &render_config.environment_map {
+            background_renderer.set_environment(device, queue, &image::open(path)?.to_rgba8())?;
+        }
+        let blitter = Blitter::new(device, render_format);
+        let lens_blitter = Blitter::circle(device, render_format);
+        let anaglyph_blitter = Blitter::red_channel(device, render_format);
+        let taa = TemporalAccumulation::new(device);
 
         let render_settings = RenderSettings {
             clipping_aabb: None,
             time: 0.,
-            step_size: 2. / 1000.,
-            spatial_filter: wgpu::FilterMode::Linear,
-            temporal_filter: wgpu::FilterMode::Linear,
+            step_size: volumes[0].default_step_size(),
+            spatial_filter: render_config.spatial_interpolation,
+            temporal_filter: render_config.temporal_interpolation,
             distance_scale: render_config.distance_scale,
             vmin: render_config.vmin,
             vmax: render_config.vmax,
-            gamma_correction: !surface_format.is_srgb(),
+            colorbar: render_config.colorbar,
+            ..Default::default()
         };
 
         let mut controller = CameraController::new(0.1, 0.05);
-        controller.center = volumes[0].aabb.center();
+        controller.center = render_config.camera.target(&volumes[0].aabb);
 
         let radius = volumes[0].aabb.radius();
         let ratio = size.width as f32 / size.height as f32;
-        let camera = Camera::new_aabb_iso(
+        let camera = Camera::with_view(
             volumes[0].aabb.clone(),
-            OrthographicProjection::new(Vector2::new(ratio, 1.) * 2. * radius, 1e-4, 100.),
+            &render_config.camera,
+            OrthographicProjection::new(
+                Vector2::new(ratio, 1.) * 2. * radius / render_config.camera.zoom,
+                1e-4,
+                100.,
+            ),
+        );
+
+        let mut timeline = Timeline::new(
+            render_config
+                .duration
+                .unwrap_or(Duration::from_secs_f32(5.)),
         );
+        if let Some(fps) = render_config.fps {
+            timeline.set_fps(fps, volumes[0].timesteps);
+        }
+        timeline.backwards = render_config.reverse_playback;
+        timeline.snap = render_config.snap_timesteps;
+
+        let mut stereo = render_config.stereo;
+        if stereo.mode.offline_only() {
+            log::warn!(
+                "stereo mode {} is only supported by offline rendering",
+                stereo.mode.name()
+            );
+            stereo.mode = StereoMode::Off;
+        }
 
-        let animation_duration = render_config
-            .duration
-            .unwrap_or(Duration::from_secs_f32(5.));
+        let bookmarks = match &render_config.bookmarks_file {
+            Some(file) => Bookmarks::open(file.clone()).unwrap_or_else(|err| {
+                log::error!("failed to load bookmarks from {}: {err}", file.display());
+                Bookmarks::default()
+            }),
+            None => Bookmarks::default(),
+        };
 
         let num_columns = volumes.len().min(4) as u32;
         let volumes_gpu = volumes
@@ -219,47 +632,554 @@ impl WindowContext {
             .map(|v| VolumeGPU::new(device, queue, v))
             .collect();
 
-        let cmap_gpu = ColorMapGPU::new(&cmap, device, queue, COLORMAP_RESOLUTION);
-        Ok(Self {
+        let cmap_sequence = render_config.cmap_sequence.as_ref().map(|seq| {
+            seq.map(|c| {
+                GenericColorMap::LinearSegmented(c.into_linear_segmented(COLORMAP_RESOLUTION))
+            })
+        });
+        let cmap_gpu = match &cmap_sequence {
+            Some(seq) => ColorMapGPU::from_sequence(seq, device, queue, COLORMAP_RESOLUTION),
+            None => ColorMapGPU::new(&cmap, device, queue, COLORMAP_RESOLUTION),
+        };
+        let opacity = TransferFunction::from_alpha(&cmap);
+        let histogram = VolumeHistogram::new(device, &wgpu_context.adapter);
+        let range_histogram = VolumeHistogram::new(device, &wgpu_context.adapter);
+        let statistics = VolumeStatistics::new(device, &wgpu_context.adapter);
+        let mut state = Self {
             wgpu_context,
             scale_factor: window.scale_factor() as f32,
+            pacing: FramePacing::new(&window, !render_config.no_vsync),
+            gpu_timer,
+            frame_stats: FrameStats::default(),
+            performance_overlay: false,
             window,
             surface,
             config,
+            render_format,
+            encode_blitter,
+            encode_target: None,
             controller,
+            #[cfg(feature = "gamepad")]
+            gamepads: gamepad::Gamepads::new(),
             ui_renderer,
+            ui_config,
             ui_visible: true,
             background_color: render_config.background_color,
+            background: render_config.background,
+            background_renderer,
             camera,
 
             volumes: volumes_gpu,
             renderer,
+            line_renderer,
+            overlay: OverlaySettings::default(),
+            annotations: render_config.annotations.clone(),
+            depth_buffer: None,
+            blitter,
+            render_target: None,
+            lens: Lens::default(),
+            lens_blitter,
+            lens_target: None,
+            stereo,
+            anaglyph_blitter,
+            stereo_target: None,
+            cursor_position: None,
+            pick_surface: false,
+            probe_mode: false,
+            probe: None,
+            initial_view: render_config.camera,
+            measure: Measurement::default(),
+            label_mode: false,
+            crop_box: CropBox::default(),
+            shift_pressed: false,
+            control_pressed: false,
+            screenshots: Screenshots::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            recorder: recording::Recorder::default(),
+            render_scale: render_config.render_scale,
+            taa,
+            reuse_frames: true,
+            last_frame: None,
+            interactive_preview: false,
+            interacting: false,
             render_settings,
             cmap_gpu,
             cmap,
-            animation_duration,
-            playing: true,
+            cmap_sequence,
+            opacity,
+            histogram,
+            range_histogram,
+            range_log_scale: false,
+            invalid_counts: None,
+            statistics,
+            reference_values: render_config.reference_values.clone(),
+            history: EditHistory::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            colormap_presets: presets::load_presets(),
+            timeline,
+            bookmarks,
+            volume_file: render_config.volume_file.clone(),
+            mask_file: render_config.mask_file.clone(),
+            invert_mask: render_config.invert_mask,
+            environment_file: render_config.environment_map.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            transfer_function_file: render_config.transfer_function_file.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            open_requests: Vec::new(),
+            dropped_volumes: Vec::new(),
+            comparison: None,
+            camera_animation: CameraAnimation::new(render_config.keyframes.clone()),
             num_columns,
             selected_channel: None,
             colormap_editor_visible: render_config.show_colormap_editor,
             volume_info_visible: render_config.show_volume_info,
             #[cfg(feature = "colormaps")]
             cmap_select_visible: render_config.show_cmap_select,
-        })
+        };
+        if let Some(render) = &render_config.render_settings {
+            render.apply(&mut state.render_settings);
+        }
+        if let Some(session) = &render_config.session {
+            session.apply(&mut state);
+        }
+        if let Some(file) = &render_config.compare_file {
+            state.open_comparison(file, render_config.difference)?;
+        }
+        Ok(state)
+    }
+
+    /// writes the colormap with the opacity as alpha channel to the transfer function file
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_transfer_function(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.transfer_function_file else {
+            return Ok(());
+        };
+        let cmap = self.cmap.with_opacity(&self.opacity);
+        let writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(writer, &cmap)?;
+        Ok(())
+    }
+
+    /// handles an event of the window, returns true if the window is closed
+    fn window_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::Resized(physical_size) => {
+                self.resize(*physical_size, None);
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.scale_factor = *scale_factor as f32;
+                // the window might be on another monitor now
+                self.pacing.update_monitor(&self.window);
+            }
+            WindowEvent::Moved(_) => {
+                self.pacing.update_monitor(&self.window);
+            }
+            WindowEvent::CloseRequested => return true,
+            WindowEvent::Focused(false) => self.controller.release_keys(),
+            WindowEvent::ModifiersChanged(m) => {
+                self.controller.alt_pressed = m.state().alt_key();
+                self.shift_pressed = m.state().shift_key();
+                self.control_pressed = m.state().control_key() || m.state().super_key();
+                // holding Ctrl moves the camera precisely, without smoothing and inertia
+                self.controller.precise = self.control_pressed;
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                if let PhysicalKey::Code(key) = event.physical_key {
+                    // Ctrl+S takes a screenshot instead of moving the camera
+                    if !(self.control_pressed && key == KeyCode::KeyS) {
+                        self.controller
+                            .process_keyboard(key, event.state == ElementState::Pressed);
+                    }
+                    if key == KeyCode::KeyU && event.state == ElementState::Released {
+                        self.ui_visible = !self.ui_visible;
+                    }
+                    if key == KeyCode::KeyL && event.state == ElementState::Released {
+                        self.lens.enabled = !self.lens.enabled;
+                    }
+                    if key == KeyCode::F3 && event.state == ElementState::Released {
+                        self.performance_overlay = !self.performance_overlay;
+                    }
+                    if event.state == ElementState::Released {
+                        match key {
+                            KeyCode::KeyF => self.zoom_to_volume(),
+                            KeyCode::KeyR => self.reset_camera(),
+                            KeyCode::KeyC => self.zoom_to_clip_region(),
+                            KeyCode::Digit0 => self.zoom_to_voxel_scale(),
+                            // Shift+F12 copies the screenshot to the clipboard
+                            KeyCode::F12 if self.shift_pressed => {
+                                self.screenshots.request(ScreenshotTarget::Clipboard)
+                            }
+                            KeyCode::F12 => self.screenshots.request(ScreenshotTarget::File),
+                            KeyCode::KeyS if self.control_pressed => {
+                                self.screenshots.request(ScreenshotTarget::File)
+                            }
+                            _ => {}
+                        }
+                    }
+                    // 1-6 snap to the axis aligned views, Shift+1-9 recall the bookmarks
+                    let number_keys = [
+                        KeyCode::Digit1,
+                        KeyCode::Digit2,
+                        KeyCode::Digit3,
+                        KeyCode::Digit4,
+                        KeyCode::Digit5,
+                        KeyCode::Digit6,
+                        KeyCode::Digit7,
+                        KeyCode::Digit8,
+                        KeyCode::Digit9,
+                    ];
+                    if let Some(i) = number_keys.iter().position(|k| *k == key) {
+                        if event.state == ElementState::Released {
+                            if self.shift_pressed {
+                                self.recall_bookmark(i);
+                            } else if i < controller::AXIS_VIEWS.len() {
+                                self.controller.snap_to_axis(i);
+                            }
+                        }
+                    }
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(Vector2::new(position.x as f32, position.y as f32));
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+            }
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                winit::event::MouseScrollDelta::LineDelta(_, dy) => {
+                    let anchor = self.zoom_anchor();
+                    self.controller.process_scroll(*dy, anchor)
+                }
+                winit::event::MouseScrollDelta::PixelDelta(p) => {
+                    let anchor = self.zoom_anchor();
+                    self.controller.process_scroll(p.y as f32 / 100., anchor)
+                }
+            },
+            WindowEvent::Touch(touch) => {
+                let position = Vector2::new(touch.location.x as f32, touch.location.y as f32);
+                // a double tap fits the volume into the view
+                if self
+                    .controller
+                    .process_touch(touch.id, touch.phase, position)
+                {
+                    self.zoom_to_volume();
+                }
+            }
+            WindowEvent::MouseInput {
+                state: button_state,
+                button,
+                ..
+            } => {
+                match button {
+                    // the camera is not rotated while picking
+                    winit::event::MouseButton::Left
+                        if self.pick_surface && *button_state == ElementState::Released =>
+                    {
+                        self.pick_surface_value(self.shift_pressed)
+                    }
+                    winit::event::MouseButton::Left if self.pick_surface => {}
+                    winit::event::MouseButton::Left
+                        if self.probe_mode && *button_state == ElementState::Released =>
+                    {
+                        self.probe_at_cursor()
+                    }
+                    winit::event::MouseButton::Left if self.probe_mode => {}
+                    winit::event::MouseButton::Left
+                        if self.measure.enabled && *button_state == ElementState::Released =>
+                    {
+                        self.measure_at_cursor()
+                    }
+                    winit::event::MouseButton::Left if self.measure.enabled => {}
+                    winit::event::MouseButton::Left
+                        if self.label_mode && *button_state == ElementState::Released =>
+                    {
+                        self.label_at_cursor()
+                    }
+                    winit::event::MouseButton::Left if self.label_mode => {}
+                    // dragging a face of the crop box does not rotate the camera
+                    winit::event::MouseButton::Left
+                        if self.crop_box.hovered.is_some()
+                            && *button_state == ElementState::Pressed => {}
+                    winit::event::MouseButton::Left => {
+                        let pressed = *button_state == ElementState::Pressed;
+                        if self
+                            .controller
+                            .process_left_button(pressed, self.cursor_position)
+                        {
+                            self.center_at_cursor();
+                        }
+                    }
+                    winit::event::MouseButton::Right => {
+                        self.controller.right_mouse_pressed = *button_state == ElementState::Pressed
+                    }
+                    _ => {}
+                }
+            }
+            WindowEvent::DroppedFile(file) => {
+                let result = FileKind::detect(file).and_then(|kind| {
+                    // Shift opens a volume or session in a new window
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if self.shift_pressed && kind != FileKind::Colormap {
+                        self.open_requests.push(file.clone());
+                        return Ok(());
+                    }
+                    // volumes dropped together are loaded as time series before the next frame
+                    if kind == FileKind::Volume {
+                        self.dropped_volumes.push(file.clone());
+                        return Ok(());
+                    }
+                    self.load_file(file, kind)
+                });
+                if let Err(e) = result {
+                    log::error!("failed to load file: {:?}", e)
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                if !self.dropped_volumes.is_empty() {
+                    let files = std::mem::take(&mut self.dropped_volumes);
+                    if let Err(e) = self.load_volume_series(files) {
+                        log::error!("failed to load volumes: {:?}", e)
+                    }
+                }
+                let dt = self.pacing.frame();
+                self.update(dt);
+
+                match self.render() {
+                    Ok(_) => {}
+                    // Reconfigure the surface if lost
+                    Err(wgpu::SurfaceError::Lost) => {
+                        log::error!("lost surface!");
+                        self.resize(self.window.inner_size(), None)
+                    }
+                    // The system is out of memory, the window is closed
+                    Err(wgpu::SurfaceError::OutOfMemory) => return true,
+                    // All other errors (Outdated, Timeout) should be resolved by the next frame
+                    Err(e) => println!("error: {:?}", e),
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// opens a volume, a colormap or a session file
+    fn load_file(&mut self, path: &PathBuf, kind: FileKind) -> anyhow::Result<()> {
+        match kind {
+            FileKind::Volume => self.load_volume(path, None),
+            FileKind::Colormap => self.load_colormap(path),
+            FileKind::Session => self.load_session(path),
+        }
+    }
+
+    /// replaces the volume with the timesteps of all `files` in natural order
+    /// (`step_2` before `step_10`), a single file is loaded as usual
+    fn load_volume_series(&mut self, mut files: Vec<PathBuf>) -> anyhow::Result<()> {
+        if files.len() == 1 {
+            return self.load_volume(&files[0], None);
+        }
+        files.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+        let mut channels: Vec<Vec<Volume>> = Vec::new();
+        for file in &files {
+            let volumes = Volume::load_numpy(std::fs::File::open(file)?, true)
+                .map_err(|err| anyhow::anyhow!("failed to read {}: {err}", file.display()))?;
+            if channels.is_empty() {
+                channels.resize_with(volumes.len(), Vec::new);
+            }
+            anyhow::ensure!(
+                volumes.len() == channels.len(),
+                "{} has {} channels, expected {}",
+                file.display(),
+                volumes.len(),
+                channels.len()
+            );
+            for (channel, volume) in channels.iter_mut().zip(volumes) {
+                channel.push(volume);
+            }
+        }
+        let volumes = channels
+            .into_iter()
+            .map(Volume::concat_timesteps)
+            .collect::<anyhow::Result<_>>()?;
+        log::info!("loaded {} files as time series", files.len());
+        // a session can only reference a single volume file
+        self.set_volumes(volumes, None);
+        Ok(())
+    }
+
+    /// replaces the transfer function, the alpha channel of the colormap becomes the opacity
+    fn load_colormap(&mut self, path: &Path) -> anyhow::Result<()> {
+        let cmap = GenericColorMap::read(std::fs::File::open(path)?)?;
+        self.cmap = cmap.into_linear_segmented(COLORMAP_RESOLUTION);
+        self.opacity = TransferFunction::from_alpha(&self.cmap);
+        self.cmap_sequence = None;
+        Ok(())
     }
 
-    fn load_file(&mut self, path: &PathBuf) -> anyhow::Result<()> {
+    /// replaces the volume, `session` (with its file's directory) sets the spacing
+    /// and mask of the loaded volume
+    fn load_volume(
+        &mut self,
+        path: &PathBuf,
+        session: Option<(&Session, &Path)>,
+    ) -> anyhow::Result<()> {
         let reader = std::fs::File::open(path)?;
-        let volume = Volume::load_numpy(reader, true)?;
-        let volume_gpu = volume
+        let mut volume = Volume::load_numpy(reader, true)?;
+        if let Some((session, base_dir)) = session {
+            volume.iter_mut().for_each(|v| session.apply_spacing(v));
+            session.load_mask(base_dir, &mut volume)?;
+        }
+        self.set_volumes(volume, Some(path.clone()));
+        self.mask_file = session.and_then(|(session, base_dir)| session.mask_file(base_dir));
+        self.invert_mask = session.is_some_and(|(session, _)| session.invert_mask);
+        Ok(())
+    }
+
+    /// replaces the volume with the channels `volumes`, loaded from `file`
+    fn set_volumes(&mut self, volumes: Vec<Volume>, file: Option<PathBuf>) {
+        let volume_gpu = volumes
             .into_iter()
             .map(|v| VolumeGPU::new(&self.wgpu_context.device, &self.wgpu_context.queue, v))
             .collect();
         self.volumes = volume_gpu;
-        // self.controller.center = volume.aabb.center();
+        self.comparison = None;
+        self.render_settings.step_size = self.volumes[0].volume.default_step_size();
+        self.volumes_changed();
         self.camera
             .projection
             .resize(self.config.width, self.config.height);
+        // the new volume may have another size
+        self.zoom_to_volume();
+        self.volume_file = file;
+        self.mask_file = None;
+        self.invert_mask = false;
+    }
+
+    /// opens a second dataset with the same shape next to the volume,
+    /// it replaces the dataset that is compared so far
+    fn open_comparison(&mut self, path: &Path, difference: bool) -> anyhow::Result<()> {
+        let channels = self
+            .comparison
+            .as_ref()
+            .map_or(self.volumes.len(), |c| c.channels);
+        let mut volumes = Volume::load_numpy(std::fs::File::open(path)?, true)?;
+        anyhow::ensure!(
+            volumes.len() == channels,
+            "expected {channels} channels, got {}",
+            volumes.len()
+        );
+        let first = &self.volumes[0].volume;
+        for v in volumes.iter_mut() {
+            anyhow::ensure!(
+                v.resolution == first.resolution && v.timesteps == first.timesteps,
+                "shape {} x {:?} does not match the volume ({} x {:?})",
+                v.timesteps,
+                v.resolution,
+                first.timesteps,
+                first.resolution
+            );
+            v.set_spacing(first.spacing);
+        }
+        self.volumes.truncate(channels);
+        self.volumes.extend(
+            volumes
+                .into_iter()
+                .map(|v| VolumeGPU::new(&self.wgpu_context.device, &self.wgpu_context.queue, v)),
+        );
+        self.comparison = Some(Comparison {
+            file: path.to_path_buf(),
+            channels,
+            difference: false,
+        });
+        self.set_difference(difference)
+    }
+
+    /// shows or hides the differences of the compared datasets
+    fn set_difference(&mut self, difference: bool) -> anyhow::Result<()> {
+        let Some(comparison) = &mut self.comparison else {
+            return Ok(());
+        };
+        let channels = comparison.channels;
+        comparison.difference = difference;
+        self.volumes.truncate(2 * channels);
+        if difference {
+            for c in 0..channels {
+                let volume = self.volumes[c]
+                    .volume
+                    .difference(&self.volumes[channels + c].volume)?;
+                self.volumes.push(VolumeGPU::new(
+                    &self.wgpu_context.device,
+                    &self.wgpu_context.queue,
+                    volume,
+                ));
+            }
+        }
+        self.volumes_changed();
+        Ok(())
+    }
+
+    /// removes the compared dataset
+    #[cfg(not(target_arch = "wasm32"))]
+    fn close_comparison(&mut self) {
+        if let Some(comparison) = self.comparison.take() {
+            self.volumes.truncate(comparison.channels);
+            self.volumes_changed();
+        }
+    }
+
+    /// applies the user interface settings and saves them in the config file
+    fn set_ui_config(&mut self, ui_config: UiConfig) {
+        self.ui_config = ui_config;
+        ui_config.apply(self.ui_renderer.winit.egui_ctx());
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut config = config::Config::load();
+            config.ui = ui_config;
+            if let Err(err) = config.save() {
+                log::error!("failed to save config: {err}");
+            }
+        }
+    }
+
+    /// resets everything that depends on the set of volumes
+    fn volumes_changed(&mut self) {
+        self.num_columns = match &self.comparison {
+            Some(comparison) => comparison.num_columns(self.volumes.len()),
+            None => self.volumes.len().min(4) as u32,
+        };
+        if self
+            .selected_channel
+            .is_some_and(|c| c >= self.volumes.len())
+        {
+            self.selected_channel = None;
+        }
+        self.histogram.reset();
+        self.range_histogram.reset();
+        self.invalid_counts = None;
+        self.statistics.reset();
+        self.probe = None;
+        self.measure.points.clear();
+        self.measure.channel = 0;
+        self.taa.reset();
+        self.last_frame = None;
+    }
+
+    /// restores the viewer state of a session file, its volume is loaded if it has one
+    fn load_session(&mut self, path: &Path) -> anyhow::Result<()> {
+        let session = Session::read(path)?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        if let Some(file) = session.volume_file(base_dir) {
+            self.load_volume(&file, Some((&session, base_dir)))?;
+        }
+        if let Some(file) = session.environment_file(base_dir) {
+            let (device, queue) = (&self.wgpu_context.device, &self.wgpu_context.queue);
+            self.background_renderer.set_environment(
+                device,
+                queue,
+                &image::open(&file)?.to_rgba8(),
+            )?;
+            self.environment_file = Some(file);
+        }
+        session.apply(self);
         Ok(())
     }
 
@@ -281,127 +1201,1186 @@ impl WindowContext {
     }
 
     fn update(&mut self, dt: Duration) {
+        #[cfg(feature = "gamepad")]
+        if self.gamepads.update(
+            &mut self.controller,
+            &mut self.timeline,
+            &mut self.render_settings.time,
+            self.volumes[0].volume.timesteps,
+            dt.as_secs_f32(),
+        ) {
+            self.reset_camera();
+        }
         self.controller.update_camera(&mut self.camera, dt);
+        self.interacting = self.controller.is_moving();
+
+        // the time is set by the keyframes while the camera animation plays
+        if let Some(keyframe) = self.camera_animation.advance(dt.as_secs_f32()) {
+            self.apply_keyframe(&keyframe);
+        } else if self.volumes[0].volume.timesteps > 1 {
+            let timesteps = self.volumes[0].volume.timesteps;
+            self.timeline
+                .advance(&mut self.render_settings.time, dt, timesteps);
+        }
+
+        if self.colormap_editor_visible {
+            let channel = self.selected_channel.unwrap_or(0);
+            let volume = &self.volumes[channel];
+            self.histogram.update(
+                &self.wgpu_context.device,
+                &self.wgpu_context.queue,
+                volume,
+                channel,
+                &self.render_settings,
+            );
+            let (min, max, normalization) =
+                ui::slider_scale(&volume.volume, &self.render_settings, self.range_log_scale);
+            self.range_histogram.enabled = self.histogram.enabled;
+            self.range_histogram.per_timestep = self.histogram.per_timestep;
+            self.range_histogram.update(
+                &self.wgpu_context.device,
+                &self.wgpu_context.queue,
+                volume,
+                channel,
+                &RenderSettings {
+                    time: self.render_settings.time,
+                    vmin: Some(min),
+                    vmax: Some(max),
+                    normalization,
+                    ..Default::default()
+                },
+            );
+        }
+
+        if self.render_settings.highlight_invalid {
+            let channel = self.selected_channel.unwrap_or(0);
+            let volume = &self.volumes[channel].volume;
+            let timestep = ((volume.timesteps - 1) as f32 * self.render_settings.time) as usize;
+            let (vmin, vmax) = self.render_settings.value_range(volume);
+            let key = (channel, timestep, vmin, vmax);
+            if self.invalid_counts.map(|(k, _)| k) != Some(key) {
+                let counts = volume.invalid_counts(timestep, vmin, vmax);
+                self.invalid_counts = Some((key, counts));
+            }
+        }
+
+        self.screenshots.poll(&self.wgpu_context.device);
+        self.gpu_timer.poll(&self.wgpu_context.device);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.record_frame();
+    }
 
-        if self.playing && self.volumes[0].volume.timesteps > 1 {
-            self.render_settings.time += dt.as_secs_f32() / self.animation_duration.as_secs_f32();
-            self.render_settings.time = self.render_settings.time.fract();
+    /// starts recording one loop of the animation to `path`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_recording(&mut self, path: &std::path::Path) {
+        let duration = self.timeline.duration.as_secs_f32();
+        match recording::Recording::start(path, &self.recorder.settings, duration) {
+            Ok(recording) => {
+                self.recorder.recording = Some(recording);
+                self.recorder.status = None;
+                if self.recorder.turntable {
+                    self.recorder.turntable_start = Some(self.camera);
+                }
+            }
+            Err(err) => {
+                log::error!("failed to start recording: {err}");
+                self.recorder.status = Some(format!("recording failed: {err}"));
+            }
         }
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let window_size = self.window.inner_size();
-        if window_size.width != self.config.width || window_size.height != self.config.height {
-            self.resize(window_size, None);
+    /// renders and encodes the next frame of the recording, if there is one.
+    /// The animation time is set to the time of the frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn record_frame(&mut self) {
+        let Some(recording) = &self.recorder.recording else {
+            return;
+        };
+        let resolution = recording.resolution();
+        // a turntable keeps the current time unless the animation plays
+        if self.recorder.turntable_start.is_none() || self.timeline.playing {
+            self.render_settings.time = recording.time();
         }
+        if let Some(start) = self.recorder.turntable_start {
+            let (frame, num_frames) = recording.progress();
+            let up = self
+                .controller
+                .up
+                .unwrap_or(start.rotation.invert().rotate_vector(Vector3::unit_y()));
+            let mut camera = start;
+            camera.orbit(
+                self.controller.center,
+                up,
+                Rad(2. * std::f32::consts::PI * frame as f32 / num_frames as f32),
+            );
+            self.camera = camera;
+        }
+        let (supersampling, legend_scale) = self
+            .recorder
+            .preset
+            .map_or((1, 1.), |p| (p.supersampling, p.legend_scale()));
+        let result =
+            pollster::block_on(self.render_offscreen(resolution, supersampling, legend_scale))
+                .and_then(|image| self.recorder.recording.as_mut().unwrap().add_frame(&image));
+        if let Err(err) = result {
+            self.stop_recording();
+            log::error!("recording failed: {err}");
+            self.recorder.status = Some(format!("recording failed: {err}"));
+        } else if self.recorder.recording.as_ref().unwrap().done() {
+            self.stop_recording();
+        }
+    }
 
-        let output = self.surface.get_current_texture()?;
-        let view_rgb = output.texture.create_view(&wgpu::TextureViewDescriptor {
-            format: Some(self.config.format),
-            ..Default::default()
+    /// finishes the video, also if not all frames are recorded
+    #[cfg(not(target_arch = "wasm32"))]
+    fn stop_recording(&mut self) {
+        let Some(recording) = self.recorder.recording.take() else {
+            return;
+        };
+        if let Some(camera) = self.recorder.turntable_start.take() {
+            self.camera = camera;
+        }
+        self.recorder.status = Some(match recording.finish() {
+            Ok(path) => {
+                log::info!("saved video to {}", path.display());
+                format!("saved video to {}", path.display())
+            }
+            Err(err) => {
+                log::error!("recording failed: {err}");
+                format!("recording failed: {err}")
+            }
         });
+    }
 
-        // do prepare stuff
-
-        let mut encoder =
-            self.wgpu_context
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("render command encoder"),
-                });
-        let mut frame_data = Vec::new();
+    /// renders the volume (selected channel or first) with the current camera, settings and
+    /// annotations at `resolution`, like the offline renderer. `supersampling` samples per
+    /// pixel along each axis are averaged, the annotations are scaled by `legend_scale`
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn render_offscreen(
+        &self,
+        resolution: Vector2<u32>,
+        supersampling: u32,
+        legend_scale: f32,
+    ) -> anyhow::Result<image::ImageBuffer<image::Rgba<u8>, Vec<u8>>> {
+        let device = &self.wgpu_context.device;
+        let queue = &self.wgpu_context.queue;
+        let renderer = self.wgpu_context.resources.lock().unwrap().volume_renderer(
+            device,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            None,
+        );
+        let mut settings = self.frame_render_settings();
+        // no reduced quality while the camera moves
+        settings.step_size = self.render_settings.step_size;
+        let supersampling = supersampling.max(1);
+        let render_resolution = resolution * supersampling;
+        let mut camera = self.camera;
+        camera
+            .projection
+            .resize(render_resolution.x, render_resolution.y);
+        let colors = match &self.cmap_sequence {
+            Some(seq) => (&seq.at(settings.time, COLORMAP_RESOLUTION))
+                .rasterize(COLORMAP_RESOLUTION as usize),
+            None => (&self.cmap).rasterize(COLORMAP_RESOLUTION as usize),
+        };
+        let annotations = Annotations {
+            scale: self.annotations.scale * legend_scale,
+            ..self.annotations.clone()
+        };
+        let annotations = (annotations.any() || settings.colorbar.is_some()).then_some((
+            &annotations,
+            supersampling as f32,
+            colors.as_slice(),
+        ));
+        let image = offline::render_view(
+            device,
+            queue,
+            &renderer,
+            &self.volumes[self.selected_channel.unwrap_or(0)],
+            &self.cmap_gpu,
+            camera,
+            &settings,
+            self.background_color,
+            render_resolution,
+            annotations,
+        )
+        .await?;
+        Ok(offline::downsample(&image, supersampling))
+    }
 
-        let columns = self.num_columns as usize;
-        let rows = (self.volumes.len() as f32 / columns as f32).ceil() as usize;
-        let cell_width = self.config.width as f32 / columns as f32;
-        let cell_height = self.config.height as f32 / rows as f32;
+    /// reverts the last transfer function or settings edit (Ctrl+Z)
+    fn undo(&mut self) {
+        if let Some(edits) = self.history.undo(Edits::of(self)) {
+            edits.apply(self);
+        }
+    }
 
-        let ui_state = if self.ui_visible {
-            self.ui_renderer.begin_frame(&self.window);
-            ui::ui(self);
+    /// restores the last undone edit (Ctrl+Y)
+    fn redo(&mut self) {
+        if let Some(edits) = self.history.redo(Edits::of(self)) {
+            edits.apply(self);
+        }
+    }
 
-            let shapes = self.ui_renderer.end_frame(&self.window);
-            Some(self.ui_renderer.prepare(
-                PhysicalSize {
-                    width: output.texture.size().width,
-                    height: output.texture.size().height,
-                },
-                self.scale_factor,
+    /// replaces the mask of all channels
+    fn set_mask(&mut self, mask: Option<Mask>) -> anyhow::Result<()> {
+        for volume in self.volumes.iter_mut() {
+            volume.set_mask(
                 &self.wgpu_context.device,
                 &self.wgpu_context.queue,
-                &mut encoder,
-                shapes,
-            ))
-        } else {
-            None
-        };
+                mask.clone(),
+            )?;
+        }
+        self.taa.reset();
+        self.last_frame = None;
+        Ok(())
+    }
 
-        if let Some(selected_channel) = self.selected_channel {
-            let camera = self.camera.clone();
-            frame_data.push(self.renderer.prepare(
+    /// zooms so that the whole volume fits into the view (key F)
+    fn zoom_to_volume(&mut self) {
+        let aabb = self.volumes[self.selected_channel.unwrap_or(0)].volume.aabb;
+        self.controller.zoom_to_aabb(&mut self.camera, &aabb);
+    }
+
+    /// looks at the volume from the initial direction and fits it into the view (key R)
+    fn reset_camera(&mut self) {
+        self.controller.stop();
+        let aabb = self.volumes[self.selected_channel.unwrap_or(0)].volume.aabb;
+        self.camera = Camera::with_view(aabb, &self.initial_view, self.camera.projection);
+        self.controller.center = aabb.center();
+        self.zoom_to_volume();
+    }
+
+    /// zooms so that the clipped region of the volume fits into the view (key C)
+    fn zoom_to_clip_region(&mut self) {
+        let aabb = self.volumes[self.selected_channel.unwrap_or(0)].volume.aabb;
+        let clip = self.render_settings.clipping_aabb.unwrap_or(Aabb::unit());
+        let clipped = crop::world_aabb(&clip, &aabb);
+        self.controller.zoom_to_aabb(&mut self.camera, &clipped);
+    }
+
+    /// zooms so that a voxel covers one pixel of the window (key 0)
+    fn zoom_to_voxel_scale(&mut self) {
+        let voxel_size = self.volumes[self.selected_channel.unwrap_or(0)]
+            .volume
+            .voxel_size();
+        let (mut width, mut height) = (self.config.width, self.config.height);
+        if self.selected_channel.is_none() {
+            let columns = self.num_columns;
+            let rows = (self.volumes.len() as u32).div_ceil(columns);
+            (width, height) = (width / columns, height / rows);
+        }
+        self.camera
+            .projection
+            .set_pixel_size(width.max(1), height.max(1), voxel_size);
+    }
+
+    /// saves the current camera pose, time and value of the first surface
+    fn add_bookmark(&mut self, name: String) {
+        let iso_value = self
+            .reference_values
+            .iter()
+            .find(|r| r.surface)
+            .map(|r| r.value);
+        self.bookmarks.list.push(Bookmark::new(
+            name,
+            &self.camera,
+            self.controller.center,
+            self.render_settings.time,
+            iso_value,
+        ));
+        self.bookmarks.save();
+    }
+
+    /// moves the camera to the bookmark `index` and restores its time and surface value
+    fn recall_bookmark(&mut self, index: usize) {
+        let Some(bookmark) = self.bookmarks.list.get(index) else {
+            return;
+        };
+        self.controller.stop();
+        bookmark.apply(&mut self.camera, (self.config.width, self.config.height));
+        self.controller.center = bookmark.center();
+        self.render_settings.time = bookmark.time;
+        self.timeline.playing = false;
+        if let Some(value) = bookmark.iso_value {
+            if let Some(surface) = self.reference_values.iter_mut().find(|r| r.surface) {
+                surface.value = value;
+            }
+        }
+    }
+
+    /// adds a keyframe with the current camera and settings at the playhead of the camera animation
+    fn add_keyframe(&mut self) {
+        let iso_value = self
+            .reference_values
+            .iter()
+            .find(|r| r.surface)
+            .map(|r| r.value);
+        let keyframe = Keyframe::new(
+            self.camera_animation.position,
+            &self.camera,
+            self.controller.center,
+            &self.volumes[0].volume.aabb,
+            &self.render_settings,
+            iso_value,
+        );
+        self.camera_animation.insert(keyframe);
+    }
+
+    /// moves the camera to the pose of `keyframe` and applies its settings
+    fn apply_keyframe(&mut self, keyframe: &Keyframe) {
+        let aabb = self.volumes[0].volume.aabb;
+        self.controller.stop();
+        self.camera = keyframe.camera(&aabb, Vector2::new(self.config.width, self.config.height));
+        self.controller.center = keyframe.center(&aabb);
+        keyframe.apply_settings(&mut self.render_settings);
+        if let Some(value) = keyframe.iso_value {
+            if let Some(surface) = self.reference_values.iter_mut().find(|r| r.surface) {
+                surface.value = value;
+            }
+        }
+    }
+
+    fn preview_active(&self) -> bool {
+        self.interactive_preview && self.interacting
+    }
+
+    /// render settings used for the current frame (reduced quality while interacting)
+    fn frame_render_settings(&self) -> RenderSettings {
+        let mut settings = self.render_settings.clone();
+        settings.contours = self
+            .reference_values
+            .iter()
+            .filter(|r| r.surface)
+            .map(ReferenceValue::contour)
+            .collect();
+        if self.preview_active() {
+            settings.step_size *= PREVIEW_STEP_SIZE_FACTOR;
+        }
+        settings
+    }
+
+    /// makes sure the offscreen render target matches the current render scale.
+    /// Returns the resolution the volume is rendered at.
+    fn prepare_render_target(&mut self) -> (u32, u32) {
+        let mut scale = self.render_scale.clamp(0.1, 1.);
+        if self.preview_active() {
+            scale = scale.min(PREVIEW_RENDER_SCALE);
+        }
+        // the last frame is kept in the render target to show it again
+        if scale >= 1. && !self.taa.enabled && !self.reuse_frames {
+            self.render_target = None;
+            return (self.config.width, self.config.height);
+        }
+        let width = ((self.config.width as f32 * scale).round() as u32).max(1);
+        let height = ((self.config.height as f32 * scale).round() as u32).max(1);
+        // accumulated frames are stored with higher precision
+        let format = if self.taa.enabled {
+            ACCUMULATION_FORMAT
+        } else {
+            self.render_format
+        };
+        if self.render_target.as_ref().map(|t| (t.size(), t.format()))
+            != Some(((width, height), format))
+        {
+            self.render_target = Some(RenderTarget::new(
                 &self.wgpu_context.device,
-                &self.volumes[selected_channel],
-                &camera,
-                &self.render_settings,
-                &self.cmap_gpu,
+                &self.blitter,
+                format,
+                width,
+                height,
+            ));
+            self.taa.reset();
+            self.last_frame = None;
+        }
+        (width, height)
+    }
+
+    /// returns a depth buffer with the given size, recreating it if necessary
+    fn prepare_depth_buffer(&mut self, width: u32, height: u32) -> wgpu::TextureView {
+        if self.depth_buffer.as_ref().map(|d| (d.width(), d.height())) != Some((width, height)) {
+            self.depth_buffer = Some(self.wgpu_context.device.create_texture(
+                &wgpu::TextureDescriptor {
+                    label: Some("depth buffer"),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: lines::DEPTH_FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                },
             ));
+        }
+        self.depth_buffer
+            .as_ref()
+            .unwrap()
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    /// makes sure the target for the left eye of an anaglyph has the given size
+    fn prepare_stereo_target(&mut self, width: u32, height: u32) {
+        if self.stereo_target.as_ref().map(|t| t.size()) != Some((width, height)) {
+            self.stereo_target = Some(RenderTarget::new(
+                &self.wgpu_context.device,
+                &self.anaglyph_blitter,
+                self.render_format,
+                width,
+                height,
+            ));
+        }
+    }
+
+    /// makes sure the intermediate target for manual sRGB encoding matches the window.
+    /// Returns false if the surface is rendered to directly.
+    fn prepare_encode_target(&mut self) -> bool {
+        let Some(blitter) = &self.encode_blitter else {
+            return false;
+        };
+        let size = (self.config.width, self.config.height);
+        if self.encode_target.as_ref().map(|t| t.size()) != Some(size) {
+            self.encode_target = Some(RenderTarget::new(
+                &self.wgpu_context.device,
+                blitter,
+                self.render_format,
+                size.0,
+                size.1,
+            ));
+        }
+        true
+    }
+
+    /// paints the scale annotations of every visible volume, the measurement and the colorbar
+    /// into the current egui frame
+    fn paint_annotations(&self) {
+        let ctx = self.ui_renderer.winit.egui_ctx();
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        let screen = ctx.screen_rect();
+        let time = self.render_settings.time;
+        if let Some(colorbar) = &self.render_settings.colorbar {
+            let colors = match &self.cmap_sequence {
+                Some(seq) => {
+                    (&seq.at(time, COLORMAP_RESOLUTION)).rasterize(COLORMAP_RESOLUTION as usize)
+                }
+                None => (&self.cmap).rasterize(COLORMAP_RESOLUTION as usize),
+            };
+            let volume = &self.volumes[self.selected_channel.unwrap_or(0)].volume;
+            self.annotations.paint_colorbar(
+                &painter,
+                screen,
+                colorbar,
+                &colors,
+                self.render_settings.value_range(volume),
+                self.render_settings.normalization,
+            );
+        }
+        if !self.measure.points.is_empty() {
+            let volume = &self.volumes[self.measure.channel].volume;
+            for (rect, camera) in self.volume_views(screen, self.measure.channel) {
+                self.measure
+                    .paint(&painter, rect, &camera, volume, &self.annotations);
+            }
+        }
+        if let Some(comparison) = &self.comparison {
+            let font = egui::FontId::proportional(14. * self.annotations.scale);
+            for i in 0..self.volumes.len() {
+                let label = comparison.label(self.volume_file.as_deref(), i);
+                for (rect, _) in self.volume_views(screen, i) {
+                    painter.text(
+                        rect.left_top() + egui::vec2(8., 8.),
+                        egui::Align2::LEFT_TOP,
+                        &label,
+                        font.clone(),
+                        self.annotations.color,
+                    );
+                }
+            }
+        }
+        if !self.annotations.any() {
+            return;
+        }
+        for (i, v) in self.volumes.iter().enumerate() {
+            for (rect, camera) in self.volume_views(screen, i) {
+                self.annotations
+                    .paint(&painter, rect, &camera, &v.volume, time);
+            }
+        }
+    }
+
+    /// rectangle (in points) and camera of every view the volume `i` is rendered in,
+    /// empty if the volume is hidden. Side-by-side stereo has a view for each eye.
+    fn volume_views(
+        &self,
+        screen: egui::Rect,
+        i: usize,
+    ) -> Vec<(egui::Rect, Camera<OrthographicProjection>)> {
+        let (cell, camera) = match self.selected_channel {
+            Some(selected) if selected != i => return Vec::new(),
+            Some(_) => (screen, self.camera),
+            None => {
+                let columns = self.num_columns as usize;
+                let rows = self.volumes.len().div_ceil(columns);
+                let cell_size = egui::vec2(
+                    screen.width() / columns as f32,
+                    screen.height() / rows as f32,
+                );
+                let cell = egui::Rect::from_min_size(
+                    screen.min + egui::vec2((i % columns) as f32, (i / columns) as f32) * cell_size,
+                    cell_size,
+                );
+                let mut camera = self.camera;
+                camera
+                    .projection
+                    .resize(cell_size.x as u32, cell_size.y as u32);
+                (cell, camera)
+            }
+        };
+        if self.stereo.mode != StereoMode::SideBySide {
+            return vec![(cell, camera)];
+        }
+        let viewport = [cell.left(), cell.top(), cell.width(), cell.height()];
+        self.stereo
+            .views(&camera, self.controller.center, viewport)
+            .into_iter()
+            .map(|(_, camera, [x, y, width, height])| {
+                let rect = egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(width, height));
+                (rect, camera)
+            })
+            .collect()
+    }
+
+    /// value of the volume seen under the cursor, see [reference::pick_value]
+    fn pick_value(&self) -> Option<f32> {
+        self.pick().map(|(_, hit)| hit.value)
+    }
+
+    /// channel and sample of the volume seen under the cursor, see [reference::pick]
+    fn pick(&self) -> Option<(usize, reference::RayHit)> {
+        let cursor = self.cursor_position?;
+        let window_size = Vector2::new(self.config.width as f32, self.config.height as f32);
+        let (i, cell_size, cell_min) = lens::cell_at(
+            cursor,
+            window_size,
+            self.num_columns as usize,
+            self.volumes.len(),
+            self.selected_channel,
+        )?;
+        let mut camera = self.camera;
+        camera
+            .projection
+            .resize(cell_size.x as u32, cell_size.y as u32);
+        let tex_coord = (cursor - cell_min).div_element_wise(cell_size);
+        let settings = self.frame_render_settings();
+        let volume = &self.volumes[i].volume;
+        let hit = match &self.cmap_sequence {
+            Some(seq) => {
+                let cmap = seq
+                    .with_opacity(&self.opacity)
+                    .at(settings.time, COLORMAP_RESOLUTION);
+                reference::pick(volume, &cmap, &camera, &settings, tex_coord)
+            }
+            None => {
+                let cmap = self.cmap.with_opacity(&self.opacity);
+                reference::pick(volume, &cmap, &camera, &settings, tex_coord)
+            }
+        }?;
+        Some((i, hit))
+    }
+
+    /// reads the voxel under the cursor, cleared if the ray misses the volume
+    fn probe_at_cursor(&mut self) {
+        self.probe = self.pick().map(|(channel, hit)| {
+            Probe::new(
+                self.cursor_position.unwrap(),
+                channel,
+                hit,
+                &self.volumes,
+                self.render_settings.time,
+            )
+        });
+    }
+
+    /// adds the point seen under the cursor to the measurement
+    fn measure_at_cursor(&mut self) {
+        if let Some((channel, hit)) = self.pick() {
+            self.measure.add(channel, hit.position);
+        }
+    }
+
+    /// orbits the camera around the point seen under the cursor
+    fn center_at_cursor(&mut self) {
+        if let Some((_, hit)) = self.pick() {
+            self.controller.center_at(&mut self.camera, hit.position);
+        }
+    }
+
+    /// offset from the view center to the point under the cursor in world space,
+    /// divided by the viewport size (see [CameraController::process_scroll])
+    fn zoom_anchor(&self) -> Option<Vector3<f32>> {
+        let cursor = self.cursor_position?;
+        let window_size = Vector2::new(self.config.width as f32, self.config.height as f32);
+        let (_, cell_size, cell_min) = lens::cell_at(
+            cursor,
+            window_size,
+            self.num_columns as usize,
+            self.volumes.len(),
+            self.selected_channel,
+        )?;
+        let mut camera = self.camera;
+        camera
+            .projection
+            .resize(cell_size.x as u32, cell_size.y as u32);
+        let tex_coord = (cursor - cell_min).div_element_wise(cell_size);
+        let inv = camera.view_proj_matrix().invert()?;
+        let unproject = |x: f32, y: f32| {
+            let p = inv * Vector4::new(x, y, 0., 1.);
+            p.truncate() / p.w
+        };
+        let offset = unproject(tex_coord.x * 2. - 1., 1. - tex_coord.y * 2.) - unproject(0., 0.);
+        Some(offset / self.camera.projection.viewport.magnitude())
+    }
+
+    /// places a new label at the point seen under the cursor
+    fn label_at_cursor(&mut self) {
+        if let Some((_, hit)) = self.pick() {
+            let labels = &mut self.annotations.labels;
+            labels.push(Label {
+                text: format!("Label {}", labels.len() + 1),
+                position: hit.position.into(),
+            });
+        }
+    }
+
+    /// sets the value of the first surface to the value under the cursor.
+    /// Adds a new surface instead if `add` is set or there is none yet.
+    fn pick_surface_value(&mut self, add: bool) {
+        let Some(value) = self.pick_value() else {
+            return;
+        };
+        match self.reference_values.iter_mut().find(|r| r.surface) {
+            Some(surface) if !add => surface.value = value,
+            _ => {
+                let mut surface = ReferenceValue::new(
+                    format!("surface {}", self.reference_values.len() + 1),
+                    value,
+                );
+                surface.surface = true;
+                self.reference_values.push(surface);
+            }
+        }
+    }
+
+    /// region shown by the magnifier lens. Makes sure the lens render target has the right size.
+    fn lens_view(&mut self) -> Option<lens::LensView> {
+        // the lens is placed in mono views only
+        if !self.lens.enabled || self.stereo.mode != StereoMode::Off {
+            return None;
+        }
+        let view = self.lens.view(
+            &self.camera,
+            self.cursor_position?,
+            Vector2::new(self.config.width as f32, self.config.height as f32),
+            self.num_columns as usize,
+            self.volumes.len(),
+            self.selected_channel,
+        )?;
+        let size = (2. * view.radius) as u32;
+        if self.lens_target.as_ref().map(|t| t.size()) != Some(size) {
+            self.lens_target = Some(LensTarget::new(
+                &self.wgpu_context.device,
+                &self.lens_blitter,
+                self.render_format,
+                size,
+            ));
+        }
+        Some(view)
+    }
+
+    /// everything that influences the volume rendering with `settings` at `resolution`
+    fn frame_state(&self, settings: &RenderSettings, resolution: (u32, u32)) -> FrameState {
+        let mut cmap_hasher = DefaultHasher::new();
+        self.cmap.hash(&mut cmap_hasher);
+        self.cmap_sequence.hash(&mut cmap_hasher);
+        self.opacity.hash(&mut cmap_hasher);
+        FrameState {
+            camera: self.camera,
+            settings: settings.clone(),
+            cmap_hash: cmap_hasher.finish(),
+            background: (self.background_color, self.background),
+            selected_channel: self.selected_channel,
+            num_columns: self.num_columns,
+            resolution,
+            overlay: self.overlay,
+            stereo: self.stereo,
+        }
+    }
+
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let window_size = self.window.inner_size();
+        if window_size.width != self.config.width || window_size.height != self.config.height {
+            self.resize(window_size, None);
+        }
+
+        let output = self.surface.get_current_texture()?;
+        let surface_view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let view_rgb = if self.prepare_encode_target() {
+            let target = self.encode_target.as_ref().unwrap();
+            target
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        } else {
+            output.texture.create_view(&wgpu::TextureViewDescriptor {
+                format: Some(self.render_format),
+                ..Default::default()
+            })
+        };
+
+        // do prepare stuff
+
+        let mut encoder =
+            self.wgpu_context
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("render command encoder"),
+                });
+        let mut frame_data = Vec::new();
+        let mut line_data = Vec::new();
+        let mut background_data = Vec::new();
+
+        let (target_width, target_height) = self.prepare_render_target();
+        let depth_view = self.prepare_depth_buffer(target_width, target_height);
+        let lens_view = self.lens_view();
+        let mut render_settings = self.frame_render_settings();
+        self.gpu_timer.begin(self.performance_overlay);
+
+        let columns = self.num_columns as usize;
+        let rows = (self.volumes.len() as f32 / columns as f32).ceil() as usize;
+        let cell_width = target_width as f32 / columns as f32;
+        let cell_height = target_height as f32 / rows as f32;
+
+        // index of the anti-aliasing sample rendered in this frame
+        // None if anti-aliasing is disabled or all samples are accumulated
+        let frame_state = self.frame_state(&render_settings, (target_width, target_height));
+        let mut render_volume = true;
+        let taa_sample = if self.taa.enabled {
+            self.last_frame = None;
+            let sample = self.taa.next_sample(frame_state);
+            render_volume = sample.is_some();
+            if let Some(sample) = sample {
+                let (offset, ray_offset) = accumulation::jitter(sample);
+                let (mut w, h) = if self.selected_channel.is_some() {
+                    (target_width as f32, target_height as f32)
+                } else {
+                    (cell_width, cell_height)
+                };
+                if self.stereo.mode == StereoMode::SideBySide {
+                    w /= 2.;
+                }
+                render_settings.jitter = Vector2::new(offset.x / w, offset.y / h);
+                render_settings.ray_offset = ray_offset;
+            }
+            sample
         } else {
-            for v in &self.volumes {
+            if self.render_target.is_some() && self.reuse_frames {
+                // the render target still holds the frame if nothing changed
+                render_volume = self.last_frame.as_ref() != Some(&frame_state);
+                self.last_frame = Some(frame_state);
+            } else {
+                self.last_frame = None;
+            }
+            None
+        };
+
+        let overlays = self.annotations.any()
+            || self.render_settings.colorbar.is_some()
+            || !self.measure.points.is_empty()
+            || self.comparison.is_some();
+        // screenshots without the user interface hide it for one frame
+        let ui_visible = self.ui_visible && !self.screenshots.hides_ui();
+        if !ui_visible {
+            self.crop_box.hovered = None;
+        }
+        let ui_state = if ui_visible || overlays {
+            self.ui_renderer.begin_frame(&self.window);
+            if ui_visible {
+                ui::ui(self);
+            }
+            self.paint_annotations();
+
+            let shapes = self.ui_renderer.end_frame(&self.window);
+            Some(self.ui_renderer.prepare(
+                PhysicalSize {
+                    width: output.texture.size().width,
+                    height: output.texture.size().height,
+                },
+                self.scale_factor,
+                &self.wgpu_context.device,
+                &self.wgpu_context.queue,
+                &mut encoder,
+                shapes,
+            ))
+        } else {
+            None
+        };
+
+        if self.stereo.mode == StereoMode::Anaglyph {
+            self.prepare_stereo_target(target_width, target_height);
+        }
+
+        // every view of a volume with its viewport, split into the views of the eyes for stereo
+        let mut views = Vec::new();
+        if let Some(selected_channel) = self.selected_channel {
+            let viewport = [0., 0., target_width as f32, target_height as f32];
+            for (eye, camera, viewport) in
+                self.stereo
+                    .views(&self.camera, self.controller.center, viewport)
+            {
+                views.push((eye, selected_channel, camera, viewport));
+            }
+        } else {
+            for i in 0..self.volumes.len() {
                 let mut camera = self.camera.clone();
                 camera
                     .projection
                     .resize(cell_width as u32, cell_height as u32);
-                frame_data.push(self.renderer.prepare(
+                let viewport = [
+                    (i % columns) as f32 * cell_width,
+                    (i / columns) as f32 * cell_height,
+                    cell_width,
+                    cell_height,
+                ];
+                for (eye, camera, viewport) in
+                    self.stereo.views(&camera, self.controller.center, viewport)
+                {
+                    views.push((eye, i, camera, viewport));
+                }
+            }
+        }
+        self.frame_stats = FrameStats {
+            rays: if render_volume {
+                views.iter().map(|(.., [_, _, w, h])| (w * h) as u64).sum()
+            } else {
+                0
+            },
+            samples_per_ray: (2. * self.volumes[0].volume.aabb.radius() / render_settings.step_size)
+                .ceil() as u32,
+        };
+        // the left eye of an anaglyph is rendered separately and composited into the red channel
+        let anaglyph = self.stereo.mode == StereoMode::Anaglyph;
+        let mut viewports = Vec::new();
+        let mut left_data = Vec::new();
+        for (eye, i, camera, viewport) in views {
+            let volume = &self.volumes[i];
+            let data = (
+                self.renderer.lock().unwrap().prepare(
                     &self.wgpu_context.device,
-                    &v,
+                    volume,
                     &camera,
-                    &self.render_settings,
+                    &render_settings,
                     &self.cmap_gpu,
-                ));
+                ),
+                self.line_renderer.prepare(
+                    &self.wgpu_context.device,
+                    &camera,
+                    render_settings.jitter,
+                    &self.overlay.lines(&volume.volume.aabb),
+                ),
+                self.background_renderer.prepare(
+                    &self.wgpu_context.device,
+                    &self.background,
+                    self.background_color,
+                    &camera,
+                ),
+            );
+            if anaglyph && eye == Eye::Left {
+                left_data.push((data, viewport));
+            } else {
+                frame_data.push(data.0);
+                line_data.push(data.1);
+                background_data.push(data.2);
+                viewports.push(viewport);
             }
         }
 
-        {
+        let lens_data = lens_view.as_ref().map(|view| {
+            let settings = self.lens.render_settings(&self.render_settings);
+            let volume = &self.volumes[view.volume];
+            (
+                self.renderer.lock().unwrap().prepare(
+                    &self.wgpu_context.device,
+                    volume,
+                    &view.camera,
+                    &settings,
+                    &self.cmap_gpu,
+                ),
+                self.line_renderer.prepare(
+                    &self.wgpu_context.device,
+                    &view.camera,
+                    Vector2::zero(),
+                    &self.overlay.lines(&volume.volume.aabb),
+                ),
+                self.background_renderer.prepare(
+                    &self.wgpu_context.device,
+                    &self.background,
+                    self.background_color,
+                    &view.camera,
+                ),
+            )
+        });
+
+        let taa_frame = if taa_sample.is_some() {
+            Some(self.taa.frame_target(
+                &self.wgpu_context.device,
+                self.render_format,
+                target_width,
+                target_height,
+            ))
+        } else {
+            None
+        };
+
+        // target of the volume rendering
+        let volume_view = taa_frame.map(|f| &f.view).unwrap_or(
+            self.render_target
+                .as_ref()
+                .map_or(&view_rgb, |target| &target.view),
+        );
+        if render_volume {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("render pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view_rgb,
+                    view: volume_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(self.background_color),
                         store: wgpu::StoreOp::Store,
                     },
                 })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: self.gpu_timer.pass("Volume"),
                 ..Default::default()
             });
-            for (i, v) in frame_data.iter().enumerate() {
-                if self.selected_channel.is_none() {
-                    let column = i % columns;
-                    let row = i / columns;
-                    render_pass.set_viewport(
-                        column as f32 * cell_width,
-                        row as f32 * cell_height,
-                        cell_width,
-                        cell_height,
-                        0.,
-                        1.,
-                    );
+            for (((v, lines), background), [x, y, width, height]) in frame_data
+                .iter()
+                .zip(&line_data)
+                .zip(&background_data)
+                .zip(&viewports)
+            {
+                render_pass.set_viewport(*x, *y, *width, *height, 0., 1.);
+                if let Some(background) = background {
+                    self.background_renderer
+                        .render(&mut render_pass, background);
                 }
-                self.renderer.render(&mut render_pass, &v);
+                self.renderer.lock().unwrap().render(&mut render_pass, &v);
+                self.line_renderer
+                    .render(&mut render_pass, lines, self.overlay.xray);
             }
+        }
+        if let (true, Some(target)) = (render_volume && anaglyph, &self.stereo_target) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("left eye pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.background_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: self.gpu_timer.pass("Left Eye"),
+                ..Default::default()
+            });
+            for ((v, lines, background), [x, y, width, height]) in &left_data {
+                render_pass.set_viewport(*x, *y, *width, *height, 0., 1.);
+                if let Some(background) = background {
+                    self.background_renderer
+                        .render(&mut render_pass, background);
+                }
+                self.renderer.lock().unwrap().render(&mut render_pass, v);
+                self.line_renderer
+                    .render(&mut render_pass, lines, self.overlay.xray);
+            }
+            drop(render_pass);
 
-            if let Some(state) = &ui_state {
-                // ui rendering
-
-                self.ui_renderer.render(&mut render_pass, state);
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("anaglyph composite pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: volume_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                timestamp_writes: self.gpu_timer.pass("Anaglyph"),
+                ..Default::default()
+            });
+            self.anaglyph_blitter
+                .render(&mut render_pass, &target.bind_group);
+        }
+        if let (Some(sample), Some(target)) = (taa_sample, &self.render_target) {
+            // blend the new sample into the accumulated frames
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("accumulation pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                timestamp_writes: self.gpu_timer.pass("Accumulation"),
+                ..Default::default()
+            });
+            self.taa.accumulate(&mut render_pass, sample);
+        }
+        if let Some(target) = &self.render_target {
+            // upscale volume rendering to window resolution
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("upscale pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view_rgb,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.background_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                timestamp_writes: self.gpu_timer.pass("Upscale"),
+                ..Default::default()
+            });
+            self.blitter.render(&mut render_pass, &target.bind_group);
+        }
+        if let (Some(view), Some((volume_data, lines, background)), Some(target)) =
+            (&lens_view, &lens_data, &self.lens_target)
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("lens pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.color.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.background_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &target.depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: self.gpu_timer.pass("Lens"),
+                ..Default::default()
+            });
+            if let Some(background) = background {
+                self.background_renderer
+                    .render(&mut render_pass, background);
             }
+            self.renderer
+                .lock()
+                .unwrap()
+                .render(&mut render_pass, volume_data);
+            self.line_renderer
+                .render(&mut render_pass, lines, self.overlay.xray);
+            drop(render_pass);
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("lens composite pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view_rgb,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                timestamp_writes: self.gpu_timer.pass("Lens Composite"),
+                ..Default::default()
+            });
+            render_pass.set_viewport(
+                view.center.x - view.radius,
+                view.center.y - view.radius,
+                2. * view.radius,
+                2. * view.radius,
+                0.,
+                1.,
+            );
+            self.lens_blitter
+                .render(&mut render_pass, &target.color.bind_group);
+        }
+        let ui_view =
+            if let (Some(blitter), Some(target)) = (&self.encode_blitter, &self.encode_target) {
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("srgb encode pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &surface_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    timestamp_writes: self.gpu_timer.pass("sRGB Encode"),
+                    ..Default::default()
+                });
+                blitter.render(&mut render_pass, &target.bind_group);
+                &surface_view
+            } else {
+                &view_rgb
+            };
+        if let Some(state) = &ui_state {
+            // ui rendering (in its own pass as the ui pipeline has no depth buffer)
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("ui pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: ui_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                timestamp_writes: self.gpu_timer.pass("UI"),
+                ..Default::default()
+            });
+            self.ui_renderer.render(&mut render_pass, state);
         }
         if let Some(ui_state) = ui_state {
             self.ui_renderer.cleanup(ui_state)
         }
+        self.screenshots
+            .capture(&self.wgpu_context.device, &mut encoder, &output.texture);
+        self.gpu_timer.resolve(&mut encoder);
         self.wgpu_context
             .queue
             .submit(std::iter::once(encoder.finish()));
+        self.screenshots.map();
+        self.gpu_timer.map();
 
         output.present();
         Ok(())
@@ -414,135 +2393,269 @@ pub async fn open_window(
     cmap: LinearSegmentedColorMap,
     config: RenderConfig,
 ) {
-    let event_loop = EventLoop::new().unwrap();
-
-    let version = env!("CARGO_PKG_VERSION");
-    let name = env!("CARGO_PKG_NAME");
+    open_windows(
+        window_builder,
+        vec![ViewerWindow {
+            volumes,
+            cmap,
+            config,
+        }],
+    )
+    .await
+}
 
-    let window = window_builder
-        .with_title(format!("{name} {version}"))
-        .build(&event_loop)
-        .unwrap();
+/// compares strings with numbers in them by value, so that `t2` comes before `t10`
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let end_a = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let end_b = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let (na, nb) = (
+                a[..end_a].trim_start_matches('0'),
+                b[..end_b].trim_start_matches('0'),
+            );
+            let order = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb));
+            if order.is_ne() {
+                return order;
+            }
+            (a, b) = (&a[end_a..], &b[end_b..]);
+        } else {
+            if ca != cb {
+                return ca.cmp(&cb);
+            }
+            (a, b) = (&a[ca.len_utf8()..], &b[cb.len_utf8()..]);
+        }
+    }
+}
 
-    let mut state = WindowContext::new(window, volumes, cmap, &config)
-        .await
-        .unwrap();
+/// content of a file dropped onto a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileKind {
+    /// npy or npz volume
+    Volume,
+    /// npy or json colormap
+    Colormap,
+    /// json session
+    Session,
+}
 
-    let mut last = Instant::now();
-    let mut last_touch_position = Vector2::zero();
-    event_loop.run(move |event,target| 
-       
-        match event {
-        Event::WindowEvent {
-            ref event,
-            window_id,
-        } if window_id == state.window.id() && !state.ui_renderer.on_event(&state.window,event) => match event {
-            WindowEvent::Resized(physical_size) => {
-                state.resize(*physical_size, None);
+impl FileKind {
+    /// detects the kind from the content: numpy arrays with two dimensions are colormaps,
+    /// json files with a camera are sessions, other json files are colormaps
+    fn detect(path: &Path) -> anyhow::Result<Self> {
+        let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        Ok(match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => {
+                let value: serde_json::Value = serde_json::from_reader(reader)?;
+                if value.get("camera").is_some() {
+                    Self::Session
+                } else {
+                    Self::Colormap
+                }
             }
-            WindowEvent::ScaleFactorChanged {
-                scale_factor,
-                ..
-            } => {
-                state.scale_factor = *scale_factor as f32;
+            Some("npy") if npyz::NpyFile::new(reader)?.shape().len() == 2 => Self::Colormap,
+            _ => Self::Volume,
+        })
+    }
+}
+
+/// windows of the viewer, they share the GPU device and the event loop
+struct Windows {
+    instance: wgpu::Instance,
+    /// context of the first window, shared by all others
+    wgpu_context: Option<Arc<WGPUContext>>,
+    windows: Vec<WindowContext>,
+}
+
+impl Windows {
+    async fn open(
+        &mut self,
+        target: &EventLoopWindowTarget<()>,
+        window_builder: WindowBuilder,
+        window: ViewerWindow,
+    ) -> anyhow::Result<()> {
+        let version = env!("CARGO_PKG_VERSION");
+        let name = env!("CARGO_PKG_NAME");
+        let title = match window
+            .config
+            .volume_file
+            .as_ref()
+            .and_then(|f| f.file_name())
+        {
+            Some(file) => format!("{name} {version} - {}", file.to_string_lossy()),
+            None => format!("{name} {version}"),
+        };
+        let os_window = window_builder.with_title(title).build(target)?;
+        let state = WindowContext::new(
+            os_window,
+            &self.instance,
+            self.wgpu_context.clone(),
+            window.volumes,
+            window.cmap,
+            &window.config,
+        )
+        .await?;
+        self.wgpu_context
+            .get_or_insert_with(|| state.wgpu_context.clone());
+        self.windows.push(state);
+        Ok(())
+    }
+
+    /// opens the files requested by the windows, a file that is open already
+    /// focuses its window instead
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_requested(
+        &mut self,
+        target: &EventLoopWindowTarget<()>,
+        window_builder: &WindowBuilder,
+    ) {
+        let mut requests = Vec::new();
+        for state in self.windows.iter_mut() {
+            for path in state.open_requests.drain(..) {
+                requests.push((path, state.cmap.clone()));
             }
-            WindowEvent::CloseRequested => {log::info!("close!");target.exit()},
-            WindowEvent::ModifiersChanged(m)=>{
-                state.controller.alt_pressed = m.state().alt_key();
+        }
+        for (path, cmap) in requests {
+            if let Some(open) = self
+                .windows
+                .iter()
+                .find(|w| w.volume_file.as_ref() == Some(&path))
+            {
+                open.window.focus_window();
+                continue;
             }
-            WindowEvent::KeyboardInput { event, .. } => {
-                if let PhysicalKey::Code(key) = event.physical_key{
-                    state
-                        .controller
-                        .process_keyboard(key, event.state == ElementState::Pressed);
-                    if key == KeyCode::KeyU && event.state == ElementState::Released{
-                        state.ui_visible = !state.ui_visible;
-                    }
-                }
+            let result = ViewerWindow::load(&path, cmap).and_then(|window| {
+                pollster::block_on(self.open(target, window_builder.clone(), window))
+            });
+            if let Err(err) = result {
+                log::error!("failed to open {}: {err:?}", path.display());
             }
-            WindowEvent::MouseWheel { delta, .. } => match delta {
-                winit::event::MouseScrollDelta::LineDelta(_, dy) => {
-                    state.controller.process_scroll(*dy )
-                }
-                winit::event::MouseScrollDelta::PixelDelta(p) => {
-                    state.controller.process_scroll(p.y as f32 / 100.)
+        }
+    }
+}
+
+/// opens a window for each of `windows` and runs the event loop until all are closed
+pub async fn open_windows(window_builder: WindowBuilder, windows: Vec<ViewerWindow>) {
+    let event_loop = EventLoop::new().unwrap();
+
+    let mut state = Windows {
+        instance: wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: Backends::all().symmetric_difference(Backends::BROWSER_WEBGPU),
+            ..Default::default()
+        }),
+        wgpu_context: None,
+        windows: Vec::new(),
+    };
+    for window in windows {
+        state
+            .open(&event_loop, window_builder.clone(), window)
+            .await
+            .unwrap();
+    }
+
+    event_loop
+        .run(move |event, target| match event {
+            Event::WindowEvent { ref event, window_id } => {
+                let Some(i) = state.windows.iter().position(|w| w.window.id() == window_id) else {
+                    return;
+                };
+                let window = &mut state.windows[i];
+                if window.ui_renderer.on_event(&window.window, event) {
+                    return;
                 }
-            },
-            WindowEvent::Touch(touch)=>{
-                match  touch.phase{
-                    winit::event::TouchPhase::Started=>{
-                        state.controller.left_mouse_pressed = true;
-                        last_touch_position = Vector2::new(touch.location.x, touch.location.y);
-                    }
-                    winit::event::TouchPhase::Ended=>{
-                        state.controller.left_mouse_pressed = false;
+                if window.window_event(event) {
+                    log::info!("close!");
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Err(err) = window.save_transfer_function() {
+                        log::error!("failed to save the transfer function: {err:?}");
                     }
-                    winit::event::TouchPhase::Moved=>{
-                        state.controller.process_mouse((touch.location.x-last_touch_position.x) as f32, (touch.location.y-last_touch_position.y) as f32);
-                        last_touch_position = Vector2::new(touch.location.x, touch.location.y);
+                    state.windows.remove(i);
+                    if state.windows.is_empty() {
+                        target.exit();
                     }
-                    _=>{}
-                    
                 }
             }
-            WindowEvent::MouseInput { state:button_state, button, .. }=>{
-                match button {
-                    winit::event::MouseButton::Left =>                         state.controller.left_mouse_pressed = *button_state == ElementState::Pressed,
-                    winit::event::MouseButton::Right => state.controller.right_mouse_pressed = *button_state == ElementState::Pressed,
-                    _=>{}
-                }
-            },
-            WindowEvent::DroppedFile(file) => {
-                if let Err(e) = state.load_file(file){
-                    log::error!("failed to load file: {:?}", e)
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                .. // We're not using device_id currently
+            } => {
+                // only the window the mouse is pressed in moves its camera
+                for window in state.windows.iter_mut() {
+                    window
+                        .controller
+                        .process_mouse(delta.0 as f32, delta.1 as f32)
                 }
             }
-            WindowEvent::RedrawRequested => {
-                let now = Instant::now();
-                let dt = now-last;
-                last = now;
-                state.update(dt);
-    
-                match state.render() {
-                    Ok(_) => {}
-                    // Reconfigure the surface if lost
-                    Err(wgpu::SurfaceError::Lost) =>{
-                        log::error!("lost surface!");
-                         state.resize(state.window.inner_size(), None)
+            Event::AboutToWait => {
+                #[cfg(not(target_arch = "wasm32"))]
+                state.open_requested(target, &window_builder);
 
-                        },
-                    // The system is out of memory, we should probably quit
-                    Err(wgpu::SurfaceError::OutOfMemory) =>target.exit(),
-                    // All other errors (Outdated, Timeout) should be resolved by the next frame
-                    Err(e) => println!("error: {:?}", e),
-                }
-            }
-            _ => {}
-        },
-        Event::DeviceEvent {
-            event: DeviceEvent::MouseMotion{ delta, },
-            .. // We're not using device_id currently
-        } => {
-            state.controller.process_mouse(delta.0 as f32, delta.1 as f32)
-        }
-        
-        Event::AboutToWait => {
-            #[cfg(target_arch = "wasm32")]
-            use winit::platform::web::WindowExtWebSys;
-            #[cfg(target_arch = "wasm32")]
-            if let Some(canvas) = state.window.canvas() {
-                if canvas.parent_node().is_none() {
+                #[cfg(target_arch = "wasm32")]
+                state.windows.iter_mut().for_each(web::sync_widget);
+
+                #[cfg(target_arch = "wasm32")]
+                use winit::platform::web::WindowExtWebSys;
+                #[cfg(target_arch = "wasm32")]
+                if state.windows.iter().any(|w| {
+                    w.window
+                        .canvas()
+                        .is_some_and(|canvas| canvas.parent_node().is_none())
+                }) {
                     // The canvas has been removed from the DOM, we should exit
                     target.exit();
                     return;
                 }
-            }
 
-            // RedrawRequested will only trigger once, unless we manually
-            // request it.
-            state.window.request_redraw();
-        }
-        _ => {},
-    }).unwrap();
+                // RedrawRequested will only trigger once, unless we manually
+                // request it.
+                let now = Instant::now();
+                let mut next_frame = None;
+                for window in state.windows.iter() {
+                    let next = window.pacing.next_frame();
+                    if now >= next {
+                        window.window.request_redraw();
+                    } else {
+                        next_frame = Some(next_frame.map_or(next, |n: Instant| n.min(next)));
+                    }
+                }
+                if let Some(next_frame) = next_frame {
+                    target.set_control_flow(ControlFlow::wait_duration(next_frame - now));
+                }
+            }
+            _ => {}
+        })
+        .unwrap();
     log::info!("exit!");
 }
+
+/// format the window is rendered in and the view formats of the surface.
+/// Surfaces without an sRGB format get an sRGB view if supported, otherwise the image
+/// is rendered into an sRGB texture and encoded manually.
+fn srgb_render_format(
+    surface_format: wgpu::TextureFormat,
+    adapter: &wgpu::Adapter,
+) -> (wgpu::TextureFormat, Vec<wgpu::TextureFormat>) {
+    // float surfaces expect linear colors
+    if surface_format.is_srgb()
+        || matches!(
+            surface_format,
+            wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgba32Float
+        )
+    {
+        return (surface_format, vec![]);
+    }
+    let srgb_format = surface_format.add_srgb_suffix();
+    let view_formats_supported = adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS);
+    if srgb_format.is_srgb() && view_formats_supported {
+        (srgb_format, vec![srgb_format])
+    } else {
+        (wgpu::TextureFormat::Rgba8UnormSrgb, vec![])
+    }
+}
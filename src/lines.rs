@@ -0,0 +1,241 @@
+use cgmath::{EuclideanSpace, Matrix4, Point3, Vector2, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    camera::{Camera, Projection},
+    volume::Aabb,
+};
+
+/// format of the depth buffer lines are tested against
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// which lines are drawn on top of the volume
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct OverlaySettings {
+    pub bounding_box: bool,
+    pub axes: bool,
+    /// draw lines on top of the volume instead of depth testing them against the first hit
+    pub xray: bool,
+}
+
+impl OverlaySettings {
+    pub fn any(&self) -> bool {
+        self.bounding_box || self.axes
+    }
+
+    pub fn lines(&self, aabb: &Aabb<f32>) -> Vec<LineVertex> {
+        let mut lines = Vec::new();
+        if self.bounding_box {
+            lines.extend(box_lines(aabb, [0.8, 0.8, 0.8, 1.]));
+        }
+        if self.axes {
+            lines.extend(axes_lines(aabb));
+        }
+        lines
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: Point3<f32>,
+    pub color: [f32; 4],
+}
+
+/// draws line lists with the same camera as the volume renderer
+pub struct LineRenderer {
+    pipeline: wgpu::RenderPipeline,
+    pipeline_xray: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl LineRenderer {
+    pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("line bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("line pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/lines.wgsl"));
+
+        let create_pipeline = |depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("line pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                    }],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        Self {
+            pipeline: create_pipeline(wgpu::CompareFunction::LessEqual),
+            pipeline_xray: create_pipeline(wgpu::CompareFunction::Always),
+            bind_group_layout,
+        }
+    }
+
+    pub fn prepare<P: Projection>(
+        &self,
+        device: &wgpu::Device,
+        camera: &Camera<P>,
+        jitter: Vector2<f32>,
+        lines: &[LineVertex],
+    ) -> LineFrameData {
+        let uniforms = LineUniforms {
+            view_proj: camera.view_proj_matrix(),
+            jitter,
+            _pad: [0.; 2],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("line uniform buffer"),
+            contents: bytemuck::bytes_of(&uniforms),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("line bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("line vertex buffer"),
+            contents: bytemuck::cast_slice(lines),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        LineFrameData {
+            bind_group,
+            vertex_buffer,
+            num_vertices: lines.len() as u32,
+        }
+    }
+
+    pub fn render<'rpass>(
+        &'rpass self,
+        render_pass: &mut wgpu::RenderPass<'rpass>,
+        frame_data: &'rpass LineFrameData,
+        xray: bool,
+    ) {
+        if frame_data.num_vertices == 0 {
+            return;
+        }
+        render_pass.set_pipeline(if xray {
+            &self.pipeline_xray
+        } else {
+            &self.pipeline
+        });
+        render_pass.set_bind_group(0, &frame_data.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, frame_data.vertex_buffer.slice(..));
+        render_pass.draw(0..frame_data.num_vertices, 0..1);
+    }
+}
+
+pub struct LineFrameData {
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    num_vertices: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineUniforms {
+    view_proj: Matrix4<f32>,
+    jitter: Vector2<f32>,
+    _pad: [f32; 2],
+}
+
+/// the 12 edges of a box
+pub fn box_lines(aabb: &Aabb<f32>, color: [f32; 4]) -> Vec<LineVertex> {
+    let corner = |i: usize| {
+        Point3::new(
+            if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+            if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+            if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+        )
+    };
+    let mut lines = Vec::with_capacity(24);
+    for i in 0..8 {
+        for axis in [1, 2, 4] {
+            if i & axis == 0 {
+                lines.push(LineVertex {
+                    position: corner(i),
+                    color,
+                });
+                lines.push(LineVertex {
+                    position: corner(i | axis),
+                    color,
+                });
+            }
+        }
+    }
+    lines
+}
+
+/// x (red), y (green) and z (blue) axes along the box edges starting at its minimum
+pub fn axes_lines(aabb: &Aabb<f32>) -> Vec<LineVertex> {
+    let size = aabb.max - aabb.min;
+    [
+        (Vector3::new(size.x, 0., 0.), [1., 0.2, 0.2, 1.]),
+        (Vector3::new(0., size.y, 0.), [0.2, 1., 0.2, 1.]),
+        (Vector3::new(0., 0., size.z), [0.3, 0.5, 1., 1.]),
+    ]
+    .into_iter()
+    .flat_map(|(dir, color)| {
+        [
+            LineVertex {
+                position: aabb.min,
+                color,
+            },
+            LineVertex {
+                position: Point3::from_vec(aabb.min.to_vec() + dir),
+                color,
+            },
+        ]
+    })
+    .collect()
+}
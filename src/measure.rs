@@ -0,0 +1,145 @@
+use cgmath::{ElementWise, InnerSpace, Point3, Vector3};
+use egui::{pos2, vec2, Align2, Color32, FontId, Painter, Rect, Stroke};
+
+use crate::{
+    annotations::Annotations,
+    camera::{Camera, Projection},
+    volume::Volume,
+};
+
+/// what is measured between the picked points
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeasureKind {
+    /// length of the line between two points
+    #[default]
+    Distance,
+    /// angle at the second of three points
+    Angle,
+}
+
+impl MeasureKind {
+    pub const ALL: [Self; 2] = [Self::Distance, Self::Angle];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MeasureKind::Distance => "Distance",
+            MeasureKind::Angle => "Angle",
+        }
+    }
+
+    pub fn num_points(&self) -> usize {
+        match self {
+            MeasureKind::Distance => 2,
+            MeasureKind::Angle => 3,
+        }
+    }
+}
+
+/// points picked on the volume in measurement mode
+#[derive(Debug, Clone, Default)]
+pub struct Measurement {
+    /// clicks into the volume pick points instead of rotating the camera
+    pub enabled: bool,
+    pub kind: MeasureKind,
+    /// channel the points were picked in
+    pub channel: usize,
+    /// picked points in world space
+    pub points: Vec<Point3<f32>>,
+}
+
+impl Measurement {
+    /// adds a point, a complete measurement or one in another channel is started again
+    pub fn add(&mut self, channel: usize, point: Point3<f32>) {
+        if self.complete() || channel != self.channel {
+            self.points.clear();
+        }
+        self.channel = channel;
+        self.points.push(point);
+    }
+
+    pub fn complete(&self) -> bool {
+        self.points.len() >= self.kind.num_points()
+    }
+
+    /// result of the measurement, None until all points are picked
+    pub fn result(&self, volume: &Volume, unit: &str) -> Option<String> {
+        if !self.complete() {
+            return None;
+        }
+        let p = &self.points;
+        Some(match self.kind {
+            MeasureKind::Distance => distance_label(volume, p[0], p[1], unit),
+            MeasureKind::Angle => format!("{:.1}°", angle(p[0], p[1], p[2])),
+        })
+    }
+
+    /// paints the points, the lines between them and the result into `rect` (in points)
+    /// with the unit, color and size of the annotations
+    pub fn paint<P: Projection>(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        camera: &Camera<P>,
+        volume: &Volume,
+        annotations: &Annotations,
+    ) {
+        let (color, scale) = (annotations.color, annotations.scale);
+        let view_proj = camera.view_proj_matrix();
+        let project = |p: Point3<f32>| {
+            let clip = view_proj * p.to_homogeneous();
+            pos2(
+                rect.left() + (clip.x / clip.w + 1.) / 2. * rect.width(),
+                rect.top() + (1. - clip.y / clip.w) / 2. * rect.height(),
+            )
+        };
+        let painter = painter.with_clip_rect(rect);
+        let points: Vec<_> = self.points.iter().map(|p| project(*p)).collect();
+        for line in points.windows(2) {
+            painter.line_segment([line[0], line[1]], Stroke::new(2. * scale, color));
+        }
+        for p in &points {
+            painter.circle(*p, 4. * scale, color, Stroke::new(1., Color32::BLACK));
+        }
+        let Some(label) = self.result(volume, &annotations.unit) else {
+            return;
+        };
+        // the distance is labeled at the middle of the line, the angle at its vertex
+        let anchor = match self.kind {
+            MeasureKind::Distance => points[0] + (points[1] - points[0]) / 2.,
+            MeasureKind::Angle => points[1],
+        };
+        let galley = painter.layout_no_wrap(label, FontId::proportional(13. * scale), color);
+        let pos = anchor + vec2(8., -8.) * scale;
+        let text_rect = Align2::LEFT_BOTTOM.anchor_size(pos, galley.size());
+        painter.rect_filled(
+            text_rect.expand(3. * scale),
+            3. * scale,
+            Color32::from_black_alpha(160),
+        );
+        painter.galley(text_rect.min, galley, color);
+    }
+}
+
+/// length of the line between `a` and `b` (in world space) in voxels and physical units
+pub fn distance(volume: &Volume, a: Point3<f32>, b: Point3<f32>) -> (f32, f32) {
+    let r = volume.resolution;
+    let voxels = (b - a)
+        .div_element_wise(volume.aabb.max - volume.aabb.min)
+        .mul_element_wise(Vector3::new(r[2] as f32, r[1] as f32, r[0] as f32));
+    (
+        voxels.magnitude(),
+        voxels.mul_element_wise(volume.spacing).magnitude(),
+    )
+}
+
+/// angle in degrees between the lines from `vertex` to `a` and `b`
+pub fn angle(a: Point3<f32>, vertex: Point3<f32>, b: Point3<f32>) -> f32 {
+    (a - vertex).angle(b - vertex).0.to_degrees()
+}
+
+fn distance_label(volume: &Volume, a: Point3<f32>, b: Point3<f32>, unit: &str) -> String {
+    let (voxels, physical) = distance(volume, a, b);
+    format!("{voxels:.1} voxels, {physical:.3} {unit}")
+        .trim_end()
+        .to_string()
+}
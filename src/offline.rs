@@ -1,46 +1,71 @@
-use cgmath::Vector2;
-use image::{ImageBuffer, Rgba};
+use std::{
+    f32::consts::PI,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use cgmath::{Angle, Deg, Point3, Rad, Vector2, Vector3, Vector4};
+use image::{DynamicImage, ImageBuffer, Luma, Pixel, Rgba};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    camera::{Camera, OrthographicProjection, Projection},
-    cmap::{ColorMapGPU, GenericColorMap, COLORMAP_RESOLUTION},
-    renderer::{RenderSettings, VolumeRenderer},
-    volume::{Volume, VolumeGPU},
+    annotations::{self, Annotations, Colorbar},
+    camera::{
+        Camera, CameraView, OrthographicProjection, PerspectiveProjection, Projection,
+        ProjectionKind, TileProjection, ViewProjection,
+    },
+    cmap::{ColorMap, ColorMapGPU, ColorMapSequence, COLORMAP_RESOLUTION},
+    keyframes::{self, Keyframe},
+    panorama, reference,
+    renderer::{Interpolation, RenderSettings, VolumeRenderer},
+    stereo::{self, Stereo, StereoMode},
+    volume::{Aabb, Volume, VolumeGPU},
     WGPUContext,
 };
 
-async fn render_view<P: Projection>(
+#[cfg(not(target_arch = "wasm32"))]
+use crate::recording::{Recording, RecordingSettings, VideoFormat};
+
+pub(crate) async fn render_view<P: Projection, Px: OfflinePixel>(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
-    renderer: &mut VolumeRenderer,
+    renderer: &Mutex<VolumeRenderer>,
     volume: &VolumeGPU,
     cmap: &ColorMapGPU,
     camera: Camera<P>,
     render_settings: &RenderSettings,
     bg: wgpu::Color,
     resolution: Vector2<u32>,
-) -> anyhow::Result<ImageBuffer<Rgba<u8>, Vec<u8>>> {
-    let target = device.create_texture(&wgpu::TextureDescriptor {
-        label: Some("render texture"),
-        size: wgpu::Extent3d {
-            width: resolution.x,
-            height: resolution.y,
-            depth_or_array_layers: 1,
-        },
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: renderer.format(),
-        usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::RENDER_ATTACHMENT,
-        view_formats: &[],
-    });
+    annotations: Option<(&Annotations, f32, &[Vector4<u8>])>,
+) -> anyhow::Result<OfflineImage<Px>> {
+    let texture = |label, format| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: resolution.x,
+                height: resolution.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+    };
+    let target = texture("render texture", renderer.lock().unwrap().format());
 
     let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
 
     let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("render encoder"),
     });
-    let frame_data = renderer.prepare(device, volume, &camera, &render_settings, cmap);
+    let frame_data =
+        renderer
+            .lock()
+            .unwrap()
+            .prepare(device, volume, &camera, &render_settings, cmap);
     {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("render pass"),
@@ -56,73 +81,1627 @@ async fn render_view<P: Projection>(
             timestamp_writes: None,
             occlusion_query_set: None,
         });
-        renderer.render(&mut render_pass, &frame_data);
+        renderer
+            .lock()
+            .unwrap()
+            .render(&mut render_pass, &frame_data);
     }
     queue.submit(std::iter::once(encoder.finish()));
-    let img = download_texture(&target, device, queue).await;
-    return Ok(img);
+    let Some(annotations) = annotations else {
+        return Ok(download_image(&target, device, queue).await);
+    };
+    if target.format().is_srgb() {
+        annotations::render(
+            device,
+            queue,
+            &target,
+            annotations,
+            &camera,
+            &volume.volume,
+            render_settings,
+        );
+        return Ok(download_image(&target, device, queue).await);
+    }
+    // egui blends in sRGB space, so the annotations of float images are painted into a
+    // transparent sRGB overlay and composited in linear space
+    let overlay = texture("annotation texture", wgpu::TextureFormat::Rgba8UnormSrgb);
+    annotations::render(
+        device,
+        queue,
+        &overlay,
+        annotations,
+        &camera,
+        &volume.volume,
+        render_settings,
+    );
+    let mut img: OfflineImage<Px> = download_image(&target, device, queue).await;
+    let overlay = download_texture(&overlay, device, queue).await;
+    for (p, o) in img.pixels_mut().zip(overlay.pixels()) {
+        let (base, o) = (p.to_linear(), o.to_linear());
+        // premultiplied alpha, like the blending of the annotations
+        *p = Px::from_linear(std::array::from_fn(|c| o[c] + (1. - o[3]) * base[c]));
+    }
+    Ok(img)
 }
 
+/// renders the `frames` (normalized times) looking at the volume from the isometric view,
+/// see [render_volume_with_view] for other cameras and [render_volume_with_settings]
+/// for supersampling, annotations and stereo images
 pub async fn render_volume(
     volumes: Vec<Volume>,
-    cmap: GenericColorMap,
+    cmap: impl Into<ColorMapSequence>,
+    resolution: Vector2<u32>,
+    frames: &[f32],
+    bg: wgpu::Color,
+    vmin: Option<f32>,
+    vmax: Option<f32>,
+    distance_scale: f32,
+    spatial_interpolation: Interpolation,
+    temporal_interpolation: Interpolation,
+) -> anyhow::Result<Vec<ImageBuffer<Rgba<u8>, Vec<u8>>>> {
+    render_volume_with_view(
+        volumes,
+        cmap,
+        resolution,
+        frames,
+        bg,
+        vmin,
+        vmax,
+        distance_scale,
+        spatial_interpolation,
+        temporal_interpolation,
+        &CameraView::default(),
+        None,
+    )
+    .await
+}
+
+/// like `render_volume`, but looks at the volume from `view` instead of the isometric view
+/// and optionally paints a colorbar into the images
+pub async fn render_volume_with_view(
+    volumes: Vec<Volume>,
+    cmap: impl Into<ColorMapSequence>,
     resolution: Vector2<u32>,
     frames: &[f32],
     bg: wgpu::Color,
     vmin: Option<f32>,
     vmax: Option<f32>,
     distance_scale: f32,
-    spatial_interpolation: wgpu::FilterMode,
-    temporal_interpolation: wgpu::FilterMode,
+    spatial_interpolation: Interpolation,
+    temporal_interpolation: Interpolation,
+    view: &CameraView,
+    colorbar: Option<Colorbar>,
 ) -> anyhow::Result<Vec<ImageBuffer<Rgba<u8>, Vec<u8>>>> {
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
-    let wgpu_context = WGPUContext::new(&instance, None).await;
-    let device = &wgpu_context.device;
-    let queue = &wgpu_context.queue;
+    let settings = OfflineSettings {
+        settings: RenderSettings {
+            vmin,
+            vmax,
+            distance_scale,
+            spatial_filter: spatial_interpolation,
+            temporal_filter: temporal_interpolation,
+            colorbar,
+            ..Default::default()
+        },
+        view: *view,
+        background: bg,
+        ..OfflineSettings::new(resolution)
+    };
+    render_volume_with_settings(volumes, cmap, frames, &settings, None, Stereo::default()).await
+}
+
+/// everything but the time of the images rendered by [render_volume_with_settings]
+#[derive(Debug, Clone)]
+pub struct OfflineSettings {
+    /// settings of all images, e.g. deserialized from the render settings of a session.
+    /// The time is set per image
+    pub settings: RenderSettings,
+    pub view: CameraView,
+    pub resolution: Vector2<u32>,
+    /// samples per pixel along each axis
+    pub supersampling: u32,
+    pub background: wgpu::Color,
+    /// averages several times around the time of each image
+    pub motion_blur: MotionBlur,
+}
+
+impl OfflineSettings {
+    /// default render settings and the isometric view
+    pub fn new(resolution: Vector2<u32>) -> Self {
+        Self {
+            settings: RenderSettings::default(),
+            view: CameraView::default(),
+            resolution,
+            supersampling: 1,
+            background: wgpu::Color::BLACK,
+            motion_blur: MotionBlur::default(),
+        }
+    }
+}
 
-    let aabb = volumes[0].aabb.clone();
-    let volume_gpu: Vec<VolumeGPU> = volumes
+/// renders one image per normalized time in `frames` with the same render settings
+/// (step size, clipping, filters, value range, surfaces, ...) as the viewer.
+/// The pixel type selects 8 bit sRGB or linear float images, see [OfflinePixel]
+pub async fn render_volume_with_settings<Px: OfflinePixel>(
+    volumes: Vec<Volume>,
+    cmap: impl Into<ColorMapSequence>,
+    frames: &[f32],
+    settings: &OfflineSettings,
+    annotations: Option<&Annotations>,
+    stereo: Stereo,
+) -> anyhow::Result<Vec<OfflineImage<Px>>> {
+    let images =
+        render_volume_with_aovs(volumes, cmap, frames, settings, annotations, stereo, &[]).await?;
+    Ok(images.into_iter().map(|(image, _)| image).collect())
+}
+
+/// like [render_volume_with_settings], but also renders the auxiliary outputs `aovs`
+/// of every image
+pub async fn render_volume_with_aovs<Px: OfflinePixel>(
+    volumes: Vec<Volume>,
+    cmap: impl Into<ColorMapSequence>,
+    frames: &[f32],
+    settings: &OfflineSettings,
+    annotations: Option<&Annotations>,
+    stereo: Stereo,
+    aovs: &[Aov],
+) -> anyhow::Result<Vec<(OfflineImage<Px>, AovImages)>> {
+    let aabb = volumes[0].aabb;
+    let center = settings.view.target(&aabb);
+    let camera = view_camera(&aabb, &settings.view, settings.resolution);
+    let render_resolution = settings.resolution * settings.supersampling.max(1);
+    let panorama = settings.view.projection == ProjectionKind::Equirectangular;
+    let frames: Vec<OfflineFrame> = settings
+        .motion_blur
+        .sample_times(frames)
+        .iter()
+        .map(|time| {
+            let settings = RenderSettings {
+                time: *time,
+                ..settings.settings.clone()
+            };
+            OfflineFrame::new(
+                &camera,
+                center,
+                settings,
+                &stereo,
+                render_resolution,
+                panorama,
+            )
+        })
+        .collect();
+    let output = FrameOutput {
+        background: settings.background,
+        supersampling: settings.supersampling,
+        motion_blur_samples: settings.motion_blur.samples,
+        aovs,
+    };
+    let mut images = Vec::with_capacity(frames.len());
+    render_frames(
+        volumes,
+        cmap.into(),
+        &frames,
+        &output,
+        annotations,
+        |image, aovs| {
+            images.push((image, aovs));
+            Ok(())
+        },
+    )
+    .await?;
+    Ok(images)
+}
+
+/// renders a camera animation at `fps` frames per second, from the first to the last keyframe.
+/// `settings` are used for everything that is not stored in the keyframes
+pub async fn render_keyframes<Px: OfflinePixel>(
+    volumes: Vec<Volume>,
+    cmap: impl Into<ColorMapSequence>,
+    resolution: Vector2<u32>,
+    keyframes: &[Keyframe],
+    fps: f32,
+    bg: wgpu::Color,
+    settings: &RenderSettings,
+    supersampling: u32,
+    annotations: Option<&Annotations>,
+    stereo: Stereo,
+) -> anyhow::Result<Vec<OfflineImage<Px>>> {
+    anyhow::ensure!(
+        !keyframes.is_empty(),
+        "the camera animation has no keyframes"
+    );
+    let aabb = volumes[0].aabb;
+    let render_resolution = resolution * supersampling.max(1);
+    let frames: Vec<OfflineFrame> = keyframes::frame_positions(keyframes, fps)
         .into_iter()
-        .map(|v| VolumeGPU::new(device, queue, v))
+        .filter_map(|at| keyframes::sample(keyframes, at))
+        .map(|keyframe| {
+            let mut settings = settings.clone();
+            keyframe.apply_settings(&mut settings);
+            let camera = keyframe.camera(&aabb, resolution).into();
+            let center = keyframe.center(&aabb);
+            OfflineFrame::new(&camera, center, settings, &stereo, render_resolution, false)
+        })
         .collect();
-    let cmap_gpu = ColorMapGPU::new(&cmap, device, queue, COLORMAP_RESOLUTION);
+    let output = FrameOutput {
+        background: bg,
+        supersampling,
+        motion_blur_samples: 1,
+        aovs: &[],
+    };
+    let mut images = Vec::with_capacity(frames.len());
+    render_frames(
+        volumes,
+        cmap.into(),
+        &frames,
+        &output,
+        annotations,
+        |image, _| {
+            images.push(image);
+            Ok(())
+        },
+    )
+    .await?;
+    Ok(images)
+}
 
-    let render_format = wgpu::TextureFormat::Rgba8UnormSrgb;
+/// time sweep, and optionally a camera path, rendered by [render_animation]
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub num_frames: usize,
+    /// normalized time of the first and the last frame
+    pub time_range: (f32, f32),
+    /// camera path spread over the frames, the camera looks from `view` if there are none.
+    /// The keyframes also set the value range and distance scale, but not the time
+    pub keyframes: Vec<Keyframe>,
+    pub view: CameraView,
+    /// orbits the camera once around the up vector of `view` instead of following the
+    /// keyframes. The last frame is one step before the first, so the animation loops
+    pub turntable: bool,
+    /// settings of all frames, the time is set per frame
+    pub settings: RenderSettings,
+    pub resolution: Vector2<u32>,
+    /// samples per pixel along each axis
+    pub supersampling: u32,
+    pub background: wgpu::Color,
+    /// bit depth of numbered images, videos are always 8 bit
+    pub bit_depth: BitDepth,
+    /// alpha of numbered images with a transparent background,
+    /// videos are premultiplied (composited over black)
+    pub alpha: AlphaMode,
+    /// auxiliary outputs written next to the numbered images, see [Aov::path]
+    pub aovs: Vec<Aov>,
+    /// averages several times around the time of each frame
+    pub motion_blur: MotionBlur,
+}
 
-    let mut renderer = VolumeRenderer::new(&device, render_format);
+impl Animation {
+    /// sweeps the whole time range over `num_frames` frames
+    pub fn new(num_frames: usize, resolution: Vector2<u32>) -> Self {
+        Self {
+            num_frames,
+            time_range: (0., 1.),
+            keyframes: Vec::new(),
+            view: CameraView::default(),
+            turntable: false,
+            settings: RenderSettings::default(),
+            resolution,
+            supersampling: 1,
+            background: wgpu::Color::BLACK,
+            bit_depth: BitDepth::default(),
+            alpha: AlphaMode::default(),
+            aovs: Vec::new(),
+            motion_blur: MotionBlur::default(),
+        }
+    }
 
-    let ratio = resolution.x as f32 / resolution.y as f32;
-    let radius = aabb.radius();
-    let camera = Camera::new_aabb_iso(
-        aabb,
-        OrthographicProjection::new(Vector2::new(ratio, 1.) * radius * 2., 0.01, 1000.),
-    );
+    /// the frames rendered at `resolution` (the resolution of the animation or
+    /// the nearest one the video supports), `motion_blur.samples` consecutive ones per frame
+    fn frames(
+        &self,
+        aabb: &Aabb<f32>,
+        stereo: &Stereo,
+        resolution: Vector2<u32>,
+    ) -> Vec<OfflineFrame> {
+        let mut keyframes = self.keyframes.clone();
+        keyframes.sort_by(|a, b| a.at.total_cmp(&b.at));
+        let render_resolution = resolution * self.supersampling.max(1);
+        let (start, end) = self.time_range;
+        let samples = self.motion_blur.samples.max(1) as usize;
+        let times: Vec<f32> = (0..self.num_frames)
+            .map(|i| start + (end - start) * i as f32 / (self.num_frames - 1).max(1) as f32)
+            .collect();
+        let sample_times = self.motion_blur.sample_times(&times);
+        (0..self.num_frames)
+            .flat_map(|i| {
+                let t = i as f32 / (self.num_frames - 1).max(1) as f32;
+                let mut settings = self.settings.clone();
+                let keyframe = match (keyframes.first(), keyframes.last()) {
+                    _ if self.turntable => None,
+                    (Some(first), Some(last)) => {
+                        keyframes::sample(&keyframes, first.at + (last.at - first.at) * t)
+                    }
+                    _ => None,
+                };
+                let (camera, center, panorama) = match keyframe {
+                    Some(keyframe) => {
+                        keyframe.apply_settings(&mut settings);
+                        (
+                            keyframe.camera(aabb, resolution).into(),
+                            keyframe.center(aabb),
+                            false,
+                        )
+                    }
+                    None => {
+                        let mut camera = view_camera(aabb, &self.view, resolution);
+                        let target = self.view.target(aabb);
+                        if self.turntable {
+                            let angle = 2. * PI * i as f32 / self.num_frames as f32;
+                            camera.orbit(target, Vector3::from(self.view.up), Rad(angle));
+                        }
+                        let panorama = self.view.projection == ProjectionKind::Equirectangular;
+                        (camera, target, panorama)
+                    }
+                };
+                // the camera stays, only the time of the data is blurred
+                sample_times[i * samples..(i + 1) * samples]
+                    .iter()
+                    .map(|time| {
+                        let settings = RenderSettings {
+                            time: *time,
+                            ..settings.clone()
+                        };
+                        OfflineFrame::new(
+                            &camera,
+                            center,
+                            settings,
+                            stereo,
+                            render_resolution,
+                            panorama,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
 
-    let mut images: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = Vec::with_capacity(frames.len());
-    for time in frames {
-        let img = render_view(
-            device,
-            queue,
-            &mut renderer,
-            &volume_gpu[0],
-            &cmap_gpu,
-            camera,
-            &RenderSettings {
-                time: *time,
-                vmin,
-                vmax,
-                distance_scale,
-                spatial_filter: spatial_interpolation,
-                temporal_filter: temporal_interpolation,
-                ..Default::default()
+/// where [render_animation] writes the frames to
+#[derive(Debug, Clone)]
+pub enum AnimationOutput {
+    /// numbered images next to the path (see [numbered_path])
+    Images(PathBuf),
+    /// video encoded by `ffmpeg`, which has to be installed
+    #[cfg(not(target_arch = "wasm32"))]
+    Video {
+        path: PathBuf,
+        format: VideoFormat,
+        fps: u32,
+    },
+}
+
+/// `path` with the frame number appended to the file name, e.g. frame_0001.png
+pub fn numbered_path(path: &Path, frame: usize) -> anyhow::Result<PathBuf> {
+    let stem = path
+        .file_stem()
+        .ok_or_else(|| anyhow::anyhow!("invalid output file"))?
+        .to_string_lossy();
+    let extension = path.extension().unwrap_or("png".as_ref()).to_string_lossy();
+    Ok(path.with_file_name(format!("{stem}_{frame:04}.{extension}")))
+}
+
+/// renders an animation frame by frame and writes each frame to `output` right away,
+/// so long animations do not have to fit into memory.
+/// `progress` is called with the number of written and total frames after every frame
+pub async fn render_animation(
+    volumes: Vec<Volume>,
+    cmap: impl Into<ColorMapSequence>,
+    animation: &Animation,
+    annotations: Option<&Annotations>,
+    stereo: Stereo,
+    output: &AnimationOutput,
+    mut progress: impl FnMut(usize, usize),
+) -> anyhow::Result<()> {
+    anyhow::ensure!(animation.num_frames > 0, "the animation has no frames");
+    #[cfg(not(target_arch = "wasm32"))]
+    let (mut recording, resolution) = match output {
+        AnimationOutput::Video { path, format, fps } => {
+            anyhow::ensure!(
+                stereo.mode != StereoMode::Pair,
+                "stereo pairs are twice as wide as the video, use side-by-side or top-bottom"
+            );
+            let settings = RecordingSettings {
+                format: *format,
+                resolution: animation.resolution,
+                fps: *fps,
+            };
+            let duration = animation.num_frames as f32 / (*fps).max(1) as f32;
+            let recording = Recording::start(path, &settings, duration)?;
+            // videos need an even resolution
+            let resolution = recording.resolution();
+            (Some(recording), resolution)
+        }
+        AnimationOutput::Images(_) => (None, animation.resolution),
+    };
+    #[cfg(target_arch = "wasm32")]
+    let resolution = animation.resolution;
+    let frames = animation.frames(&volumes[0].aabb, &stereo, resolution);
+    let mut written = 0;
+    let straight = animation.alpha == AlphaMode::Straight && animation.background.a < 1.;
+    let (bit_depth, straight) = match output {
+        AnimationOutput::Images(path) => (animation.bit_depth.for_path(path), straight),
+        #[cfg(not(target_arch = "wasm32"))]
+        AnimationOutput::Video { format, .. } => {
+            anyhow::ensure!(
+                animation.aovs.is_empty(),
+                "auxiliary outputs are only written for images"
+            );
+            anyhow::ensure!(
+                animation.bit_depth == BitDepth::Eight,
+                "videos are 8 bit, render {:?} images instead",
+                animation.bit_depth
+            );
+            // the others are composited over black
+            (BitDepth::Eight, straight && format.has_alpha())
+        }
+    };
+    let frame_output = FrameOutput {
+        background: animation.background,
+        supersampling: animation.supersampling,
+        motion_blur_samples: animation.motion_blur.samples,
+        aovs: &animation.aovs,
+    };
+    let save_aovs = |aovs: AovImages, path: &Path| {
+        for (aov, img) in aovs {
+            aov.save(&img, path, bit_depth)?;
+        }
+        anyhow::Ok(())
+    };
+    // straight alpha is computed before the quantization, faint colors would be lost otherwise
+    if bit_depth != BitDepth::Eight || straight {
+        render_frames::<Rgba<f32>>(
+            volumes,
+            cmap.into(),
+            &frames,
+            &frame_output,
+            annotations,
+            |image, aovs| {
+                match output {
+                    AnimationOutput::Images(path) => {
+                        let image = if straight {
+                            unpremultiply(&image)
+                        } else {
+                            image
+                        };
+                        let path = numbered_path(path, written)?;
+                        save_image(&image, &path, bit_depth)?;
+                        save_aovs(aovs, &path)?;
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    AnimationOutput::Video { .. } => {
+                        if let Some(recording) = &mut recording {
+                            recording.add_frame(&unpremultiply(&image))?;
+                        }
+                    }
+                }
+                written += 1;
+                progress(written, animation.num_frames);
+                Ok(())
+            },
+        )
+        .await?;
+    } else {
+        render_frames::<Rgba<u8>>(
+            volumes,
+            cmap.into(),
+            &frames,
+            &frame_output,
+            annotations,
+            |image, aovs| {
+                match output {
+                    AnimationOutput::Images(path) => {
+                        let path = numbered_path(path, written)?;
+                        image.save(&path)?;
+                        save_aovs(aovs, &path)?;
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    AnimationOutput::Video { .. } => {
+                        if let Some(recording) = &mut recording {
+                            recording.add_frame(&image)?;
+                        }
+                    }
+                }
+                written += 1;
+                progress(written, animation.num_frames);
+                Ok(())
             },
-            bg,
-            resolution,
         )
         .await?;
-        images.push(img);
     }
-    Ok(images)
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(recording) = recording {
+        recording.finish()?;
+    }
+    Ok(())
+}
+
+/// camera looking at the volume from `view`, with the aspect ratio of `resolution`
+pub(crate) fn view_camera(
+    aabb: &Aabb<f32>,
+    view: &CameraView,
+    resolution: Vector2<u32>,
+) -> Camera<ViewProjection> {
+    let ratio = resolution.x as f32 / resolution.y as f32;
+    let projection = match view.projection {
+        ProjectionKind::Orthographic => ViewProjection::Orthographic(OrthographicProjection::new(
+            Vector2::new(ratio, 1.) * aabb.radius() * 2. / view.zoom,
+            0.01,
+            1000.,
+        )),
+        // only the position and rotation are used, see [OfflineFrame::new]
+        ProjectionKind::Equirectangular => ViewProjection::Perspective(PerspectiveProjection::new(
+            resolution,
+            Deg(90.),
+            0.01,
+            1000.,
+        )),
+        ProjectionKind::Perspective => {
+            let fovy = view.zoomed_fov();
+            ViewProjection::Perspective(PerspectiveProjection {
+                fovy,
+                fovx: Rad::atan((fovy / 2.).tan() * ratio) * 2.,
+                znear: 0.01,
+                zfar: 1000.,
+                aspect_ratio: ratio,
+            })
+        }
+    };
+    Camera::with_view(*aabb, view, projection)
+}
+
+/// camera views (one per eye) and settings of one image rendered offline
+struct OfflineFrame {
+    /// cameras and resolutions of the images composed into the frame
+    views: Vec<(Camera<ViewProjection>, Vector2<u32>)>,
+    /// how the images of the views are composed
+    stereo: StereoMode,
+    /// resolutions of the equirectangular panoramas (one per eye) the views are resampled to.
+    /// None if the views are the images of the eyes
+    panoramas: Option<Vec<Vector2<u32>>>,
+    settings: RenderSettings,
+}
+
+impl OfflineFrame {
+    /// a frame looking from `camera`, or an equirectangular panorama around it
+    fn new(
+        camera: &Camera<ViewProjection>,
+        center: Point3<f32>,
+        settings: RenderSettings,
+        stereo: &Stereo,
+        render_resolution: Vector2<u32>,
+        panorama: bool,
+    ) -> Self {
+        if !panorama {
+            return Self {
+                // one image per eye for stereo
+                views: stereo.images(camera, center, render_resolution),
+                stereo: stereo.mode,
+                panoramas: None,
+                settings,
+            };
+        }
+        let resolutions = stereo.eye_resolutions(render_resolution);
+        let eye_offsets = match stereo.mode {
+            StereoMode::Off => vec![0.],
+            _ => vec![-stereo.eye_separation / 2., stereo.eye_separation / 2.],
+        };
+        Self {
+            views: resolutions
+                .iter()
+                .zip(eye_offsets)
+                .flat_map(|(resolution, offset)| panorama::cube_faces(camera, offset, *resolution))
+                .collect(),
+            stereo: stereo.mode,
+            panoramas: Some(resolutions),
+            settings,
+        }
+    }
+
+    /// combines the images rendered for the views into the image of the frame
+    fn compose<Px: OfflinePixel>(&self, images: Vec<OfflineImage<Px>>) -> OfflineImage<Px> {
+        let images = match &self.panoramas {
+            Some(resolutions) => self
+                .views
+                .chunks(panorama::NUM_FACES)
+                .zip(images.chunks(panorama::NUM_FACES))
+                .zip(resolutions)
+                .map(|((views, faces), resolution)| {
+                    let cameras: Vec<_> = views.iter().map(|(camera, _)| *camera).collect();
+                    panorama::equirectangular(&cameras, faces, *resolution)
+                })
+                .collect(),
+            None => images,
+        };
+        stereo::compose(self.stereo, images)
+    }
+}
+
+/// how the frames rendered by [render_frames] are finished
+struct FrameOutput<'a> {
+    background: wgpu::Color,
+    /// samples per pixel along each axis
+    supersampling: u32,
+    /// number of consecutive frames averaged into one image, see [MotionBlur]
+    motion_blur_samples: u32,
+    /// auxiliary outputs rendered in addition to the colors
+    aovs: &'a [Aov],
+}
+
+/// renders the frames one after the other and passes each image with its auxiliary outputs
+/// to `sink`
+async fn render_frames<Px: OfflinePixel>(
+    volumes: Vec<Volume>,
+    cmap: ColorMapSequence,
+    frames: &[OfflineFrame],
+    output: &FrameOutput<'_>,
+    annotations: Option<&Annotations>,
+    mut sink: impl FnMut(OfflineImage<Px>, AovImages) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let renderer = OfflineRenderer::new(volumes, cmap).await;
+    renderer.check_frames::<Px>(frames, annotations);
+    let samples = output.motion_blur_samples.max(1) as usize;
+    for frame in frames.chunks(samples) {
+        let mut images = Vec::with_capacity(frame.len());
+        let mut frame_aovs = Vec::new();
+        for (i, sample) in frame.iter().enumerate() {
+            let (img, aovs) = renderer.render_frame(sample, output, annotations).await?;
+            images.push(img);
+            // the auxiliary outputs are not blurred, they are taken at the frame time
+            if i == frame.len() / 2 {
+                frame_aovs = aovs;
+            }
+        }
+        let img = match images.len() {
+            1 => images.pop().unwrap(),
+            _ => average(&images),
+        };
+        sink(img, frame_aovs)?;
+    }
+    Ok(())
+}
+
+/// renders images of the same volumes and colormap one by one, e.g. frames requested from
+/// Python or the web. The device, the uploaded volumes and the pipelines are kept between
+/// the images. Falls back to the (slow) CPU renderer if there is no GPU
+pub struct OfflineRenderer {
+    cmap: ColorMapSequence,
+    backend: Backend,
+}
+
+/// where an [OfflineRenderer] renders
+enum Backend {
+    Gpu {
+        context: Arc<WGPUContext>,
+        volumes: Vec<VolumeGPU>,
+        cmap: Arc<ColorMapGPU>,
+    },
+    /// the reference renderer, if there is no GPU
+    Cpu(Vec<Volume>),
+}
+
+impl OfflineRenderer {
+    /// uploads the volumes and the colormap to the GPU of the shared headless context
+    pub async fn new(volumes: Vec<Volume>, cmap: impl Into<ColorMapSequence>) -> Self {
+        let cmap = cmap.into();
+        let backend = match WGPUContext::shared().await {
+            Ok(context) => {
+                let (device, queue) = (&context.device, &context.queue);
+                let volumes = volumes
+                    .into_iter()
+                    .map(|v| VolumeGPU::new(device, queue, v))
+                    .collect();
+                let cmap_gpu = context.resources.lock().unwrap().colormap_sequence(
+                    device,
+                    queue,
+                    &cmap,
+                    COLORMAP_RESOLUTION,
+                );
+                Backend::Gpu {
+                    context,
+                    volumes,
+                    cmap: cmap_gpu,
+                }
+            }
+            Err(err) => {
+                log::warn!("{err}, falling back to the (slow) CPU renderer");
+                Backend::Cpu(volumes)
+            }
+        };
+        Self { cmap, backend }
+    }
+
+    /// the rendered volume
+    pub fn volume(&self) -> &Volume {
+        match &self.backend {
+            Backend::Gpu { volumes, .. } => &volumes[0].volume,
+            Backend::Cpu(volumes) => &volumes[0],
+        }
+    }
+
+    /// renders the image at the normalized `time` with the camera and settings of `settings`,
+    /// like one frame of [render_volume_with_settings]
+    pub async fn render<Px: OfflinePixel>(
+        &self,
+        time: f32,
+        settings: &OfflineSettings,
+        annotations: Option<&Annotations>,
+        stereo: &Stereo,
+    ) -> anyhow::Result<OfflineImage<Px>> {
+        let aabb = self.volume().aabb;
+        let camera = view_camera(&aabb, &settings.view, settings.resolution);
+        let frame = OfflineFrame::new(
+            &camera,
+            settings.view.target(&aabb),
+            RenderSettings {
+                time,
+                ..settings.settings.clone()
+            },
+            stereo,
+            settings.resolution * settings.supersampling.max(1),
+            settings.view.projection == ProjectionKind::Equirectangular,
+        );
+        let output = FrameOutput {
+            background: settings.background,
+            supersampling: settings.supersampling,
+            motion_blur_samples: 1,
+            aovs: &[],
+        };
+        self.check_frames::<Px>(std::slice::from_ref(&frame), annotations);
+        let (img, _) = self.render_frame(&frame, &output, annotations).await?;
+        Ok(img)
+    }
+
+    /// tells what of the `frames` is not rendered as requested, e.g. annotations that
+    /// the CPU renderer does not paint
+    fn check_frames<Px: OfflinePixel>(
+        &self,
+        frames: &[OfflineFrame],
+        annotations: Option<&Annotations>,
+    ) {
+        match self.tile_size::<Px>() {
+            Some(tile_size) => {
+                let tiled = |r: &Vector2<u32>| r.x > tile_size.x || r.y > tile_size.y;
+                if frames.iter().flat_map(|f| &f.views).any(|(_, r)| tiled(r)) {
+                    log::info!(
+                        "rendering in tiles of {}x{} pixels, annotations are not painted",
+                        tile_size.x,
+                        tile_size.y
+                    );
+                }
+            }
+            None => {
+                if annotations.is_some() || frames.iter().any(|f| f.settings.colorbar.is_some()) {
+                    log::warn!("annotations and the colorbar are not painted by the CPU renderer");
+                }
+            }
+        }
+        if annotations.is_some() && frames.iter().any(|f| f.panoramas.is_some()) {
+            log::warn!("annotations are not painted into panoramas");
+        }
+    }
+
+    /// largest image rendered at once, larger images are rendered in tiles which also
+    /// have to fit into the download buffer. None for the CPU renderer
+    fn tile_size<Px: OfflinePixel>(&self) -> Option<Vector2<u32>> {
+        let Backend::Gpu { context, .. } = &self.backend else {
+            return None;
+        };
+        let limits = context.device.limits();
+        let max_size = limits.max_texture_dimension_2d;
+        let row_size = max_size as u64 * Px::FORMAT.block_copy_size(None).unwrap() as u64;
+        Some(Vector2::new(
+            max_size,
+            max_size.min((limits.max_buffer_size / row_size) as u32),
+        ))
+    }
+
+    async fn render_frame<Px: OfflinePixel>(
+        &self,
+        frame: &OfflineFrame,
+        output: &FrameOutput<'_>,
+        annotations: Option<&Annotations>,
+    ) -> anyhow::Result<(OfflineImage<Px>, AovImages)> {
+        let supersampling = output.supersampling.max(1);
+        let bg = output.background;
+        // depth and normal are rendered in one pass
+        let aov_pass = output.aovs.iter().any(|a| *a != Aov::Alpha);
+        let cmap = self.cmap.at(frame.settings.time, COLORMAP_RESOLUTION);
+
+        let (context, volume_gpu, cmap_gpu) = match &self.backend {
+            Backend::Gpu {
+                context,
+                volumes,
+                cmap,
+            } => (context, &volumes[0], cmap),
+            Backend::Cpu(volumes) => {
+                let images = frame
+                    .views
+                    .iter()
+                    .map(|(camera, resolution)| {
+                        let img = reference::render_view(
+                            &volumes[0],
+                            &cmap,
+                            camera,
+                            &frame.settings,
+                            bg,
+                            *resolution,
+                        );
+                        ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+                            Px::from_linear(img.get_pixel(x, y).to_linear())
+                        })
+                    })
+                    .collect();
+                let aov = aov_pass.then(|| {
+                    let images = frame
+                        .views
+                        .iter()
+                        .map(|(camera, resolution)| {
+                            reference::render_aov_view(
+                                &volumes[0],
+                                &cmap,
+                                camera,
+                                &frame.settings,
+                                *resolution,
+                            )
+                        })
+                        .collect();
+                    frame.compose(images)
+                });
+                let img = downsample(&frame.compose(images), supersampling);
+                let aovs = aov_images(output.aovs, &img, aov.as_ref(), supersampling);
+                return Ok((img, aovs));
+            }
+        };
+        let device = &context.device;
+        let queue = &context.queue;
+
+        let tile_size = self.tile_size::<Px>().unwrap();
+        let tiled = |r: &Vector2<u32>| r.x > tile_size.x || r.y > tile_size.y;
+        anyhow::ensure!(
+            !aov_pass || !frame.views.iter().any(|(_, r)| tiled(r)),
+            "depth and normal outputs are not supported for images larger than {}x{} pixels",
+            tile_size.x,
+            tile_size.y
+        );
+
+        let (renderer, aov_renderer) = {
+            let mut resources = context.resources.lock().unwrap();
+            (
+                resources.volume_renderer(device, Px::FORMAT, None),
+                aov_pass.then(|| resources.aov_renderer(device, Rgba::<f32>::FORMAT)),
+            )
+        };
+
+        // the colorbar is painted with the annotations, default ones if there are none.
+        // Panoramas have no annotations, they would be painted onto every cube face
+        let default_annotations = Annotations::default();
+        let colorbar = frame.settings.colorbar.is_some();
+        let annotations = match annotations {
+            _ if frame.panoramas.is_some() => None,
+            Some(a) if a.any() || colorbar => Some(a),
+            None if colorbar => Some(&default_annotations),
+            _ => None,
+        };
+        let colors = (&cmap).rasterize(COLORMAP_RESOLUTION as usize);
+        let mut eye_images = Vec::with_capacity(frame.views.len());
+        let mut eye_aovs = Vec::new();
+        for (camera, resolution) in &frame.views {
+            if let Some(aov_renderer) = &aov_renderer {
+                eye_aovs.push(
+                    render_view(
+                        device,
+                        queue,
+                        aov_renderer,
+                        volume_gpu,
+                        cmap_gpu,
+                        *camera,
+                        &frame.settings,
+                        wgpu::Color::TRANSPARENT,
+                        *resolution,
+                        None,
+                    )
+                    .await?,
+                );
+            }
+            if tiled(resolution) {
+                let mut img = ImageBuffer::new(resolution.x, resolution.y);
+                for y in (0..resolution.y).step_by(tile_size.y as usize) {
+                    for x in (0..resolution.x).step_by(tile_size.x as usize) {
+                        let offset = Vector2::new(x, y);
+                        let size = Vector2::new(
+                            tile_size.x.min(resolution.x - x),
+                            tile_size.y.min(resolution.y - y),
+                        );
+                        let projection =
+                            TileProjection::new(camera.projection, *resolution, offset, size);
+                        let tile: OfflineImage<Px> = render_view(
+                            device,
+                            queue,
+                            &renderer,
+                            volume_gpu,
+                            cmap_gpu,
+                            Camera::new(camera.position, camera.rotation, projection),
+                            &frame.settings,
+                            bg,
+                            size,
+                            None,
+                        )
+                        .await?;
+                        image::imageops::replace(&mut img, &tile, x as i64, y as i64);
+                    }
+                }
+                eye_images.push(img);
+                continue;
+            }
+            eye_images.push(
+                render_view(
+                    device,
+                    queue,
+                    &renderer,
+                    volume_gpu,
+                    cmap_gpu,
+                    *camera,
+                    &frame.settings,
+                    bg,
+                    *resolution,
+                    annotations.map(|a| (a, supersampling as f32, colors.as_slice())),
+                )
+                .await?,
+            );
+        }
+        let img = downsample(&frame.compose(eye_images), supersampling);
+        let aov = aov_renderer.is_some().then(|| frame.compose(eye_aovs));
+        let aovs = aov_images(output.aovs, &img, aov.as_ref(), supersampling);
+        Ok((img, aovs))
+    }
+}
+
+/// splits the normal and depth rendered with [VolumeRenderer::aov] (at the supersampled
+/// resolution) and the alpha of the color image `img` into the requested outputs.
+/// Normals and depth are not averaged, the center sample of each pixel is taken
+fn aov_images<Px: OfflinePixel>(
+    aovs: &[Aov],
+    img: &OfflineImage<Px>,
+    normal_depth: Option<&OfflineImage<Rgba<f32>>>,
+    supersampling: u32,
+) -> AovImages {
+    let (width, height) = img.dimensions();
+    let sample = |x: u32, y: u32| {
+        let normal_depth = normal_depth.expect("depth and normal are rendered");
+        normal_depth
+            .get_pixel(
+                x * supersampling + supersampling / 2,
+                y * supersampling + supersampling / 2,
+            )
+            .0
+    };
+    aovs.iter()
+        .map(|aov| {
+            let img = ImageBuffer::from_fn(width, height, |x, y| match aov {
+                Aov::Depth => {
+                    let d = sample(x, y)[3];
+                    Rgba([d, d, d, 1.])
+                }
+                Aov::Normal => {
+                    let [nx, ny, nz, _] = sample(x, y);
+                    Rgba([nx, ny, nz, 1.])
+                }
+                Aov::Alpha => {
+                    let a = img.get_pixel(x, y).to_linear()[3];
+                    Rgba([a, a, a, 1.])
+                }
+            });
+            (*aov, img)
+        })
+        .collect()
+}
+
+/// inserts `factor - 1` sub-timestep positions between each pair of consecutive frames.
+/// The sub-frames are rendered with the temporal interpolation of the volume renderer,
+/// so slow motion exports stay faithful to the data instead of guessing motion.
+pub fn interpolate_frames(frames: &[f32], factor: u32) -> Vec<f32> {
+    if factor <= 1 || frames.len() < 2 {
+        return frames.to_vec();
+    }
+    let mut times = Vec::with_capacity((frames.len() - 1) * factor as usize + 1);
+    for w in frames.windows(2) {
+        for i in 0..factor {
+            let t = i as f32 / factor as f32;
+            times.push(w[0] + (w[1] - w[0]) * t);
+        }
+    }
+    times.push(frames[frames.len() - 1]);
+    times
+}
+
+/// accumulation motion blur along the time axis of the data
+#[derive(Debug, Clone, Copy)]
+pub struct MotionBlur {
+    /// number of sub-steps averaged per output frame
+    pub samples: u32,
+    /// exposure time as a fraction of the time between two output frames (0.5 = 180° shutter)
+    pub shutter: f32,
+}
+
+impl MotionBlur {
+    /// returns `samples` render times for every frame, centered around the frame time
+    pub fn sample_times(&self, frames: &[f32]) -> Vec<f32> {
+        let samples = self.samples.max(1);
+        let mut times = Vec::with_capacity(frames.len() * samples as usize);
+        for (i, t) in frames.iter().enumerate() {
+            let interval = if i + 1 < frames.len() {
+                frames[i + 1] - t
+            } else if i > 0 {
+                t - frames[i - 1]
+            } else {
+                0.
+            };
+            let exposure = interval * self.shutter;
+            for j in 0..samples {
+                let offset = if samples > 1 {
+                    j as f32 / (samples - 1) as f32 - 0.5
+                } else {
+                    0.
+                };
+                times.push((t + offset * exposure).clamp(0., 1.));
+            }
+        }
+        times
+    }
+
+    /// averages each group of `samples` consecutive images in linear color space
+    pub fn accumulate<Px: OfflinePixel>(
+        &self,
+        images: Vec<OfflineImage<Px>>,
+    ) -> Vec<OfflineImage<Px>> {
+        let samples = self.samples.max(1) as usize;
+        if samples == 1 {
+            return images;
+        }
+        images.chunks(samples).map(average).collect()
+    }
+}
+
+impl Default for MotionBlur {
+    /// no motion blur
+    fn default() -> Self {
+        Self {
+            samples: 1,
+            shutter: 0.5,
+        }
+    }
+}
+
+/// average of images of the same size in linear color space
+fn average<Px: OfflinePixel>(images: &[OfflineImage<Px>]) -> OfflineImage<Px> {
+    let (width, height) = images[0].dimensions();
+    let mut acc = vec![[0f32; 4]; (width * height) as usize];
+    for img in images {
+        for (a, p) in acc.iter_mut().zip(img.pixels()) {
+            let p = p.to_linear();
+            *a = std::array::from_fn(|c| a[c] + p[c]);
+        }
+    }
+    let n = images.len() as f32;
+    let mut pixels = acc.into_iter();
+    ImageBuffer::from_fn(width, height, |_, _| {
+        let a = pixels.next().unwrap();
+        Px::from_linear(a.map(|v| v / n))
+    })
+}
+
+/// named output format for offline renders
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputPreset {
+    pub name: &'static str,
+    pub resolution: Vector2<u32>,
+    /// number of rendered samples per output pixel along each axis
+    pub supersampling: u32,
+    /// pixel density of the output medium
+    pub dpi: f32,
+}
+
+impl OutputPreset {
+    /// preset for a print medium with the given physical size in millimeters
+    pub fn print(name: &'static str, width_mm: f32, height_mm: f32, dpi: f32) -> Self {
+        let px = |mm: f32| (mm / 25.4 * dpi).round() as u32;
+        Self {
+            name,
+            resolution: Vector2::new(px(width_mm), px(height_mm)),
+            supersampling: 1,
+            dpi,
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self {
+                name: "Slide 1080p",
+                resolution: Vector2::new(1920, 1080),
+                supersampling: 2,
+                dpi: 96.,
+            },
+            Self {
+                name: "Slide 4K",
+                resolution: Vector2::new(3840, 2160),
+                supersampling: 1,
+                dpi: 192.,
+            },
+            Self {
+                name: "Twitter 16:9",
+                resolution: Vector2::new(1600, 900),
+                supersampling: 2,
+                dpi: 96.,
+            },
+            Self::print("Poster A0 @300dpi", 841., 1189., 300.),
+            Self::print("Poster A1 @300dpi", 594., 841., 300.),
+            Self::print("Paper A4 @300dpi", 210., 297., 300.),
+        ]
+    }
+
+    /// looks up a preset by name, ignoring case and punctuation ("slide-1080p" matches "Slide 1080p")
+    pub fn find(name: &str) -> Option<Self> {
+        let normalize = |s: &str| {
+            s.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        };
+        let name = normalize(name);
+        Self::all().into_iter().find(|p| normalize(p.name) == name)
+    }
+
+    /// scale factor for overlays like legends and labels relative to a 96 dpi screen
+    pub fn legend_scale(&self) -> f32 {
+        self.dpi / 96.
+    }
+}
+
+/// averages blocks of `factor`x`factor` pixels in linear color space
+pub fn downsample<Px: OfflinePixel>(img: &OfflineImage<Px>, factor: u32) -> OfflineImage<Px> {
+    if factor <= 1 {
+        return img.clone();
+    }
+    let width = img.width() / factor;
+    let height = img.height() / factor;
+    let n = (factor * factor) as f32;
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let mut acc = [0f32; 4];
+        for dy in 0..factor {
+            for dx in 0..factor {
+                let p = img.get_pixel(x * factor + dx, y * factor + dy).to_linear();
+                for (a, v) in acc.iter_mut().zip(p) {
+                    *a += v;
+                }
+            }
+        }
+        Px::from_linear(acc.map(|v| v / n))
+    })
+}
+
+/// grid of images (e.g. of several timesteps) in one image, for figures that show
+/// the temporal evolution side by side
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContactSheet {
+    /// number of images per row (0 = as square as possible)
+    pub columns: u32,
+    /// gap between the images in pixels
+    pub spacing: u32,
+}
+
+impl Default for ContactSheet {
+    fn default() -> Self {
+        Self {
+            columns: 0,
+            spacing: 8,
+        }
+    }
+}
+
+impl ContactSheet {
+    /// tiles the `images` (all of the same size) row by row, gaps are filled with `background`
+    pub fn compose<Px: OfflinePixel>(
+        &self,
+        images: &[OfflineImage<Px>],
+        background: wgpu::Color,
+    ) -> OfflineImage<Px> {
+        let Some(first) = images.first() else {
+            return ImageBuffer::new(0, 0);
+        };
+        let n = images.len() as u32;
+        let columns = match self.columns {
+            0 => (n as f32).sqrt().ceil() as u32,
+            c => c.min(n),
+        };
+        let rows = n.div_ceil(columns);
+        let (width, height) = first.dimensions();
+        let bg = Px::from_linear(
+            [background.r, background.g, background.b, background.a].map(|c| c as f32),
+        );
+        let mut sheet = ImageBuffer::from_pixel(
+            columns * width + (columns - 1) * self.spacing,
+            rows * height + (rows - 1) * self.spacing,
+            bg,
+        );
+        for (i, img) in images.iter().enumerate() {
+            let (column, row) = (i as u32 % columns, i as u32 / columns);
+            image::imageops::replace(
+                &mut sheet,
+                img,
+                (column * (width + self.spacing)) as i64,
+                (row * (height + self.spacing)) as i64,
+            );
+        }
+        sheet
+    }
+}
+
+/// drop shadow (or glow with zero offset and a bright color) composited behind the volume.
+/// Meant for renders with a transparent background.
+#[derive(Debug, Clone, Copy)]
+pub struct DropShadow {
+    /// shadow color (straight alpha)
+    pub color: [f32; 4],
+    /// offset of the shadow in pixels
+    pub offset: (i32, i32),
+    /// standard deviation of the gaussian blur in pixels
+    pub blur: f32,
+}
+
+impl DropShadow {
+    /// composites the shadow behind a premultiplied image
+    pub fn apply(&self, img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        let (width, height) = img.dimensions();
+        let mut alpha = vec![0f32; (width * height) as usize];
+        for (x, y, p) in img.enumerate_pixels() {
+            let (sx, sy) = (x as i32 + self.offset.0, y as i32 + self.offset.1);
+            if sx >= 0 && sy >= 0 && (sx as u32) < width && (sy as u32) < height {
+                alpha[(sy as u32 * width + sx as u32) as usize] = p[3] as f32 / 255.;
+            }
+        }
+        let alpha = gaussian_blur(&alpha, width as usize, height as usize, self.blur);
+
+        let color = self.color.map(srgb_to_linear);
+        ImageBuffer::from_fn(width, height, |x, y| {
+            let p = img.get_pixel(x, y);
+            let a = p[3] as f32 / 255.;
+            let shadow = alpha[(y * width + x) as usize] * self.color[3] * (1. - a);
+            let mut out = [0u8; 4];
+            for (c, o) in out.iter_mut().enumerate() {
+                let v = if c == 3 {
+                    a + shadow
+                } else {
+                    linear_to_srgb(srgb_to_linear(p[c] as f32 / 255.) + color[c] * shadow)
+                };
+                *o = (v * 255.).round().clamp(0., 255.) as u8;
+            }
+            Rgba(out)
+        })
+    }
+}
+
+/// separable gaussian blur of a single channel image
+fn gaussian_blur(data: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    if sigma <= 0. {
+        return data.to_vec();
+    }
+    let radius = (sigma * 3.).ceil() as isize;
+    let kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2. * sigma * sigma)).exp())
+        .collect();
+    let norm: f32 = kernel.iter().sum();
+
+    let blur_1d = |src: &[f32], stride: usize, step: usize, len: usize, lines: usize| {
+        let mut dst = vec![0f32; src.len()];
+        for line in 0..lines {
+            for i in 0..len as isize {
+                let mut acc = 0.;
+                for (k, w) in kernel.iter().enumerate() {
+                    let j = i + k as isize - radius;
+                    if j >= 0 && j < len as isize {
+                        acc += w * src[line * stride + j as usize * step];
+                    }
+                }
+                dst[line * stride + i as usize * step] = acc / norm;
+            }
+        }
+        dst
+    };
+    let horizontal = blur_1d(data, width, 1, width, height);
+    blur_1d(&horizontal, 1, width, height, width)
+}
+
+/// image rendered offline, see [OfflinePixel]
+pub type OfflineImage<Px> = ImageBuffer<Px, Vec<<Px as Pixel>::Subpixel>>;
+
+/// pixel of images rendered offline: 8 bit sRGB (`Rgba<u8>`) or linear float
+/// (`Rgba<f32>`, rendered into a half float texture without 8 bit quantization)
+pub trait OfflinePixel: Pixel + 'static {
+    /// format of the render target
+    const FORMAT: wgpu::TextureFormat;
+
+    /// linear color and alpha
+    fn to_linear(&self) -> [f32; 4];
+
+    fn from_linear(color: [f32; 4]) -> Self;
+
+    /// image from the rows of a downloaded texture in [OfflinePixel::FORMAT]
+    fn from_texels(data: &[u8], bytes_per_row: u32, width: u32, height: u32) -> OfflineImage<Self>;
+}
+
+impl OfflinePixel for Rgba<u8> {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    fn to_linear(&self) -> [f32; 4] {
+        // alpha is stored linearly
+        std::array::from_fn(|c| {
+            let v = self.0[c] as f32 / 255.;
+            if c == 3 {
+                v
+            } else {
+                srgb_to_linear(v)
+            }
+        })
+    }
+
+    fn from_linear(color: [f32; 4]) -> Self {
+        Rgba(std::array::from_fn(|c| {
+            let v = if c == 3 {
+                color[c]
+            } else {
+                linear_to_srgb(color[c])
+            };
+            (v * 255.).round().clamp(0., 255.) as u8
+        }))
+    }
+
+    fn from_texels(data: &[u8], bytes_per_row: u32, width: u32, height: u32) -> OfflineImage<Self> {
+        let mut image =
+            ImageBuffer::<Self, _>::from_raw(bytes_per_row / 4, height, data.to_vec()).unwrap();
+        image::imageops::crop(&mut image, 0, 0, width, height).to_image()
+    }
+}
+
+impl OfflinePixel for Rgba<f32> {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    fn to_linear(&self) -> [f32; 4] {
+        self.0
+    }
+
+    fn from_linear(color: [f32; 4]) -> Self {
+        Rgba(color)
+    }
+
+    fn from_texels(data: &[u8], bytes_per_row: u32, width: u32, height: u32) -> OfflineImage<Self> {
+        let pixels = (0..height as usize)
+            .flat_map(|y| {
+                let row = y * bytes_per_row as usize;
+                data[row..row + width as usize * 8].chunks_exact(2)
+            })
+            .map(|v| half::f16::from_le_bytes([v[0], v[1]]).to_f32())
+            .collect();
+        ImageBuffer::from_raw(width, height, pixels).unwrap()
+    }
+}
+
+/// bit depth of images rendered offline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BitDepth {
+    /// 8 bit sRGB
+    #[default]
+    #[serde(rename = "8")]
+    Eight,
+    /// 16 bit sRGB, for png and tiff files
+    #[serde(rename = "16")]
+    Sixteen,
+    /// linear 32 bit float, for OpenEXR files
+    #[serde(rename = "float")]
+    Float,
+}
+
+impl BitDepth {
+    /// float for .exr files, `self` otherwise
+    pub fn for_path(self, path: &Path) -> Self {
+        let exr = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"));
+        if exr {
+            Self::Float
+        } else {
+            self
+        }
+    }
+}
+
+impl std::str::FromStr for BitDepth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(Self::Eight),
+            "16" => Ok(Self::Sixteen),
+            "32" | "float" => Ok(Self::Float),
+            _ => Err(anyhow::anyhow!(
+                "unknown bit depth {s}, expected 8, 16 or float"
+            )),
+        }
+    }
+}
+
+/// alpha of images with a transparent background
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlphaMode {
+    /// color not multiplied by alpha, what PNG files and most image viewers expect
+    #[default]
+    Straight,
+    /// color multiplied by alpha, as rendered. Composited with `color + (1 - alpha) * below`
+    Premultiplied,
+}
+
+impl std::str::FromStr for AlphaMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "straight" => Ok(Self::Straight),
+            "premultiplied" => Ok(Self::Premultiplied),
+            _ => Err(anyhow::anyhow!(
+                "unknown alpha mode {s}, expected straight or premultiplied"
+            )),
+        }
+    }
+}
+
+/// converts a rendered (premultiplied) image to straight alpha,
+/// the color is divided by alpha in linear space
+pub fn unpremultiply<P: OfflinePixel, Px: OfflinePixel>(img: &OfflineImage<P>) -> OfflineImage<Px> {
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).to_linear();
+        let s = if a > 0. { 1. / a } else { 0. };
+        Px::from_linear([r * s, g * s, b * s, a])
+    })
+}
+
+/// auxiliary output rendered in addition to the colors, for compositing, relighting or
+/// depth of field in post
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aov {
+    /// distance along the view direction of the sample seen at each pixel
+    /// (the iso-surface or the most opaque sample), zero where nothing is seen
+    Depth,
+    /// world space normal (gradient) at the sample seen at each pixel, facing the camera
+    Normal,
+    /// opacity of the color image
+    Alpha,
+}
+
+/// auxiliary outputs of one frame, stored as [r, g, b, 1] (depth and alpha in r, g and b)
+pub type AovImages = Vec<(Aov, OfflineImage<Rgba<f32>>)>;
+
+impl Aov {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Depth => "depth",
+            Self::Normal => "normal",
+            Self::Alpha => "alpha",
+        }
+    }
+
+    /// file next to the color image at `path` (e.g. frame_depth.exr). Depth and normals are
+    /// always linear float OpenEXR files, alpha has the format of the color image
+    pub fn path(self, path: &Path) -> anyhow::Result<PathBuf> {
+        let stem = path
+            .file_stem()
+            .ok_or_else(|| anyhow::anyhow!("invalid output file"))?
+            .to_string_lossy();
+        let extension = match self {
+            Self::Depth | Self::Normal => "exr".into(),
+            Self::Alpha => path.extension().unwrap_or("png".as_ref()).to_string_lossy(),
+        };
+        Ok(path.with_file_name(format!("{stem}_{}.{extension}", self.name())))
+    }
+
+    /// writes the output next to the color image at `path`, see [Aov::path].
+    /// The values are written as they are, without sRGB encoding
+    pub fn save(
+        self,
+        img: &OfflineImage<Rgba<f32>>,
+        path: &Path,
+        bit_depth: BitDepth,
+    ) -> anyhow::Result<()> {
+        let path = self.path(path)?;
+        let (width, height) = img.dimensions();
+        match (self, bit_depth.for_path(&path)) {
+            (Self::Depth | Self::Normal, _) | (Self::Alpha, BitDepth::Float) => {
+                DynamicImage::ImageRgb32F(ImageBuffer::from_fn(width, height, |x, y| {
+                    img.get_pixel(x, y).to_rgb()
+                }))
+                .save(path)?
+            }
+            (Self::Alpha, BitDepth::Sixteen) => ImageBuffer::from_fn(width, height, |x, y| {
+                Luma([(img.get_pixel(x, y)[0].clamp(0., 1.) * 65535.).round() as u16])
+            })
+            .save(path)?,
+            (Self::Alpha, BitDepth::Eight) => ImageBuffer::from_fn(width, height, |x, y| {
+                Luma([(img.get_pixel(x, y)[0].clamp(0., 1.) * 255.).round() as u8])
+            })
+            .save(path)?,
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Aov {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "depth" => Ok(Self::Depth),
+            "normal" | "normals" => Ok(Self::Normal),
+            "alpha" => Ok(Self::Alpha),
+            _ => Err(anyhow::anyhow!(
+                "unknown output {s}, expected depth, normal or alpha"
+            )),
+        }
+    }
+}
+
+/// writes a linear float image with the given bit depth,
+/// the format is chosen by the file extension
+pub fn save_image(
+    img: &OfflineImage<Rgba<f32>>,
+    path: &Path,
+    bit_depth: BitDepth,
+) -> anyhow::Result<()> {
+    let (width, height) = img.dimensions();
+    let encoded = |x, y| {
+        let p = img.get_pixel(x, y).0;
+        std::array::from_fn::<f32, 4, _>(|c| {
+            let v = if c == 3 { p[c] } else { linear_to_srgb(p[c]) };
+            v.clamp(0., 1.)
+        })
+    };
+    match bit_depth {
+        BitDepth::Float => DynamicImage::ImageRgba32F(img.clone()).save(path)?,
+        BitDepth::Sixteen => ImageBuffer::from_fn(width, height, |x, y| {
+            Rgba(encoded(x, y).map(|v| (v * 65535.).round() as u16))
+        })
+        .save(path)?,
+        BitDepth::Eight => ImageBuffer::from_fn(width, height, |x, y| {
+            Rgba(encoded(x, y).map(|v| (v * 255.).round() as u8))
+        })
+        .save(path)?,
+    }
+    Ok(())
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1. / 2.4) - 0.055
+    }
 }
 
 pub async fn download_texture(
@@ -130,6 +1709,15 @@ pub async fn download_texture(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
 ) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    download_image(texture, device, queue).await
+}
+
+/// downloads a texture in the format of the pixel type ([OfflinePixel::FORMAT])
+async fn download_image<Px: OfflinePixel>(
+    texture: &wgpu::Texture,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> OfflineImage<Px> {
     let texture_format = texture.format();
 
     let texel_size: u32 = texture_format.block_copy_size(None).unwrap();
@@ -166,20 +1754,13 @@ pub async fn download_texture(
     );
     let sub_idx = queue.submit(std::iter::once(encoder.finish()));
 
-    let mut image = {
+    let image = {
         let data: wgpu::BufferView<'_> =
             download_buffer(device, &staging_buffer, Some(sub_idx)).await;
-
-        ImageBuffer::<Rgba<u8>, _>::from_raw(
-            bytes_per_row / texel_size,
-            fb_size.height,
-            data.to_vec(),
-        )
-        .unwrap()
+        Px::from_texels(&data, bytes_per_row, fb_size.width, fb_size.height)
     };
     staging_buffer.unmap();
-
-    return image::imageops::crop(&mut image, 0, 0, fb_size.width, fb_size.height).to_image();
+    return image;
 }
 
 async fn download_buffer<'a>(
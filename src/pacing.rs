@@ -0,0 +1,77 @@
+#[cfg(target_arch = "wasm32")]
+use instant::{Duration, Instant};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+use winit::window::Window;
+
+/// limits the frame rate to the refresh rate of the monitor the window is on
+pub struct FramePacing {
+    pub enabled: bool,
+    /// refresh rate of the current monitor in Hz (None if unknown)
+    refresh_rate: Option<f32>,
+    /// minimum time between two frames
+    min_wait: Duration,
+    last_frame: Instant,
+    /// smoothed time between two frames
+    frame_time: Duration,
+}
+
+impl FramePacing {
+    pub fn new(window: &Window, enabled: bool) -> Self {
+        let mut pacing = Self {
+            enabled,
+            refresh_rate: None,
+            min_wait: Duration::ZERO,
+            last_frame: Instant::now(),
+            frame_time: Duration::ZERO,
+        };
+        pacing.update_monitor(window);
+        pacing
+    }
+
+    /// recomputes the refresh rate, e.g. after the window moved to another monitor
+    pub fn update_monitor(&mut self, window: &Window) {
+        let refresh_rate = window
+            .current_monitor()
+            .and_then(|m| m.refresh_rate_millihertz())
+            .map(|mhz| mhz as f32 / 1000.);
+        if refresh_rate != self.refresh_rate {
+            log::info!("monitor refresh rate: {:?} Hz", refresh_rate);
+            self.refresh_rate = refresh_rate;
+            // a bit shorter than the refresh interval so that vsync still sets the pace
+            self.min_wait =
+                refresh_rate.map_or(Duration::ZERO, |hz| Duration::from_secs_f32(0.9 / hz));
+        }
+    }
+
+    /// earliest time the next frame should be drawn
+    pub fn next_frame(&self) -> Instant {
+        if self.enabled {
+            self.last_frame + self.min_wait
+        } else {
+            self.last_frame
+        }
+    }
+
+    /// starts a new frame and returns the time since the last one
+    pub fn frame(&mut self) -> Duration {
+        let now = Instant::now();
+        let dt = now - self.last_frame;
+        self.last_frame = now;
+        self.frame_time = self.frame_time.mul_f32(0.9) + dt.mul_f32(0.1);
+        dt
+    }
+
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    pub fn refresh_rate(&self) -> Option<f32> {
+        self.refresh_rate
+    }
+
+    pub fn min_wait(&self) -> Duration {
+        self.min_wait
+    }
+}
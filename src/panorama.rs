@@ -0,0 +1,131 @@
+use std::f32::consts::PI;
+
+use image::ImageBuffer;
+
+use cgmath::{Deg, Matrix3, Quaternion, Rotation, Vector2, Vector3, Vector4};
+
+use crate::{
+    camera::{Camera, PerspectiveProjection, Projection, ViewProjection},
+    offline::{OfflineImage, OfflinePixel},
+};
+
+/// number of cube faces rendered for a panorama
+pub const NUM_FACES: usize = 6;
+
+/// cameras of the cube faces (front, right, back, left, top, bottom) rendered for an
+/// equirectangular panorama of `resolution` around `camera`.
+/// For stereo, the faces around the up axis are moved by `eye_offset` to the right of their
+/// view direction (an approximation of omni-directional stereo), the top and bottom have no parallax
+pub fn cube_faces<P: Projection>(
+    camera: &Camera<P>,
+    eye_offset: f32,
+    resolution: Vector2<u32>,
+) -> Vec<(Camera<ViewProjection>, Vector2<u32>)> {
+    let view_t: Matrix3<f32> = camera.rotation.invert().into();
+    let (right, up, forward) = (view_t.x, view_t.y, view_t.z);
+    // 90° at the equator and from the equator to the poles
+    let size = (resolution.x / 4).max(resolution.y / 2).max(1);
+    let projection = ViewProjection::Perspective(PerspectiveProjection {
+        fovy: Deg(90.).into(),
+        fovx: Deg(90.).into(),
+        znear: 0.01,
+        zfar: 1000.,
+        aspect_ratio: 1.,
+    });
+    [
+        (forward, up),
+        (right, up),
+        (-forward, up),
+        (-right, up),
+        (up, -forward),
+        (-up, forward),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(i, (direction, face_up))| {
+        let rotation = Quaternion::look_at(direction, face_up);
+        let position = if i < 4 {
+            let face_right = Matrix3::from(rotation.invert()).x;
+            camera.position + face_right * eye_offset
+        } else {
+            camera.position
+        };
+        (
+            Camera::new(position, rotation, projection),
+            Vector2::new(size, size),
+        )
+    })
+    .collect()
+}
+
+/// resamples the images of the [cube_faces] into an equirectangular panorama of `resolution`.
+/// The center of the panorama looks along the view direction of the front face
+pub fn equirectangular<Px: OfflinePixel>(
+    cameras: &[Camera<ViewProjection>],
+    faces: &[OfflineImage<Px>],
+    resolution: Vector2<u32>,
+) -> OfflineImage<Px> {
+    let project = |camera: &Camera<ViewProjection>, direction: Vector3<f32>| {
+        let p = camera.position + direction;
+        camera.view_proj_matrix() * Vector4::new(p.x, p.y, p.z, 1.)
+    };
+    // the axes of the front image, independent of the handedness of the camera
+    let front = &cameras[0];
+    let view_t: Matrix3<f32> = front.rotation.invert().into();
+    let forward = if project(front, view_t.z).w > 0. {
+        view_t.z
+    } else {
+        -view_t.z
+    };
+    let clip = project(front, forward + view_t.x * 0.5);
+    let right = if clip.x > 0. { view_t.x } else { -view_t.x };
+    let clip = project(front, forward + view_t.y * 0.5);
+    let up = if clip.y > 0. { view_t.y } else { -view_t.y };
+
+    ImageBuffer::from_fn(resolution.x, resolution.y, |x, y| {
+        let longitude = ((x as f32 + 0.5) / resolution.x as f32 - 0.5) * 2. * PI;
+        let latitude = (0.5 - (y as f32 + 0.5) / resolution.y as f32) * PI;
+        let direction = (forward * longitude.cos() + right * longitude.sin()) * latitude.cos()
+            + up * latitude.sin();
+        // the face the direction passes through closest to its center
+        let (face, ndc) = cameras
+            .iter()
+            .zip(faces)
+            .filter_map(|(camera, face)| {
+                let clip = project(camera, direction);
+                (clip.w > 0.).then(|| (face, Vector2::new(clip.x, clip.y) / clip.w))
+            })
+            .min_by(|(_, a), (_, b)| {
+                let extent = |ndc: &Vector2<f32>| ndc.x.abs().max(ndc.y.abs());
+                extent(a).total_cmp(&extent(b))
+            })
+            .unwrap();
+        // the upper row of the image is at y = 1
+        sample_bilinear(
+            face,
+            (ndc.x + 1.) / 2. * face.width() as f32 - 0.5,
+            (1. - ndc.y) / 2. * face.height() as f32 - 0.5,
+        )
+    })
+}
+
+/// interpolates the linear colors of the pixels around (x, y), clamped to the image
+fn sample_bilinear<Px: OfflinePixel>(img: &OfflineImage<Px>, x: f32, y: f32) -> Px {
+    let max = Vector2::new(img.width() - 1, img.height() - 1)
+        .cast::<f32>()
+        .unwrap();
+    let (x, y) = (x.clamp(0., max.x), y.clamp(0., max.y));
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let pixel = |x: f32, y: f32| {
+        img.get_pixel(x.min(max.x) as u32, y.min(max.y) as u32)
+            .to_linear()
+    };
+    let (a, b) = (pixel(x0, y0), pixel(x0 + 1., y0));
+    let (c, d) = (pixel(x0, y0 + 1.), pixel(x0 + 1., y0 + 1.));
+    Px::from_linear(std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    }))
+}
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use crate::cmap::{GenericColorMap, LinearSegmentedColorMap};
+
+/// directory with the user's colormap presets:
+/// `$VAPE4D_COLORMAP_DIR` or `colormaps` in the [config directory](crate::config::config_dir)
+pub fn preset_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("VAPE4D_COLORMAP_DIR") {
+        return Some(dir.into());
+    }
+    crate::config::config_dir().map(|dir| dir.join("colormaps"))
+}
+
+/// colormaps (json or npy files) in the preset directory by name, sorted by name
+pub fn load_presets() -> Vec<(String, GenericColorMap)> {
+    let Some(entries) = preset_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+    let mut presets: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("json" | "npy")
+            )
+        })
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_string();
+            let cmap = std::fs::File::open(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(GenericColorMap::read);
+            match cmap {
+                Ok(cmap) => Some((name, cmap)),
+                Err(err) => {
+                    log::warn!("skipping colormap preset {}: {err}", path.display());
+                    None
+                }
+            }
+        })
+        .collect();
+    presets.sort_by(|a, b| a.0.cmp(&b.0));
+    presets
+}
+
+/// saves the colormap (including its alpha channel) as json preset `name`.
+/// Returns the path of the file.
+pub fn save_preset(name: &str, cmap: &LinearSegmentedColorMap) -> anyhow::Result<PathBuf> {
+    anyhow::ensure!(
+        !name.is_empty() && !name.contains(['/', '\\', '.']),
+        "invalid preset name '{name}'"
+    );
+    let dir = preset_dir()
+        .ok_or_else(|| anyhow::anyhow!("no preset directory, set VAPE4D_COLORMAP_DIR"))?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{name}.json"));
+    std::fs::write(&path, serde_json::to_vec(cmap)?)?;
+    Ok(path)
+}
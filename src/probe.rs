@@ -0,0 +1,78 @@
+use cgmath::{Point3, Vector2};
+
+use crate::{reference::RayHit, volume::VolumeGPU};
+
+/// voxel under the cursor, read when clicking into the view in probe mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    /// cursor position in physical pixels
+    pub cursor: Vector2<f32>,
+    /// channel that was clicked
+    pub channel: usize,
+    /// world space position of the hit
+    pub position: Point3<f32>,
+    /// index (depth, height, width) of the voxel containing the hit
+    pub voxel: [usize; 3],
+    /// timestep the voxel values are read from
+    pub timestep: usize,
+    /// interpolated value seen along the ray
+    pub value: f32,
+    /// value of the voxel in every channel
+    pub voxel_values: Vec<f32>,
+    /// value of the voxel at every timestep, for every channel
+    pub series: Vec<Vec<f32>>,
+}
+
+impl Probe {
+    /// reads the voxel at `hit` of the `channel` from all volumes at the normalized `time`
+    pub fn new(
+        cursor: Vector2<f32>,
+        channel: usize,
+        hit: RayHit,
+        volumes: &[VolumeGPU],
+        time: f32,
+    ) -> Self {
+        let volume = &volumes[channel].volume;
+        let timestep = (time * (volume.timesteps - 1) as f32).round() as usize;
+        let voxel = volume.voxel_index(hit.position);
+        Self {
+            cursor,
+            channel,
+            position: hit.position,
+            voxel,
+            timestep,
+            value: hit.value,
+            voxel_values: volumes
+                .iter()
+                .map(|v| v.volume.voxel_value(timestep, voxel))
+                .collect(),
+            series: volumes
+                .iter()
+                .map(|v| {
+                    (0..v.volume.timesteps as usize)
+                        .map(|t| v.volume.voxel_value(t, voxel))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// lines shown in the tooltip
+    pub fn lines(&self) -> Vec<String> {
+        let [d, h, w] = self.voxel;
+        let p = self.position;
+        let mut lines = vec![
+            format!("voxel [{d}, {h}, {w}] (timestep {})", self.timestep),
+            format!("position ({:.3}, {:.3}, {:.3})", p.x, p.y, p.z),
+            format!("value {:.4}", self.value),
+        ];
+        if self.voxel_values.len() > 1 {
+            for (i, v) in self.voxel_values.iter().enumerate() {
+                lines.push(format!("channel {i}: {v:.4}"));
+            }
+        } else {
+            lines.push(format!("voxel value {:.4}", self.voxel_values[0]));
+        }
+        lines
+    }
+}
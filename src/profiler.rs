@@ -0,0 +1,175 @@
+use crate::readback::BufferMapping;
+
+/// maximum number of passes timed per frame
+const MAX_PASSES: usize = 16;
+/// size of one timestamp in bytes
+const TIMESTAMP_SIZE: u64 = std::mem::size_of::<u64>() as u64;
+
+/// work done for the volume rendering in the last frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    /// rays cast through the volume (pixels of all views), zero if the volume was not rendered
+    pub rays: u64,
+    /// samples of a ray along the diagonal of the volume, the longest possible ray
+    pub samples_per_ray: u32,
+}
+
+struct Queries {
+    set: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    /// nanoseconds per timestamp tick
+    period: f32,
+}
+
+/// GPU time of the render passes measured with timestamp queries.
+/// The timestamps are read back asynchronously, a frame is only timed if the
+/// previous measurement is read back.
+pub struct GpuTimer {
+    /// None if the device does not support timestamp queries
+    queries: Option<Queries>,
+    /// timed passes of the frame that is recorded
+    passes: Vec<&'static str>,
+    recording: bool,
+    /// timed passes of the frame that is read back
+    pending: Option<Vec<&'static str>>,
+    mapping: BufferMapping,
+    /// smoothed GPU time of each pass in milliseconds
+    timings: Vec<(&'static str, f32)>,
+}
+
+impl GpuTimer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let queries = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| {
+                let size = 2 * MAX_PASSES as u64 * TIMESTAMP_SIZE;
+                Queries {
+                    set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some("pass timestamps"),
+                        ty: wgpu::QueryType::Timestamp,
+                        count: 2 * MAX_PASSES as u32,
+                    }),
+                    resolve: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("timestamp resolve buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback: device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("timestamp readback buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    period: queue.get_timestamp_period(),
+                }
+            });
+        Self {
+            queries,
+            passes: Vec::new(),
+            recording: false,
+            pending: None,
+            mapping: BufferMapping::default(),
+            timings: Vec::new(),
+        }
+    }
+
+    /// false if the device does not support timestamp queries
+    pub fn supported(&self) -> bool {
+        self.queries.is_some()
+    }
+
+    /// starts a frame, its passes are timed if `enabled`
+    pub fn begin(&mut self, enabled: bool) {
+        self.passes.clear();
+        self.recording = enabled && self.queries.is_some() && self.pending.is_none();
+        if !enabled {
+            self.timings.clear();
+        }
+    }
+
+    /// timestamp writes for a render pass named `name`, None if the frame is not timed
+    pub fn pass(&mut self, name: &'static str) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let queries = self.queries.as_ref().filter(|_| self.recording)?;
+        if self.passes.len() >= MAX_PASSES {
+            return None;
+        }
+        let index = 2 * self.passes.len() as u32;
+        self.passes.push(name);
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &queries.set,
+            beginning_of_pass_write_index: Some(index),
+            end_of_pass_write_index: Some(index + 1),
+        })
+    }
+
+    /// copies the timestamps of the frame into the readback buffer.
+    /// [GpuTimer::map] has to be called after the submit.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(queries) = &self.queries else {
+            return;
+        };
+        if self.passes.is_empty() {
+            return;
+        }
+        let count = 2 * self.passes.len() as u32;
+        encoder.resolve_query_set(&queries.set, 0..count, &queries.resolve, 0);
+        encoder.copy_buffer_to_buffer(
+            &queries.resolve,
+            0,
+            &queries.readback,
+            0,
+            count as u64 * TIMESTAMP_SIZE,
+        );
+        self.pending = Some(std::mem::take(&mut self.passes));
+    }
+
+    /// starts reading back the timestamps of the submitted frame
+    pub fn map(&mut self) {
+        let (Some(queries), Some(_)) = (&self.queries, &self.pending) else {
+            return;
+        };
+        self.mapping.map(&queries.readback);
+    }
+
+    /// updates the timings once the timestamps are read back
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let (Some(queries), Some(_)) = (&self.queries, &self.pending) else {
+            return;
+        };
+        let Some(result) = self.mapping.poll(device) else {
+            return;
+        };
+        let passes = self.pending.take().unwrap();
+        if let Err(err) = result {
+            log::warn!("failed to read the timestamps: {err}");
+            return;
+        }
+        let timestamps: Vec<u64> =
+            bytemuck::cast_slice(&queries.readback.slice(..).get_mapped_range())
+                [..2 * passes.len()]
+                .to_vec();
+        queries.readback.unmap();
+        for (name, t) in passes.iter().zip(timestamps.chunks(2)) {
+            let ms = t[1].saturating_sub(t[0]) as f32 * queries.period / 1e6;
+            match self.timings.iter_mut().find(|(n, _)| n == name) {
+                Some((_, time)) => *time = *time * 0.9 + ms * 0.1,
+                None => self.timings.push((name, ms)),
+            }
+        }
+        // passes that are not rendered anymore (e.g. the lens) fade out
+        for (name, time) in self.timings.iter_mut() {
+            if !passes.contains(name) {
+                *time *= 0.9;
+            }
+        }
+        self.timings.retain(|(_, time)| *time > 1e-3);
+    }
+
+    /// smoothed GPU time of each pass in milliseconds
+    pub fn timings(&self) -> &[(&'static str, f32)] {
+        &self.timings
+    }
+}
@@ -1,19 +1,47 @@
-use cgmath::Vector2;
+use cgmath::{Vector2, Vector3};
 use half::f16;
 use image::{ImageBuffer, Rgba};
-use numpy::{ndarray::StrideShape, IntoPyArray, PyArray4, PyReadonlyArrayDyn};
+use numpy::{
+    ndarray::{ArrayView3, ArrayViewD, Axis, StrideShape},
+    IntoPyArray, PyArray2, PyArray3, PyArray4, PyReadonlyArray3, PyReadonlyArray4,
+    PyReadonlyArrayDyn,
+};
 use pyo3::prelude::*;
-use std::env::{self};
+use serde::Deserialize;
+use std::{
+    env::{self},
+    path::Path,
+};
 
 use crate::{
-    cmap::{self, ListedColorMap},
-    offline::render_volume,
+    annotations::Annotations,
+    camera::CameraView,
+    cmap::{
+        self, ColorMap, LinearSegmentedColorMap, ListedColorMap, TransferFunction,
+        COLORMAP_RESOLUTION,
+    },
+    datasets::{self, EXAMPLE_DATASETS},
+    diff,
+    keyframes::Keyframe,
+    offline::{
+        interpolate_frames, render_animation, render_keyframes, render_volume_with_settings,
+        unpremultiply, AlphaMode, Animation, AnimationOutput, ContactSheet, DropShadow, MotionBlur,
+        OfflineRenderer, OfflineSettings, OutputPreset,
+    },
+    recording::{Recording, RecordingSettings, VideoFormat},
+    renderer::RenderSettings,
+    session::Session,
+    spec::RenderSpec,
+    statistics::VolumeSummary,
+    stereo::Stereo,
     viewer,
     volume::Volume,
 };
 
 #[pymodule]
 fn vape4d<'py>(m: &Bound<'py, PyModule>) -> PyResult<()> {
+    /// renders the volume at the normalized times in `time` into an array of shape
+    /// [N, H, W, 4]. `options` is json, see [VideoOptions]
     #[pyfn(m)]
     fn render_video<'py>(
         py: Python<'py>,
@@ -23,44 +51,488 @@ fn vape4d<'py>(m: &Bound<'py, PyModule>) -> PyResult<()> {
         height: u32,
         time: Vec<f32>,
         background: (f32, f32, f32, f32),
-        distance_scale: f32,
-        vmin: Option<f32>,
-        vmax: Option<f32>,
-        spatial_interpolation: Option<String>,
-        temporal_interpolation: Option<String>,
-    ) -> Bound<'py, PyArray4<u8>> {
-        let volume = Volume::from_array(volume.as_array());
-        let cmap = ListedColorMap::from_array(cmap.as_array());
-        let img: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = pollster::block_on(render_volume(
+        options: Option<String>,
+    ) -> PyResult<Bound<'py, PyArray4<u8>>> {
+        if time.is_empty() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "time must contain at least one value",
+            ));
+        }
+        let options: VideoOptions = match options {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+            None => VideoOptions::default(),
+        };
+        let mut annotations = Annotations {
+            scale_bar: options.scale_bar,
+            ticks: options.axis_ticks,
+            time_label: options.time_label,
+            unit: options.unit,
+            ..Default::default()
+        };
+        let mut stereo_settings = Stereo::default();
+        if let Some(mode) = options.stereo {
+            stereo_settings.mode = mode.parse().map_err(|e: anyhow::Error| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+            })?;
+        }
+        if let Some(eye_separation) = options.eye_separation {
+            stereo_settings.eye_separation = eye_separation;
+        }
+        let (width, height, supersampling) = match options.preset {
+            Some(name) => {
+                let preset = OutputPreset::find(&name).ok_or_else(|| {
+                    PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "unknown output preset '{}'",
+                        name
+                    ))
+                })?;
+                annotations.scale = preset.legend_scale();
+                (
+                    preset.resolution.x,
+                    preset.resolution.y,
+                    options.supersampling.unwrap_or(preset.supersampling),
+                )
+            }
+            None => (width, height, options.supersampling.unwrap_or(1)),
+        };
+        let mut volume = Volume::from_array(volume.as_array());
+        if let Some((d, h, w)) = options.spacing {
+            volume.set_spacing(Vector3::new(w, h, d));
+        }
+        let cmap = cmap.as_array();
+        // [N, 4] for a single colormap, [K, N, 4] for K colormaps over time
+        let cmaps: Vec<_> = if cmap.ndim() == 3 {
+            cmap.axis_iter(Axis(0)).map(colormap_from_array).collect()
+        } else {
+            vec![colormap_from_array(cmap)]
+        };
+        let mut cmap = match options.cmap_times {
+            Some(times) => {
+                if times.len() != cmaps.len() {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                        "expected {} colormap times, got {}",
+                        cmaps.len(),
+                        times.len()
+                    )));
+                }
+                cmap::ColorMapSequence::new(times.into_iter().zip(cmaps).collect())
+            }
+            None => cmap::ColorMapSequence::uniform(cmaps),
+        }
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        if let Some(points) = options.opacity {
+            let opacity = TransferFunction::from_points(&points)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+            cmap = cmap.with_opacity(&opacity);
+        }
+        let view = options.camera.unwrap_or_default();
+        view.validate()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let time = interpolate_frames(&time, options.frame_interpolation.unwrap_or(1));
+        let motion_blur = MotionBlur {
+            samples: options.motion_blur_samples.unwrap_or(1),
+            shutter: options.shutter.unwrap_or(0.5),
+        };
+        let background = wgpu::Color {
+            r: background.0 as f64,
+            g: background.1 as f64,
+            b: background.2 as f64,
+            a: background.3 as f64,
+        };
+        // the options take precedence over the render settings
+        let mut render_settings = options.settings.unwrap_or_default();
+        if options.vmin.is_some() {
+            render_settings.vmin = options.vmin;
+        }
+        if options.vmax.is_some() {
+            render_settings.vmax = options.vmax;
+        }
+        if let Some(distance_scale) = options.distance_scale {
+            render_settings.distance_scale = distance_scale;
+        }
+        if let Some(interpolation) = options.spatial_interpolation {
+            render_settings.spatial_filter =
+                interpolation.parse().map_err(|e: anyhow::Error| {
+                    PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+                })?;
+        }
+        if let Some(interpolation) = options.temporal_interpolation {
+            render_settings.temporal_filter =
+                interpolation.parse().map_err(|e: anyhow::Error| {
+                    PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+                })?;
+        }
+        let offline_settings = OfflineSettings {
+            settings: render_settings,
+            view,
+            supersampling,
+            background,
+            motion_blur,
+            ..OfflineSettings::new(Vector2::new(width, height))
+        };
+        let mut img: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> =
+            pollster::block_on(render_volume_with_settings(
+                vec![volume],
+                cmap,
+                &time,
+                &offline_settings,
+                Some(&annotations),
+                stereo_settings,
+            ))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))?;
+        if let Some(color) = options.shadow_color {
+            let shadow = DropShadow {
+                color,
+                offset: options.shadow_offset.unwrap_or((0, 0)),
+                blur: options.shadow_blur.unwrap_or(0.),
+            };
+            img = img.iter().map(|img| shadow.apply(img)).collect();
+        }
+        if let Some(columns) = options.contact_sheet {
+            let sheet = ContactSheet {
+                columns,
+                ..Default::default()
+            };
+            img = vec![sheet.compose(&img, background)];
+        }
+        let alpha: AlphaMode = match options.alpha {
+            Some(alpha) => alpha.parse().map_err(|e: anyhow::Error| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+            })?,
+            None => AlphaMode::Premultiplied,
+        };
+        if alpha == AlphaMode::Straight {
+            img = img.iter().map(unpremultiply).collect();
+        }
+
+        let (width, height) = img[0].dimensions();
+        let shape = StrideShape::from((img.len(), height as usize, width as usize, 4 as usize));
+        let arr = numpy::ndarray::Array4::from_shape_vec(
+            shape,
+            img.iter().flat_map(|img| img.to_vec()).collect(),
+        )
+        .unwrap();
+        return Ok(arr.into_pyarray_bound(py));
+    }
+
+    /// renders the frames of a render spec (json).
+    /// Relative paths in the spec are relative to `base_dir`, `volume` replaces the spec's volume
+    #[pyfn(m)]
+    fn render_spec<'py>(
+        py: Python<'py>,
+        spec: String,
+        base_dir: Option<String>,
+        volume: Option<PyReadonlyArrayDyn<'py, f16>>,
+    ) -> PyResult<Bound<'py, PyArray4<u8>>> {
+        let mut spec = RenderSpec::from_json(&spec)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let volume = volume.map(|volume| {
+            let mut volume = Volume::from_array(volume.as_array());
+            spec.volume.apply(&mut volume);
+            volume
+        });
+        if volume.is_some() {
+            // the array replaces the volume file of the spec
+            spec.volume.path.clear();
+        }
+        let files = spec
+            .load_files(Path::new(&base_dir.unwrap_or_default()))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        let volumes = match volume {
+            Some(volume) => vec![volume],
+            None => spec
+                .volumes(&files)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+        };
+        let img = pollster::block_on(spec.render::<Rgba<u8>>(volumes, &files))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))?;
+        let (width, height) = img[0].dimensions();
+        let arr = numpy::ndarray::Array4::from_shape_vec(
+            (img.len(), height as usize, width as usize, 4),
+            img.iter().flat_map(|img| img.to_vec()).collect(),
+        )
+        .unwrap();
+        Ok(arr.into_pyarray_bound(py))
+    }
+
+    /// renders an image of a session saved in the viewer (json) with its camera and settings.
+    /// The volume file of the session is relative to `base_dir`, `volume` replaces it
+    #[pyfn(m)]
+    fn render_session<'py>(
+        py: Python<'py>,
+        session: String,
+        width: u32,
+        height: u32,
+        base_dir: Option<String>,
+        volume: Option<PyReadonlyArrayDyn<'py, f16>>,
+    ) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let session = Session::from_json(&session)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let base_dir = base_dir.unwrap_or_default();
+        let base_dir = Path::new(&base_dir);
+        let mut volumes = match volume {
+            Some(volume) => vec![Volume::from_array(volume.as_array())],
+            None => {
+                let file = session.volume_file(base_dir).ok_or_else(|| {
+                    PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                        "the session has no volume file",
+                    )
+                })?;
+                let reader = std::fs::File::open(file)
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+                let mut volumes = Volume::load_numpy(std::io::BufReader::new(reader), true)
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+                session
+                    .load_mask(base_dir, &mut volumes)
+                    .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+                volumes
+            }
+        };
+        volumes.iter_mut().for_each(|v| session.apply_spacing(v));
+        // the selected channel, or the first one if all channels are shown
+        let channel = session.selected_channel.unwrap_or(0).min(volumes.len() - 1);
+        let volume = volumes.swap_remove(channel);
+        let (settings, keyframe) = session.view(&volume.aabb);
+        let cmap =
+            cmap::GenericColorMap::LinearSegmented(session.colormap.with_opacity(&session.opacity));
+        let img = pollster::block_on(render_keyframes::<Rgba<u8>>(
             vec![volume],
-            cmap::GenericColorMap::Listed(cmap),
+            cmap,
             Vector2::new(width, height),
-            &time,
-            wgpu::Color {
+            &[keyframe],
+            1.,
+            session.background_color(),
+            &settings,
+            1,
+            None,
+            Stereo::default(),
+        ))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))?;
+        let arr = numpy::ndarray::Array3::from_shape_vec(
+            (height as usize, width as usize, 4),
+            img[0].to_vec(),
+        )
+        .unwrap();
+        Ok(arr.into_pyarray_bound(py))
+    }
+
+    /// statistics of the finite values of a volume ([T, D, H, W]) of all timesteps (or only `timestep`)
+    /// computed on the GPU: (count, nan, min, max, mean, std, percentiles, histogram).
+    /// The histogram has 128 bins between min and max
+    #[pyfn(m)]
+    fn volume_statistics<'py>(
+        volume: PyReadonlyArrayDyn<'py, f16>,
+        percentiles: Vec<f32>,
+        timestep: Option<usize>,
+    ) -> PyResult<(u64, u64, f32, f32, f64, f64, Vec<f32>, Vec<u32>)> {
+        let volume = Volume::from_array(volume.as_array());
+        let summary = pollster::block_on(VolumeSummary::compute(volume, timestep, &percentiles))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let stats = summary.statistics;
+        Ok((
+            stats.count,
+            stats.nan,
+            stats.min,
+            stats.max,
+            stats.mean,
+            stats.std,
+            summary.percentiles,
+            summary.histogram.bins,
+        ))
+    }
+
+    /// samples a colormap given as comma-separated hex colors.
+    /// Returns RGBA values in [0, 1] of shape [256, 4]
+    #[pyfn(m)]
+    fn colormap_from_hex<'py>(
+        py: Python<'py>,
+        colors: &str,
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let cmap = cmap::LinearSegmentedColorMap::from_hex_list(colors)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let values = (&cmap).rasterize(cmap::COLORMAP_RESOLUTION as usize);
+        let arr = numpy::ndarray::Array2::from_shape_vec(
+            (values.len(), 4),
+            values
+                .iter()
+                .flat_map(|v| [v.x, v.y, v.z, v.w].map(|c| c as f32 / 255.))
+                .collect(),
+        )
+        .unwrap();
+        Ok(arr.into_pyarray_bound(py))
+    }
+
+    /// downloads the example dataset `name` (if it is not cached) and returns the path
+    /// of its npy file
+    #[pyfn(m)]
+    fn fetch_example(name: &str) -> PyResult<String> {
+        let path = datasets::fetch_example(name)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(e.to_string()))?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    /// returns the available example datasets as (name, description)
+    #[pyfn(m)]
+    fn example_datasets() -> Vec<(&'static str, &'static str)> {
+        EXAMPLE_DATASETS
+            .iter()
+            .map(|d| (d.name, d.description))
+            .collect()
+    }
+
+    /// returns the available output presets as (name, width, height, supersampling, dpi)
+    #[pyfn(m)]
+    fn output_presets() -> Vec<(&'static str, u32, u32, u32, f32)> {
+        OutputPreset::all()
+            .into_iter()
+            .map(|p| {
+                (
+                    p.name,
+                    p.resolution.x,
+                    p.resolution.y,
+                    p.supersampling,
+                    p.dpi,
+                )
+            })
+            .collect()
+    }
+
+    /// compares two RGBA images of shape [H, W, 4] and returns (psnr, ssim, heatmap)
+    #[pyfn(m)]
+    fn diff_images<'py>(
+        py: Python<'py>,
+        a: PyReadonlyArray3<'py, u8>,
+        b: PyReadonlyArray3<'py, u8>,
+    ) -> PyResult<(f64, f64, Bound<'py, PyArray3<u8>>)> {
+        let result = diff::diff_images(
+            &array_to_image(a.as_array())?,
+            &array_to_image(b.as_array())?,
+        )
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("{:?}", e)))?;
+        let (width, height) = result.heatmap.dimensions();
+        let heatmap = numpy::ndarray::Array3::from_shape_vec(
+            (height as usize, width as usize, 4),
+            result.heatmap.into_raw(),
+        )
+        .unwrap();
+        Ok((result.psnr, result.ssim, heatmap.into_pyarray_bound(py)))
+    }
+
+    /// renders an animation frame by frame into a video (mp4, webm, gif or apng by the file
+    /// extension) or numbered images. The camera follows `keyframes` (json list), orbits
+    /// around the volume with `turntable` or stays at `camera` (json like in `render_video`)
+    #[pyfn(m)]
+    fn render_animation_file<'py>(
+        volume: PyReadonlyArrayDyn<'py, f16>,
+        cmap: PyReadonlyArrayDyn<'py, f32>,
+        path: String,
+        fps: u32,
+        num_frames: usize,
+        width: u32,
+        height: u32,
+        time_range: (f32, f32),
+        background: (f32, f32, f32, f32),
+        supersampling: Option<u32>,
+        spacing: Option<(f32, f32, f32)>,
+        camera: Option<String>,
+        keyframes: Option<String>,
+        turntable: Option<bool>,
+        settings: Option<String>,
+    ) -> PyResult<()> {
+        let mut volume = Volume::from_array(volume.as_array());
+        if let Some((d, h, w)) = spacing {
+            volume.set_spacing(Vector3::new(w, h, d));
+        }
+        let cmap = colormap_from_array(cmap.as_array());
+        let view: CameraView = match camera {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+            None => CameraView::default(),
+        };
+        view.validate()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let keyframes: Vec<Keyframe> = match keyframes {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+            None => Vec::new(),
+        };
+        for keyframe in keyframes.iter() {
+            keyframe
+                .validate()
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        }
+        let settings: RenderSettings = match settings {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+            None => RenderSettings::default(),
+        };
+        let animation = Animation {
+            time_range,
+            keyframes,
+            view,
+            turntable: turntable.unwrap_or(false),
+            settings,
+            supersampling: supersampling.unwrap_or(1),
+            background: wgpu::Color {
                 r: background.0 as f64,
                 g: background.1 as f64,
                 b: background.2 as f64,
                 a: background.3 as f64,
             },
-            vmin,
-            vmax,
-            distance_scale,
-            spatial_interpolation
-                .map(|s| parse_interpolation(&s).unwrap())
-                .unwrap_or_default(),
-            temporal_interpolation
-                .map(|s| parse_interpolation(&s).unwrap())
-                .unwrap_or_default(),
+            ..Animation::new(num_frames, Vector2::new(width, height))
+        };
+        let path = Path::new(&path).to_path_buf();
+        let output = match VideoFormat::from_path(&path) {
+            Some(format) => AnimationOutput::Video { path, format, fps },
+            None => AnimationOutput::Images(path),
+        };
+        pollster::block_on(render_animation(
+            vec![volume],
+            cmap,
+            &animation,
+            None,
+            Stereo::default(),
+            &output,
+            |_, _| {},
         ))
-        .unwrap();
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))
+    }
 
-        let shape = StrideShape::from((time.len(), width as usize, height as usize, 4 as usize));
-        let arr = numpy::ndarray::Array4::from_shape_vec(
-            shape,
-            img.iter().flat_map(|img| img.to_vec()).collect(),
-        )
-        .unwrap();
-        return arr.into_pyarray_bound(py);
+    /// writes frames of shape [T, H, W, 4] to a looping GIF or APNG (by the file extension)
+    #[pyfn(m)]
+    fn save_animation<'py>(
+        frames: PyReadonlyArray4<'py, u8>,
+        path: String,
+        fps: u32,
+    ) -> PyResult<()> {
+        let path = Path::new(&path);
+        let format = VideoFormat::from_path(path)
+            .filter(|format| !format.needs_ffmpeg())
+            .ok_or_else(|| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                    "animations are written as .gif or .apng",
+                )
+            })?;
+        let frames = frames.as_array();
+        let shape = frames.shape();
+        let settings = RecordingSettings {
+            format,
+            resolution: Vector2::new(shape[2] as u32, shape[1] as u32),
+            fps,
+        };
+        let duration = shape[0] as f32 / fps.max(1) as f32;
+        let mut recording = Recording::start(path, &settings, duration)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))?;
+        for frame in frames.axis_iter(Axis(0)) {
+            recording
+                .add_frame(&array_to_image(frame)?)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))?;
+        }
+        recording
+            .finish()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))?;
+        Ok(())
     }
 
     #[pyfn(m)]
@@ -74,13 +546,168 @@ fn vape4d<'py>(m: &Bound<'py, PyModule>) -> PyResult<()> {
             ))),
         }
     }
+    /// runs the viewer with command line arguments (without the program name),
+    /// e.g. in a separate process
+    #[pyfn(m)]
+    fn run_viewer<'py>(_py: Python<'py>, args: Vec<String>) -> PyResult<()> {
+        let args = std::iter::once("vape4d".to_string()).chain(args);
+        pollster::block_on(viewer(args))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))
+    }
+
+    m.add_class::<Renderer>()?;
     Ok(())
 }
 
-fn parse_interpolation(text: &str) -> anyhow::Result<wgpu::FilterMode> {
-    match text.to_lowercase().as_str() {
-        "nearest" => Ok(wgpu::FilterMode::Nearest),
-        "linear" => Ok(wgpu::FilterMode::Linear),
-        _ => Err(anyhow::format_err!("Invalid interpolation mode")),
+/// renders many images of the same volume, the device and the uploaded volume are kept
+/// between the renders
+#[pyclass]
+struct Renderer {
+    renderer: OfflineRenderer,
+}
+
+#[pymethods]
+impl Renderer {
+    /// uploads the volume and the colormap ([N, 4])
+    #[new]
+    fn new<'py>(
+        volume: PyReadonlyArrayDyn<'py, f16>,
+        cmap: PyReadonlyArrayDyn<'py, f32>,
+        spacing: Option<(f32, f32, f32)>,
+    ) -> Self {
+        let mut volume = Volume::from_array(volume.as_array());
+        if let Some((d, h, w)) = spacing {
+            volume.set_spacing(Vector3::new(w, h, d));
+        }
+        let cmap = colormap_from_array(cmap.as_array());
+        Self {
+            renderer: pollster::block_on(OfflineRenderer::new(vec![volume], cmap)),
+        }
+    }
+
+    /// renders one image per normalized time in `times` into an array of shape [N, H, W, 4].
+    /// `camera` and `settings` are json like in `render_video`
+    fn render<'py>(
+        &self,
+        py: Python<'py>,
+        times: Vec<f32>,
+        width: u32,
+        height: u32,
+        background: (f32, f32, f32, f32),
+        supersampling: Option<u32>,
+        camera: Option<String>,
+        settings: Option<String>,
+        alpha: Option<String>,
+    ) -> PyResult<Bound<'py, PyArray4<u8>>> {
+        let view: CameraView = match camera {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+            None => CameraView::default(),
+        };
+        view.validate()
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        let render_settings: RenderSettings = match settings {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?,
+            None => RenderSettings::default(),
+        };
+        let alpha: AlphaMode = match alpha {
+            Some(alpha) => alpha.parse().map_err(|e: anyhow::Error| {
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string())
+            })?,
+            None => AlphaMode::Premultiplied,
+        };
+        let settings = OfflineSettings {
+            settings: render_settings,
+            view,
+            supersampling: supersampling.unwrap_or(1),
+            background: wgpu::Color {
+                r: background.0 as f64,
+                g: background.1 as f64,
+                b: background.2 as f64,
+                a: background.3 as f64,
+            },
+            ..OfflineSettings::new(Vector2::new(width, height))
+        };
+        let mut img = Vec::with_capacity(times.len());
+        for time in times {
+            let frame = pollster::block_on(self.renderer.render::<Rgba<u8>>(
+                time,
+                &settings,
+                None,
+                &Stereo::default(),
+            ))
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyException, _>(format!("{:?}", e)))?;
+            img.push(match alpha {
+                AlphaMode::Straight => unpremultiply(&frame),
+                AlphaMode::Premultiplied => frame,
+            });
+        }
+        let arr = numpy::ndarray::Array4::from_shape_vec(
+            (img.len(), height as usize, width as usize, 4),
+            img.into_iter().flat_map(|img| img.into_raw()).collect(),
+        )
+        .unwrap();
+        Ok(arr.into_pyarray_bound(py))
+    }
+}
+
+/// optional arguments of `render_video` (json), the names are those of `vape4d.render`
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct VideoOptions {
+    distance_scale: Option<f32>,
+    vmin: Option<f32>,
+    vmax: Option<f32>,
+    spatial_interpolation: Option<String>,
+    temporal_interpolation: Option<String>,
+    frame_interpolation: Option<u32>,
+    motion_blur_samples: Option<u32>,
+    shutter: Option<f32>,
+    preset: Option<String>,
+    supersampling: Option<u32>,
+    shadow_color: Option<[f32; 4]>,
+    shadow_offset: Option<(i32, i32)>,
+    shadow_blur: Option<f32>,
+    /// voxel size along (D, H, W)
+    spacing: Option<(f32, f32, f32)>,
+    unit: String,
+    scale_bar: bool,
+    axis_ticks: bool,
+    stereo: Option<String>,
+    eye_separation: Option<f32>,
+    /// (value, opacity) control points
+    opacity: Option<Vec<(f32, f32)>>,
+    cmap_times: Option<Vec<f32>>,
+    time_label: bool,
+    contact_sheet: Option<u32>,
+    /// same as the camera section of a render spec
+    camera: Option<CameraView>,
+    /// same as the render section of a session, the options above take precedence
+    settings: Option<RenderSettings>,
+    alpha: Option<String>,
+}
+
+/// colormap sampled by matplotlib ([N, 4]), converted to control points
+/// like the colormaps edited in the viewer
+fn colormap_from_array(data: ArrayViewD<f32>) -> cmap::GenericColorMap {
+    cmap::GenericColorMap::LinearSegmented(LinearSegmentedColorMap::from_color_map(
+        &ListedColorMap::from_array(data),
+        COLORMAP_RESOLUTION,
+    ))
+}
+
+fn array_to_image(arr: ArrayView3<u8>) -> PyResult<ImageBuffer<Rgba<u8>, Vec<u8>>> {
+    let shape = arr.shape();
+    if shape[2] != 4 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+            "image must have shape [H, W, 4]",
+        ));
     }
+    Ok(ImageBuffer::from_raw(
+        shape[1] as u32,
+        shape[0] as u32,
+        arr.iter().copied().collect(),
+    )
+    .unwrap())
 }
@@ -0,0 +1,33 @@
+use std::sync::{Arc, Mutex};
+
+/// asynchronous mapping of a readback buffer, so the render loop never waits for the GPU
+#[derive(Default)]
+pub(crate) struct BufferMapping {
+    /// a buffer is being mapped
+    mapping: bool,
+    /// result of mapping the buffer, set by the callback once it is done
+    result: Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>,
+}
+
+impl BufferMapping {
+    /// starts mapping `buffer` for reading. Does nothing while the last mapping is not done.
+    pub fn map(&mut self, buffer: &wgpu::Buffer) {
+        if self.mapping {
+            return;
+        }
+        self.mapping = true;
+        let result = self.result.clone();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |r| {
+            *result.lock().unwrap() = Some(r);
+        });
+    }
+
+    /// polls the device and returns the result of the mapping once it is done.
+    /// The buffer has to be unmapped after reading it.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<Result<(), wgpu::BufferAsyncError>> {
+        device.poll(wgpu::Maintain::Poll);
+        let result = self.result.lock().unwrap().take()?;
+        self.mapping = false;
+        Some(result)
+    }
+}
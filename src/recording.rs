@@ -0,0 +1,301 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+use crate::{
+    camera::{Camera, OrthographicProjection},
+    offline::OutputPreset,
+};
+use cgmath::Vector2;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, ImageBuffer, Rgba,
+};
+
+/// container (and codec) of recorded videos
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// H.264
+    Mp4,
+    /// VP9
+    WebM,
+    /// looping animated GIF, encoded without ffmpeg
+    Gif,
+    /// looping animated PNG, encoded without ffmpeg. Lossless and with transparency
+    Apng,
+}
+
+impl VideoFormat {
+    pub const ALL: [VideoFormat; 4] = [
+        VideoFormat::Mp4,
+        VideoFormat::WebM,
+        VideoFormat::Gif,
+        VideoFormat::Apng,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VideoFormat::Mp4 => "MP4",
+            VideoFormat::WebM => "WebM",
+            VideoFormat::Gif => "GIF",
+            VideoFormat::Apng => "APNG",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VideoFormat::Mp4 => "mp4",
+            VideoFormat::WebM => "webm",
+            VideoFormat::Gif => "gif",
+            // .png is written as numbered images
+            VideoFormat::Apng => "apng",
+        }
+    }
+
+    /// encoded by ffmpeg, which has to be installed
+    pub fn needs_ffmpeg(&self) -> bool {
+        matches!(self, VideoFormat::Mp4 | VideoFormat::WebM)
+    }
+
+    /// keeps a transparent background instead of compositing over black
+    pub fn has_alpha(&self) -> bool {
+        *self == VideoFormat::Apng
+    }
+
+    /// format of a video file by its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|format| format.extension() == extension)
+    }
+
+    fn codec_args(&self) -> &'static [&'static str] {
+        match self {
+            VideoFormat::Mp4 => &["-c:v", "libx264", "-crf", "18"],
+            VideoFormat::WebM => &["-c:v", "libvpx-vp9", "-crf", "30", "-b:v", "0"],
+            VideoFormat::Gif | VideoFormat::Apng => &[],
+        }
+    }
+}
+
+/// writes the frames of a recording
+enum Encoder {
+    /// raw frames piped into ffmpeg
+    Ffmpeg {
+        process: Child,
+        stdin: Option<ChildStdin>,
+    },
+    Gif(Box<GifEncoder<BufWriter<File>>>),
+    Apng {
+        writer: png::Writer<BufWriter<File>>,
+        /// last frame, repeated if the recording is stopped early
+        last_frame: Vec<u8>,
+    },
+}
+
+/// resolution and frame rate of recorded videos
+#[derive(Debug, Clone, Copy)]
+pub struct RecordingSettings {
+    pub format: VideoFormat,
+    /// width and height in pixels, rounded down to even numbers for ffmpeg
+    pub resolution: Vector2<u32>,
+    pub fps: u32,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self {
+            format: VideoFormat::Mp4,
+            resolution: Vector2::new(1920, 1080),
+            fps: 30,
+        }
+    }
+}
+
+/// one loop of the time animation, rendered frame by frame and piped into `ffmpeg`
+/// (which has to be installed) or encoded as GIF or APNG.
+/// The frames are rendered at fixed time steps, so the video does not depend on the
+/// refresh rate of the display or how long a frame takes to render.
+pub struct Recording {
+    encoder: Encoder,
+    path: PathBuf,
+    resolution: Vector2<u32>,
+    fps: u32,
+    num_frames: usize,
+    frame: usize,
+}
+
+impl Recording {
+    /// starts encoding a video of `duration` seconds to `path`
+    pub fn start(path: &Path, settings: &RecordingSettings, duration: f32) -> anyhow::Result<Self> {
+        let resolution = if settings.format.needs_ffmpeg() {
+            settings.resolution.map(|v| (v & !1).max(2))
+        } else {
+            settings.resolution.map(|v| v.max(1))
+        };
+        let fps = settings.fps.max(1);
+        let num_frames = ((duration * fps as f32).round() as usize).max(1);
+        let encoder = match settings.format {
+            VideoFormat::Gif => Encoder::Gif(Box::new(gif_encoder(path)?)),
+            VideoFormat::Apng => Encoder::Apng {
+                writer: apng_encoder(path, resolution, fps, num_frames)?,
+                last_frame: Vec::new(),
+            },
+            _ => Self::spawn_ffmpeg(path, settings.format, resolution, fps)?,
+        };
+        Ok(Self {
+            encoder,
+            path: path.to_path_buf(),
+            resolution,
+            fps,
+            num_frames,
+            frame: 0,
+        })
+    }
+
+    fn spawn_ffmpeg(
+        path: &Path,
+        format: VideoFormat,
+        resolution: Vector2<u32>,
+        fps: u32,
+    ) -> anyhow::Result<Encoder> {
+        let mut process = Command::new("ffmpeg")
+            .args([
+                "-loglevel",
+                "error",
+                "-y",
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "rgba",
+            ])
+            .args(["-s", &format!("{}x{}", resolution.x, resolution.y)])
+            .args(["-r", &fps.to_string(), "-i", "-"])
+            .args(format.codec_args())
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| anyhow::anyhow!("failed to run ffmpeg (is it installed?): {err}"))?;
+        let stdin = process.stdin.take();
+        Ok(Encoder::Ffmpeg { process, stdin })
+    }
+
+    pub fn resolution(&self) -> Vector2<u32> {
+        self.resolution
+    }
+
+    /// animation time (0 to 1) of the next frame
+    pub fn time(&self) -> f32 {
+        self.frame as f32 / self.num_frames as f32
+    }
+
+    /// recorded and total number of frames
+    pub fn progress(&self) -> (usize, usize) {
+        (self.frame, self.num_frames)
+    }
+
+    pub fn done(&self) -> bool {
+        self.frame >= self.num_frames
+    }
+
+    /// encodes the next frame, which has to have the resolution of the recording
+    pub fn add_frame(&mut self, image: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            image.dimensions() == self.resolution.into(),
+            "frame size {:?} does not match the video size",
+            image.dimensions()
+        );
+        match &mut self.encoder {
+            Encoder::Ffmpeg { stdin, .. } => stdin
+                .as_mut()
+                .ok_or_else(|| anyhow::anyhow!("the recording is finished"))?
+                .write_all(image.as_raw())
+                .map_err(|err| anyhow::anyhow!("failed to write frame to ffmpeg: {err}"))?,
+            Encoder::Gif(encoder) => encoder.encode_frame(Frame::from_parts(
+                image.clone(),
+                0,
+                0,
+                Delay::from_numer_denom_ms(1000, self.fps),
+            ))?,
+            Encoder::Apng { writer, last_frame } => {
+                writer.write_image_data(image.as_raw())?;
+                last_frame.clone_from(image.as_raw());
+            }
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// waits until the video is written and returns its path
+    pub fn finish(self) -> anyhow::Result<PathBuf> {
+        match self.encoder {
+            Encoder::Ffmpeg { mut process, stdin } => {
+                // closing stdin ends the video
+                drop(stdin);
+                let status = process.wait()?;
+                anyhow::ensure!(status.success(), "ffmpeg failed with {status}");
+            }
+            // the trailer is written when the encoder is dropped
+            Encoder::Gif(encoder) => drop(encoder),
+            Encoder::Apng {
+                mut writer,
+                last_frame,
+            } => {
+                anyhow::ensure!(self.frame > 0, "no frames were recorded");
+                // the number of frames is fixed in the header, so a stopped recording
+                // ends with the last frame
+                for _ in self.frame..self.num_frames {
+                    writer.write_image_data(&last_frame)?;
+                }
+                writer.finish()?;
+            }
+        }
+        Ok(self.path)
+    }
+}
+
+fn gif_encoder(path: &Path) -> anyhow::Result<GifEncoder<BufWriter<File>>> {
+    let file = BufWriter::new(File::create(path)?);
+    // better colors than the default speed, still fast enough for long animations
+    let mut encoder = GifEncoder::new_with_speed(file, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    Ok(encoder)
+}
+
+/// APNG of `num_frames` frames, which have to be known before the first frame is written
+fn apng_encoder(
+    path: &Path,
+    resolution: Vector2<u32>,
+    fps: u32,
+    num_frames: usize,
+) -> anyhow::Result<png::Writer<BufWriter<File>>> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, resolution.x, resolution.y);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    // 0 plays repeat forever
+    encoder.set_animated(num_frames as u32, 0)?;
+    encoder.set_frame_delay(1, fps.min(u16::MAX as u32) as u16)?;
+    Ok(encoder.write_header()?)
+}
+
+/// video recording of the viewer
+#[derive(Default)]
+pub struct Recorder {
+    pub settings: RecordingSettings,
+    /// output preset the resolution was chosen from,
+    /// it also sets the supersampling and the scale of the annotations
+    pub preset: Option<OutputPreset>,
+    /// orbits the camera once around the volume during the recording
+    pub turntable: bool,
+    pub recording: Option<Recording>,
+    /// camera when the turntable recording started, restored when it ends
+    pub turntable_start: Option<Camera<OrthographicProjection>>,
+    /// outcome of the last recording, shown in the ui
+    pub status: Option<String>,
+}
@@ -0,0 +1,613 @@
+use cgmath::{ElementWise, EuclideanSpace, InnerSpace, Matrix4, Point3, Vector2, Vector3, Vector4};
+use half::f16;
+use image::{ImageBuffer, Rgba};
+
+use crate::{
+    camera::{Camera, Projection},
+    cmap::{ColorMap, COLORMAP_RESOLUTION},
+    offline::linear_to_srgb,
+    renderer::{CameraUniform, Interpolation, RenderSettings, MAX_CONTOURS},
+    volume::{Aabb, Volume},
+};
+
+/// Slow CPU implementation of the raymarching in `raymarch.wgsl`.
+/// Follows the shader step by step (including texture filtering) so its output can be
+/// used to validate the GPU renderer. Also used when no GPU adapter is available.
+pub fn render_view<P: Projection>(
+    volume: &Volume,
+    cmap: impl ColorMap,
+    camera: &Camera<P>,
+    settings: &RenderSettings,
+    bg: wgpu::Color,
+    resolution: Vector2<u32>,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let tracer = Tracer::new(volume, cmap, camera, settings);
+    let pixels = trace_pixels(resolution, |tex_coord| {
+        let color = tracer.shade(tex_coord);
+        // premultiplied alpha blending onto the background
+        let bg = [bg.r, bg.g, bg.b, bg.a].map(|c| c as f32);
+        std::array::from_fn(|c| {
+            let mut v = color[c] + (1. - color.w) * bg[c];
+            if c < 3 {
+                v = linear_to_srgb(v.clamp(0., 1.));
+            }
+            (v.clamp(0., 1.) * 255.).round() as u8
+        })
+    });
+    ImageBuffer::from_raw(resolution.x, resolution.y, pixels).unwrap()
+}
+
+/// like [render_view], but renders the auxiliary outputs of `fs_aov`: the world space normal
+/// (rgb) and the depth along the view direction (alpha) of the sample seen at each pixel
+/// (see [pick]), zero if nothing is seen
+pub fn render_aov_view<P: Projection>(
+    volume: &Volume,
+    cmap: impl ColorMap,
+    camera: &Camera<P>,
+    settings: &RenderSettings,
+    resolution: Vector2<u32>,
+) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
+    let tracer = Tracer::new(volume, cmap, camera, settings);
+    let pixels = trace_pixels(resolution, |tex_coord| tracer.aov(tex_coord));
+    ImageBuffer::from_raw(resolution.x, resolution.y, pixels).unwrap()
+}
+
+/// calls `shade` with the texture coordinate of every pixel, spread over all cores
+#[cfg(not(target_arch = "wasm32"))]
+fn trace_pixels<T: Copy + Default + Send>(
+    resolution: Vector2<u32>,
+    shade: impl Fn(Vector2<f32>) -> [T; 4] + Sync,
+) -> Vec<T> {
+    let (width, height) = (resolution.x as usize, resolution.y as usize);
+    let mut pixels = vec![T::default(); width * height * 4];
+
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let rows_per_thread = height.div_ceil(threads).max(1);
+    std::thread::scope(|s| {
+        for (chunk_i, chunk) in pixels.chunks_mut(rows_per_thread * width * 4).enumerate() {
+            let shade = &shade;
+            s.spawn(move || {
+                for (i, pixel) in chunk.chunks_exact_mut(4).enumerate() {
+                    let y = chunk_i * rows_per_thread + i / width;
+                    pixel.copy_from_slice(&shade(pixel_coord(i % width, y, width, height)));
+                }
+            });
+        }
+    });
+    pixels
+}
+
+/// calls `shade` with the texture coordinate of every pixel, one after the other
+/// (threads can not be spawned in the browser)
+#[cfg(target_arch = "wasm32")]
+fn trace_pixels<T: Copy + Default + Send>(
+    resolution: Vector2<u32>,
+    shade: impl Fn(Vector2<f32>) -> [T; 4] + Sync,
+) -> Vec<T> {
+    let (width, height) = (resolution.x as usize, resolution.y as usize);
+    let mut pixels = vec![T::default(); width * height * 4];
+    for (i, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+        pixel.copy_from_slice(&shade(pixel_coord(i % width, i / width, width, height)));
+    }
+    pixels
+}
+
+/// texture coordinate of the center of pixel (`x`, `y`)
+fn pixel_coord(x: usize, y: usize, width: usize, height: usize) -> Vector2<f32> {
+    Vector2::new(
+        (x as f32 + 0.5) / width as f32,
+        (y as f32 + 0.5) / height as f32,
+    )
+}
+
+/// value of the volume that is seen at `tex_coord` (in [0, 1], y pointing down) of the view:
+/// the sample at which the accumulated opacity exceeds the depth threshold
+/// (see [ShaderConstants](crate::renderer::ShaderConstants)) or else the most opaque sample.
+/// None if the ray misses the volume or everything along it is transparent.
+pub fn pick_value<P: Projection>(
+    volume: &Volume,
+    cmap: impl ColorMap,
+    camera: &Camera<P>,
+    settings: &RenderSettings,
+    tex_coord: Vector2<f32>,
+) -> Option<f32> {
+    pick(volume, cmap, camera, settings, tex_coord).map(|hit| hit.value)
+}
+
+/// sample of the volume seen at a pixel, see [pick]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// world space position of the sample
+    pub position: Point3<f32>,
+    /// interpolated value at the position
+    pub value: f32,
+}
+
+/// like [pick_value], but also returns where the ray hits
+pub fn pick<P: Projection>(
+    volume: &Volume,
+    cmap: impl ColorMap,
+    camera: &Camera<P>,
+    settings: &RenderSettings,
+    tex_coord: Vector2<f32>,
+) -> Option<RayHit> {
+    let tracer = Tracer::new(volume, cmap, camera, settings);
+    let (orig, dir) = tracer.create_ray(Vector2::new(tex_coord.x, 1. - tex_coord.y));
+    tracer.trace_ray(orig, dir).1
+}
+
+/// the uniforms and textures of a single draw call
+struct Tracer<'a> {
+    camera: CameraUniform,
+    /// current, next, previous and second next timestep
+    steps: [&'a [f16]; 4],
+    /// values of the volume's mask
+    mask: Option<&'a [f16]>,
+    /// texture size (width, height, depth)
+    size: Vector3<usize>,
+    lut: Vec<Vector4<f32>>,
+    settings: &'a RenderSettings,
+    aabb: Aabb<f32>,
+    clipping: Aabb<f32>,
+    time_steps: u32,
+    vmin: f32,
+    vmax: f32,
+}
+
+impl<'a> Tracer<'a> {
+    fn new<P: Projection>(
+        volume: &'a Volume,
+        cmap: impl ColorMap,
+        camera: &Camera<P>,
+        settings: &'a RenderSettings,
+    ) -> Self {
+        let step = ((volume.timesteps - 1) as f32 * settings.time) as usize;
+        let last_step = volume.timesteps as usize - 1;
+        let steps = [
+            step,
+            (step + 1) % volume.timesteps as usize,
+            step.saturating_sub(1),
+            (step + 2).min(last_step),
+        ]
+        .map(|i| volume.timestep(i));
+        let (vmin, vmax) = settings.value_range(volume);
+        Self {
+            camera: CameraUniform::from(camera),
+            steps,
+            mask: volume.mask().map(|mask| mask.data()),
+            size: Vector3::new(
+                volume.resolution[2] as usize,
+                volume.resolution[1] as usize,
+                volume.resolution[0] as usize,
+            ),
+            lut: cmap
+                .rasterize(COLORMAP_RESOLUTION as usize)
+                .into_iter()
+                .map(|c| c.cast::<f32>().unwrap() / 255.)
+                .collect(),
+            settings,
+            aabb: volume.aabb,
+            clipping: settings.clipping_aabb.unwrap_or(Aabb {
+                min: Point3::new(0., 0., 0.),
+                max: Point3::new(1., 1., 1.),
+            }),
+            time_steps: volume.timesteps,
+            vmin,
+            vmax,
+        }
+    }
+
+    fn shade(&self, tex_coord: Vector2<f32>) -> Vector4<f32> {
+        let r_pos = Vector2::new(tex_coord.x, 1. - tex_coord.y) + self.settings.jitter;
+        let (orig, dir) = self.create_ray(r_pos);
+        let (color, _) = self.trace_ray(orig, dir);
+        if self.settings.gamma_correction {
+            let c = color.truncate().map(linear_to_srgb);
+            c.extend(color.w)
+        } else {
+            color
+        }
+    }
+
+    /// normal and depth of the sample seen at `tex_coord`, see [render_aov_view]
+    fn aov(&self, tex_coord: Vector2<f32>) -> [f32; 4] {
+        let r_pos = Vector2::new(tex_coord.x, 1. - tex_coord.y) + self.settings.jitter;
+        let (orig, dir) = self.create_ray(r_pos);
+        let Some(hit) = self.trace_ray(orig, dir).1 else {
+            return [0.; 4];
+        };
+        let aabb_size = self.aabb.max - self.aabb.min;
+        let gradient =
+            self.sample_gradient((hit.position - self.aabb.min).div_element_wise(aabb_size));
+        let grad_mag = gradient.magnitude();
+        let mut normal = Vector3::new(0., 0., 0.);
+        if grad_mag > 1e-6 && grad_mag.is_finite() {
+            normal = gradient
+                .mul_element_wise(self.size.cast::<f32>().unwrap())
+                .div_element_wise(aabb_size)
+                .normalize();
+            if normal.dot(dir) > 0. {
+                normal = -normal;
+            }
+        }
+        let depth = (self.camera.view_matrix * hit.position.to_homogeneous())
+            .z
+            .abs();
+        [normal.x, normal.y, normal.z, depth]
+    }
+
+    fn create_ray(&self, px: Vector2<f32>) -> (Point3<f32>, Vector3<f32>) {
+        let inv: Matrix4<f32> = self.camera.view_inv_matrix * self.camera.proj_inv_matrix;
+        let unproject = |z: f32| {
+            let p = inv * Vector4::new(px.x * 2. - 1., -(px.y * 2. - 1.), z, 1.);
+            p.truncate() / (p.w + 1e-4)
+        };
+        let near = unproject(0.);
+        let far = unproject(1.);
+        (Point3::from_vec(near), (far - near).normalize())
+    }
+
+    /// color along the ray and the sample at which it counts as hit (see [pick_value])
+    fn trace_ray(&self, orig: Point3<f32>, dir: Vector3<f32>) -> (Vector4<f32>, Option<RayHit>) {
+        let aabb_size = self.aabb.max - self.aabb.min;
+        let slice_min = self.clipping.min.to_vec();
+        let slice_max = self.clipping.max.to_vec();
+        let box_min = self.aabb.min + slice_min.mul_element_wise(aabb_size);
+        let box_max =
+            self.aabb.max - (Vector3::new(1., 1., 1.) - slice_max).mul_element_wise(aabb_size);
+
+        let t_min = (box_min - orig).div_element_wise(dir);
+        let t_max = (box_max - orig).div_element_wise(dir);
+        let t1 = t_min.zip(t_max, f32::min);
+        let t2 = t_min.zip(t_max, f32::max);
+        let t_near = t1.x.max(t1.y).max(t1.z);
+        let t_far = t2.x.min(t2.y).min(t2.z);
+        if t_near > t_far {
+            return (Vector4::new(0., 0., 0., 0.), None);
+        }
+
+        let settings = self.settings;
+        let constants = &settings.constants;
+        let start =
+            t_near.max(0.) + constants.ray_epsilon + settings.ray_offset * settings.step_size;
+        let mut pos = orig + dir * start;
+        let mut color = Vector3::new(0., 0., 0.);
+        let mut transmittance = 1.;
+        let mut last_sample = 0.;
+        let mut last_pos = pos;
+        let size = self.size.cast::<f32>().unwrap();
+        let mut hit = None;
+        // most opaque sample (value, opacity) in case the ray is never hit
+        let mut most_opaque = (None, 0.);
+
+        for iters in 0.. {
+            let sample_pos = (pos - self.aabb.min).div_element_wise(aabb_size);
+            let world_pos = pos;
+            pos += dir * settings.step_size;
+
+            let sample = self.sample_volume(sample_pos);
+            let mask_value = self.sample_mask(sample_pos);
+
+            // surfaces where the value crosses a contour value between two samples
+            for (value, contour) in settings.contours.iter().take(MAX_CONTOURS) {
+                if iters > 0 && (last_sample - value) * (sample - value) < 0. {
+                    let contour_a = contour.w * mask_value;
+                    color += transmittance * contour_a * contour.truncate();
+                    transmittance *= 1. - contour_a;
+                    // the surface lies where the value crosses between the two samples
+                    let f = (value - last_sample) / (sample - last_sample);
+                    let contour_hit = RayHit {
+                        position: last_pos + (world_pos - last_pos) * f,
+                        value: *value,
+                    };
+                    if contour_a > most_opaque.1 {
+                        most_opaque = (Some(contour_hit), contour_a);
+                    }
+                    if hit.is_none() && 1. - transmittance >= constants.depth_opacity_threshold {
+                        hit = Some(contour_hit);
+                    }
+                }
+            }
+            last_sample = sample;
+            last_pos = world_pos;
+
+            let color_tf = self.sample_color(sample);
+            let mut sigma = color_tf.w * mask_value * (1. - 1e-6);
+
+            if sigma > 0. {
+                let mut sample_color = color_tf.truncate();
+                if settings.boundary_enhancement > 0. || settings.silhouette > 0. {
+                    let gradient = self.sample_gradient(sample_pos);
+                    let grad_mag = Some(gradient.magnitude())
+                        .filter(|m| m.is_finite())
+                        .unwrap_or(0.);
+                    let boundary = (grad_mag / constants.boundary_saturation).min(1.);
+                    sigma *= mix(1., boundary, settings.boundary_enhancement);
+                    if grad_mag > 1e-6 {
+                        let normal = gradient
+                            .mul_element_wise(size)
+                            .div_element_wise(aabb_size)
+                            .normalize();
+                        let edge = (1. - normal.dot(dir).abs()).powf(constants.silhouette_exponent);
+                        sigma *= mix(1., edge, settings.silhouette);
+                        sample_color *= 1. - settings.silhouette * edge;
+                    }
+                }
+                let a_i = 1. - (1. - sigma).powf(settings.step_size * settings.distance_scale);
+                color += transmittance * a_i * sample_color;
+                transmittance *= 1. - a_i;
+                let sample_hit = RayHit {
+                    position: world_pos,
+                    value: sample,
+                };
+                if a_i > most_opaque.1 {
+                    most_opaque = (Some(sample_hit), a_i);
+                }
+                if hit.is_none() && 1. - transmittance >= constants.depth_opacity_threshold {
+                    hit = Some(sample_hit);
+                }
+                if transmittance <= constants.early_stopping_threshold {
+                    break;
+                }
+            }
+            let outside =
+                (0..3).any(|i| sample_pos[i] < slice_min[i] || sample_pos[i] > slice_max[i]);
+            if outside || iters > constants.max_steps {
+                break;
+            }
+        }
+        (color.extend(1. - transmittance), hit.or(most_opaque.0))
+    }
+
+    fn sample_volume(&self, pos: Vector3<f32>) -> f32 {
+        let pos_m = Vector3::new(pos.x, 1. - pos.y, pos.z);
+        let sample_curr = self.sample_texture(self.steps[0], pos_m);
+        let sample_next = self.sample_texture(self.steps[1], pos_m);
+        if self.settings.temporal_filter == Interpolation::Nearest {
+            return sample_curr;
+        }
+        let time_fraction = (self.settings.time * (self.time_steps - 1) as f32).fract();
+        if self.settings.temporal_filter == Interpolation::Cubic {
+            let sample_prev = self.sample_texture(self.steps[2], pos_m);
+            let sample_next2 = self.sample_texture(self.steps[3], pos_m);
+            return catmull_rom(
+                sample_prev,
+                sample_curr,
+                sample_next,
+                sample_next2,
+                time_fraction,
+            );
+        }
+        mix(sample_curr, sample_next, time_fraction)
+    }
+
+    fn sample_mask(&self, pos: Vector3<f32>) -> f32 {
+        let Some(mask) = self.mask else {
+            return 1.;
+        };
+        let pos_m = Vector3::new(pos.x, 1. - pos.y, pos.z);
+        // the mask is sampled with the volume sampler, so cubic filtering is linear
+        let m = if self.settings.spatial_filter == Interpolation::Nearest {
+            self.sample_nearest(mask, pos_m)
+        } else {
+            self.sample_linear(mask, pos_m)
+        };
+        if self.settings.invert_mask {
+            1. - m
+        } else {
+            m
+        }
+    }
+
+    fn sample_texture(&self, data: &[f16], pos: Vector3<f32>) -> f32 {
+        match self.settings.spatial_filter {
+            Interpolation::Nearest => self.sample_nearest(data, pos),
+            Interpolation::Linear => self.sample_linear(data, pos),
+            Interpolation::Cubic => self.sample_tricubic(data, pos),
+        }
+    }
+
+    fn texel(&self, data: &[f16], x: isize, y: isize, z: isize) -> f32 {
+        // clamp to edge
+        let x = x.clamp(0, self.size.x as isize - 1) as usize;
+        let y = y.clamp(0, self.size.y as isize - 1) as usize;
+        let z = z.clamp(0, self.size.z as isize - 1) as usize;
+        data[(z * self.size.y + y) * self.size.x + x].to_f32()
+    }
+
+    fn sample_nearest(&self, data: &[f16], pos: Vector3<f32>) -> f32 {
+        let coord = pos.mul_element_wise(self.size.cast::<f32>().unwrap());
+        self.texel(
+            data,
+            coord.x.floor() as isize,
+            coord.y.floor() as isize,
+            coord.z.floor() as isize,
+        )
+    }
+
+    fn sample_linear(&self, data: &[f16], pos: Vector3<f32>) -> f32 {
+        let coord =
+            pos.mul_element_wise(self.size.cast::<f32>().unwrap()) - Vector3::new(0.5, 0.5, 0.5);
+        let index = coord.map(f32::floor);
+        let f = coord - index;
+        let (x, y, z) = (index.x as isize, index.y as isize, index.z as isize);
+        let lerp_x = |y, z| {
+            mix(
+                self.texel(data, x, y, z),
+                self.texel(data, x + 1, y, z),
+                f.x,
+            )
+        };
+        let lerp_y = |z| mix(lerp_x(y, z), lerp_x(y + 1, z), f.y);
+        mix(lerp_y(z), lerp_y(z + 1), f.z)
+    }
+
+    // same 8 trilinear lookups as the shader
+    fn sample_tricubic(&self, data: &[f16], pos: Vector3<f32>) -> f32 {
+        let size = self.size.cast::<f32>().unwrap();
+        let coord = pos.mul_element_wise(size) - Vector3::new(0.5, 0.5, 0.5);
+        let index = coord.map(f32::floor);
+        let f = coord - index;
+
+        let w0 = f.map(|f| (1. - 3. * f + 3. * f * f - f * f * f) / 6.);
+        let w1 = f.map(|f| (4. - 6. * f * f + 3. * f * f * f) / 6.);
+        let w2 = f.map(|f| (1. + 3. * f + 3. * f * f - 3. * f * f * f) / 6.);
+        let w3 = f.map(|f| f * f * f / 6.);
+
+        let g0 = w0 + w1;
+        let g1 = w2 + w3;
+        let h0 =
+            (index - Vector3::new(0.5, 0.5, 0.5) + w1.div_element_wise(g0)).div_element_wise(size);
+        let h1 =
+            (index + Vector3::new(1.5, 1.5, 1.5) + w3.div_element_wise(g1)).div_element_wise(size);
+
+        let s = |x: f32, y: f32, z: f32| self.sample_linear(data, Vector3::new(x, y, z));
+        let s00 = g0.x * s(h0.x, h0.y, h0.z) + g1.x * s(h1.x, h0.y, h0.z);
+        let s10 = g0.x * s(h0.x, h1.y, h0.z) + g1.x * s(h1.x, h1.y, h0.z);
+        let s01 = g0.x * s(h0.x, h0.y, h1.z) + g1.x * s(h1.x, h0.y, h1.z);
+        let s11 = g0.x * s(h0.x, h1.y, h1.z) + g1.x * s(h1.x, h1.y, h1.z);
+        g0.z * (g0.y * s00 + g1.y * s10) + g1.z * (g0.y * s01 + g1.y * s11)
+    }
+
+    fn sample_gradient(&self, pos: Vector3<f32>) -> Vector3<f32> {
+        let step = self.settings.constants.gradient_step;
+        let h = self.size.cast::<f32>().unwrap().map(|s| step / s);
+        let diff = |d: Vector3<f32>| self.sample_volume(pos + d) - self.sample_volume(pos - d);
+        Vector3::new(
+            diff(Vector3::new(h.x, 0., 0.)),
+            diff(Vector3::new(0., h.y, 0.)),
+            diff(Vector3::new(0., 0., h.z)),
+        ) / (2. * (self.vmax - self.vmin))
+    }
+
+    fn sample_color(&self, value: f32) -> Vector4<f32> {
+        let [nan_style, below_style, above_style] = self.settings.value_styles();
+        if !value.is_finite() {
+            nan_style.color(Vector4::new(0., 0., 0., 0.))
+        } else if value < self.vmin {
+            below_style.color(self.sample_cmap(value))
+        } else if value > self.vmax {
+            above_style.color(self.sample_cmap(value))
+        } else {
+            self.sample_cmap(value)
+        }
+    }
+
+    fn sample_cmap(&self, value: f32) -> Vector4<f32> {
+        let value_n = self
+            .settings
+            .normalization
+            .normalize(value, self.vmin, self.vmax);
+        let coord = value_n * self.lut.len() as f32 - 0.5;
+        let index = coord.floor();
+        let f = coord - index;
+        let texel = |i: f32| self.lut[(i.max(0.) as usize).min(self.lut.len() - 1)];
+        texel(index) * (1. - f) + texel(index + 1.) * f
+    }
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1. - t) + b * t
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2. * p1
+        + (p2 - p0) * t
+        + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2
+        + (3. * (p1 - p2) + p3 - p0) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use cgmath::Vector2;
+    use image::Rgba;
+
+    use super::render_view;
+    use crate::{
+        cmap::{ColorMapSequence, GenericColorMap, LinearSegmentedColorMap, COLORMAP_RESOLUTION},
+        offline::{view_camera, OfflineRenderer, OfflineSettings},
+        stereo::Stereo,
+        volume::Volume,
+        WGPUContext,
+    };
+
+    /// gaussian blob in the center of a 16³ volume
+    fn blob() -> Volume {
+        let n = 16;
+        let data: Vec<f32> = (0..n * n * n)
+            .map(|i| {
+                let distance_sq: f32 = [i % n, i / n % n, i / (n * n)]
+                    .iter()
+                    .map(|c| (*c as f32 + 0.5) / n as f32 - 0.5)
+                    .map(|c| c * c)
+                    .sum();
+                (-16. * distance_sq).exp()
+            })
+            .collect();
+        let mut buffer = Vec::new();
+        let mut writer = npyz::WriteOptions::new()
+            .default_dtype()
+            .shape(&[1, 1, n as u64, n as u64, n as u64])
+            .writer(&mut buffer)
+            .begin_nd()
+            .unwrap();
+        writer.extend(data).unwrap();
+        writer.finish().unwrap();
+        Volume::load_npy(Cursor::new(buffer), true)
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter, run with --ignored"]
+    fn gpu_matches_reference() {
+        pollster::block_on(async {
+            // the offline renderer would silently fall back to this renderer otherwise
+            let _context = WGPUContext::shared().await.expect("no GPU adapter");
+            let cmap = GenericColorMap::LinearSegmented(
+                LinearSegmentedColorMap::from_hex_list("#000000,#ff8000,#ffffff").unwrap(),
+            );
+            let settings = OfflineSettings::new(Vector2::new(64, 64));
+
+            let renderer = OfflineRenderer::new(vec![blob()], cmap.clone()).await;
+            let gpu = renderer
+                .render::<Rgba<u8>>(0., &settings, None, &Stereo::default())
+                .await
+                .unwrap();
+
+            // the same colormap, camera and settings as the GPU renderer
+            let volume = blob();
+            let cmap = ColorMapSequence::from(cmap).at(0., COLORMAP_RESOLUTION);
+            let camera = view_camera(&volume.aabb, &settings.view, settings.resolution);
+            let cpu = render_view(
+                &volume,
+                &cmap,
+                &camera,
+                &settings.settings,
+                settings.background,
+                settings.resolution,
+            );
+
+            let errors: Vec<u8> = gpu
+                .as_raw()
+                .iter()
+                .zip(cpu.as_raw())
+                .map(|(a, b)| a.abs_diff(*b))
+                .collect();
+            let mean = errors.iter().map(|e| *e as f32).sum::<f32>() / errors.len() as f32;
+            let outliers = errors.iter().filter(|e| **e > 16).count();
+            assert!(mean < 2., "mean error {mean} too large");
+            assert!(
+                outliers * 100 < errors.len(),
+                "{outliers} of {} channels differ by more than 16",
+                errors.len()
+            );
+        });
+    }
+}
@@ -1,65 +1,77 @@
 use crate::{
+    annotations::Colorbar,
     camera::{Camera, Projection, VIEWPORT_Y_FLIP},
     cmap::ColorMapGPU,
+    session::SessionRender,
     volume::{Aabb, Volume, VolumeGPU},
 };
 
-use cgmath::{EuclideanSpace, Matrix4, SquareMatrix, Vector4, Zero};
+use cgmath::{EuclideanSpace, Matrix4, SquareMatrix, Vector2, Vector4, Zero};
+use std::{collections::HashMap, sync::Arc};
 use wgpu::util::DeviceExt;
 
+/// number of bind groups kept around for reuse (e.g. one per channel in the grid view)
+const MAX_CACHED_BIND_GROUPS: usize = 64;
+
+/// number of pipelines with different shader constants kept around
+const MAX_CACHED_PIPELINES: usize = 4;
+
 pub struct VolumeRenderer {
-    pipeline: wgpu::RenderPipeline,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    depth_format: Option<wgpu::TextureFormat>,
+    /// renders the auxiliary outputs (normal and depth) instead of colors, see [VolumeRenderer::aov]
+    aov: bool,
+    /// pipelines for recently used shader constants, least recently used first
+    pipelines: Vec<(ShaderConstants, Arc<wgpu::RenderPipeline>)>,
     sampler_nearest: wgpu::Sampler,
     sampler_linear: wgpu::Sampler,
     format: wgpu::TextureFormat,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// recently used bind groups, least recently used first.
+    /// Uniforms are only uploaded if no bind group with the same content exists.
+    bind_groups: Vec<(BindGroupKey, Arc<wgpu::BindGroup>)>,
+    /// bound in place of the scene depth if there is no user geometry
+    empty_depth: wgpu::TextureView,
 }
 
 impl VolumeRenderer {
     pub fn new(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Self {
+        Self::with_depth(device, color_format, None)
+    }
+
+    /// with a depth format the renderer writes the depth of the first hit
+    /// (or of the user geometry if nothing was hit) into the depth attachment
+    pub fn with_depth(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) -> Self {
+        Self::create(device, color_format, depth_format, false)
+    }
+
+    /// renderer of the auxiliary outputs of offline renders into a float target: the world
+    /// space normal (rgb) and the depth along the view direction (alpha) of the sample seen
+    /// at each pixel, zero if nothing is seen
+    pub fn aov(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        Self::create(device, format, None, true)
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        aov: bool,
+    ) -> Self {
+        let bind_group_layout = Self::bind_group_layout(device);
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("render pipeline layout"),
-            bind_group_layouts: &[
-                &Self::bind_group_layout(device),
-                &ColorMapGPU::bind_group_layout(device),
-            ],
+            bind_group_layouts: &[&bind_group_layout, &ColorMapGPU::bind_group_layout(device)],
             push_constant_ranges: &[],
         });
 
         let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/raymarch.wgsl"));
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("volume render pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: color_format,
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-        });
-
         let sampler_linear = device.create_sampler(&wgpu::SamplerDescriptor {
             label: Some("volume sampler"),
             mag_filter: wgpu::FilterMode::Linear,
@@ -74,10 +86,32 @@ impl VolumeRenderer {
         });
 
         VolumeRenderer {
-            pipeline,
+            shader,
+            pipeline_layout,
+            depth_format,
+            aov,
+            pipelines: Vec::new(),
             sampler_nearest,
             sampler_linear,
             format: color_format,
+            bind_group_layout,
+            bind_groups: Vec::new(),
+            empty_depth: device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("empty depth texture"),
+                    size: wgpu::Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Depth32Float,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default()),
         }
     }
 
@@ -89,43 +123,187 @@ impl VolumeRenderer {
         render_settings: &RenderSettings,
         cmap: &'a ColorMapGPU,
     ) -> PerFrameData<'a> {
+        self.prepare_with_scene_depth(device, volume, camera, render_settings, cmap, None)
+    }
+
+    /// like `prepare`, but rays stop at the depth of opaque user geometry.
+    /// The geometry has to be rendered with `Camera::view_proj_matrix` into a depth texture
+    /// of the same size as the render target before the volume is rendered on top of it.
+    pub fn prepare_with_scene_depth<'a, P: Projection>(
+        &mut self,
+        device: &wgpu::Device,
+        volume: &VolumeGPU,
+        camera: &Camera<P>,
+        render_settings: &RenderSettings,
+        cmap: &'a ColorMapGPU,
+        scene_depth: Option<&wgpu::TextureView>,
+    ) -> PerFrameData<'a> {
+        let camera_uniform = CameraUniform::from(camera);
+        let mut settings_uniform =
+            RenderSettingsUniform::from_settings(render_settings, &volume.volume);
+        settings_uniform.scene_depth = scene_depth.is_some() as u32;
+
+        let step = ((volume.volume.timesteps - 1) as f32 * render_settings.time) as usize;
+        let last_step = volume.volume.timesteps as usize - 1;
+        // current, next, previous and second next timestep (the latter two for cubic interpolation)
+        let steps = [
+            step,
+            (step + 1) % volume.volume.timesteps as usize,
+            step.saturating_sub(1),
+            (step + 2).min(last_step),
+        ];
+        let key = BindGroupKey {
+            camera: camera_uniform,
+            settings: settings_uniform,
+            textures: steps.map(|i| volume.textures[i].global_id()),
+            mask: volume.mask.global_id(),
+            spatial_filter: render_settings.spatial_filter,
+            scene_depth: scene_depth.map(|view| view.global_id()),
+        };
+
+        let bind_group = if let Some(i) = self.bind_groups.iter().position(|(k, _)| *k == key) {
+            let entry = self.bind_groups.remove(i);
+            let bind_group = entry.1.clone();
+            self.bind_groups.push(entry);
+            bind_group
+        } else {
+            let bind_group = Arc::new(self.create_bind_group(
+                device,
+                volume,
+                &key,
+                steps,
+                scene_depth.unwrap_or(&self.empty_depth),
+            ));
+            if self.bind_groups.len() >= MAX_CACHED_BIND_GROUPS {
+                self.bind_groups.remove(0);
+            }
+            self.bind_groups.push((key, bind_group.clone()));
+            bind_group
+        };
+        PerFrameData {
+            pipeline: self.pipeline(device, &render_settings.constants),
+            bind_group,
+            cmap_bind_group: cmap.bindgroup(),
+        }
+    }
+
+    /// returns the pipeline for the given shader constants, creating it if necessary
+    fn pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        constants: &ShaderConstants,
+    ) -> Arc<wgpu::RenderPipeline> {
+        if let Some(i) = self.pipelines.iter().position(|(c, _)| c == constants) {
+            let entry = self.pipelines.remove(i);
+            let pipeline = entry.1.clone();
+            self.pipelines.push(entry);
+            return pipeline;
+        }
+        let pipeline = Arc::new(self.create_pipeline(device, constants));
+        if self.pipelines.len() >= MAX_CACHED_PIPELINES {
+            self.pipelines.remove(0);
+        }
+        self.pipelines.push((*constants, pipeline.clone()));
+        pipeline
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        constants: &ShaderConstants,
+    ) -> wgpu::RenderPipeline {
+        let constants = constants.pipeline_constants();
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("volume render pipeline"),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: if self.aov {
+                    "fs_aov"
+                } else if self.depth_format.is_some() {
+                    "fs_main_depth"
+                } else {
+                    "fs_main"
+                },
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.format,
+                    blend: (!self.aov).then_some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: self.depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: true,
+                // occlusion by user geometry is handled in the shader
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        volume: &VolumeGPU,
+        key: &BindGroupKey,
+        steps: [usize; 4],
+        scene_depth: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("camera buffer"),
-            contents: bytemuck::bytes_of(&CameraUniform::from(camera)),
+            contents: bytemuck::bytes_of(&key.camera),
             usage: wgpu::BufferUsages::UNIFORM,
         });
 
         let settings_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("settnigs buffer"),
-            contents: bytemuck::bytes_of(&RenderSettingsUniform::from_settings(
-                &render_settings,
-                &volume.volume,
-            )),
+            contents: bytemuck::bytes_of(&key.settings),
             usage: wgpu::BufferUsages::UNIFORM,
         });
 
-        let step = ((volume.volume.timesteps - 1) as f32 * render_settings.time) as usize;
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        let step_view =
+            |i: usize| volume.textures[i].create_view(&wgpu::TextureViewDescriptor::default());
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("volume renderer bind group"),
-            layout: &Self::bind_group_layout(device),
+            layout: &self.bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(
-                        &volume.textures[step].create_view(&wgpu::TextureViewDescriptor::default()),
-                    ),
+                    resource: wgpu::BindingResource::TextureView(&step_view(steps[0])),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::TextureView(
-                        &volume.textures[(step + 1) % volume.volume.timesteps as usize]
-                            .create_view(&wgpu::TextureViewDescriptor::default()),
-                    ),
+                    resource: wgpu::BindingResource::TextureView(&step_view(steps[1])),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
                     resource: wgpu::BindingResource::Sampler(
-                        if render_settings.spatial_filter == wgpu::FilterMode::Nearest {
+                        if key.spatial_filter == Interpolation::Nearest {
                             &self.sampler_nearest
                         } else {
                             &self.sampler_linear
@@ -144,22 +322,38 @@ impl VolumeRenderer {
                         settings_buffer.as_entire_buffer_binding(),
                     ),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&step_view(steps[2])),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&step_view(steps[3])),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(scene_depth),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(
+                        &volume
+                            .mask
+                            .create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
             ],
-        });
-        PerFrameData {
-            bind_group,
-            cmap_bind_group: cmap.bindgroup(),
-        }
+        })
     }
 
     pub fn render<'rpass>(
-        &'rpass self,
+        &self,
         render_pass: &mut wgpu::RenderPass<'rpass>,
         frame_data: &'rpass PerFrameData,
     ) {
         render_pass.set_bind_group(0, &frame_data.bind_group, &[]);
         render_pass.set_bind_group(1, frame_data.cmap_bind_group, &[]);
-        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_pipeline(&frame_data.pipeline);
 
         render_pass.draw(0..4, 0..1);
     }
@@ -214,6 +408,47 @@ impl VolumeRenderer {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // bound as float texture as GL does not support loads from depth textures
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
             ],
         })
     }
@@ -223,10 +458,32 @@ impl VolumeRenderer {
 }
 
 pub struct PerFrameData<'a> {
-    bind_group: wgpu::BindGroup,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    bind_group: Arc<wgpu::BindGroup>,
     cmap_bind_group: &'a wgpu::BindGroup,
 }
 
+/// everything a bind group of the volume renderer depends on
+struct BindGroupKey {
+    camera: CameraUniform,
+    settings: RenderSettingsUniform,
+    textures: [wgpu::Id<wgpu::Texture>; 4],
+    mask: wgpu::Id<wgpu::Texture>,
+    spatial_filter: Interpolation,
+    scene_depth: Option<wgpu::Id<wgpu::TextureView>>,
+}
+
+impl PartialEq for BindGroupKey {
+    fn eq(&self, other: &Self) -> bool {
+        bytemuck::bytes_of(&self.camera) == bytemuck::bytes_of(&other.camera)
+            && bytemuck::bytes_of(&self.settings) == bytemuck::bytes_of(&other.settings)
+            && self.textures == other.textures
+            && self.mask == other.mask
+            && self.spatial_filter == other.spatial_filter
+            && self.scene_depth == other.scene_depth
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
@@ -278,17 +535,224 @@ impl<P: Projection> From<&Camera<P>> for CameraUniform {
     }
 }
 
-#[derive(Debug, Clone)]
+/// interpolation between samples of the volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    Nearest = 0,
+    #[default]
+    Linear = 1,
+    /// Catmull-Rom spline through four neighboring timesteps in time,
+    /// cubic B-spline (tricubic) reconstruction in space
+    Cubic = 2,
+}
+
+impl Interpolation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interpolation::Nearest => "Nearest",
+            Interpolation::Linear => "Linear",
+            Interpolation::Cubic => "Cubic",
+        }
+    }
+}
+
+impl std::str::FromStr for Interpolation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nearest" => Ok(Interpolation::Nearest),
+            "linear" => Ok(Interpolation::Linear),
+            "cubic" => Ok(Interpolation::Cubic),
+            _ => anyhow::bail!("unknown interpolation '{s}' (expected nearest, linear or cubic)"),
+        }
+    }
+}
+
+/// mapping of the values between vmin and vmax to the colormap domain [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Normalization {
+    #[default]
+    Linear,
+    /// logarithmic scale. Values below `vmax * 1e-6` (or vmin if it is larger and positive)
+    /// are mapped to 0.
+    Log,
+    Sqrt,
+    /// linear value raised to the exponent
+    Power(f32),
+}
+
+impl Normalization {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Normalization::Linear => "Linear",
+            Normalization::Log => "Log",
+            Normalization::Sqrt => "Sqrt",
+            Normalization::Power(_) => "Power",
+        }
+    }
+
+    /// id and exponent used by the shader
+    pub(crate) fn uniform(&self) -> (u32, f32) {
+        match self {
+            Normalization::Linear => (0, 1.),
+            Normalization::Log => (1, 1.),
+            Normalization::Sqrt => (2, 0.5),
+            Normalization::Power(exponent) => (2, *exponent),
+        }
+    }
+
+    /// maps `value` in the range `vmin..vmax` to [0, 1].
+    /// Must match `normalize_value` in raymarch.wgsl.
+    pub fn normalize(&self, value: f32, vmin: f32, vmax: f32) -> f32 {
+        match self {
+            Normalization::Linear => (value - vmin) / (vmax - vmin),
+            Normalization::Log => {
+                let low = log_min(vmin, vmax);
+                (value.max(low) / low).ln() / (vmax / low).ln()
+            }
+            Normalization::Sqrt | Normalization::Power(_) => {
+                let (_, exponent) = self.uniform();
+                ((value - vmin) / (vmax - vmin)).max(0.).powf(exponent)
+            }
+        }
+    }
+
+    /// inverse of [Normalization::normalize] for `x` in [0, 1]
+    pub fn denormalize(&self, x: f32, vmin: f32, vmax: f32) -> f32 {
+        match self {
+            Normalization::Linear => vmin + x * (vmax - vmin),
+            Normalization::Log => {
+                let low = log_min(vmin, vmax);
+                low * (vmax / low).powf(x)
+            }
+            Normalization::Sqrt | Normalization::Power(_) => {
+                let (_, exponent) = self.uniform();
+                vmin + x.powf(1. / exponent) * (vmax - vmin)
+            }
+        }
+    }
+}
+
+/// smallest value on the logarithmic scale
+fn log_min(vmin: f32, vmax: f32) -> f32 {
+    vmin.max(vmax * 1e-6)
+}
+
+/// how values that have no color in the colormap are rendered:
+/// NaN and infinite values as well as values outside of [vmin, vmax]
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueStyle {
+    /// color at the closer end of the colormap (transparent for NaN and infinite values)
+    #[default]
+    Clamp,
+    Transparent,
+    /// fixed color (same color space as the colormap) and opacity
+    Color([u8; 4]),
+}
+
+impl ValueStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueStyle::Clamp => "Clamp",
+            ValueStyle::Transparent => "Transparent",
+            ValueStyle::Color(_) => "Color",
+        }
+    }
+
+    /// id and color used by the shader
+    pub(crate) fn uniform(&self) -> (u32, Vector4<f32>) {
+        match self {
+            ValueStyle::Clamp => (0, Vector4::zero()),
+            ValueStyle::Transparent => (1, Vector4::zero()),
+            ValueStyle::Color(c) => (2, Vector4::from(*c).map(|c| c as f32 / 255.)),
+        }
+    }
+
+    /// color of a value with this style, `clamped` is the color at the end of the colormap
+    pub fn color(&self, clamped: Vector4<f32>) -> Vector4<f32> {
+        match self {
+            ValueStyle::Clamp => clamped,
+            _ => self.uniform().1,
+        }
+    }
+}
+
+/// colors of NaN/Inf values, values below vmin and above vmax
+/// if [RenderSettings::highlight_invalid] is set
+pub const HIGHLIGHT_COLORS: [[u8; 4]; 3] =
+    [[255, 0, 255, 255], [0, 255, 255, 255], [255, 255, 0, 255]];
+
+/// maximum number of contour surfaces (see [RenderSettings::contours])
+pub const MAX_CONTOURS: usize = 8;
+
+/// settings of the volume rendering.
+/// Serialized like the render settings of a session, see [SessionRender]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(from = "SessionRender", into = "SessionRender")]
 pub struct RenderSettings {
     pub clipping_aabb: Option<Aabb<f32>>,
     pub time: f32,
     pub step_size: f32,
-    pub spatial_filter: wgpu::FilterMode,
-    pub temporal_filter: wgpu::FilterMode,
+    pub spatial_filter: Interpolation,
+    pub temporal_filter: Interpolation,
     pub distance_scale: f32,
     pub vmin: Option<f32>,
     pub vmax: Option<f32>,
+    /// vmin and vmax default to the value range of the current timestep
+    /// instead of the range of the whole volume
+    pub per_timestep_range: bool,
+    pub normalization: Normalization,
+    /// rendering of NaN and infinite values
+    pub nan_style: ValueStyle,
+    /// rendering of values below vmin
+    pub below_style: ValueStyle,
+    /// rendering of values above vmax
+    pub above_style: ValueStyle,
+    /// debug mode that draws NaN/Inf values and values outside of [vmin, vmax]
+    /// opaque in the [HIGHLIGHT_COLORS] instead of their styles
+    pub highlight_invalid: bool,
+    /// hides the voxels of the volume's [Mask](crate::volume::Mask) instead of the others
+    pub invert_mask: bool,
+    /// legend of the colormap painted into the frame
+    pub colorbar: Option<Colorbar>,
     pub gamma_correction: bool,
+    /// sub-pixel offset of the rays in texture coordinates (used for anti-aliasing)
+    pub jitter: Vector2<f32>,
+    /// offset of the first sample along the ray as fraction of the step size
+    pub ray_offset: f32,
+    /// strength of the gradient magnitude based opacity modulation (0 = off)
+    pub boundary_enhancement: f32,
+    /// strength of the view angle based opacity modulation and darkening (0 = off)
+    pub silhouette: f32,
+    /// surfaces drawn where the volume crosses these values, with their color (rgb and opacity).
+    /// At most [MAX_CONTOURS] are used.
+    pub contours: Vec<(f32, Vector4<f32>)>,
+    pub constants: ShaderConstants,
+}
+
+impl RenderSettings {
+    /// vmin and vmax used for `volume`
+    pub fn value_range(&self, volume: &Volume) -> (f32, f32) {
+        let (min, max) = if self.per_timestep_range {
+            volume.range_at(self.time)
+        } else {
+            (volume.min_value, volume.max_value)
+        };
+        (self.vmin.unwrap_or(min), self.vmax.unwrap_or(max))
+    }
+
+    /// styles of NaN/Inf values, values below vmin and above vmax
+    pub fn value_styles(&self) -> [ValueStyle; 3] {
+        if self.highlight_invalid {
+            HIGHLIGHT_COLORS.map(ValueStyle::Color)
+        } else {
+            [self.nan_style, self.below_style, self.above_style]
+        }
+    }
 }
 
 impl Default for RenderSettings {
@@ -297,16 +761,90 @@ impl Default for RenderSettings {
             clipping_aabb: None,
             time: 0.,
             step_size: 1e-4,
-            spatial_filter: wgpu::FilterMode::Linear,
-            temporal_filter: wgpu::FilterMode::Linear,
+            spatial_filter: Interpolation::Linear,
+            temporal_filter: Interpolation::Linear,
             distance_scale: 1.,
             vmin: None,
             vmax: None,
+            per_timestep_range: false,
+            normalization: Normalization::Linear,
+            nan_style: ValueStyle::Transparent,
+            below_style: ValueStyle::Clamp,
+            above_style: ValueStyle::Clamp,
+            highlight_invalid: false,
+            invert_mask: false,
+            colorbar: None,
             gamma_correction: false,
+            jitter: Vector2::zero(),
+            ray_offset: 0.,
+            boundary_enhancement: 0.,
+            silhouette: 0.,
+            contours: Vec::new(),
+            constants: ShaderConstants::default(),
+        }
+    }
+}
+
+/// tuning constants of the raymarching shader.
+/// Set as pipeline-overridable constants, so changing them recompiles the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShaderConstants {
+    /// maximum number of samples along a ray
+    pub max_steps: u32,
+    /// rays stop once their transmittance drops below this value
+    pub early_stopping_threshold: f32,
+    /// distance between the volume boundary and the first sample
+    pub ray_epsilon: f32,
+    /// distance of the samples used for the central differences of the gradient (in voxels)
+    pub gradient_step: f32,
+    /// gradient magnitude (in value range per voxel) at which boundary enhancement saturates
+    pub boundary_saturation: f32,
+    pub silhouette_exponent: f32,
+    /// accumulated opacity at which a ray counts as hit for the depth output
+    pub depth_opacity_threshold: f32,
+}
+
+impl Default for ShaderConstants {
+    fn default() -> Self {
+        Self {
+            max_steps: 10000,
+            early_stopping_threshold: 1. / 255.,
+            ray_epsilon: 1e-4,
+            gradient_step: 1.,
+            boundary_saturation: 0.1,
+            silhouette_exponent: 4.,
+            depth_opacity_threshold: 0.5,
         }
     }
 }
 
+impl ShaderConstants {
+    fn pipeline_constants(&self) -> HashMap<String, f64> {
+        HashMap::from([
+            ("MAX_STEPS".to_string(), self.max_steps as f64),
+            (
+                "EARLY_STOPPING_THRESHOLD".to_string(),
+                self.early_stopping_threshold as f64,
+            ),
+            ("RAY_EPSILON".to_string(), self.ray_epsilon as f64),
+            ("GRADIENT_STEP".to_string(), self.gradient_step as f64),
+            (
+                "BOUNDARY_SATURATION".to_string(),
+                self.boundary_saturation as f64,
+            ),
+            (
+                "SILHOUETTE_EXPONENT".to_string(),
+                self.silhouette_exponent as f64,
+            ),
+            (
+                "DEPTH_OPACITY_THRESHOLD".to_string(),
+                self.depth_opacity_threshold as f64,
+            ),
+        ])
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct RenderSettingsUniform {
@@ -322,11 +860,43 @@ pub struct RenderSettingsUniform {
     vmin: f32,
     vmax: f32,
     gamma_correction: u32,
+    jitter: Vector2<f32>,
+    ray_offset: f32,
+    boundary_enhancement: f32,
+    silhouette: f32,
+    spatial_filter: u32,
+    scene_depth: u32,
+    _pad: u32,
+    /// four values per vector
+    contour_values: [Vector4<f32>; MAX_CONTOURS / 4],
+    contour_colors: [Vector4<f32>; MAX_CONTOURS],
+    num_contours: u32,
+    normalization: u32,
+    exponent: f32,
+    /// 0: no mask, 1: mask, 2: inverted mask
+    mask: u32,
+    /// NaN/Inf, below vmin, above vmax (see [ValueStyle::uniform])
+    value_styles: Vector4<u32>,
+    nan_color: Vector4<f32>,
+    below_color: Vector4<f32>,
+    above_color: Vector4<f32>,
 }
 
 impl RenderSettingsUniform {
     pub fn from_settings(settings: &RenderSettings, volume: &Volume) -> Self {
         let volume_aabb = volume.aabb;
+        let contours = &settings.contours[..settings.contours.len().min(MAX_CONTOURS)];
+        let mut contour_values = [Vector4::zero(); MAX_CONTOURS / 4];
+        let mut contour_colors = [Vector4::zero(); MAX_CONTOURS];
+        for (i, (value, color)) in contours.iter().enumerate() {
+            contour_values[i / 4][i % 4] = *value;
+            contour_colors[i] = *color;
+        }
+
+        let (normalization, exponent) = settings.normalization.uniform();
+        let (vmin, vmax) = settings.value_range(volume);
+        let [(nan_style, nan_color), (below_style, below_color), (above_style, above_color)] =
+            settings.value_styles().map(|style| style.uniform());
 
         Self {
             volume_aabb_min: volume_aabb.min.to_vec().extend(0.),
@@ -344,9 +914,30 @@ impl RenderSettingsUniform {
             step_size: settings.step_size,
             temporal_filter: settings.temporal_filter as u32,
             distance_scale: settings.distance_scale,
-            vmin: settings.vmin.unwrap_or(volume.min_value),
-            vmax: settings.vmax.unwrap_or(volume.max_value),
+            vmin,
+            vmax,
             gamma_correction: settings.gamma_correction as u32,
+            jitter: settings.jitter,
+            ray_offset: settings.ray_offset,
+            boundary_enhancement: settings.boundary_enhancement,
+            silhouette: settings.silhouette,
+            spatial_filter: settings.spatial_filter as u32,
+            scene_depth: 0,
+            _pad: 0,
+            contour_values,
+            contour_colors,
+            num_contours: contours.len() as u32,
+            normalization,
+            exponent,
+            mask: match (volume.mask().is_some(), settings.invert_mask) {
+                (false, _) => 0,
+                (true, false) => 1,
+                (true, true) => 2,
+            },
+            value_styles: Vector4::new(nan_style, below_style, above_style, 0),
+            nan_color,
+            below_color,
+            above_color,
         }
     }
 }
@@ -361,11 +952,28 @@ impl Default for RenderSettingsUniform {
             time: 0.,
             time_steps: 1,
             step_size: 0.01,
-            temporal_filter: wgpu::FilterMode::Nearest as u32,
+            temporal_filter: Interpolation::Nearest as u32,
             distance_scale: 1.,
             vmin: 0.,
             vmax: 1.,
             gamma_correction: 0,
+            jitter: Vector2::zero(),
+            ray_offset: 0.,
+            boundary_enhancement: 0.,
+            silhouette: 0.,
+            spatial_filter: Interpolation::Linear as u32,
+            scene_depth: 0,
+            _pad: 0,
+            contour_values: [Vector4::zero(); MAX_CONTOURS / 4],
+            contour_colors: [Vector4::zero(); MAX_CONTOURS],
+            num_contours: 0,
+            normalization: 0,
+            exponent: 1.,
+            mask: 0,
+            value_styles: Vector4::new(1, 0, 0, 0),
+            nan_color: Vector4::zero(),
+            below_color: Vector4::zero(),
+            above_color: Vector4::zero(),
         }
     }
 }
@@ -0,0 +1,105 @@
+use std::sync::{Arc, Mutex};
+
+use cgmath::Vector4;
+
+use crate::{
+    cmap::{ColorMap, ColorMapGPU, ColorMapSequence},
+    renderer::VolumeRenderer,
+};
+
+/// number of colormap LUTs kept around for reuse
+const MAX_CACHED_COLORMAPS: usize = 16;
+
+/// rasterized colormap and its number of rows (timesteps)
+type LutKey = (Vec<Vector4<u8>>, usize);
+
+/// color and depth format of a volume renderer
+type RendererKey = (wgpu::TextureFormat, Option<wgpu::TextureFormat>);
+
+/// GPU resources shared by everything that uses the same [WGPUContext](crate::WGPUContext),
+/// e.g. all windows or all offline renders on a thread
+#[derive(Default)]
+pub struct ResourceRegistry {
+    /// recently used colormap LUTs by their values, least recently used first
+    colormaps: Vec<(LutKey, Arc<ColorMapGPU>)>,
+    /// volume renderers (and with them their pipeline caches) by color and depth format
+    renderers: Vec<(RendererKey, Arc<Mutex<VolumeRenderer>>)>,
+    /// renderers of the auxiliary outputs of offline renders by format
+    aov_renderers: Vec<(wgpu::TextureFormat, Arc<Mutex<VolumeRenderer>>)>,
+}
+
+impl ResourceRegistry {
+    /// returns the LUT texture for `cmap` sampled at `n` positions, uploading it if necessary
+    pub fn colormap(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cmap: impl ColorMap,
+        n: u32,
+    ) -> Arc<ColorMapGPU> {
+        self.lut(device, queue, (cmap.rasterize(n as usize), 1))
+    }
+
+    /// returns the LUT texture for the time-varying `cmap` with `n` colors per row,
+    /// uploading it if necessary
+    pub fn colormap_sequence(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cmap: &ColorMapSequence,
+        n: u32,
+    ) -> Arc<ColorMapGPU> {
+        let rows = cmap.rows();
+        self.lut(device, queue, (cmap.rasterize(n as usize, rows), rows))
+    }
+
+    fn lut(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, key: LutKey) -> Arc<ColorMapGPU> {
+        if let Some(i) = self.colormaps.iter().position(|(k, _)| *k == key) {
+            let entry = self.colormaps.remove(i);
+            let cmap_gpu = entry.1.clone();
+            self.colormaps.push(entry);
+            return cmap_gpu;
+        }
+        let (lut, rows) = key.clone();
+        let cmap_gpu = Arc::new(ColorMapGPU::with_rows(lut, rows, device, queue));
+        if self.colormaps.len() >= MAX_CACHED_COLORMAPS {
+            self.colormaps.remove(0);
+        }
+        self.colormaps.push((key, cmap_gpu.clone()));
+        cmap_gpu
+    }
+
+    /// returns the volume renderer for the given target formats, creating it if necessary
+    pub fn volume_renderer(
+        &mut self,
+        device: &wgpu::Device,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+    ) -> Arc<Mutex<VolumeRenderer>> {
+        let key = (color_format, depth_format);
+        if let Some((_, renderer)) = self.renderers.iter().find(|(k, _)| *k == key) {
+            return renderer.clone();
+        }
+        let renderer = Arc::new(Mutex::new(VolumeRenderer::with_depth(
+            device,
+            color_format,
+            depth_format,
+        )));
+        self.renderers.push((key, renderer.clone()));
+        renderer
+    }
+
+    /// returns the renderer of the auxiliary outputs (see [VolumeRenderer::aov])
+    pub fn aov_renderer(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Arc<Mutex<VolumeRenderer>> {
+        if let Some((_, renderer)) = self.aov_renderers.iter().find(|(f, _)| *f == format) {
+            return renderer.clone();
+        }
+        let renderer = Arc::new(Mutex::new(VolumeRenderer::aov(device, format)));
+        self.aov_renderers.push((format, renderer.clone()));
+        renderer
+    }
+}
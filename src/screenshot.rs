@@ -0,0 +1,273 @@
+use std::path::PathBuf;
+
+use image::{ImageBuffer, Rgba};
+
+use crate::readback::BufferMapping;
+
+/// what happens with a captured frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotTarget {
+    /// png file with an automatically numbered name
+    File,
+    Clipboard,
+}
+
+/// frame copied into the readback buffer
+struct PendingFrame {
+    target: ScreenshotTarget,
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    bgra: bool,
+}
+
+/// screenshots of the window (F12 or Ctrl+S).
+/// The frame is read back asynchronously, so the render loop never waits for it.
+pub struct Screenshots {
+    /// also captures the user interface, annotations are always included
+    pub include_ui: bool,
+    /// directory the png files are written to (current directory if None)
+    pub directory: Option<PathBuf>,
+    /// captured with the next frame
+    requested: Option<ScreenshotTarget>,
+    pending: Option<PendingFrame>,
+    /// the buffer of the pending frame is mapped after the frame is submitted
+    mapping: BufferMapping,
+    /// outcome of the last screenshot, shown in the ui
+    pub status: Option<String>,
+}
+
+impl Default for Screenshots {
+    fn default() -> Self {
+        Self {
+            include_ui: false,
+            directory: None,
+            requested: None,
+            pending: None,
+            mapping: BufferMapping::default(),
+            status: None,
+        }
+    }
+}
+
+impl Screenshots {
+    /// captures the next frame
+    pub fn request(&mut self, target: ScreenshotTarget) {
+        self.requested = Some(target);
+    }
+
+    /// true if the next frame is captured without the user interface
+    pub fn hides_ui(&self) -> bool {
+        self.requested.is_some() && !self.include_ui
+    }
+
+    /// copies `texture` (the frame shown in the window) into a readback buffer if a
+    /// screenshot was requested. [Screenshots::map] has to be called after the submit.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) {
+        if self.pending.is_some() {
+            return;
+        }
+        let Some(target) = self.requested.take() else {
+            return;
+        };
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            self.fail(anyhow::anyhow!("the window surface can not be copied"));
+            return;
+        }
+        let bgra = match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => {
+                self.fail(anyhow::anyhow!(
+                    "screenshots of {format:?} surfaces are not supported"
+                ));
+                return;
+            }
+        };
+        let size = texture.size();
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row = (4 * size.width).div_ceil(align) * align;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("screenshot readback"),
+            size: (bytes_per_row * size.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+        self.pending = Some(PendingFrame {
+            target,
+            buffer,
+            width: size.width,
+            height: size.height,
+            bytes_per_row,
+            bgra,
+        });
+    }
+
+    /// starts reading back the captured frame, called after the frame is submitted
+    pub fn map(&mut self) {
+        if let Some(frame) = &self.pending {
+            self.mapping.map(&frame.buffer);
+        }
+    }
+
+    /// saves (or copies) the captured frame once it is read back
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        if self.pending.is_none() {
+            return;
+        }
+        let Some(result) = self.mapping.poll(device) else {
+            return;
+        };
+        let frame = self.pending.take().unwrap();
+        if let Err(err) = result {
+            self.fail(err.into());
+            return;
+        }
+        let mut data = Vec::with_capacity((4 * frame.width * frame.height) as usize);
+        for row in frame
+            .buffer
+            .slice(..)
+            .get_mapped_range()
+            .chunks_exact(frame.bytes_per_row as usize)
+        {
+            data.extend_from_slice(&row[..4 * frame.width as usize]);
+        }
+        frame.buffer.unmap();
+        if frame.bgra {
+            data.chunks_exact_mut(4).for_each(|p| p.swap(0, 2));
+        }
+        // the window is opaque
+        data.chunks_exact_mut(4).for_each(|p| p[3] = 255);
+        let image = ImageBuffer::<Rgba<u8>, _>::from_raw(frame.width, frame.height, data).unwrap();
+        let result = match frame.target {
+            ScreenshotTarget::File => self.save(&image),
+            ScreenshotTarget::Clipboard => {
+                copy_to_clipboard(&image).map(|_| "copied screenshot to the clipboard".to_string())
+            }
+        };
+        match result {
+            Ok(status) => {
+                log::info!("{status}");
+                self.status = Some(status);
+            }
+            Err(err) => self.fail(err),
+        }
+    }
+
+    fn fail(&mut self, err: anyhow::Error) {
+        log::error!("screenshot failed: {err}");
+        self.status = Some(format!("screenshot failed: {err}"));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save(&self, image: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> anyhow::Result<String> {
+        let directory = self.directory.clone().unwrap_or_default();
+        let path = (1..)
+            .map(|i| directory.join(format!("vape4d_{i:04}.png")))
+            .find(|path| !path.exists())
+            .unwrap();
+        image.save(&path)?;
+        Ok(format!("saved screenshot to {}", path.display()))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn save(&self, image: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> anyhow::Result<String> {
+        let png = encode_png(image)?;
+        wasm_bindgen_futures::spawn_local(async move {
+            let file = rfd::AsyncFileDialog::new()
+                .set_file_name("vape4d.png")
+                .save_file()
+                .await;
+            if let Some(file) = file {
+                file.write(&png).await.unwrap();
+            }
+        });
+        Ok("saved screenshot".to_string())
+    }
+}
+
+fn encode_png(image: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> anyhow::Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// copies the image with the clipboard tool of the platform
+/// (wl-copy or xclip on Linux, which have to be installed)
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_to_clipboard(image: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> anyhow::Result<()> {
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    let png = encode_png(image)?;
+    // the linux tools read the image from stdin, the others from a file
+    let from_file = cfg!(any(target_os = "macos", windows));
+    let mut command = if from_file {
+        let path = std::env::temp_dir().join("vape4d_clipboard.png");
+        std::fs::write(&path, &png)?;
+        if cfg!(windows) {
+            let mut command = Command::new("powershell");
+            command.args(["-NoProfile", "-Sta", "-Command"]).arg(format!(
+                "Add-Type -AssemblyName System.Windows.Forms; \
+                 [System.Windows.Forms.Clipboard]::SetImage([System.Drawing.Image]::FromFile('{}'))",
+                path.display()
+            ));
+            command
+        } else {
+            let mut command = Command::new("osascript");
+            command.arg("-e").arg(format!(
+                "set the clipboard to (read (POSIX file \"{}\") as «class PNGf»)",
+                path.display()
+            ));
+            command
+        }
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        let mut command = Command::new("wl-copy");
+        command.args(["--type", "image/png"]);
+        command
+    } else {
+        let mut command = Command::new("xclip");
+        command.args(["-selection", "clipboard", "-target", "image/png", "-in"]);
+        command
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(if from_file {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        })
+        .spawn()
+        .map_err(|err| anyhow::anyhow!("failed to run {program} (is it installed?): {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&png)?;
+    }
+    let status = child.wait()?;
+    anyhow::ensure!(status.success(), "{program} failed with {status}");
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn copy_to_clipboard(_image: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> anyhow::Result<()> {
+    anyhow::bail!("copying images to the clipboard is not supported in the browser")
+}
@@ -0,0 +1,627 @@
+use std::path::{Path, PathBuf};
+
+use cgmath::{Point3, Quaternion, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations::{Annotations, Colorbar, Label, ReferenceValue},
+    background::Background,
+    bookmarks::Bookmark,
+    camera::{Camera, CameraView, OrthographicProjection},
+    cmap::{
+        ColorMapGPU, ColorMapSequence, GenericColorMap, LinearSegmentedColorMap, TransferFunction,
+        COLORMAP_RESOLUTION,
+    },
+    keyframes::{CameraAnimation, Keyframe},
+    renderer::{Interpolation, Normalization, RenderSettings, ShaderConstants, ValueStyle},
+    stereo::{Stereo, StereoMode},
+    timeline::PlaybackMode,
+    volume::{apply_mask, Aabb, Mask, Volume},
+    Duration, RenderConfig, WindowContext,
+};
+
+/// complete state of the viewer (camera, render settings, transfer function, clipping,
+/// playback and bookmarks), saved as json so that a visualization can be restored exactly
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Session {
+    /// npy or npz file of the volume, relative to the session file.
+    /// Empty if the volume was not loaded from a file
+    #[serde(default)]
+    pub volume: String,
+    /// physical size of a voxel in array order (depth, height, width)
+    pub spacing: [f32; 3],
+    pub camera: SessionCamera,
+    pub render: SessionRender,
+    pub colormap: LinearSegmentedColorMap,
+    pub opacity: TransferFunction,
+    #[serde(default)]
+    pub reference_values: Vec<ReferenceValue>,
+    #[serde(default)]
+    pub colorbar: Option<Colorbar>,
+    pub playback: SessionPlayback,
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    #[serde(default)]
+    pub keyframes: Vec<Keyframe>,
+    /// channel shown alone instead of all channels side by side
+    #[serde(default)]
+    pub selected_channel: Option<usize>,
+    /// background color (RGBA)
+    pub background: [f32; 4],
+    /// unit of the voxel spacing
+    #[serde(default)]
+    pub unit: String,
+    /// text labels anchored to points of the volume
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    /// what is drawn behind the volume
+    #[serde(default)]
+    pub background_style: SessionBackground,
+    /// equirectangular image of the environment background, relative to the session file
+    #[serde(default)]
+    pub environment_map: String,
+    #[serde(default)]
+    pub stereo: Stereo,
+    /// time-varying colormap used instead of `colormap` as (time, colormap) keyframes
+    #[serde(default)]
+    pub colormap_sequence: Vec<(f32, LinearSegmentedColorMap)>,
+    #[serde(default)]
+    pub overlay: SessionOverlay,
+    /// npy or npz file of the mask, relative to the session file.
+    /// Empty if there is none or it is stored in the volume file
+    #[serde(default)]
+    pub mask: String,
+    /// the mask hides the masked voxels instead of the others
+    #[serde(default)]
+    pub invert_mask: bool,
+}
+
+/// what is drawn behind the volume, see [Background]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionBackground {
+    #[default]
+    Solid,
+    Gradient {
+        top: [f32; 4],
+    },
+    Checkerboard {
+        color: [f32; 4],
+        size: f32,
+    },
+    Environment,
+}
+
+impl SessionBackground {
+    pub fn new(background: &Background) -> Self {
+        match *background {
+            Background::Solid => Self::Solid,
+            Background::Gradient { top } => Self::Gradient {
+                top: color_array(top),
+            },
+            Background::Checkerboard { color, size } => Self::Checkerboard {
+                color: color_array(color),
+                size,
+            },
+            Background::Environment => Self::Environment,
+        }
+    }
+
+    pub fn background(&self) -> Background {
+        match *self {
+            Self::Solid => Background::Solid,
+            Self::Gradient { top } => Background::Gradient { top: color(top) },
+            Self::Checkerboard { color: c, size } => Background::Checkerboard {
+                color: color(c),
+                size,
+            },
+            Self::Environment => Background::Environment,
+        }
+    }
+}
+
+/// lines and annotations drawn on top of the volume
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionOverlay {
+    pub scale_bar: bool,
+    pub axis_ticks: bool,
+    pub time_label: bool,
+    pub bounding_box: bool,
+    pub axes: bool,
+    /// lines are drawn on top of the volume instead of being depth tested
+    pub xray: bool,
+}
+
+/// orthographic camera of the viewer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionCamera {
+    pub position: [f32; 3],
+    /// rotation as quaternion (w, x, y, z)
+    pub rotation: [f32; 4],
+    /// point the camera rotates around
+    pub center: [f32; 3],
+    /// width and height of the view
+    pub viewport: [f32; 2],
+}
+
+impl SessionCamera {
+    pub fn new(camera: &Camera<OrthographicProjection>, center: Point3<f32>) -> Self {
+        let r = camera.rotation;
+        Self {
+            position: camera.position.into(),
+            rotation: [r.s, r.v.x, r.v.y, r.v.z],
+            center: center.into(),
+            viewport: camera.projection.viewport.into(),
+        }
+    }
+
+    pub fn camera(&self) -> Camera<OrthographicProjection> {
+        let [w, x, y, z] = self.rotation;
+        Camera::new(
+            self.position.into(),
+            Quaternion::new(w, x, y, z),
+            OrthographicProjection::new(Vector2::from(self.viewport), 1e-4, 100.),
+        )
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.center.into()
+    }
+}
+
+/// render settings of the viewer, see [RenderSettings]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionRender {
+    /// region of the volume that is rendered, relative to its size (min, max)
+    pub clipping: Option<[[f32; 3]; 2]>,
+    pub step_size: Option<f32>,
+    pub spatial_interpolation: Interpolation,
+    pub temporal_interpolation: Interpolation,
+    pub distance_scale: f32,
+    pub vmin: Option<f32>,
+    pub vmax: Option<f32>,
+    pub per_timestep_range: bool,
+    pub normalization: Normalization,
+    pub nan_style: ValueStyle,
+    pub below_style: ValueStyle,
+    pub above_style: ValueStyle,
+    pub invert_mask: bool,
+    pub gamma_correction: bool,
+    pub boundary_enhancement: f32,
+    pub silhouette: f32,
+    /// surfaces where the volume crosses a value, with their color (rgb and opacity).
+    /// Sessions store them as reference values instead
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub contours: Vec<(f32, [f32; 4])>,
+    /// tuning constants of the raymarching shader
+    pub constants: ShaderConstants,
+}
+
+impl Default for SessionRender {
+    fn default() -> Self {
+        Self {
+            clipping: None,
+            step_size: None,
+            spatial_interpolation: Interpolation::Linear,
+            temporal_interpolation: Interpolation::Linear,
+            distance_scale: 1.,
+            vmin: None,
+            vmax: None,
+            per_timestep_range: false,
+            normalization: Normalization::Linear,
+            nan_style: ValueStyle::Transparent,
+            below_style: ValueStyle::Clamp,
+            above_style: ValueStyle::Clamp,
+            invert_mask: false,
+            gamma_correction: false,
+            boundary_enhancement: 0.,
+            silhouette: 0.,
+            contours: Vec::new(),
+            constants: ShaderConstants::default(),
+        }
+    }
+}
+
+impl SessionRender {
+    pub fn new(settings: &RenderSettings) -> Self {
+        Self {
+            clipping: settings.clipping_aabb.map(|c| [c.min.into(), c.max.into()]),
+            step_size: Some(settings.step_size),
+            spatial_interpolation: settings.spatial_filter,
+            temporal_interpolation: settings.temporal_filter,
+            distance_scale: settings.distance_scale,
+            vmin: settings.vmin,
+            vmax: settings.vmax,
+            per_timestep_range: settings.per_timestep_range,
+            normalization: settings.normalization,
+            nan_style: settings.nan_style,
+            below_style: settings.below_style,
+            above_style: settings.above_style,
+            invert_mask: settings.invert_mask,
+            gamma_correction: settings.gamma_correction,
+            boundary_enhancement: settings.boundary_enhancement,
+            silhouette: settings.silhouette,
+            contours: settings
+                .contours
+                .iter()
+                .map(|(value, color)| (*value, (*color).into()))
+                .collect(),
+            constants: settings.constants,
+        }
+    }
+
+    /// sets the saved fields of `settings`, a missing step size keeps the current one
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        settings.clipping_aabb = self.clipping.map(|[min, max]| Aabb {
+            min: min.into(),
+            max: max.into(),
+        });
+        if let Some(step_size) = self.step_size {
+            settings.step_size = step_size;
+        }
+        settings.spatial_filter = self.spatial_interpolation;
+        settings.temporal_filter = self.temporal_interpolation;
+        settings.distance_scale = self.distance_scale;
+        settings.vmin = self.vmin;
+        settings.vmax = self.vmax;
+        settings.per_timestep_range = self.per_timestep_range;
+        settings.normalization = self.normalization;
+        settings.nan_style = self.nan_style;
+        settings.below_style = self.below_style;
+        settings.above_style = self.above_style;
+        settings.invert_mask = self.invert_mask;
+        settings.gamma_correction = self.gamma_correction;
+        settings.boundary_enhancement = self.boundary_enhancement;
+        settings.silhouette = self.silhouette;
+        settings.contours = self
+            .contours
+            .iter()
+            .map(|(value, color)| (*value, (*color).into()))
+            .collect();
+        settings.constants = self.constants;
+    }
+}
+
+impl From<SessionRender> for RenderSettings {
+    fn from(render: SessionRender) -> Self {
+        let mut settings = RenderSettings::default();
+        render.apply(&mut settings);
+        settings
+    }
+}
+
+impl From<RenderSettings> for SessionRender {
+    fn from(settings: RenderSettings) -> Self {
+        Self::new(&settings)
+    }
+}
+
+/// state of the time animation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionPlayback {
+    /// normalized time
+    pub time: f32,
+    pub playing: bool,
+    pub mode: PlaybackMode,
+    /// seconds it takes to play from the first to the last timestep
+    pub duration: f32,
+    pub backwards: bool,
+    pub snap_timesteps: bool,
+}
+
+impl Default for SessionPlayback {
+    fn default() -> Self {
+        Self {
+            time: 0.,
+            playing: true,
+            mode: PlaybackMode::Loop,
+            duration: 5.,
+            backwards: false,
+            snap_timesteps: false,
+        }
+    }
+}
+
+impl Session {
+    /// state of the viewer, `path` is the file the session is saved to
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn of(state: &WindowContext, path: &Path) -> Self {
+        let volume = &state.volumes[0].volume;
+        let spacing = volume.spacing;
+        let timeline = &state.timeline;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let relative = |file: &Option<PathBuf>| {
+            file.as_deref()
+                .map(|file| relative_path(file, base_dir))
+                .unwrap_or_default()
+        };
+        Self {
+            volume: relative(&state.volume_file),
+            spacing: [spacing.z, spacing.y, spacing.x],
+            camera: SessionCamera::new(&state.camera, state.controller.center),
+            render: SessionRender::new(&state.render_settings),
+            colormap: state.cmap.clone(),
+            opacity: state.opacity.clone(),
+            reference_values: state.reference_values.clone(),
+            colorbar: state.render_settings.colorbar,
+            playback: SessionPlayback {
+                time: state.render_settings.time,
+                playing: timeline.playing,
+                mode: timeline.mode,
+                duration: timeline.duration.as_secs_f32(),
+                backwards: timeline.backwards,
+                snap_timesteps: timeline.snap,
+            },
+            bookmarks: state.bookmarks.list.clone(),
+            keyframes: state.camera_animation.keyframes.clone(),
+            selected_channel: state.selected_channel,
+            background: color_array(state.background_color),
+            unit: state.annotations.unit.clone(),
+            labels: state.annotations.labels.clone(),
+            background_style: SessionBackground::new(&state.background),
+            environment_map: relative(&state.environment_file),
+            stereo: state.stereo,
+            colormap_sequence: state
+                .cmap_sequence
+                .iter()
+                .flat_map(|seq| seq.keyframes())
+                .map(|(time, cmap)| (time, cmap.into_linear_segmented(COLORMAP_RESOLUTION)))
+                .collect(),
+            overlay: SessionOverlay {
+                scale_bar: state.annotations.scale_bar,
+                axis_ticks: state.annotations.ticks,
+                time_label: state.annotations.time_label,
+                bounding_box: state.overlay.bounding_box,
+                axes: state.overlay.axes,
+                xray: state.overlay.xray,
+            },
+            mask: relative(&state.mask_file),
+            invert_mask: state.invert_mask,
+        }
+    }
+
+    /// restores the state of the viewer, the volume has to be loaded already
+    pub(crate) fn apply(&self, state: &mut WindowContext) {
+        state.controller.stop();
+        state.camera = self.camera.camera();
+        state
+            .camera
+            .projection
+            .resize(state.config.width, state.config.height);
+        state.controller.center = self.camera.center();
+        self.render.apply(&mut state.render_settings);
+        state.render_settings.colorbar = self.colorbar;
+        state.render_settings.time = self.playback.time.clamp(0., 1.);
+        state.cmap = self.colormap.clone();
+        state.opacity = self.opacity.clone();
+        state.reference_values = self.reference_values.clone();
+        state.timeline.playing = self.playback.playing;
+        state.timeline.mode = self.playback.mode;
+        state.timeline.duration = Duration::from_secs_f32(self.playback.duration.max(1e-3));
+        state.timeline.backwards = self.playback.backwards;
+        state.timeline.snap = self.playback.snap_timesteps;
+        state.bookmarks.list = self.bookmarks.clone();
+        state.camera_animation = CameraAnimation::new(self.keyframes.clone());
+        state.selected_channel = self.selected_channel.filter(|i| *i < state.volumes.len());
+        state.background_color = self.background_color();
+        state.annotations.unit = self.unit.clone();
+        state.annotations.labels = self.labels.clone();
+        state.annotations.scale_bar = self.overlay.scale_bar;
+        state.annotations.ticks = self.overlay.axis_ticks;
+        state.annotations.time_label = self.overlay.time_label;
+        state.overlay.bounding_box = self.overlay.bounding_box;
+        state.overlay.axes = self.overlay.axes;
+        state.overlay.xray = self.overlay.xray;
+        state.background = self.background_style.background();
+        if state.background == Background::Environment
+            && !state.background_renderer.has_environment()
+        {
+            log::warn!("the environment image of the session is not loaded");
+            state.background = Background::Solid;
+        }
+        state.stereo = self.stereo;
+        if state.stereo.mode.offline_only() {
+            state.stereo.mode = StereoMode::Off;
+        }
+        // the texture of a colormap sequence has a row per time
+        let (device, queue) = (&state.wgpu_context.device, &state.wgpu_context.queue);
+        state.cmap_sequence = self.colormap_sequence();
+        state.cmap_gpu = match &state.cmap_sequence {
+            Some(seq) => ColorMapGPU::from_sequence(seq, device, queue, COLORMAP_RESOLUTION),
+            None => ColorMapGPU::new(&state.cmap, device, queue, COLORMAP_RESOLUTION),
+        };
+        state.last_frame = None;
+        state.taa.reset();
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.spacing.iter().all(|s| *s > 0.),
+            "spacing must be positive"
+        );
+        anyhow::ensure!(
+            self.camera.viewport.iter().all(|v| *v > 0.),
+            "camera viewport must be positive"
+        );
+        for keyframe in &self.keyframes {
+            keyframe.validate()?;
+        }
+        if !self.colormap_sequence.is_empty() {
+            ColorMapSequence::new(self.sequence_keyframes())?;
+        }
+        Ok(())
+    }
+
+    /// sets the spacing of the session
+    pub fn apply_spacing(&self, volume: &mut Volume) {
+        let [d, h, w] = self.spacing;
+        volume.set_spacing(Vector3::new(w, h, d));
+    }
+
+    /// file of the volume, relative paths are relative to the directory `base_dir`
+    /// of the session file. None if the session has no volume file
+    pub fn volume_file(&self, base_dir: &Path) -> Option<PathBuf> {
+        (!self.volume.is_empty()).then(|| base_dir.join(&self.volume))
+    }
+
+    /// loads the mask file of the session into `volumes`,
+    /// relative paths are relative to the directory `base_dir` of the session file
+    pub fn load_mask(&self, base_dir: &Path, volumes: &mut [Volume]) -> anyhow::Result<()> {
+        if let Some(file) = self.mask_file(base_dir) {
+            let mask = Mask::load_numpy(std::io::BufReader::new(std::fs::File::open(file)?))?;
+            apply_mask(volumes, Some(mask), self.invert_mask)?;
+        }
+        Ok(())
+    }
+
+    /// file of the mask, None if the session has no mask file
+    pub fn mask_file(&self, base_dir: &Path) -> Option<PathBuf> {
+        (!self.mask.is_empty()).then(|| base_dir.join(&self.mask))
+    }
+
+    /// file of the environment image, None if the session has none
+    pub fn environment_file(&self, base_dir: &Path) -> Option<PathBuf> {
+        (!self.environment_map.is_empty()).then(|| base_dir.join(&self.environment_map))
+    }
+
+    /// time-varying colormap, None if the session uses a single colormap
+    pub fn colormap_sequence(&self) -> Option<ColorMapSequence> {
+        if self.colormap_sequence.is_empty() {
+            return None;
+        }
+        ColorMapSequence::new(self.sequence_keyframes()).ok()
+    }
+
+    fn sequence_keyframes(&self) -> Vec<(f32, GenericColorMap)> {
+        self.colormap_sequence
+            .iter()
+            .map(|(time, cmap)| (*time, GenericColorMap::LinearSegmented(cmap.clone())))
+            .collect()
+    }
+
+    pub fn background_color(&self) -> wgpu::Color {
+        color(self.background)
+    }
+
+    /// viewer settings used to open a window, the rest of the state is applied
+    /// once the window exists. Relative paths are relative to the directory `base_dir`
+    /// of the session file
+    pub fn render_config(&self, base_dir: &Path) -> RenderConfig {
+        RenderConfig {
+            no_vsync: false,
+            background_color: self.background_color(),
+            background: self.background_style.background(),
+            environment_map: self.environment_file(base_dir),
+            annotations: Annotations {
+                unit: self.unit.clone(),
+                labels: self.labels.clone(),
+                scale_bar: self.overlay.scale_bar,
+                ticks: self.overlay.axis_ticks,
+                time_label: self.overlay.time_label,
+                ..Default::default()
+            },
+            stereo: self.stereo,
+            cmap_sequence: self.colormap_sequence(),
+            reference_values: self.reference_values.clone(),
+            colorbar: self.colorbar,
+            show_colormap_editor: true,
+            show_volume_info: true,
+            vmin: self.render.vmin,
+            vmax: self.render.vmax,
+            distance_scale: self.render.distance_scale,
+            spatial_interpolation: self.render.spatial_interpolation,
+            temporal_interpolation: self.render.temporal_interpolation,
+            camera: CameraView::default(),
+            render_scale: 1.0,
+            #[cfg(feature = "colormaps")]
+            show_cmap_select: true,
+            duration: Some(Duration::from_secs_f32(self.playback.duration.max(1e-3))),
+            fps: None,
+            reverse_playback: self.playback.backwards,
+            snap_timesteps: self.playback.snap_timesteps,
+            bookmarks_file: None,
+            keyframes: self.keyframes.clone(),
+            volume_file: None,
+            mask_file: self.mask_file(base_dir),
+            invert_mask: self.invert_mask,
+            render_settings: None,
+            transfer_function_file: None,
+            session: Some(self.clone()),
+            compare_file: None,
+            difference: false,
+        }
+    }
+
+    /// render settings and keyframe with the camera of the session, used for offline rendering
+    pub fn view(&self, aabb: &Aabb<f32>) -> (RenderSettings, Keyframe) {
+        let mut settings = RenderSettings {
+            time: self.playback.time,
+            colorbar: self.colorbar,
+            ..Default::default()
+        };
+        self.render.apply(&mut settings);
+        if settings.contours.is_empty() {
+            settings.contours = self
+                .reference_values
+                .iter()
+                .filter(|r| r.surface)
+                .map(ReferenceValue::contour)
+                .collect();
+        }
+        let iso_value = self
+            .reference_values
+            .iter()
+            .find(|r| r.surface)
+            .map(|r| r.value);
+        let camera = self.camera.camera();
+        let keyframe = Keyframe::new(
+            0.,
+            &camera,
+            self.camera.center(),
+            aabb,
+            &settings,
+            iso_value,
+        );
+        (settings, keyframe)
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let session: Self = serde_json::from_str(json)?;
+        session.validate()?;
+        Ok(session)
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn color_array(color: wgpu::Color) -> [f32; 4] {
+    [color.r, color.g, color.b, color.a].map(|c| c as f32)
+}
+
+fn color([r, g, b, a]: [f32; 4]) -> wgpu::Color {
+    let [r, g, b, a] = [r, g, b, a].map(|c| c as f64);
+    wgpu::Color { r, g, b, a }
+}
+
+/// `file` relative to `dir` if it is inside of it, otherwise the absolute path
+#[cfg(not(target_arch = "wasm32"))]
+fn relative_path(file: &Path, dir: &Path) -> String {
+    let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    file.strip_prefix(&dir)
+        .unwrap_or(&file)
+        .to_string_lossy()
+        .into_owned()
+}
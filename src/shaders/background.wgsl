@@ -0,0 +1,73 @@
+const MODE_GRADIENT:u32 = 1;
+const MODE_CHECKERBOARD:u32 = 2;
+const MODE_ENVIRONMENT:u32 = 3;
+
+const PI:f32 = 3.1415926535897932384626433832795;
+// vertical field of view the environment is seen with (the volume uses an orthographic camera)
+const ENVIRONMENT_FOV:f32 = 1.0471975511965976;
+
+struct Uniforms {
+    view_inv: mat4x4<f32>,
+    color_a: vec4<f32>,
+    color_b: vec4<f32>,
+    mode: u32,
+    checker_size: f32,
+    aspect: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+@group(0) @binding(1)
+var environment: texture_2d<f32>;
+@group(0) @binding(2)
+var environment_sampler: sampler;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOut {
+
+    // creates two triangles that cover the whole screen
+    let xy = vec2<f32>(
+        f32(in_vertex_index % 2u == 0u),
+        f32(in_vertex_index < 2u)
+    );
+    return VertexOut(vec4<f32>(xy * 2. - (1.), 1., 1.), vec2<f32>(xy.x, 1. - xy.y));
+}
+
+@fragment
+fn fs_main(vertex_in: VertexOut) -> @location(0) vec4<f32> {
+    switch uniforms.mode {
+        case MODE_GRADIENT: {
+            return mix(uniforms.color_b, uniforms.color_a, vertex_in.tex_coord.y);
+        }
+        case MODE_CHECKERBOARD: {
+            let cell = vec2<u32>(vertex_in.pos.xy / uniforms.checker_size);
+            if (cell.x + cell.y) % 2u == 0u {
+                return uniforms.color_a;
+            }
+            return uniforms.color_b;
+        }
+        case MODE_ENVIRONMENT: {
+            let ndc = vertex_in.tex_coord * 2. - 1.;
+            let t = tan(ENVIRONMENT_FOV / 2.);
+            // view space looks along +z with y pointing down
+            let dir_view = normalize(vec3<f32>(ndc.x * t * uniforms.aspect, ndc.y * t, 1.));
+            let dir = normalize((uniforms.view_inv * vec4<f32>(dir_view, 0.)).xyz);
+            // -y is up on the screen (see sample_volume in raymarch.wgsl)
+            let uv = vec2<f32>(
+                atan2(-dir.x, -dir.z) / (2. * PI) + 0.5,
+                acos(clamp(-dir.y, -1., 1.)) / PI,
+            );
+            return textureSampleLevel(environment, environment_sampler, uv, 0.);
+        }
+        default: {
+            return uniforms.color_a;
+        }
+    }
+}
@@ -0,0 +1,55 @@
+@group(0) @binding(0)
+var source : texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) in_vertex_index: u32,
+) -> VertexOut {
+
+    // creates two triangles that cover the whole screen
+    let xy = vec2<f32>(
+        f32(in_vertex_index % 2u == 0u),
+        f32(in_vertex_index < 2u)
+    );
+    return VertexOut(vec4<f32>(xy * 2. - (1.), 0., 1.), vec2<f32>(xy.x, 1. - xy.y));
+}
+
+@fragment
+fn fs_main(vertex_in: VertexOut) -> @location(0) vec4<f32> {
+    return textureSampleLevel(source, source_sampler, vertex_in.tex_coord, 0.);
+}
+
+// only draws the circle inscribed in the viewport, with a thin border
+@fragment
+fn fs_circle(vertex_in: VertexOut) -> @location(0) vec4<f32> {
+    let r = length(vertex_in.tex_coord * 2. - 1.);
+    // about two pixels wide
+    let border = 1. - 2. * fwidth(r);
+    if r > 1. {
+        discard;
+    }
+    if r > border {
+        return vec4<f32>(1.);
+    }
+    return textureSampleLevel(source, source_sampler, vertex_in.tex_coord, 0.);
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1. / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+// for non sRGB targets
+@fragment
+fn fs_srgb_encode(vertex_in: VertexOut) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(source, source_sampler, vertex_in.tex_coord, 0.);
+    return vec4<f32>(linear_to_srgb(max(color.rgb, vec3<f32>(0.))), color.a);
+}
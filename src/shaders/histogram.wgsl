@@ -0,0 +1,68 @@
+// value histogram of a volume texture.
+// The counts of all dispatches are added up until the bins are cleared.
+
+// must match HISTOGRAM_BINS
+const BINS: u32 = 128u;
+
+struct Settings {
+    vmin: f32,
+    vmax: f32,
+    // 0: linear, 1: logarithmic, 2: power (see normalize_value in raymarch.wgsl)
+    normalization: u32,
+    exponent: f32,
+};
+
+@group(0) @binding(0)
+var volume: texture_3d<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> bins: array<atomic<u32>, BINS>;
+
+@group(0) @binding(2)
+var<uniform> settings: Settings;
+
+// maps the value range to [0, 1] (must match Normalization::normalize)
+fn normalize_value(value: f32) -> f32 {
+    let linear = (value - settings.vmin) / (settings.vmax - settings.vmin);
+    switch settings.normalization {
+        case 1u: {
+            let low = max(settings.vmin, settings.vmax * 1e-6);
+            return log(max(value, low) / low) / log(settings.vmax / low);
+        }
+        case 2u: {
+            return pow(max(linear, 0.), settings.exponent);
+        }
+        default: {
+            return linear;
+        }
+    }
+}
+
+// counts of the workgroup, added to the global bins at the end to reduce contention
+var<workgroup> local_bins: array<atomic<u32>, BINS>;
+
+@compute @workgroup_size(8, 8)
+fn main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    let size = textureDimensions(volume);
+    // each invocation counts a column along z
+    if id.x < size.x && id.y < size.y {
+        for (var z = 0u; z < size.z; z++) {
+            let value = textureLoad(volume, vec3<u32>(id.xy, z), 0).r;
+            // values outside of the range (and NaNs) are not counted
+            if value >= settings.vmin && value <= settings.vmax {
+                let x = normalize_value(value);
+                atomicAdd(&local_bins[min(u32(x * f32(BINS)), BINS - 1u)], 1u);
+            }
+        }
+    }
+    workgroupBarrier();
+    for (var i = index; i < BINS; i += 64u) {
+        let count = atomicLoad(&local_bins[i]);
+        if count > 0u {
+            atomicAdd(&bins[i], count);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+struct Uniforms {
+    view_proj: mat4x4<f32>,
+    // sub-pixel offset in texture coordinates (same as the volume rendering)
+    jitter: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> uniforms: Uniforms;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(vertex_in: VertexIn) -> VertexOut {
+    var pos = uniforms.view_proj * vec4<f32>(vertex_in.position, 1.);
+    pos = vec4<f32>(pos.xy - 2. * uniforms.jitter * pos.w, pos.zw);
+    return VertexOut(pos, vertex_in.color);
+}
+
+@fragment
+fn fs_main(vertex_in: VertexOut) -> @location(0) vec4<f32> {
+    // premultiplied alpha
+    return vec4<f32>(vertex_in.color.rgb * vertex_in.color.a, vertex_in.color.a);
+}
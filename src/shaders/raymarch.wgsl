@@ -1,9 +1,25 @@
 const FILTER_NEAREST:u32 = 0;
 const FILTER_LINEAR:u32 = 1;
+const FILTER_CUBIC:u32 = 2;
 
 const PI:f32 = 3.1415926535897932384626433832795;
 const TWO_PI:f32 = 6.283185307179586476925286766559;
 
+// tuning constants, set from ShaderConstants in renderer.rs
+// maximum number of samples along a ray
+override MAX_STEPS:u32 = 10000u;
+// rays stop once their transmittance drops below this value
+override EARLY_STOPPING_THRESHOLD:f32 = 0.00392156862745098;
+// distance between the volume boundary and the first sample
+override RAY_EPSILON:f32 = 1e-4;
+// distance of the samples used for the gradient in voxels
+override GRADIENT_STEP:f32 = 1.;
+// gradient magnitude (in value range per voxel) at which boundary enhancement saturates
+override BOUNDARY_SATURATION:f32 = 0.1;
+override SILHOUETTE_EXPONENT:f32 = 4.;
+// accumulated opacity at which a ray counts as hit for the depth output
+override DEPTH_OPACITY_THRESHOLD:f32 = 0.5;
+
 struct CameraUniforms {
     view: mat4x4<f32>,
     view_inv: mat4x4<f32>,
@@ -21,7 +37,31 @@ struct Settings {
     distance_scale: f32,
     vmin: f32,
     vmax: f32,
-    gamma_correction: u32
+    gamma_correction: u32,
+    // sub-pixel offset in texture coordinates
+    jitter: vec2<f32>,
+    // offset of the first sample as fraction of the step size
+    ray_offset: f32,
+    boundary_enhancement: f32,
+    silhouette: f32,
+    spatial_filter: u32,
+    // 1 if rays are terminated at the depth of user geometry
+    scene_depth: u32,
+    // values of the contour surfaces, four per vector
+    contour_values: array<vec4<f32>, 2>,
+    // color (rgb and opacity) of the contour surfaces
+    contour_colors: array<vec4<f32>, 8>,
+    num_contours: u32,
+    // 0: linear, 1: logarithmic, 2: power
+    normalization: u32,
+    exponent: f32,
+    // 0: no mask, 1: mask, 2: inverted mask
+    mask: u32,
+    // style of NaN/Inf values, values below vmin and above vmax: 0: clamp, 1: transparent, 2: color
+    value_styles: vec4<u32>,
+    nan_color: vec4<f32>,
+    below_color: vec4<f32>,
+    above_color: vec4<f32>,
 }
 
 
@@ -49,14 +89,14 @@ fn intersectAABB(ray: Ray, box_min: vec3<f32>, box_max: vec3<f32>) -> vec2<f32>
 // ray is created based on view and proj matrix so
 // that it matches the rasterizer used for drawing other stuff
 fn create_ray(view_inv: mat4x4<f32>, proj_inv: mat4x4<f32>, px: vec2<f32>) -> Ray {
-    var far = vec4<f32>((px * 2. - (1.)), -1., 1.);
+    var far = vec4<f32>((px * 2. - (1.)), 1., 1.);
     far.y *= -1.;
     // depth prepass location
     var far_w = view_inv * proj_inv * far;
     far_w /= far_w.w + 1e-4;
 
 
-    var near = vec4<f32>((px * 2. - (1.)), 1., 1.);
+    var near = vec4<f32>((px * 2. - (1.)), 0., 1.);
     near.y *= -1.;
     // depth prepass location
     var near_w = view_inv * proj_inv * near;
@@ -68,6 +108,14 @@ fn create_ray(view_inv: mat4x4<f32>, proj_inv: mat4x4<f32>, px: vec2<f32>) -> Ra
     );
 }
 
+// world space position of a pixel at the given depth
+fn unproject(px: vec2<f32>, depth: f32) -> vec3<f32> {
+    var p = vec4<f32>((px * 2. - (1.)), depth, 1.);
+    p.y *= -1.;
+    let p_w = camera.view_inv * camera.proj_inv * p;
+    return p_w.xyz / p_w.w;
+}
+
 @group(0) @binding(0)
 var volume : texture_3d<f32>;
 @group(0) @binding(1)
@@ -81,6 +129,19 @@ var<uniform> camera: CameraUniforms;
 @group(0) @binding(4)
 var<uniform> settings: Settings;
 
+@group(0) @binding(5)
+var volume_prev : texture_3d<f32>;
+@group(0) @binding(6)
+var volume_next2 : texture_3d<f32>;
+
+// depth of opaque user geometry
+@group(0) @binding(7)
+var scene_depth : texture_2d<f32>;
+
+// opacity multiplier, shared by all timesteps
+@group(0) @binding(8)
+var mask : texture_3d<f32>;
+
 @group(1) @binding(0)
 var cmap : texture_2d<f32>;
 @group(1) @binding(1)
@@ -119,24 +180,158 @@ fn next_pos(pos: ptr<function,vec3<f32>>, step_size: f32, ray_dir: vec3<f32>) ->
 fn sample_volume(pos: vec3<f32>) -> f32 {
     //  origin is in bottom left corner so we need to flip y 
     let pos_m = vec3<f32>(pos.x, 1. - pos.y, pos.z);
-    let sample_curr = textureSampleLevel(volume, volume_sampler, pos_m, 0.).r;
-    let sample_next = textureSampleLevel(volume_next, volume_sampler, pos_m, 0.).r;
+    let sample_curr = sample_texture(volume, pos_m);
+    let sample_next = sample_texture(volume_next, pos_m);
     if settings.temporal_filter == FILTER_NEAREST {
         return sample_curr;
-    } else {
-        let time_fraction = fract(settings.time * f32(settings.time_steps - (1)));
-        return mix(sample_curr, sample_next, time_fraction);
+    }
+    let time_fraction = fract(settings.time * f32(settings.time_steps - (1)));
+    if settings.temporal_filter == FILTER_CUBIC {
+        let sample_prev = sample_texture(volume_prev, pos_m);
+        let sample_next2 = sample_texture(volume_next2, pos_m);
+        return catmull_rom(sample_prev, sample_curr, sample_next, sample_next2, time_fraction);
+    }
+    return mix(sample_curr, sample_next, time_fraction);
+}
+
+fn sample_mask(pos: vec3<f32>) -> f32 {
+    if settings.mask == 0u {
+        return 1.;
+    }
+    let m = textureSampleLevel(mask, volume_sampler, vec3<f32>(pos.x, 1. - pos.y, pos.z), 0.).r;
+    return select(m, 1. - m, settings.mask == 2u);
+}
+
+fn sample_texture(tex: texture_3d<f32>, pos: vec3<f32>) -> f32 {
+    if settings.spatial_filter == FILTER_CUBIC {
+        return sample_tricubic(tex, pos);
+    }
+    return textureSampleLevel(tex, volume_sampler, pos, 0.).r;
+}
+
+// cubic B-spline reconstruction with 8 trilinear lookups
+// see "Fast Third-Order Texture Filtering" (Sigg and Hadwiger, GPU Gems 2)
+fn sample_tricubic(tex: texture_3d<f32>, pos: vec3<f32>) -> f32 {
+    let size = vec3<f32>(textureDimensions(tex));
+    let coord = pos * size - 0.5;
+    let index = floor(coord);
+    let f = coord - index;
+
+    let f2 = f * f;
+    let f3 = f2 * f;
+    let w0 = (1. - 3. * f + 3. * f2 - f3) / 6.;
+    let w1 = (4. - 6. * f2 + 3. * f3) / 6.;
+    let w2 = (1. + 3. * f + 3. * f2 - 3. * f3) / 6.;
+    let w3 = f3 / 6.;
+
+    let g0 = w0 + w1;
+    let g1 = w2 + w3;
+    // sample positions in texture coordinates
+    let h0 = (index - 0.5 + w1 / g0) / size;
+    let h1 = (index + 1.5 + w3 / g1) / size;
+
+    let s000 = textureSampleLevel(tex, volume_sampler, vec3<f32>(h0.x, h0.y, h0.z), 0.).r;
+    let s100 = textureSampleLevel(tex, volume_sampler, vec3<f32>(h1.x, h0.y, h0.z), 0.).r;
+    let s010 = textureSampleLevel(tex, volume_sampler, vec3<f32>(h0.x, h1.y, h0.z), 0.).r;
+    let s110 = textureSampleLevel(tex, volume_sampler, vec3<f32>(h1.x, h1.y, h0.z), 0.).r;
+    let s001 = textureSampleLevel(tex, volume_sampler, vec3<f32>(h0.x, h0.y, h1.z), 0.).r;
+    let s101 = textureSampleLevel(tex, volume_sampler, vec3<f32>(h1.x, h0.y, h1.z), 0.).r;
+    let s011 = textureSampleLevel(tex, volume_sampler, vec3<f32>(h0.x, h1.y, h1.z), 0.).r;
+    let s111 = textureSampleLevel(tex, volume_sampler, vec3<f32>(h1.x, h1.y, h1.z), 0.).r;
+
+    let s00 = g0.x * s000 + g1.x * s100;
+    let s10 = g0.x * s010 + g1.x * s110;
+    let s01 = g0.x * s001 + g1.x * s101;
+    let s11 = g0.x * s011 + g1.x * s111;
+    return g0.z * (g0.y * s00 + g1.y * s10) + g1.z * (g0.y * s01 + g1.y * s11);
+}
+
+// Catmull-Rom spline between p1 and p2
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    return 0.5 * (2. * p1 + (p2 - p0) * t + (2. * p0 - 5. * p1 + 4. * p2 - p3) * t2 + (3. * (p1 - p2) + p3 - p0) * t3);
+}
+
+// central differences in texture space, scaled to value range per voxel
+fn sample_gradient(pos: vec3<f32>) -> vec3<f32> {
+    let h = GRADIENT_STEP / vec3<f32>(textureDimensions(volume));
+    let dx = sample_volume(pos + vec3<f32>(h.x, 0., 0.)) - sample_volume(pos - vec3<f32>(h.x, 0., 0.));
+    let dy = sample_volume(pos + vec3<f32>(0., h.y, 0.)) - sample_volume(pos - vec3<f32>(0., h.y, 0.));
+    let dz = sample_volume(pos + vec3<f32>(0., 0., h.z)) - sample_volume(pos - vec3<f32>(0., 0., h.z));
+    return vec3<f32>(dx, dy, dz) / (2. * (settings.vmax - settings.vmin));
+}
+
+// maps the value range to [0, 1] (must match Normalization::normalize)
+fn normalize_value(value: f32) -> f32 {
+    let linear = (value - settings.vmin) / (settings.vmax - settings.vmin);
+    switch settings.normalization {
+        case 1u: {
+            let low = max(settings.vmin, settings.vmax * 1e-6);
+            return log(max(value, low) / low) / log(settings.vmax / low);
+        }
+        case 2u: {
+            return pow(max(linear, 0.), settings.exponent);
+        }
+        default: {
+            return linear;
+        }
     }
 }
 
 fn sample_cmap(value: f32) -> vec4<f32> {
-    let value_n = (value - settings.vmin) / (settings.vmax - settings.vmin);
-    return textureSampleLevel(cmap, cmap_sampler, vec2<f32>(value_n, 0.5), 0.);
+    let value_n = normalize_value(value);
+    // one row per timestep for time-varying colormaps, blended by the sampler
+    let rows = f32(textureDimensions(cmap).y);
+    let row = (settings.time * (rows - 1.) + 0.5) / rows;
+    return textureSampleLevel(cmap, cmap_sampler, vec2<f32>(value_n, row), 0.);
+}
+
+// true for NaN and infinite values
+fn is_special(value: f32) -> bool {
+    return (bitcast<u32>(value) & 0x7f800000u) == 0x7f800000u;
 }
 
+// color of a value with the given style (see ValueStyle), clamped is the colormap color
+fn style_color(style: u32, color: vec4<f32>, clamped: vec4<f32>) -> vec4<f32> {
+    switch style {
+        case 1u: {
+            return vec4<f32>(0.);
+        }
+        case 2u: {
+            return color;
+        }
+        default: {
+            return clamped;
+        }
+    }
+}
 
-// traces ray trough volume and returns color
-fn trace_ray(ray_in: Ray) -> vec4<f32> {
+// colormap color of the value, NaN/Inf and values outside of [vmin, vmax] are styled separately
+fn sample_color(value: f32) -> vec4<f32> {
+    if is_special(value) {
+        return style_color(settings.value_styles.x, settings.nan_color, vec4<f32>(0.));
+    }
+    if value < settings.vmin {
+        return style_color(settings.value_styles.y, settings.below_color, sample_cmap(value));
+    }
+    if value > settings.vmax {
+        return style_color(settings.value_styles.z, settings.above_color, sample_cmap(value));
+    }
+    return sample_cmap(value);
+}
+
+struct TraceResult {
+    color: vec4<f32>,
+    // distance along the ray where the accumulated opacity exceeds DEPTH_OPACITY_THRESHOLD (-1 for no hit)
+    hit_distance: f32,
+    // distance of the sample seen at the pixel: the hit or else the most opaque sample
+    // (-1 if everything is transparent), like reference::pick
+    sample_distance: f32,
+}
+
+// traces ray trough volume until it leaves the volume or reaches max_distance
+fn trace_ray(ray_in: Ray, max_distance: f32) -> TraceResult {
     let aabb = settings.volume_aabb;
     let aabb_size = aabb.max - aabb.min;
     var ray = ray_in;
@@ -148,10 +343,10 @@ fn trace_ray(ray_in: Ray) -> vec4<f32> {
     let intersec = intersectAABB(ray, aabb_min, aabb_max);
 
     if intersec.x > intersec.y {
-        return vec4<f32>(0.);
+        return TraceResult(vec4<f32>(0.), -1., -1.);
     }
 
-    let start = max(0., intersec.x) + 1e-4;
+    let start = max(0., intersec.x) + RAY_EPSILON + settings.ray_offset * settings.step_size;
     ray.orig += start * ray.dir;
 
     var iters = 0u;
@@ -164,37 +359,87 @@ fn trace_ray(ray_in: Ray) -> vec4<f32> {
 
     var pos = ray.orig;
 
-    let early_stopping_t = 1. / 255.;
     let step_size_g = settings.step_size;
     var sample_pos: vec4<f32>;
+    var hit_distance = -1.;
+    var most_opaque = 0.;
+    var most_opaque_distance = -1.;
+    var last_sample = 0.;
     loop{
+        let ray_distance = length(pos - ray_in.orig);
+        if ray_distance > max_distance {
+            break;
+        }
         sample_pos = next_pos(&pos, step_size_g, ray.dir);
         let step_size = sample_pos.w;
 
         let sample = sample_volume(sample_pos.xyz);
-        let color_tf = sample_cmap(sample);
+        let mask_value = sample_mask(sample_pos.xyz);
+
+        // surfaces where the value crosses a contour value between two samples
+        for (var i = 0u; i < settings.num_contours; i++) {
+            let value = settings.contour_values[i / 4u][i % 4u];
+            if iters > 0u && (last_sample - value) * (sample - value) < 0. {
+                let contour = settings.contour_colors[i];
+                let contour_a = contour.a * mask_value;
+                color += transmittance * contour_a * contour.rgb;
+                transmittance *= 1. - contour_a;
+                if contour_a > most_opaque {
+                    most_opaque = contour_a;
+                    most_opaque_distance = ray_distance;
+                }
+                if hit_distance < 0. && 1. - transmittance >= DEPTH_OPACITY_THRESHOLD {
+                    hit_distance = ray_distance;
+                }
+            }
+        }
+        last_sample = sample;
+
+        let color_tf = sample_color(sample);
         // we try to avoid values that are exactly one as this can cause artifacts
-        let sigma = color_tf.a * (1. - 1e-6);
+        var sigma = color_tf.a * mask_value * (1. - 1e-6);
 
         if sigma > 0. {
             var sample_color = color_tf.rgb;
+            if settings.boundary_enhancement > 0. || settings.silhouette > 0. {
+                let gradient = sample_gradient(sample_pos.xyz);
+                // NaNs in the neighborhood give no gradient
+                let grad_mag = select(length(gradient), 0., is_special(length(gradient)));
+                let boundary = min(grad_mag / BOUNDARY_SATURATION, 1.);
+                sigma *= mix(1., boundary, settings.boundary_enhancement);
+                if grad_mag > 1e-6 {
+                    // gradient in world space (texture space is scaled by the aabb size)
+                    let normal = normalize(gradient * vec3<f32>(volume_size) / aabb_size);
+                    let edge = pow(1. - abs(dot(normal, ray.dir)), SILHOUETTE_EXPONENT);
+                    sigma *= mix(1., edge, settings.silhouette);
+                    sample_color *= 1. - settings.silhouette * edge;
+                }
+            }
             let a_i = 1. - pow(1. - sigma, step_size * distance_scale);
             color += transmittance * a_i * sample_color;
             transmittance *= 1. - a_i;
+            if a_i > most_opaque {
+                most_opaque = a_i;
+                most_opaque_distance = ray_distance;
+            }
+            if hit_distance < 0. && 1. - transmittance >= DEPTH_OPACITY_THRESHOLD {
+                hit_distance = ray_distance;
+            }
 
-            if exp(-transmittance) <= early_stopping_t {
+            if transmittance <= EARLY_STOPPING_THRESHOLD {
                 break;
             }
         }
         // check if within slice
         let slice_test = any(sample_pos.xyz < settings.clipping.min) || any(sample_pos.xyz > settings.clipping.max) ;
 
-        if slice_test || iters > 10000 {
+        if slice_test || iters > MAX_STEPS {
             break;
         }
         iters += 1u;
     }
-    return vec4<f32>(color, 1. - transmittance);
+    let sample_distance = select(most_opaque_distance, hit_distance, hit_distance >= 0.);
+    return TraceResult(vec4<f32>(color, 1. - transmittance), hit_distance, sample_distance);
 }
 
 fn gamma_correction(color: vec4<f32>) -> vec4<f32> {
@@ -202,15 +447,69 @@ fn gamma_correction(color: vec4<f32>) -> vec4<f32> {
 }
 
 
-@fragment
-fn fs_main(vertex_in: VertexOut) -> @location(0) vec4<f32> {
-    let r_pos = vec2<f32>(vertex_in.tex_coord.x, 1. - vertex_in.tex_coord.y);
+struct FragmentOut {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+fn shade(vertex_in: VertexOut) -> FragmentOut {
+    let r_pos = vec2<f32>(vertex_in.tex_coord.x, 1. - vertex_in.tex_coord.y) + settings.jitter;
     let ray = create_ray(camera.view_inv, camera.proj_inv, r_pos);
-    var color = trace_ray(ray);
+
+    // rays stop at opaque user geometry
+    var depth = 1.;
+    var max_distance = 1e30;
+    if settings.scene_depth == 1u {
+        depth = textureLoad(scene_depth, vec2<i32>(vertex_in.pos.xy), 0).r;
+        if depth < 1. {
+            max_distance = length(unproject(r_pos, depth) - ray.orig);
+        }
+    }
+
+    let result = trace_ray(ray, max_distance);
+    var color = result.color;
     if settings.gamma_correction == 1u {
         color = fromLinear(color);
     }
-    return color;
+    if result.hit_distance >= 0. {
+        let hit = camera.proj * camera.view * vec4<f32>(ray.orig + ray.dir * result.hit_distance, 1.);
+        depth = clamp(hit.z / hit.w, 0., 1.);
+    }
+    return FragmentOut(color, depth);
+}
+
+@fragment
+fn fs_main(vertex_in: VertexOut) -> @location(0) vec4<f32> {
+    return shade(vertex_in).color;
+}
+
+// additionally writes the depth of the first hit (or of the user geometry)
+@fragment
+fn fs_main_depth(vertex_in: VertexOut) -> FragmentOut {
+    return shade(vertex_in);
+}
+
+// auxiliary outputs of offline renders: the world space normal of the sample seen at the
+// pixel (facing the camera) and its depth along the view direction, zero if nothing is seen
+@fragment
+fn fs_aov(vertex_in: VertexOut) -> @location(0) vec4<f32> {
+    let r_pos = vec2<f32>(vertex_in.tex_coord.x, 1. - vertex_in.tex_coord.y) + settings.jitter;
+    let ray = create_ray(camera.view_inv, camera.proj_inv, r_pos);
+    let result = trace_ray(ray, 1e30);
+    if result.sample_distance < 0. {
+        return vec4<f32>(0.);
+    }
+    let position = ray.orig + ray.dir * result.sample_distance;
+    let aabb_size = settings.volume_aabb.max - settings.volume_aabb.min;
+    let gradient = sample_gradient((position - settings.volume_aabb.min) / aabb_size);
+    var normal = vec3<f32>(0.);
+    let grad_mag = length(gradient);
+    if grad_mag > 1e-6 && !is_special(grad_mag) {
+        normal = normalize(gradient * vec3<f32>(textureDimensions(volume)) / aabb_size);
+        normal = select(normal, -normal, dot(normal, ray.dir) > 0.);
+    }
+    let depth = abs((camera.view * vec4<f32>(position, 1.)).z);
+    return vec4<f32>(normal, depth);
 }
 
 
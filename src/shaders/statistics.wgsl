@@ -0,0 +1,105 @@
+// statistics of the values of a volume texture. Every workgroup writes the number of finite
+// and non-finite values, the minimum, the maximum and the sums of the (shifted) values and
+// their squares, which are added up on the CPU. The values are also counted in bins
+// between vmin and vmax.
+
+// must match STATISTICS_BINS
+const BINS: u32 = 32u;
+const WORKGROUP_SIZE: u32 = 64u;
+
+struct Settings {
+    timestep: u32,
+    // subtracted from the values before summing them up, reduces the rounding error
+    shift: f32,
+    vmin: f32,
+    vmax: f32,
+};
+
+struct Partial {
+    count: u32,
+    nan: u32,
+    min: f32,
+    max: f32,
+    sum: f32,
+    sum_sq: f32,
+};
+
+@group(0) @binding(0)
+var volume: texture_3d<f32>;
+
+// one for each workgroup of each timestep
+@group(0) @binding(1)
+var<storage, read_write> partials: array<Partial>;
+
+// BINS for each timestep
+@group(0) @binding(2)
+var<storage, read_write> bins: array<atomic<u32>>;
+
+@group(0) @binding(3)
+var<uniform> settings: Settings;
+
+var<workgroup> local_partials: array<Partial, WORKGROUP_SIZE>;
+var<workgroup> local_bins: array<atomic<u32>, BINS>;
+
+// false for NaN and infinite values
+fn is_finite(value: f32) -> bool {
+    return (bitcast<u32>(value) & 0x7f800000u) != 0x7f800000u;
+}
+
+fn combine(a: Partial, b: Partial) -> Partial {
+    return Partial(
+        a.count + b.count,
+        a.nan + b.nan,
+        min(a.min, b.min),
+        max(a.max, b.max),
+        a.sum + b.sum,
+        a.sum_sq + b.sum_sq,
+    );
+}
+
+@compute @workgroup_size(8, 8)
+fn main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+    @builtin(workgroup_id) group: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let size = textureDimensions(volume);
+    var p = Partial(0u, 0u, 3.4e38, -3.4e38, 0., 0.);
+    // each invocation reads a column along z
+    if id.x < size.x && id.y < size.y {
+        for (var z = 0u; z < size.z; z++) {
+            let value = textureLoad(volume, vec3<u32>(id.xy, z), 0).r;
+            if !is_finite(value) {
+                p.nan += 1u;
+                continue;
+            }
+            p.count += 1u;
+            p.min = min(p.min, value);
+            p.max = max(p.max, value);
+            let v = value - settings.shift;
+            p.sum += v;
+            p.sum_sq += v * v;
+            let x = (value - settings.vmin) / (settings.vmax - settings.vmin);
+            atomicAdd(&local_bins[min(u32(max(x, 0.) * f32(BINS)), BINS - 1u)], 1u);
+        }
+    }
+    local_partials[index] = p;
+    workgroupBarrier();
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if index < stride {
+            local_partials[index] = combine(local_partials[index], local_partials[index + stride]);
+        }
+        workgroupBarrier();
+    }
+    if index == 0u {
+        let offset = settings.timestep * groups.x * groups.y;
+        partials[offset + group.y * groups.x + group.x] = local_partials[0];
+    }
+    for (var i = index; i < BINS; i += WORKGROUP_SIZE) {
+        let count = atomicLoad(&local_bins[i]);
+        if count > 0u {
+            atomicAdd(&bins[settings.timestep * BINS + i], count);
+        }
+    }
+}
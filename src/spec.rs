@@ -0,0 +1,743 @@
+use std::{collections::HashMap, io::Cursor, path::Path};
+
+use cgmath::{Vector2, Vector3};
+use image::Rgba;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations::{Annotations, Colorbar, Label, ReferenceValue},
+    background::Background,
+    camera::CameraView,
+    cmap::{
+        ColorMapSequence, GenericColorMap, LinearSegmentedColorMap, TransferFunction,
+        COLORMAP_RESOLUTION,
+    },
+    keyframes::Keyframe,
+    offline::{
+        interpolate_frames, render_animation, render_keyframes, render_volume_with_aovs,
+        unpremultiply, AlphaMode, Animation, AnimationOutput, Aov, AovImages, BitDepth,
+        ContactSheet, MotionBlur, OfflineImage, OfflinePixel, OfflineSettings, OutputPreset,
+    },
+    renderer::{Normalization, RenderSettings, ValueStyle},
+    session::SessionRender,
+    stereo::Stereo,
+    volume::{apply_mask, Mask, Volume},
+    Duration, RenderConfig,
+};
+
+/// contents of the files referenced by a spec, by their path (or url) as written in the spec
+pub type SpecFiles = HashMap<String, Vec<u8>>;
+
+/// declarative description of a visualization (volume, colormap, camera, overlays and animation).
+/// The viewer, `vape4d render`, the python bindings and the web viewer all read the same json,
+/// so one file describes a visualization regardless of how it is rendered.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSpec {
+    pub volume: VolumeSpec,
+    pub colormap: ColormapSpec,
+    pub render: RenderOptions,
+    pub camera: CameraView,
+    pub overlays: OverlaySpec,
+    pub animation: AnimationSpec,
+    /// image size, only used for offline rendering
+    pub output: OutputSpec,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VolumeSpec {
+    /// npy or npz file (an url in the web viewer)
+    pub path: String,
+    /// the array is stored as [C, T, D, H, W] instead of [T, C, D, H, W]
+    pub channel_first: bool,
+    /// physical size of a voxel in array order (depth, height, width).
+    /// Overrides the spacing stored in npz files
+    pub spacing: Option<[f32; 3]>,
+    /// unit of the spacing (e.g. "mm")
+    pub unit: String,
+    /// npy or npz file with a mask of shape [D, H, W] that multiplies the opacity.
+    /// Overrides the mask stored in npz files
+    pub mask: Option<String>,
+    /// hides the masked voxels instead of the others
+    pub invert_mask: bool,
+}
+
+impl VolumeSpec {
+    /// sets the spacing of the spec (if any)
+    pub fn apply(&self, volume: &mut Volume) {
+        if let Some([d, h, w]) = self.spacing {
+            volume.set_spacing(Vector3::new(w, h, d));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColormapSpec {
+    /// builtin colormap ("group/name", e.g. "seaborn/icefire"), comma-separated hex colors
+    /// (e.g. "#000000,#ff0000") or colormap file (json or npy).
+    /// Defaults to seaborn/icefire if the builtin colormaps are available
+    pub name: Option<String>,
+    /// colormaps (builtin or files) blended over time, only their colors are used
+    pub sequence: Vec<String>,
+    /// normalized time in [0, 1] of each colormap of the sequence. Evenly spaced if omitted
+    pub times: Option<Vec<f32>>,
+    /// (value, opacity) control points replacing the opacity of the colormap
+    pub opacity: Option<Vec<(f32, f32)>>,
+}
+
+impl ColormapSpec {
+    /// the colormap including the opacity of the spec
+    pub fn colormap(&self, files: &SpecFiles) -> anyhow::Result<LinearSegmentedColorMap> {
+        let cmap = match &self.name {
+            Some(name) => read_colormap(name, files)?,
+            None => default_colormap()?,
+        }
+        .into_linear_segmented(COLORMAP_RESOLUTION);
+        Ok(match self.transfer_function()? {
+            Some(opacity) => cmap.with_opacity(&opacity),
+            None => cmap,
+        })
+    }
+
+    /// the time-varying colormap, None if the spec has no sequence
+    pub fn sequence(&self, files: &SpecFiles) -> anyhow::Result<Option<ColorMapSequence>> {
+        if self.sequence.is_empty() {
+            return Ok(None);
+        }
+        let cmaps = self
+            .sequence
+            .iter()
+            .map(|name| read_colormap(name, files))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Some(match &self.times {
+            Some(times) => {
+                anyhow::ensure!(
+                    times.len() == cmaps.len(),
+                    "expected {} colormap times, got {}",
+                    cmaps.len(),
+                    times.len()
+                );
+                ColorMapSequence::new(times.iter().copied().zip(cmaps).collect())?
+            }
+            None => ColorMapSequence::uniform(cmaps)?,
+        }))
+    }
+
+    /// colors (sequence or single colormap) and opacity as used by the offline renderer
+    pub fn colormap_sequence(&self, files: &SpecFiles) -> anyhow::Result<ColorMapSequence> {
+        let cmap = self.colormap(files)?;
+        Ok(match self.sequence(files)? {
+            Some(sequence) => sequence.with_opacity(&TransferFunction::from_alpha(&cmap)),
+            None => GenericColorMap::LinearSegmented(cmap).into(),
+        })
+    }
+
+    fn transfer_function(&self) -> anyhow::Result<Option<TransferFunction>> {
+        self.opacity
+            .as_ref()
+            .map(|points| TransferFunction::from_points(points))
+            .transpose()
+    }
+
+    /// files referenced by the colormap section (builtin colormaps are not files)
+    fn files(&self) -> impl Iterator<Item = &str> {
+        self.name
+            .iter()
+            .chain(&self.sequence)
+            .map(String::as_str)
+            .filter(|name| !is_hex_list(name) && builtin_colormap(name).is_none())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderOptions {
+    /// value mapped to the start of the colormap, defaults to the minimum of the volume
+    pub vmin: Option<f32>,
+    /// value mapped to the end of the colormap, defaults to the maximum of the volume
+    pub vmax: Option<f32>,
+    /// larger values make everything more opaque
+    pub distance_scale: f32,
+    /// nearest, linear or cubic
+    pub spatial_interpolation: String,
+    /// nearest, linear or cubic
+    pub temporal_interpolation: String,
+    /// background color (RGBA)
+    pub background: [f32; 4],
+    /// off, side-by-side, anaglyph, top-bottom or pair (the last two offline only)
+    pub stereo: String,
+    /// interocular distance for stereo rendering (the smallest side of the volume is 1)
+    pub eye_separation: f32,
+    /// distance between the samples along a ray, defaults to the step size of the viewer
+    pub step_size: Option<f32>,
+    /// rendered region of the volume, relative to its size (min, max)
+    pub clipping: Option<[[f32; 3]; 2]>,
+    /// linear, log, sqrt or {"power": exponent}
+    pub normalization: Normalization,
+    /// vmin and vmax default to the value range of each timestep instead of the whole volume
+    pub per_timestep_range: bool,
+    /// clamp, transparent or {"color": [r, g, b, a]}
+    pub nan_style: ValueStyle,
+    pub below_style: ValueStyle,
+    pub above_style: ValueStyle,
+    pub boundary_enhancement: f32,
+    pub silhouette: f32,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            vmin: None,
+            vmax: None,
+            distance_scale: 1.,
+            spatial_interpolation: "linear".to_string(),
+            temporal_interpolation: "linear".to_string(),
+            background: [0., 0., 0., 1.],
+            stereo: "off".to_string(),
+            eye_separation: 0.1,
+            step_size: None,
+            clipping: None,
+            normalization: Normalization::Linear,
+            per_timestep_range: false,
+            nan_style: ValueStyle::Transparent,
+            below_style: ValueStyle::Clamp,
+            above_style: ValueStyle::Clamp,
+            boundary_enhancement: 0.,
+            silhouette: 0.,
+        }
+    }
+}
+
+impl RenderOptions {
+    pub fn background_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.background.map(|c| c as f64);
+        wgpu::Color { r, g, b, a }
+    }
+
+    /// settings of the volume rendering (without surfaces and colorbar)
+    pub fn settings(&self) -> anyhow::Result<RenderSettings> {
+        let settings = RenderSettings::from(SessionRender {
+            clipping: self.clipping,
+            step_size: self.step_size,
+            spatial_interpolation: self.spatial_interpolation.parse()?,
+            temporal_interpolation: self.temporal_interpolation.parse()?,
+            distance_scale: self.distance_scale,
+            vmin: self.vmin,
+            vmax: self.vmax,
+            per_timestep_range: self.per_timestep_range,
+            normalization: self.normalization,
+            nan_style: self.nan_style,
+            below_style: self.below_style,
+            above_style: self.above_style,
+            boundary_enhancement: self.boundary_enhancement,
+            silhouette: self.silhouette,
+            ..Default::default()
+        });
+        anyhow::ensure!(settings.step_size > 0., "step size must be positive");
+        if let Some(clipping) = &settings.clipping_aabb {
+            anyhow::ensure!(
+                (0..3).all(|i| clipping.min[i] < clipping.max[i]),
+                "the clipping region must not be empty"
+            );
+        }
+        Ok(settings)
+    }
+
+    pub fn stereo(&self) -> anyhow::Result<Stereo> {
+        Ok(Stereo {
+            mode: self.stereo.parse()?,
+            eye_separation: self.eye_separation,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverlaySpec {
+    pub scale_bar: bool,
+    pub axis_ticks: bool,
+    /// timestep in the upper left corner
+    pub time_label: bool,
+    /// named values marked on the legend and optionally drawn as surfaces
+    pub reference_values: Vec<ReferenceValue>,
+    /// legend of the colormap with value ticks
+    pub colorbar: Option<Colorbar>,
+    /// text labels anchored to points of the volume (in world space)
+    pub labels: Vec<Label>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnimationSpec {
+    /// normalized times of the rendered frames (offline rendering)
+    pub times: Vec<f32>,
+    /// number of frames rendered per time (slow motion)
+    pub frame_interpolation: u32,
+    /// duration of one animation loop in seconds (viewer)
+    pub duration: Option<f32>,
+    /// playback speed in timesteps per second, used instead of `duration` (viewer)
+    pub fps: Option<f32>,
+    /// plays the animation backwards (viewer)
+    pub reverse: bool,
+    /// shows whole timesteps only (viewer)
+    pub snap_timesteps: bool,
+    /// camera animation, rendered instead of `times` if there are keyframes
+    pub keyframes: Vec<Keyframe>,
+    /// frames per second of the rendered camera animation
+    pub keyframe_fps: f32,
+    /// number of times averaged per rendered frame for motion blur along the time axis,
+    /// 1 for none (offline rendering)
+    pub motion_blur_samples: u32,
+    /// exposure time of the motion blur as a fraction of the time between two frames
+    pub shutter: f32,
+}
+
+impl Default for AnimationSpec {
+    fn default() -> Self {
+        Self {
+            times: vec![0.],
+            frame_interpolation: 1,
+            duration: None,
+            fps: None,
+            reverse: false,
+            snap_timesteps: false,
+            keyframes: Vec::new(),
+            keyframe_fps: 30.,
+            motion_blur_samples: 1,
+            shutter: 0.5,
+        }
+    }
+}
+
+impl AnimationSpec {
+    pub fn motion_blur(&self) -> MotionBlur {
+        MotionBlur {
+            samples: self.motion_blur_samples,
+            shutter: self.shutter,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSpec {
+    pub width: u32,
+    pub height: u32,
+    /// named output preset (see `OutputPreset`), overrides width and height
+    pub preset: Option<String>,
+    /// samples per pixel along each axis, defaults to the preset's value or 1
+    pub supersampling: Option<u32>,
+    /// renders all frames into a single grid image instead of one image per frame
+    pub contact_sheet: Option<ContactSheet>,
+    /// bit depth of the written images ("8", "16" or "float"), exr files are always float
+    pub bit_depth: BitDepth,
+    /// alpha of images with a transparent background ("straight" or "premultiplied")
+    pub alpha: AlphaMode,
+    /// auxiliary outputs ("depth", "normal" or "alpha") written next to each image
+    pub aovs: Vec<Aov>,
+}
+
+impl Default for OutputSpec {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 1024,
+            preset: None,
+            supersampling: None,
+            contact_sheet: None,
+            bit_depth: BitDepth::default(),
+            alpha: AlphaMode::default(),
+            aovs: Vec::new(),
+        }
+    }
+}
+
+impl RenderSpec {
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let spec: Self = serde_json::from_str(json)?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    pub fn read_json<R: std::io::Read>(reader: R) -> anyhow::Result<Self> {
+        let spec: Self = serde_json::from_reader(reader)?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// checks the values the json format can not express, called when the spec is parsed.
+    /// Specs changed afterwards have to be checked again
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.camera.validate()?;
+        anyhow::ensure!(
+            self.output.width > 0 && self.output.height > 0,
+            "image size must be positive"
+        );
+        if let Some(name) = &self.output.preset {
+            anyhow::ensure!(
+                OutputPreset::find(name).is_some(),
+                "unknown output preset '{name}' (expected one of {})",
+                OutputPreset::all()
+                    .iter()
+                    .map(|p| format!("'{}'", p.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        anyhow::ensure!(
+            self.output.supersampling.map_or(true, |s| s >= 1),
+            "supersampling must be at least 1"
+        );
+        if let Some(spacing) = self.volume.spacing {
+            anyhow::ensure!(spacing.iter().all(|s| *s > 0.), "spacing must be positive");
+        }
+        anyhow::ensure!(
+            !self.animation.times.is_empty(),
+            "animation needs at least one time"
+        );
+        anyhow::ensure!(
+            self.animation.frame_interpolation >= 1,
+            "frame_interpolation must be at least 1"
+        );
+        for keyframe in &self.animation.keyframes {
+            keyframe.validate()?;
+        }
+        anyhow::ensure!(
+            self.animation.keyframe_fps > 0.,
+            "keyframe_fps must be positive"
+        );
+        anyhow::ensure!(
+            self.animation.motion_blur_samples >= 1,
+            "motion_blur_samples must be at least 1"
+        );
+        anyhow::ensure!(
+            (0. ..=1.).contains(&self.animation.shutter),
+            "shutter must be in [0, 1]"
+        );
+        Ok(())
+    }
+
+    /// paths (or urls) of all files referenced by the spec
+    pub fn files(&self) -> Vec<&str> {
+        std::iter::once(self.volume.path.as_str())
+            .filter(|path| !path.is_empty())
+            .chain(self.volume.mask.as_deref())
+            .chain(self.colormap.files())
+            .collect()
+    }
+
+    /// reads all referenced files, relative paths are relative to `base_dir`
+    pub fn load_files(&self, base_dir: &Path) -> anyhow::Result<SpecFiles> {
+        self.files()
+            .into_iter()
+            .map(|path| {
+                let data = std::fs::read(base_dir.join(path))
+                    .map_err(|e| anyhow::anyhow!("cannot read '{path}': {e}"))?;
+                Ok((path.to_string(), data))
+            })
+            .collect()
+    }
+
+    /// the volumes (one per channel) with the spacing and mask of the spec
+    pub fn volumes(&self, files: &SpecFiles) -> anyhow::Result<Vec<Volume>> {
+        anyhow::ensure!(!self.volume.path.is_empty(), "the spec has no volume");
+        let data = spec_file(files, &self.volume.path)?;
+        let mut volumes = Volume::load_numpy(Cursor::new(data), !self.volume.channel_first)?;
+        volumes.iter_mut().for_each(|v| self.volume.apply(v));
+        let mask = self
+            .volume
+            .mask
+            .as_ref()
+            .map(|path| Mask::load_numpy(Cursor::new(spec_file(files, path)?)))
+            .transpose()?;
+        apply_mask(&mut volumes, mask, self.volume.invert_mask)?;
+        Ok(volumes)
+    }
+
+    pub fn annotations(&self) -> Annotations {
+        Annotations {
+            scale_bar: self.overlays.scale_bar,
+            ticks: self.overlays.axis_ticks,
+            time_label: self.overlays.time_label,
+            unit: self.volume.unit.clone(),
+            labels: self.overlays.labels.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// viewer settings described by the spec
+    pub fn render_config(&self, files: &SpecFiles) -> anyhow::Result<RenderConfig> {
+        Ok(RenderConfig {
+            no_vsync: false,
+            background_color: self.render.background_color(),
+            background: Background::Solid,
+            environment_map: None,
+            annotations: self.annotations(),
+            stereo: self.render.stereo()?,
+            cmap_sequence: self.colormap.sequence(files)?,
+            reference_values: self.overlays.reference_values.clone(),
+            colorbar: self.overlays.colorbar,
+            show_colormap_editor: true,
+            show_volume_info: true,
+            vmin: self.render.vmin,
+            vmax: self.render.vmax,
+            distance_scale: self.render.distance_scale,
+            spatial_interpolation: self.render.spatial_interpolation.parse()?,
+            temporal_interpolation: self.render.temporal_interpolation.parse()?,
+            camera: self.camera,
+            render_scale: 1.0,
+            #[cfg(feature = "colormaps")]
+            show_cmap_select: true,
+            duration: self.animation.duration.map(Duration::from_secs_f32),
+            fps: self.animation.fps,
+            reverse_playback: self.animation.reverse,
+            snap_timesteps: self.animation.snap_timesteps,
+            bookmarks_file: None,
+            keyframes: self.animation.keyframes.clone(),
+            volume_file: None,
+            mask_file: None,
+            invert_mask: self.volume.invert_mask,
+            render_settings: None,
+            transfer_function_file: None,
+            session: None,
+            compare_file: None,
+            difference: false,
+        })
+    }
+
+    /// render settings of the offline images, with the surfaces of the reference values
+    /// and the colorbar
+    pub fn render_settings(&self) -> anyhow::Result<RenderSettings> {
+        Ok(RenderSettings {
+            contours: self
+                .overlays
+                .reference_values
+                .iter()
+                .filter(|r| r.surface)
+                .map(ReferenceValue::contour)
+                .collect(),
+            colorbar: self.overlays.colorbar,
+            ..self.render.settings()?
+        })
+    }
+
+    /// normalized times of all rendered frames
+    pub fn frames(&self) -> Vec<f32> {
+        interpolate_frames(&self.animation.times, self.animation.frame_interpolation)
+    }
+
+    /// resolution and supersampling of the output (or its preset).
+    /// Presets also scale the legend of `annotations`
+    fn output_size(&self, annotations: &mut Annotations) -> anyhow::Result<(Vector2<u32>, u32)> {
+        Ok(match &self.output.preset {
+            Some(name) => {
+                let preset = OutputPreset::find(name)
+                    .ok_or_else(|| anyhow::anyhow!("unknown output preset '{name}'"))?;
+                annotations.scale = preset.legend_scale();
+                (
+                    preset.resolution,
+                    self.output.supersampling.unwrap_or(preset.supersampling),
+                )
+            }
+            None => (
+                Vector2::new(self.output.width, self.output.height),
+                self.output.supersampling.unwrap_or(1),
+            ),
+        })
+    }
+
+    /// renders `num_frames` frames and writes them to `output` one by one.
+    /// The frames follow the camera animation if the spec has keyframes, or orbit around
+    /// the volume with `turntable`. They sweep from the first to the last of the animation
+    /// times, the whole time range if there is only one time (a turntable stays at it).
+    /// `progress` is called with the number of written and total frames
+    pub async fn render_animation(
+        &self,
+        volumes: Vec<Volume>,
+        files: &SpecFiles,
+        num_frames: usize,
+        turntable: bool,
+        output: &AnimationOutput,
+        progress: impl FnMut(usize, usize),
+    ) -> anyhow::Result<()> {
+        let mut annotations = self.annotations();
+        let (resolution, supersampling) = self.output_size(&mut annotations)?;
+        let time_range = match self.animation.times.as_slice() {
+            [time] if turntable => (*time, *time),
+            [first, .., last] => (*first, *last),
+            _ => (0., 1.),
+        };
+        let animation = Animation {
+            time_range,
+            keyframes: self.animation.keyframes.clone(),
+            view: self.camera,
+            turntable,
+            settings: self.render_settings()?,
+            supersampling,
+            background: self.render.background_color(),
+            bit_depth: self.output.bit_depth,
+            alpha: self.output.alpha,
+            aovs: self.output.aovs.clone(),
+            motion_blur: self.animation.motion_blur(),
+            ..Animation::new(num_frames, resolution)
+        };
+        render_animation(
+            volumes,
+            self.colormap.colormap_sequence(files)?,
+            &animation,
+            Some(&annotations),
+            self.render.stereo()?,
+            output,
+            progress,
+        )
+        .await
+    }
+
+    /// renders the frames of the spec offline (a single image with a contact sheet)
+    /// as 8 bit or float images, see [OfflinePixel].
+    /// `volumes` are usually loaded with [RenderSpec::volumes]
+    pub async fn render<Px: OfflinePixel>(
+        &self,
+        volumes: Vec<Volume>,
+        files: &SpecFiles,
+    ) -> anyhow::Result<Vec<OfflineImage<Px>>> {
+        let frames = self.render_frames(volumes, files, &[]).await?;
+        Ok(frames.into_iter().map(|(image, _)| image).collect())
+    }
+
+    /// like [RenderSpec::render], but also renders the auxiliary outputs of the spec
+    pub async fn render_with_aovs<Px: OfflinePixel>(
+        &self,
+        volumes: Vec<Volume>,
+        files: &SpecFiles,
+    ) -> anyhow::Result<Vec<(OfflineImage<Px>, AovImages)>> {
+        self.render_frames(volumes, files, &self.output.aovs).await
+    }
+
+    async fn render_frames<Px: OfflinePixel>(
+        &self,
+        volumes: Vec<Volume>,
+        files: &SpecFiles,
+        aovs: &[Aov],
+    ) -> anyhow::Result<Vec<(OfflineImage<Px>, AovImages)>> {
+        if self.output.alpha == AlphaMode::Straight && self.render.background[3] < 1. {
+            // divided by alpha before the quantization, faint colors would be lost otherwise
+            let frames = self
+                .render_premultiplied::<Rgba<f32>>(volumes, files, aovs)
+                .await?;
+            return Ok(frames
+                .into_iter()
+                .map(|(image, aovs)| (unpremultiply(&image), aovs))
+                .collect());
+        }
+        self.render_premultiplied(volumes, files, aovs).await
+    }
+
+    async fn render_premultiplied<Px: OfflinePixel>(
+        &self,
+        volumes: Vec<Volume>,
+        files: &SpecFiles,
+        aovs: &[Aov],
+    ) -> anyhow::Result<Vec<(OfflineImage<Px>, AovImages)>> {
+        let mut annotations = self.annotations();
+        let (resolution, supersampling) = self.output_size(&mut annotations)?;
+        let settings = OfflineSettings {
+            settings: self.render_settings()?,
+            view: self.camera,
+            supersampling,
+            background: self.render.background_color(),
+            motion_blur: self.animation.motion_blur(),
+            ..OfflineSettings::new(resolution)
+        };
+        let frames = if !self.animation.keyframes.is_empty() {
+            anyhow::ensure!(
+                aovs.is_empty(),
+                "auxiliary outputs of camera animations are only written by render_animation"
+            );
+            let mut keyframes = self.animation.keyframes.clone();
+            keyframes.sort_by(|a, b| a.at.total_cmp(&b.at));
+            render_keyframes(
+                volumes,
+                self.colormap.colormap_sequence(files)?,
+                resolution,
+                &keyframes,
+                self.animation.keyframe_fps,
+                settings.background,
+                &settings.settings,
+                supersampling,
+                Some(&annotations),
+                self.render.stereo()?,
+            )
+            .await?
+            .into_iter()
+            .map(|image| (image, Vec::new()))
+            .collect()
+        } else {
+            render_volume_with_aovs(
+                volumes,
+                self.colormap.colormap_sequence(files)?,
+                &self.frames(),
+                &settings,
+                Some(&annotations),
+                self.render.stereo()?,
+                aovs,
+            )
+            .await?
+        };
+        let Some(sheet) = &self.output.contact_sheet else {
+            return Ok(frames);
+        };
+        let (images, aov_frames): (Vec<_>, Vec<_>) = frames.into_iter().unzip();
+        let aovs = aovs
+            .iter()
+            .enumerate()
+            .map(|(i, aov)| {
+                let images: Vec<_> = aov_frames.iter().map(|f| f[i].1.clone()).collect();
+                (*aov, sheet.compose(&images, wgpu::Color::BLACK))
+            })
+            .collect();
+        Ok(vec![(sheet.compose(&images, settings.background), aovs)])
+    }
+}
+
+fn spec_file<'a>(files: &'a SpecFiles, path: &str) -> anyhow::Result<&'a [u8]> {
+    files
+        .get(path)
+        .map(Vec::as_slice)
+        .ok_or_else(|| anyhow::anyhow!("file '{path}' was not loaded"))
+}
+
+fn is_hex_list(name: &str) -> bool {
+    name.starts_with('#') || name.contains(',')
+}
+
+fn read_colormap(name: &str, files: &SpecFiles) -> anyhow::Result<GenericColorMap> {
+    if is_hex_list(name) {
+        return Ok(GenericColorMap::LinearSegmented(
+            LinearSegmentedColorMap::from_hex_list(name)?,
+        ));
+    }
+    match builtin_colormap(name) {
+        Some(cmap) => Ok(cmap.clone()),
+        None => GenericColorMap::read(Cursor::new(spec_file(files, name)?)),
+    }
+}
+
+#[cfg(feature = "colormaps")]
+fn builtin_colormap(name: &str) -> Option<&'static GenericColorMap> {
+    let (group, name) = name.split_once('/')?;
+    crate::cmap::COLORMAPS.get(group)?.get(name)
+}
+
+#[cfg(not(feature = "colormaps"))]
+fn builtin_colormap(_name: &str) -> Option<&'static GenericColorMap> {
+    None
+}
+
+fn default_colormap() -> anyhow::Result<GenericColorMap> {
+    builtin_colormap("seaborn/icefire")
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("the spec has no colormap"))
+}
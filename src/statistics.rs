@@ -0,0 +1,648 @@
+use std::collections::HashMap;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    histogram::Histogram,
+    readback::BufferMapping,
+    volume::{Volume, VolumeGPU},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    histogram::{HistogramGPU, HISTOGRAM_BINS},
+    renderer::Normalization,
+};
+
+/// number of bins of [Statistics::histogram] (must match BINS in statistics.wgsl)
+pub const STATISTICS_BINS: usize = 32;
+
+/// statistics of the finite values of a timestep (or of all timesteps).
+/// NaN and infinite values are only counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    /// number of finite values
+    pub count: u64,
+    /// number of NaN and infinite values
+    pub nan: u64,
+    /// NaN if there are no finite values
+    pub min: f32,
+    pub max: f32,
+    pub mean: f64,
+    pub std: f64,
+    /// number of values in evenly sized bins between the min and max value of the volume
+    pub histogram: Vec<u32>,
+}
+
+impl Statistics {
+    /// `sum` and `sum_sq` are the sums of the values minus `shift` and of their squares
+    fn from_sums(
+        count: u64,
+        nan: u64,
+        (min, max): (f32, f32),
+        (sum, sum_sq): (f64, f64),
+        shift: f64,
+        histogram: Vec<u32>,
+    ) -> Self {
+        if count == 0 {
+            return Self {
+                count,
+                nan,
+                min: f32::NAN,
+                max: f32::NAN,
+                mean: f64::NAN,
+                std: f64::NAN,
+                histogram,
+            };
+        }
+        let mean = sum / count as f64;
+        Self {
+            count,
+            nan,
+            min,
+            max,
+            mean: shift + mean,
+            std: (sum_sq / count as f64 - mean * mean).max(0.).sqrt(),
+            histogram,
+        }
+    }
+
+    /// computes the statistics of each timestep on the CPU
+    pub fn compute(volume: &Volume) -> Vec<Self> {
+        let (vmin, vmax) = (volume.min_value, volume.max_value);
+        let shift = (vmin as f64 + vmax as f64) / 2.;
+        (0..volume.timesteps as usize)
+            .map(|t| {
+                let (mut count, mut nan) = (0, 0);
+                let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+                let (mut sum, mut sum_sq) = (0., 0.);
+                let mut histogram = vec![0; STATISTICS_BINS];
+                for v in volume.timestep(t) {
+                    let v = v.to_f32();
+                    if !v.is_finite() {
+                        nan += 1;
+                        continue;
+                    }
+                    count += 1;
+                    min = min.min(v);
+                    max = max.max(v);
+                    let d = v as f64 - shift;
+                    sum += d;
+                    sum_sq += d * d;
+                    let x = ((v - vmin) / (vmax - vmin)).max(0.);
+                    let bin = (x * STATISTICS_BINS as f32) as usize;
+                    histogram[bin.min(STATISTICS_BINS - 1)] += 1;
+                }
+                Self::from_sums(count, nan, (min, max), (sum, sum_sq), shift, histogram)
+            })
+            .collect()
+    }
+
+    /// statistics of all values of `stats` (e.g. all timesteps)
+    pub fn combine(stats: &[Self]) -> Self {
+        let finite = || stats.iter().filter(|s| s.count > 0);
+        let count: u64 = stats.iter().map(|s| s.count).sum();
+        let nan = stats.iter().map(|s| s.nan).sum();
+        let mut histogram = vec![0; STATISTICS_BINS];
+        for s in stats {
+            for (bin, c) in histogram.iter_mut().zip(&s.histogram) {
+                *bin += c;
+            }
+        }
+        if count == 0 {
+            return Self::from_sums(0, nan, (0., 0.), (0., 0.), 0., histogram);
+        }
+        let mean = finite().map(|s| s.count as f64 * s.mean).sum::<f64>() / count as f64;
+        // mean of the squares of each part is std² + mean²
+        let mean_sq = finite()
+            .map(|s| s.count as f64 * (s.std * s.std + s.mean * s.mean))
+            .sum::<f64>()
+            / count as f64;
+        Self {
+            count,
+            nan,
+            min: finite().map(|s| s.min).fold(f32::INFINITY, f32::min),
+            max: finite().map(|s| s.max).fold(f32::NEG_INFINITY, f32::max),
+            mean,
+            std: (mean_sq - mean * mean).max(0.).sqrt(),
+            histogram,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct StatisticsSettingsUniform {
+    timestep: u32,
+    shift: f32,
+    vmin: f32,
+    vmax: f32,
+}
+
+/// statistics of a workgroup (Partial in statistics.wgsl)
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Partial {
+    count: u32,
+    nan: u32,
+    min: f32,
+    max: f32,
+    sum: f32,
+    sum_sq: f32,
+}
+
+/// statistics that are computed on the GPU and read back
+struct PendingStatistics {
+    readback: wgpu::Buffer,
+    /// number of workgroups per timestep
+    groups: usize,
+    timesteps: usize,
+    shift: f32,
+}
+
+/// computes the statistics of all timesteps with a parallel reduction in a compute shader.
+/// The workgroups' partial sums are added up on the CPU once they are read back asynchronously.
+pub struct StatisticsGPU {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pending: Option<PendingStatistics>,
+    mapping: BufferMapping,
+}
+
+impl StatisticsGPU {
+    /// returns None if compute shaders are not supported (e.g. WebGL)
+    pub fn new(device: &wgpu::Device, adapter: &wgpu::Adapter) -> Option<Self> {
+        if !adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return None;
+        }
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/statistics.wgsl"));
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("statistics bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("statistics pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("statistics pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "main",
+            compilation_options: Default::default(),
+        });
+        Some(Self {
+            pipeline,
+            bind_group_layout,
+            pending: None,
+            mapping: BufferMapping::default(),
+        })
+    }
+
+    /// true while statistics are computed or read back
+    pub fn busy(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// starts computing the statistics of each timestep of the volume.
+    /// Does nothing while the last ones are not finished.
+    pub fn dispatch(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, volume: &VolumeGPU) {
+        if self.busy() {
+            return;
+        }
+        let (vmin, vmax) = (volume.volume.min_value, volume.volume.max_value);
+        let shift = (vmin + vmax) / 2.;
+        let res = volume.volume.resolution;
+        let (groups_x, groups_y) = (res.x.div_ceil(8), res.y.div_ceil(8));
+        let groups = (groups_x * groups_y) as usize;
+        let timesteps = volume.textures.len();
+        let partials_size = (timesteps * groups * std::mem::size_of::<Partial>()) as u64;
+        let bins_size = (timesteps * STATISTICS_BINS * std::mem::size_of::<u32>()) as u64;
+        let partials = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("statistics partials"),
+            size: partials_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bins = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("statistics bins"),
+            size: bins_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("statistics readback"),
+            size: partials_size + bins_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_groups: Vec<_> = volume
+            .textures
+            .iter()
+            .enumerate()
+            .map(|(t, texture)| {
+                let settings = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("statistics settings"),
+                    contents: bytemuck::bytes_of(&StatisticsSettingsUniform {
+                        timestep: t as u32,
+                        shift,
+                        vmin,
+                        vmax,
+                    }),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("statistics bind group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(
+                                &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                            ),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: partials.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: bins.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: settings.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("statistics encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("statistics pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            for bind_group in &bind_groups {
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch_workgroups(groups_x, groups_y, 1);
+            }
+        }
+        encoder.copy_buffer_to_buffer(&partials, 0, &readback, 0, partials_size);
+        encoder.copy_buffer_to_buffer(&bins, 0, &readback, partials_size, bins_size);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.mapping.map(&readback);
+        self.pending = Some(PendingStatistics {
+            readback,
+            groups,
+            timesteps,
+            shift,
+        });
+    }
+
+    /// returns the statistics of each timestep of the last dispatch once they are available
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<Vec<Statistics>> {
+        self.pending.as_ref()?;
+        let result = self.mapping.poll(device)?;
+        let pending = self.pending.take().unwrap();
+        if let Err(err) = result {
+            log::error!("failed to read back statistics: {err}");
+            return None;
+        }
+        let data = pending.readback.slice(..).get_mapped_range();
+        let partials_size = pending.timesteps * pending.groups * std::mem::size_of::<Partial>();
+        let partials: &[Partial] = bytemuck::cast_slice(&data[..partials_size]);
+        let bins: &[u32] = bytemuck::cast_slice(&data[partials_size..]);
+        let statistics = partials
+            .chunks(pending.groups)
+            .zip(bins.chunks(STATISTICS_BINS))
+            .map(|(partials, bins)| {
+                let (mut count, mut nan) = (0, 0);
+                let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+                let (mut sum, mut sum_sq) = (0., 0.);
+                for p in partials {
+                    nan += p.nan as u64;
+                    if p.count == 0 {
+                        continue;
+                    }
+                    count += p.count as u64;
+                    min = min.min(p.min);
+                    max = max.max(p.max);
+                    sum += p.sum as f64;
+                    sum_sq += p.sum_sq as f64;
+                }
+                let shift = pending.shift as f64;
+                Statistics::from_sums(count, nan, (min, max), (sum, sum_sq), shift, bins.to_vec())
+            })
+            .collect();
+        drop(data);
+        pending.readback.unmap();
+        Some(statistics)
+    }
+}
+
+/// statistics of a channel: one per timestep and one of all timesteps
+pub struct ChannelStatistics {
+    pub timesteps: Vec<Statistics>,
+    pub global: Statistics,
+}
+
+impl ChannelStatistics {
+    fn new(timesteps: Vec<Statistics>) -> Self {
+        Self {
+            global: Statistics::combine(&timesteps),
+            timesteps,
+        }
+    }
+}
+
+/// statistics shown in the volume info window.
+/// Computed (on the GPU if possible) the first time a channel is shown.
+pub struct VolumeStatistics {
+    channels: HashMap<usize, ChannelStatistics>,
+    /// channel whose statistics are currently computed on the GPU
+    requested: Option<usize>,
+    gpu: Option<StatisticsGPU>,
+}
+
+impl VolumeStatistics {
+    pub fn new(device: &wgpu::Device, adapter: &wgpu::Adapter) -> Self {
+        Self {
+            channels: HashMap::new(),
+            requested: None,
+            gpu: StatisticsGPU::new(device, adapter),
+        }
+    }
+
+    /// statistics of `channel`, None until they are computed
+    pub fn get(&self, channel: usize) -> Option<&ChannelStatistics> {
+        self.channels.get(&channel)
+    }
+
+    /// forgets all statistics, e.g. after a new volume was loaded
+    pub fn reset(&mut self) {
+        self.channels.clear();
+        self.requested = None;
+    }
+
+    /// starts computing the statistics of `channel` if they are not known yet
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        volume: &VolumeGPU,
+        channel: usize,
+    ) {
+        match &mut self.gpu {
+            Some(gpu) => {
+                if let Some(timesteps) = gpu.poll(device) {
+                    if let Some(requested) = self.requested.take() {
+                        self.channels
+                            .insert(requested, ChannelStatistics::new(timesteps));
+                    }
+                }
+                if !self.channels.contains_key(&channel) && !gpu.busy() {
+                    gpu.dispatch(device, queue, volume);
+                    self.requested = Some(channel);
+                }
+            }
+            None => {
+                self.channels
+                    .entry(channel)
+                    .or_insert_with(|| ChannelStatistics::new(Statistics::compute(&volume.volume)));
+            }
+        }
+    }
+}
+
+/// statistics, percentiles and histogram of a volume, e.g. to pick the value range before rendering
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeSummary {
+    pub statistics: Statistics,
+    /// value of each requested percentile, NaN if there are no finite values
+    pub percentiles: Vec<f32>,
+    /// histogram between the min and max value
+    pub histogram: Histogram,
+}
+
+impl VolumeSummary {
+    /// computes the summary of all timesteps (or only `timestep`) with the compute shaders of the
+    /// viewer on the shared headless GPU, or on the CPU if there is none.
+    /// `percentiles` are in [0, 100]. They are found in the histogram bin containing them, which
+    /// is refined by a second histogram, so they are accurate to about `(max - min) / 128²`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn compute(
+        volume: Volume,
+        timestep: Option<usize>,
+        percentiles: &[f32],
+    ) -> anyhow::Result<Self> {
+        if let Some(t) = timestep {
+            anyhow::ensure!(
+                t < volume.timesteps as usize,
+                "timestep {t} out of range (volume has {} timesteps)",
+                volume.timesteps
+            );
+        }
+        if let Some(p) = percentiles.iter().find(|p| !(0. ..=100.).contains(*p)) {
+            anyhow::bail!("percentiles must be in [0, 100], got {p}");
+        }
+        let mut backend = SummaryBackend::new(volume).await;
+        let timesteps = backend.statistics()?;
+        let statistics = match timestep {
+            Some(t) => timesteps[t].clone(),
+            None => Statistics::combine(&timesteps),
+        };
+        let (min, max) = (statistics.min, statistics.max);
+        let mut bins = vec![0; HISTOGRAM_BINS as usize];
+        if statistics.count == 0 || min == max {
+            // nothing to refine, all finite values (if any) are the same
+            bins[0] = statistics.count.min(u32::MAX as u64) as u32;
+            return Ok(Self {
+                percentiles: vec![min; percentiles.len()],
+                histogram: Histogram {
+                    bins,
+                    min,
+                    max,
+                    normalization: Normalization::Linear,
+                },
+                statistics,
+            });
+        }
+        let histogram = backend.histogram(timestep, min, max)?;
+        let percentiles = percentiles
+            .iter()
+            .map(|p| {
+                // position among the sorted values, like numpy's default (linear) method
+                let rank = *p as f64 / 100. * (statistics.count - 1) as f64;
+                let (bin, below) = find_bin(&histogram.bins, rank);
+                let (lo, hi) = bin_range(&histogram, bin);
+                let fine = backend.histogram(timestep, lo, hi)?;
+                let (bin, below_fine) = find_bin(&fine.bins, rank - below);
+                let (lo, hi) = bin_range(&fine, bin);
+                // values are assumed to be evenly spread within the bin
+                let fraction = (rank - below - below_fine + 0.5) / fine.bins[bin].max(1) as f64;
+                Ok((lo + (hi - lo) * fraction.clamp(0., 1.) as f32).clamp(min, max))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            statistics,
+            percentiles,
+            histogram,
+        })
+    }
+}
+
+/// index of the bin that contains the value at `rank` and the number of values before it
+#[cfg(not(target_arch = "wasm32"))]
+fn find_bin(bins: &[u32], rank: f64) -> (usize, f64) {
+    let mut below = 0.;
+    for (i, count) in bins.iter().enumerate() {
+        if rank < below + *count as f64 {
+            return (i, below);
+        }
+        below += *count as f64;
+    }
+    // rounding errors at the end, the last value is in the last non-empty bin
+    let last = bins.iter().rposition(|c| *c > 0).unwrap_or(bins.len() - 1);
+    (last, below - bins[last] as f64)
+}
+
+/// value range of a bin of a linear histogram
+#[cfg(not(target_arch = "wasm32"))]
+fn bin_range(histogram: &Histogram, bin: usize) -> (f32, f32) {
+    let width = (histogram.max - histogram.min) / histogram.bins.len() as f32;
+    let lo = histogram.min + width * bin as f32;
+    (lo, lo + width)
+}
+
+/// where a [VolumeSummary] is computed. Unlike in the viewer, the results are waited for.
+#[cfg(not(target_arch = "wasm32"))]
+enum SummaryBackend {
+    Gpu {
+        context: std::sync::Arc<crate::WGPUContext>,
+        volume: VolumeGPU,
+        statistics: StatisticsGPU,
+        histogram: HistogramGPU,
+    },
+    Cpu(Volume),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SummaryBackend {
+    async fn new(volume: Volume) -> Self {
+        let context = match crate::WGPUContext::shared().await {
+            Ok(context) => context,
+            Err(err) => {
+                log::warn!("{err}, computing the statistics on the CPU");
+                return Self::Cpu(volume);
+            }
+        };
+        let (device, adapter) = (&context.device, &context.adapter);
+        match StatisticsGPU::new(device, adapter).zip(HistogramGPU::new(device, adapter)) {
+            Some((statistics, histogram)) => Self::Gpu {
+                volume: VolumeGPU::new(device, &context.queue, volume),
+                statistics,
+                histogram,
+                context,
+            },
+            None => Self::Cpu(volume),
+        }
+    }
+
+    /// statistics of each timestep
+    fn statistics(&mut self) -> anyhow::Result<Vec<Statistics>> {
+        match self {
+            Self::Gpu {
+                context,
+                volume,
+                statistics,
+                ..
+            } => {
+                statistics.dispatch(&context.device, &context.queue, volume);
+                context.device.poll(wgpu::Maintain::Wait);
+                statistics
+                    .poll(&context.device)
+                    .ok_or_else(|| anyhow::anyhow!("failed to read back the statistics"))
+            }
+            Self::Cpu(volume) => Ok(Statistics::compute(volume)),
+        }
+    }
+
+    /// linear histogram of all timesteps (or only `timestep`) between `min` and `max`
+    fn histogram(
+        &mut self,
+        timestep: Option<usize>,
+        min: f32,
+        max: f32,
+    ) -> anyhow::Result<Histogram> {
+        match self {
+            Self::Gpu {
+                context,
+                volume,
+                histogram,
+                ..
+            } => {
+                histogram.dispatch(
+                    &context.device,
+                    &context.queue,
+                    volume,
+                    timestep,
+                    (min, max),
+                    Normalization::Linear,
+                );
+                context.device.poll(wgpu::Maintain::Wait);
+                histogram
+                    .poll(&context.device)
+                    .ok_or_else(|| anyhow::anyhow!("failed to read back the histogram"))
+            }
+            Self::Cpu(volume) => Ok(Histogram::compute(
+                volume,
+                timestep,
+                min,
+                max,
+                Normalization::Linear,
+            )),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use cgmath::{InnerSpace, Matrix3, Point3, Quaternion, Rotation, Vector2};
+use image::{imageops, ImageBuffer, Pixel};
+
+use crate::camera::{Camera, Projection};
+
+/// how the views of the two eyes are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StereoMode {
+    #[default]
+    Off,
+    /// left eye in the left half, right eye in the right half.
+    /// Both views are squeezed to half the width (half side-by-side), as expected by 3D displays.
+    SideBySide,
+    /// red (left eye) / cyan (right eye) anaglyph
+    Anaglyph,
+    /// left eye above the right eye, both squeezed to half the height.
+    /// The usual layout of stereo 360° panoramas (offline only)
+    TopBottom,
+    /// left and right eye next to each other at the full resolution,
+    /// the image is twice as wide (offline only)
+    Pair,
+}
+
+impl StereoMode {
+    /// modes of the viewer
+    pub const ALL: [Self; 3] = [Self::Off, Self::SideBySide, Self::Anaglyph];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StereoMode::Off => "Off",
+            StereoMode::SideBySide => "Side-by-Side",
+            StereoMode::Anaglyph => "Anaglyph",
+            StereoMode::TopBottom => "Top-Bottom",
+            StereoMode::Pair => "Pair",
+        }
+    }
+
+    /// only supported by offline rendering, not by the viewer
+    pub fn offline_only(&self) -> bool {
+        !Self::ALL.contains(self)
+    }
+}
+
+impl std::str::FromStr for StereoMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok(StereoMode::Off),
+            "side-by-side" | "sbs" => Ok(StereoMode::SideBySide),
+            "anaglyph" => Ok(StereoMode::Anaglyph),
+            "top-bottom" | "over-under" => Ok(StereoMode::TopBottom),
+            "pair" => Ok(StereoMode::Pair),
+            _ => anyhow::bail!(
+                "unknown stereo mode '{s}' (expected off, side-by-side, anaglyph, top-bottom or pair)"
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Stereo {
+    pub mode: StereoMode,
+    /// interocular distance in world units (the smallest side of the volume is 1)
+    pub eye_separation: f32,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            mode: StereoMode::Off,
+            eye_separation: 0.1,
+        }
+    }
+}
+
+/// which eye a view is rendered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eye {
+    /// stereo is off
+    Both,
+    Left,
+    Right,
+}
+
+impl Stereo {
+    /// splits a view with the `viewport` `[x, y, width, height]` into the views of the eyes.
+    /// Both eyes converge at `target`.
+    pub fn views<P: Projection + Copy>(
+        &self,
+        camera: &Camera<P>,
+        target: Point3<f32>,
+        viewport: [f32; 4],
+    ) -> Vec<(Eye, Camera<P>, [f32; 4])> {
+        if self.mode == StereoMode::Off {
+            return vec![(Eye::Both, *camera, viewport)];
+        }
+        let [left, right] = eye_cameras(camera, target, self.eye_separation);
+        match self.mode {
+            StereoMode::SideBySide => {
+                let [x, y, width, height] = viewport;
+                vec![
+                    (Eye::Left, left, [x, y, width / 2., height]),
+                    (Eye::Right, right, [x + width / 2., y, width / 2., height]),
+                ]
+            }
+            _ => vec![(Eye::Left, left, viewport), (Eye::Right, right, viewport)],
+        }
+    }
+}
+
+impl Stereo {
+    /// cameras and resolutions of the images rendered for an image of `resolution`.
+    /// A single image if stereo is off, otherwise one per eye.
+    pub fn images<P: Projection + Copy>(
+        &self,
+        camera: &Camera<P>,
+        target: Point3<f32>,
+        resolution: Vector2<u32>,
+    ) -> Vec<(Camera<P>, Vector2<u32>)> {
+        if self.mode == StereoMode::Off {
+            return vec![(*camera, resolution)];
+        }
+        eye_cameras(camera, target, self.eye_separation)
+            .into_iter()
+            .zip(self.eye_resolutions(resolution))
+            .collect()
+    }
+
+    /// resolutions of the images of the eyes composed into an image of `resolution`
+    pub fn eye_resolutions(&self, resolution: Vector2<u32>) -> Vec<Vector2<u32>> {
+        match self.mode {
+            StereoMode::Off => vec![resolution],
+            StereoMode::SideBySide => {
+                let half = resolution.x / 2;
+                vec![
+                    Vector2::new(half, resolution.y),
+                    Vector2::new(resolution.x - half, resolution.y),
+                ]
+            }
+            StereoMode::TopBottom => {
+                let half = resolution.y / 2;
+                vec![
+                    Vector2::new(resolution.x, half),
+                    Vector2::new(resolution.x, resolution.y - half),
+                ]
+            }
+            StereoMode::Anaglyph | StereoMode::Pair => vec![resolution, resolution],
+        }
+    }
+}
+
+/// cameras of the left and right eye, moved sideways by half the `eye_separation`
+/// and rotated towards `target` (toe-in)
+pub fn eye_cameras<P: Projection + Copy>(
+    camera: &Camera<P>,
+    target: Point3<f32>,
+    eye_separation: f32,
+) -> [Camera<P>; 2] {
+    let view_t: Matrix3<f32> = camera.rotation.invert().into();
+    let right = view_t.x;
+    let up = view_t.y;
+    [-0.5, 0.5].map(|side| {
+        let position = camera.position + right * side * eye_separation;
+        let rotation = Quaternion::look_at((target - position).normalize(), up);
+        Camera::new(position, rotation, camera.projection)
+    })
+}
+
+/// combines the images rendered for the [Stereo::images] into one
+pub fn compose<P: Pixel>(
+    mode: StereoMode,
+    mut images: Vec<ImageBuffer<P, Vec<P::Subpixel>>>,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    if let [left, right] = images.as_slice() {
+        match mode {
+            StereoMode::SideBySide | StereoMode::Pair => {
+                let mut img = ImageBuffer::new(left.width() + right.width(), left.height());
+                imageops::replace(&mut img, left, 0, 0);
+                imageops::replace(&mut img, right, left.width() as i64, 0);
+                return img;
+            }
+            StereoMode::TopBottom => {
+                let mut img = ImageBuffer::new(left.width(), left.height() + right.height());
+                imageops::replace(&mut img, left, 0, 0);
+                imageops::replace(&mut img, right, 0, left.height() as i64);
+                return img;
+            }
+            StereoMode::Anaglyph => {
+                let mut img = right.clone();
+                for (out, l) in img.pixels_mut().zip(left.pixels()) {
+                    let (out, l) = (out.channels_mut(), l.channels());
+                    out[0] = l[0];
+                    if l[3] > out[3] {
+                        out[3] = l[3];
+                    }
+                }
+                return img;
+            }
+            StereoMode::Off => {}
+        }
+    }
+    images.swap_remove(0)
+}
@@ -0,0 +1,167 @@
+#[cfg(target_arch = "wasm32")]
+use instant::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+/// what the animation does when it reaches the last timestep
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlaybackMode {
+    /// starts again at the first timestep
+    #[default]
+    Loop,
+    /// plays backwards to the first timestep and forwards again
+    PingPong,
+    /// stops at the last timestep
+    Once,
+}
+
+impl PlaybackMode {
+    pub const ALL: [Self; 3] = [Self::Loop, Self::PingPong, Self::Once];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlaybackMode::Loop => "Loop",
+            PlaybackMode::PingPong => "Ping-Pong",
+            PlaybackMode::Once => "Once",
+        }
+    }
+}
+
+/// playback of the time animation.
+/// The time is normalized, 0 is the first and 1 the last timestep.
+pub struct Timeline {
+    pub playing: bool,
+    pub mode: PlaybackMode,
+    /// time it takes to play from the first to the last timestep
+    pub duration: Duration,
+    /// plays from the last to the first timestep
+    pub backwards: bool,
+    /// shows whole timesteps only instead of interpolating between them
+    pub snap: bool,
+    /// physical time between two timesteps
+    pub time_step: f32,
+    /// unit of the physical time
+    pub time_unit: String,
+    /// the direction is reversed (in ping-pong mode)
+    reverse: bool,
+    /// timesteps not yet played in snap mode
+    carry: f32,
+}
+
+impl Timeline {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            playing: true,
+            mode: PlaybackMode::Loop,
+            duration,
+            backwards: false,
+            snap: false,
+            time_step: 1.,
+            time_unit: String::new(),
+            reverse: false,
+            carry: 0.,
+        }
+    }
+
+    /// speed in timesteps per second
+    pub fn fps(&self, timesteps: u32) -> f32 {
+        timesteps.saturating_sub(1) as f32 / self.duration.as_secs_f32().max(1e-3)
+    }
+
+    /// sets the duration to play `fps` timesteps per second
+    pub fn set_fps(&mut self, fps: f32, timesteps: u32) {
+        self.duration = Duration::from_secs_f32(timesteps.saturating_sub(1) as f32 / fps.max(1e-3));
+    }
+
+    /// moves `time` by `dt` in the playback direction if the animation is playing
+    pub fn advance(&mut self, time: &mut f32, dt: Duration, timesteps: u32) {
+        if !self.playing {
+            return;
+        }
+        let delta = dt.as_secs_f32() / self.duration.as_secs_f32().max(1e-3);
+        if !self.snap {
+            *time = self.move_by(*time, delta, 1., 1.);
+            return;
+        }
+        // whole timesteps are played once enough time has passed
+        let last = timesteps.saturating_sub(1) as f32;
+        self.carry += delta * last;
+        let steps = self.carry.floor();
+        self.carry -= steps;
+        let index = (*time * last).round();
+        // the last timestep is shown before the loop starts again
+        let index = self.move_by(index, steps, last, last + 1.);
+        *time = index / last.max(1.);
+    }
+
+    /// moves `pos` by `delta` towards the end at `end` (or the start at 0), depending on the
+    /// direction and mode. Looping positions are wrapped to `period`.
+    fn move_by(&mut self, pos: f32, delta: f32, end: f32, period: f32) -> f32 {
+        let forward = self.backwards == self.reverse;
+        let p = if forward { pos + delta } else { pos - delta };
+        match self.mode {
+            PlaybackMode::Loop => p.rem_euclid(period),
+            PlaybackMode::Once => {
+                if (forward && p >= end) || (!forward && p <= 0.) {
+                    self.playing = false;
+                }
+                p.clamp(0., end)
+            }
+            // reflected at the ends
+            PlaybackMode::PingPong => {
+                if p >= end {
+                    self.reverse = !self.backwards;
+                    (2. * end - p).max(0.)
+                } else if p <= 0. {
+                    self.reverse = self.backwards;
+                    (-p).min(end)
+                } else {
+                    p
+                }
+            }
+        }
+    }
+
+    /// starts or pauses the animation, a finished animation starts again at the beginning
+    pub fn toggle(&mut self, time: &mut f32) {
+        self.playing = !self.playing;
+        if self.playing && self.mode == PlaybackMode::Once {
+            if !self.backwards && *time >= 1. {
+                *time = 0.;
+            } else if self.backwards && *time <= 0. {
+                *time = 1.;
+            }
+        }
+    }
+
+    /// pauses the animation and moves `time` by `steps` timesteps, snapped to a timestep
+    pub fn step(&mut self, time: &mut f32, steps: i32, timesteps: u32) {
+        self.playing = false;
+        let last = timesteps.saturating_sub(1) as i32;
+        let current = (*time * last as f32).round() as i32;
+        *time = index_time((current + steps).clamp(0, last) as usize, timesteps);
+    }
+
+    /// pauses the animation and moves `time` by `delta` (relative to the whole animation)
+    #[cfg(feature = "gamepad")]
+    pub fn scrub(&mut self, time: &mut f32, delta: f32) {
+        self.playing = false;
+        *time = (*time + delta).clamp(0., 1.);
+    }
+
+    /// physical time of the (fractional) `timestep`
+    pub fn physical_time(&self, timestep: f32) -> f32 {
+        timestep * self.time_step
+    }
+}
+
+/// (fractional) index of the timestep at the normalized `time`
+pub fn time_index(time: f32, timesteps: u32) -> f32 {
+    time * timesteps.saturating_sub(1) as f32
+}
+
+/// normalized time of the timestep `index`
+pub fn index_time(index: usize, timesteps: u32) -> f32 {
+    index as f32 / timesteps.saturating_sub(1).max(1) as f32
+}
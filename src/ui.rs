@@ -1,19 +1,75 @@
 use std::{f32::consts::PI, ops::RangeInclusive, time::Duration};
 
-use egui::{emath::Numeric, epaint::TextShape, vec2};
-use egui_plot::{Plot, PlotImage, PlotPoint};
+use egui::{emath::Numeric, vec2};
+use egui_plot::{Plot, PlotImage, PlotPoint, Text, VLine};
 
 use crate::{
-    cmap::{ColorMap, COLORMAP_RESOLUTION},
+    annotations::{Colorbar, ColorbarPosition, LabelFormat, ReferenceValue},
+    background::Background,
+    bookmarks::NUM_BOOKMARK_KEYS,
+    cmap::{ColorMap, TransferFunction, COLORMAP_RESOLUTION},
+    config::{Theme, UiConfig},
+    controller::{CameraInertia, CameraSmoothing, NavigationMode, AXIS_VIEWS, UP_AXES},
+    crop,
+    histogram::Histogram,
+    history::Edits,
+    keyframes,
+    measure::MeasureKind,
+    probe::Probe,
+    renderer::{
+        Interpolation, Normalization, RenderSettings, ValueStyle, HIGHLIGHT_COLORS, MAX_CONTOURS,
+    },
+    screenshot::ScreenshotTarget,
+    stereo::StereoMode,
+    timeline::{self, PlaybackMode},
+    volume::{Aabb, Volume},
     WindowContext,
 };
 
 #[cfg(feature = "colormaps")]
-use crate::cmap::COLORMAPS;
+use crate::cmap::{COLORMAPS, COLORMAP_CATEGORIES};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    annotations, bookmarks, offline::OutputPreset, presets, recording::VideoFormat,
+    session::Session,
+};
+
+/// percentiles used as vmin and vmax by the auto range button,
+/// robust against outliers unlike the minimum and maximum
+const AUTO_RANGE_PERCENTILES: (f32, f32) = (1., 99.);
 
 pub(crate) fn ui(state: &mut WindowContext) {
-    let ctx = state.ui_renderer.winit.egui_ctx();
+    let ctx = &state.ui_renderer.winit.egui_ctx().clone();
     let with_animation = state.volumes[0].volume.timesteps > 1;
+
+    // Ctrl +/- zooms the interface as well
+    if ctx.zoom_factor() != state.ui_config.scale {
+        state.set_ui_config(UiConfig {
+            scale: ctx.zoom_factor(),
+            ..state.ui_config
+        });
+    }
+
+    // text fields have their own undo
+    if !ctx.wants_keyboard_input() {
+        let shortcut = |modifiers, key| egui::KeyboardShortcut::new(modifiers, key);
+        let (undo, redo) = ctx.input_mut(|i| {
+            let redo = i.consume_shortcut(&shortcut(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::Z,
+            )) || i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Y));
+            (
+                i.consume_shortcut(&shortcut(egui::Modifiers::COMMAND, egui::Key::Z)),
+                redo,
+            )
+        });
+        if undo {
+            state.undo();
+        }
+        if redo {
+            state.redo();
+        }
+    }
     egui::Window::new("Render Settings").show(ctx, |ui| {
         egui::Grid::new("render_settings")
             .num_columns(2)
@@ -21,37 +77,92 @@ pub(crate) fn ui(state: &mut WindowContext) {
             .show(ui, |ui| {
                 if with_animation {
                     ui.label("Time");
-                    ui.add(
-                        egui::Slider::new(&mut state.render_settings.time, (0.)..=(1.))
-                            .clamp_to_range(true)
-                            .fixed_decimals(2),
-                    );
-                    if ui.button(if state.playing { "||" } else { "▶" }).clicked() {
-                        state.playing = !state.playing;
-                    }
+                    timeline_ui(ui, state);
                     ui.end_row();
-                    ui.label("Animation Duration");
-                    ui.add(
-                        egui::DragValue::from_get_set(|v| {
-                            if let Some(v) = v {
-                                state.animation_duration = Duration::from_secs_f64(v);
-                                return v;
-                            } else {
-                                return state.animation_duration.as_secs_f64();
-                            }
-                        })
-                        .suffix("s")
-                        .range((0.)..=1000.),
-                    );
+
+                    let timesteps = state.volumes[0].volume.timesteps;
+                    let timeline = &mut state.timeline;
+                    ui.label("Playback");
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("playback_mode")
+                            .selected_text(timeline.mode.name())
+                            .show_ui(ui, |ui| {
+                                for mode in PlaybackMode::ALL {
+                                    ui.selectable_value(&mut timeline.mode, mode, mode.name());
+                                }
+                            });
+                        ui.toggle_value(&mut timeline.backwards, "⏪ Reverse")
+                            .on_hover_text("Plays from the last to the first timestep");
+                        ui.checkbox(&mut timeline.snap, "Snap")
+                            .on_hover_text("Shows whole timesteps only instead of interpolating between them");
+                    });
+                    ui.end_row();
+
+                    ui.label("Speed");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::from_get_set(|v| {
+                                if let Some(v) = v {
+                                    timeline.duration = Duration::from_secs_f64(v);
+                                    return v;
+                                } else {
+                                    return timeline.duration.as_secs_f64();
+                                }
+                            })
+                            .suffix("s")
+                            .range((0.)..=1000.),
+                        )
+                        .on_hover_text("Duration of the animation from the first to the last timestep");
+                        ui.add(
+                            egui::DragValue::from_get_set(|v| {
+                                if let Some(v) = v {
+                                    timeline.set_fps(v as f32, timesteps);
+                                }
+                                timeline.fps(timesteps) as f64
+                            })
+                            .suffix(" fps")
+                            .speed(0.1)
+                            .range((0.01)..=240.),
+                        )
+                        .on_hover_text("Timesteps per second");
+                    });
+                    ui.end_row();
+
+                    ui.label("Time Step");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::DragValue::new(&mut state.timeline.time_step)
+                                .speed(0.01)
+                                .range(0.0..=f32::MAX),
+                        )
+                        .on_hover_text("Physical time between two timesteps");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut state.timeline.time_unit)
+                                .hint_text("unit")
+                                .desired_width(40.),
+                        );
+                    });
                     ui.end_row();
+
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        ui.label("Video");
+                        recording_ui(ui, state);
+                        ui.end_row();
+                    }
                 }
 
                 ui.label("Step Size");
                 ui.add(
                     egui::DragValue::new(&mut state.render_settings.step_size)
-                        .speed(0.01)
-                        .range((1e-3)..=(0.1)),
-                );
+                        .speed(1e-4)
+                        .range((1e-5)..=(0.1))
+                        .min_decimals(5),
+                )
+                .on_hover_text(format!(
+                    "default: {:.5} (half a voxel)",
+                    state.volumes[0].volume.default_step_size()
+                ));
                 ui.end_row();
 
                 ui.label("Distance Scale");
@@ -61,6 +172,162 @@ pub(crate) fn ui(state: &mut WindowContext) {
                         .range((1e-4)..=(100000.)),
                 );
                 ui.end_row();
+
+                ui.label("Navigation");
+                ui.horizontal(|ui| {
+                    let controller = &mut state.controller;
+                    for mode in [NavigationMode::Orbit, NavigationMode::Fly] {
+                        ui.selectable_value(&mut controller.mode, mode, mode.name());
+                    }
+                    if controller.mode == NavigationMode::Fly {
+                        ui.add(
+                            egui::DragValue::new(&mut controller.fly_speed)
+                                .speed(0.01)
+                                .range((1e-4)..=100.)
+                                .prefix("speed: "),
+                        )
+                        .on_hover_text("distance per second, scroll to change it");
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Fly: WASD moves, Space and Shift move up and down, dragging looks around",
+                );
+                ui.end_row();
+
+                ui.label("Up Axis");
+                let up = state.controller.up;
+                let up_name = UP_AXES
+                    .iter()
+                    .find(|(_, axis)| Some(*axis) == up)
+                    .map_or("Free", |(name, _)| name);
+                egui::ComboBox::new("up_axis", "")
+                    .selected_text(up_name)
+                    .show_ui(ui, |ui| {
+                        if ui.selectable_label(up.is_none(), "Free").clicked() {
+                            state.controller.lock_up(&state.camera, None);
+                        }
+                        for (name, axis) in UP_AXES {
+                            if ui.selectable_label(up == Some(axis), name).clicked() {
+                                state.controller.lock_up(&state.camera, Some(axis));
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Keeps the axis pointing up on the screen, e.g. for geophysical data.\n\
+                        Q/E and Alt+drag roll the camera if the up axis is free.",
+                    );
+                ui.end_row();
+
+                ui.label("Camera Smoothing");
+                ui.horizontal(|ui| {
+                    let smoothing = &mut state.controller.smoothing;
+                    egui::ComboBox::new("camera_smoothing", "")
+                        .selected_text(smoothing.name())
+                        .show_ui(ui, |ui| {
+                            for s in [
+                                CameraSmoothing::Off,
+                                CameraSmoothing::EXPONENTIAL,
+                                CameraSmoothing::ONE_EURO,
+                            ] {
+                                if ui
+                                    .selectable_label(smoothing.name() == s.name(), s.name())
+                                    .clicked()
+                                {
+                                    *smoothing = s;
+                                }
+                            }
+                        });
+                    match smoothing {
+                        CameraSmoothing::Off => {}
+                        CameraSmoothing::Exponential { factor } => {
+                            ui.add(
+                                egui::DragValue::new(factor)
+                                    .speed(0.01)
+                                    .range(0.0..=0.99)
+                                    .prefix("factor: "),
+                            );
+                        }
+                        CameraSmoothing::OneEuro { min_cutoff, beta } => {
+                            ui.add(
+                                egui::DragValue::new(min_cutoff)
+                                    .speed(0.01)
+                                    .range(0.01..=10.)
+                                    .prefix("cutoff: "),
+                            );
+                            ui.add(
+                                egui::DragValue::new(beta)
+                                    .speed(0.001)
+                                    .range(0.0..=1.)
+                                    .prefix("beta: "),
+                            );
+                        }
+                    }
+                })
+                .response
+                .on_hover_text("Smooths camera input, e.g. for screen recordings");
+                ui.end_row();
+
+                ui.label("Camera Inertia");
+                ui.horizontal(|ui| {
+                    let inertia = &mut state.controller.inertia;
+                    for (name, value) in [
+                        ("orbit: ", &mut inertia.orbit),
+                        ("pan: ", &mut inertia.pan),
+                        ("zoom: ", &mut inertia.zoom),
+                    ] {
+                        ui.add(
+                            egui::DragValue::new(value)
+                                .speed(0.01)
+                                .range(0.0..=0.99)
+                                .prefix(name),
+                        );
+                    }
+                    if ui
+                        .add_enabled(*inertia != CameraInertia::default(), egui::Button::new("↺"))
+                        .on_hover_text("Reset to default")
+                        .clicked()
+                    {
+                        *inertia = CameraInertia::default();
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Motion kept per frame after the input, 0 stops immediately.\n\
+                    Hold Ctrl for precise mode without smoothing and inertia.",
+                );
+                ui.end_row();
+
+                ui.label("Zoom");
+                ui.horizontal(|ui| {
+                    if ui.button("Fit").on_hover_text("fit the volume (key F)").clicked() {
+                        state.zoom_to_volume();
+                    }
+                    if ui
+                        .button("Reset")
+                        .on_hover_text("look from the initial direction and fit the volume (key R)")
+                        .clicked()
+                    {
+                        state.reset_camera();
+                    }
+                    if ui
+                        .button("Clip Region")
+                        .on_hover_text("fit the clipped region (key C)")
+                        .clicked()
+                    {
+                        state.zoom_to_clip_region();
+                    }
+                    if ui
+                        .button("1:1")
+                        .on_hover_text("one voxel per pixel (key 0)")
+                        .clicked()
+                    {
+                        state.zoom_to_voxel_scale();
+                    }
+                });
+                ui.end_row();
+
                 ui.label("Background Color");
                 let mut bg = [
                     state.background_color.r as f32,
@@ -76,22 +343,108 @@ pub(crate) fn ui(state: &mut WindowContext) {
                     a: bg[3] as f64,
                 };
                 ui.end_row();
+
+                ui.label("Background");
+                ui.horizontal(|ui| {
+                    let gray = wgpu::Color {
+                        r: 0.5,
+                        g: 0.5,
+                        b: 0.5,
+                        a: 1.,
+                    };
+                    egui::ComboBox::new("background", "")
+                        .selected_text(state.background.name())
+                        .show_ui(ui, |ui| {
+                            for b in [
+                                Background::Solid,
+                                Background::Gradient { top: gray },
+                                Background::Checkerboard {
+                                    color: gray,
+                                    size: 16.,
+                                },
+                            ] {
+                                if ui
+                                    .selectable_label(state.background.name() == b.name(), b.name())
+                                    .clicked()
+                                {
+                                    state.background = b;
+                                }
+                            }
+                            ui.add_enabled_ui(state.background_renderer.has_environment(), |ui| {
+                                ui.selectable_value(
+                                    &mut state.background,
+                                    Background::Environment,
+                                    Background::Environment.name(),
+                                )
+                                .on_disabled_hover_text("Load an environment image first");
+                            });
+                        });
+                    match &mut state.background {
+                        Background::Solid | Background::Environment => {}
+                        Background::Gradient { top: color }
+                        | Background::Checkerboard { color, .. } => {
+                            let mut c = [
+                                color.r as f32,
+                                color.g as f32,
+                                color.b as f32,
+                                color.a as f32,
+                            ];
+                            ui.color_edit_button_rgba_premultiplied(&mut c);
+                            *color = wgpu::Color {
+                                r: c[0] as f64,
+                                g: c[1] as f64,
+                                b: c[2] as f64,
+                                a: c[3] as f64,
+                            };
+                        }
+                    }
+                    if let Background::Checkerboard { size, .. } = &mut state.background {
+                        ui.add(
+                            egui::DragValue::new(size)
+                                .range(1.0..=256.)
+                                .suffix("px"),
+                        );
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui
+                        .button("Load…")
+                        .on_hover_text("Load an equirectangular environment image")
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("image", &["png", "jpg", "jpeg", "hdr", "exr"])
+                            .pick_file()
+                        {
+                            match image::open(&path).map_err(anyhow::Error::from).and_then(|img| {
+                                state.background_renderer.set_environment(
+                                    &state.wgpu_context.device,
+                                    &state.wgpu_context.queue,
+                                    &img.to_rgba8(),
+                                )
+                            }) {
+                                Ok(()) => {
+                                    state.background = Background::Environment;
+                                    state.environment_file = Some(path);
+                                }
+                                Err(e) => log::error!("failed to load environment image: {:?}", e),
+                            }
+                        }
+                    }
+                });
+                ui.end_row();
                 if state.volumes.len() > 1 {
                     ui.label("Channel");
                     egui::ComboBox::new("selected_channel", "")
                         .selected_text(
                             state
                                 .selected_channel
-                                .map_or("All".to_string(), |v| v.to_string()),
+                                .map_or("All".to_string(), |v| channel_name(state, v)),
                         )
                         .show_ui(ui, |ui| {
                             ui.selectable_value(&mut state.selected_channel, None, "All");
                             for i in 0..state.volumes.len() {
-                                ui.selectable_value(
-                                    &mut state.selected_channel,
-                                    Some(i),
-                                    format!("{}", i),
-                                );
+                                let name = channel_name(state, i);
+                                ui.selectable_value(&mut state.selected_channel, Some(i), name);
                             }
                         });
                     ui.end_row();
@@ -109,165 +462,801 @@ pub(crate) fn ui(state: &mut WindowContext) {
                 ui.label("Spatial Interpolation");
 
                 egui::ComboBox::new("spatial_interpolation", "")
-                    .selected_text(match state.render_settings.spatial_filter {
-                        wgpu::FilterMode::Nearest => "Nearest",
-                        wgpu::FilterMode::Linear => "Linear",
-                    })
+                    .selected_text(state.render_settings.spatial_filter.name())
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut state.render_settings.spatial_filter,
-                            wgpu::FilterMode::Nearest,
-                            "Nearest",
-                        );
-                        ui.selectable_value(
-                            &mut state.render_settings.spatial_filter,
-                            wgpu::FilterMode::Linear,
-                            "Linear",
-                        )
+                        for filter in [
+                            Interpolation::Nearest,
+                            Interpolation::Linear,
+                            Interpolation::Cubic,
+                        ] {
+                            ui.selectable_value(
+                                &mut state.render_settings.spatial_filter,
+                                filter,
+                                filter.name(),
+                            );
+                        }
                     });
                 ui.end_row();
                 ui.label("Temporal Interpolation");
                 egui::ComboBox::new("temporal_interpolation", "")
-                    .selected_text(match state.render_settings.temporal_filter {
-                        wgpu::FilterMode::Nearest => "Nearest",
-                        wgpu::FilterMode::Linear => "Linear",
-                    })
+                    .selected_text(state.render_settings.temporal_filter.name())
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut state.render_settings.temporal_filter,
-                            wgpu::FilterMode::Nearest,
-                            "Nearest",
-                        );
-                        ui.selectable_value(
-                            &mut state.render_settings.temporal_filter,
-                            wgpu::FilterMode::Linear,
-                            "Linear",
-                        )
+                        for filter in [
+                            Interpolation::Nearest,
+                            Interpolation::Linear,
+                            Interpolation::Cubic,
+                        ] {
+                            ui.selectable_value(
+                                &mut state.render_settings.temporal_filter,
+                                filter,
+                                filter.name(),
+                            );
+                        }
                     });
                 ui.end_row();
             });
-    });
+        ui.collapsing("Quality", |ui| {
+            egui::Grid::new("quality_settings")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Render Scale");
+                    ui.add(
+                        egui::Slider::new(&mut state.render_scale, 0.1..=1.)
+                            .clamp_to_range(true)
+                            .fixed_decimals(2),
+                    )
+                    .on_hover_text("Fraction of the window resolution the volume is rendered at");
+                    ui.end_row();
 
-    // let mut cmap = state.cmap.clone();
+                    ui.label("Interactive Preview");
+                    ui.checkbox(&mut state.interactive_preview, "")
+                        .on_hover_text(
+                        "Render with reduced resolution and sampling rate while the camera moves",
+                    );
+                    ui.end_row();
 
-    if state.colormap_editor_visible {
-        egui::Window::new("Transfer Function")
-            .default_size(vec2(300., 50.))
-            .show(ctx, |ui| {
-                ui.horizontal(|ui| {
-                    ui.label("vmin");
-                    let min_b = state
-                        .render_settings
-                        .vmin
-                        .unwrap_or(state.volumes[0].volume.min_value);
-                    let max_b = state
-                        .render_settings
-                        .vmax
-                        .unwrap_or(state.volumes[0].volume.max_value);
+                    ui.label("Anti-Aliasing");
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut state.taa.enabled, "");
+                        if state.taa.enabled {
+                            ui.add(
+                                egui::DragValue::new(&mut state.taa.max_samples)
+                                    .range(1..=256)
+                                    .prefix("samples: "),
+                            );
+                            ui.label(format!("{}/{}", state.taa.samples(), state.taa.max_samples));
+                        }
+                    })
+                    .response
+                    .on_hover_text("Accumulates jittered frames while the view does not change");
+                    ui.end_row();
 
-                    let vmin_min = state.volumes[0].volume.min_value.min(min_b);
-                    let vmax_max = state.volumes[0].volume.max_value.max(max_b);
+                    ui.label("Reuse Static Frames");
+                    ui.checkbox(&mut state.reuse_frames, "").on_hover_text(
+                        "Shows the last image instead of rendering the volume again \
+                        while nothing changes",
+                    );
+                    ui.end_row();
+                });
+        });
+        ui.collapsing("Shading", |ui| {
+            egui::Grid::new("shading_settings")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Boundary Enhancement");
+                    ui.add(
+                        egui::Slider::new(&mut state.render_settings.boundary_enhancement, 0.0..=1.)
+                            .clamp_to_range(true)
+                            .fixed_decimals(2),
+                    )
+                    .on_hover_text("Makes homogeneous regions transparent and emphasizes boundaries between materials");
+                    ui.end_row();
+
+                    ui.label("Silhouettes");
+                    ui.add(
+                        egui::Slider::new(&mut state.render_settings.silhouette, 0.0..=1.)
+                            .clamp_to_range(true)
+                            .fixed_decimals(2),
+                    )
+                    .on_hover_text("Emphasizes and darkens boundaries seen at grazing angles");
+                    ui.end_row();
+                });
+        });
+        ui.collapsing("Shader Constants", |ui| {
+            let constants = &mut state.render_settings.constants;
+            egui::Grid::new("shader_constants")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Max. Steps");
+                    ui.add(egui::DragValue::new(&mut constants.max_steps).range(1..=100000));
+                    ui.end_row();
+
+                    ui.label("Early Stopping");
+                    ui.add(
+                        egui::DragValue::new(&mut constants.early_stopping_threshold)
+                            .speed(0.001)
+                            .range(0.0..=1.),
+                    )
+                    .on_hover_text("Rays stop once their transmittance drops below this value");
+                    ui.end_row();
+
+                    ui.label("Ray Epsilon");
+                    ui.add(
+                        egui::DragValue::new(&mut constants.ray_epsilon)
+                            .speed(1e-5)
+                            .range(0.0..=0.1),
+                    );
+                    ui.end_row();
+
+                    ui.label("Gradient Step");
+                    ui.add(
+                        egui::DragValue::new(&mut constants.gradient_step)
+                            .speed(0.01)
+                            .range(0.1..=8.)
+                            .suffix(" voxels"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Boundary Saturation");
+                    ui.add(
+                        egui::DragValue::new(&mut constants.boundary_saturation)
+                            .speed(0.001)
+                            .range(1e-4..=1.),
+                    );
+                    ui.end_row();
+
+                    ui.label("Silhouette Exponent");
+                    ui.add(
+                        egui::DragValue::new(&mut constants.silhouette_exponent)
+                            .speed(0.1)
+                            .range(0.1..=32.),
+                    );
+                    ui.end_row();
+
+                    ui.label("Depth Opacity Threshold");
+                    ui.add(
+                        egui::DragValue::new(&mut constants.depth_opacity_threshold)
+                            .speed(0.01)
+                            .range(0.0..=1.),
+                    );
+                    ui.end_row();
+                });
+            if ui.button("Reset").clicked() {
+                *constants = Default::default();
+            }
+        });
+        ui.collapsing("Overlays", |ui| {
+            ui.checkbox(&mut state.overlay.bounding_box, "Bounding Box");
+            ui.checkbox(&mut state.overlay.axes, "Axes");
+            ui.add_enabled(
+                state.overlay.any(),
+                egui::Checkbox::new(&mut state.overlay.xray, "X-Ray Lines"),
+            )
+            .on_hover_text("Draw lines on top of the volume instead of hiding them behind it");
+            ui.separator();
+            ui.checkbox(&mut state.annotations.scale_bar, "Scale Bar");
+            ui.checkbox(&mut state.annotations.ticks, "Axis Ticks");
+            ui.checkbox(&mut state.annotations.time_label, "Time Label");
+            egui::Grid::new("annotation_settings")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Unit");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut state.annotations.unit)
+                            .desired_width(60.),
+                    )
+                    .on_hover_text("Unit of the voxel spacing");
+                    ui.end_row();
+
+                    ui.label("Size");
+                    ui.add(
+                        egui::Slider::new(&mut state.annotations.scale, 0.5..=4.)
+                            .clamp_to_range(true)
+                            .suffix("x"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Color");
+                    ui.color_edit_button_srgba(&mut state.annotations.color);
+                    ui.end_row();
+                });
+            ui.separator();
+            let mut show_colorbar = state.render_settings.colorbar.is_some();
+            if ui.checkbox(&mut show_colorbar, "Colorbar").changed() {
+                state.render_settings.colorbar = show_colorbar.then(Colorbar::default);
+            }
+            if let Some(colorbar) = &mut state.render_settings.colorbar {
+                colorbar_ui(ui, colorbar);
+            }
+        });
+        ui.collapsing("Crop Box", |ui| crop_box_ui(ui, state));
+        ui.collapsing("Magnifier Lens", |ui| {
+            ui.checkbox(&mut state.lens.enabled, "Enabled (L)");
+            egui::Grid::new("lens_settings")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Radius");
+                    ui.add(
+                        egui::Slider::new(&mut state.lens.radius, 20.0..=500.)
+                            .clamp_to_range(true)
+                            .suffix("px"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Zoom");
+                    ui.add(
+                        egui::Slider::new(&mut state.lens.zoom, 1.0..=16.)
+                            .logarithmic(true)
+                            .clamp_to_range(true)
+                            .fixed_decimals(1)
+                            .suffix("x"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Sampling Rate");
+                    ui.add(
+                        egui::Slider::new(&mut state.lens.sampling_rate, 1.0..=16.)
+                            .logarithmic(true)
+                            .clamp_to_range(true)
+                            .fixed_decimals(1)
+                            .suffix("x"),
+                    )
+                    .on_hover_text("Samples per step of the main view");
+                    ui.end_row();
+
+                    let (vmin, vmax) = state
+                        .render_settings
+                        .value_range(&state.volumes[0].volume);
+                    ui.label("vmin");
                     optional_drag(
                         ui,
-                        &mut state.render_settings.vmin,
-                        Some(vmin_min..=max_b),
+                        &mut state.lens.vmin,
+                        Some(state.volumes[0].volume.min_value.min(vmin)..=vmax),
                         Some(0.01),
-                        Some(vmin_min),
+                        Some(vmin),
                     );
+                    ui.end_row();
                     ui.label("vmax");
                     optional_drag(
                         ui,
-                        &mut state.render_settings.vmax,
-                        Some(min_b..=vmax_max),
+                        &mut state.lens.vmax,
+                        Some(vmin..=state.volumes[0].volume.max_value.max(vmax)),
                         Some(0.01),
-                        Some(vmax_max),
+                        Some(vmax),
                     );
+                    ui.end_row();
                 });
-                #[cfg(feature = "colormaps")]
-                if state.cmap_select_visible {
-                    ui.horizontal(|ui| {
-                        let cmaps = &COLORMAPS;
-                        let mut selected_cmap: (String, String) = ui.ctx().data_mut(|d| {
-                            d.get_persisted_mut_or(
-                                "selected_cmap".into(),
-                                ("seaborn".to_string(), "icefire".to_string()),
-                            )
-                            .clone()
-                        });
-                        let mut search_term: String = ui.ctx().data_mut(|d| {
-                            d.get_temp_mut_or("cmap_search".into(), "".to_string())
-                                .clone()
-                        });
-                        ui.label("Colormap");
-                        let old_selected_cmap = selected_cmap.clone();
-                        egui::ComboBox::new("cmap_select", "")
-                            .selected_text(selected_cmap.1.clone())
-                            .show_ui(ui, |ui| {
-                                ui.add(
-                                    egui::text_edit::TextEdit::singleline(&mut search_term)
-                                        .hint_text("Search..."),
-                                );
-                                for (group, cmaps) in cmaps.iter() {
-                                    ui.label(group);
-                                    let mut sorted_cmaps: Vec<_> = cmaps.iter().collect();
-                                    sorted_cmaps.sort_by_key(|e| e.0);
-                                    for (name, cmap) in sorted_cmaps {
-                                        if name.contains(&search_term) {
-                                            let texture =
-                                                load_or_create(ui, cmap, COLORMAP_RESOLUTION);
-                                            ui.horizontal(|ui| {
-                                                ui.image(egui::ImageSource::Texture(
-                                                    egui::load::SizedTexture {
-                                                        id: texture,
-                                                        size: vec2(50., 10.),
-                                                    },
-                                                ));
-                                                ui.selectable_value(
-                                                    &mut selected_cmap,
-                                                    (group.clone(), name.clone()),
-                                                    name,
-                                                );
-                                            });
-                                        }
-                                    }
-                                    ui.separator();
-                                }
-                            });
-                        if old_selected_cmap != selected_cmap {
-                            let old_alpha = state.cmap.a.clone();
-                            state.cmap = cmaps[&selected_cmap.0][&selected_cmap.1]
-                                .into_linear_segmented(COLORMAP_RESOLUTION);
-                            if state.cmap.a.is_none()
-                                || cmaps[&selected_cmap.0][&selected_cmap.1]
-                                    .has_boring_alpha_channel()
-                            {
-                                state.cmap.a = old_alpha;
+        });
+        ui.collapsing("Probe", |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .toggle_value(&mut state.probe_mode, "Probe Mode")
+                    .on_hover_text("click into the volume to read the voxel there")
+                    .changed()
+                {
+                    state.pick_surface = false;
+                    state.measure.enabled = false;
+                    state.label_mode = false;
+                }
+                if ui
+                    .add_enabled(state.probe.is_some(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    state.probe = None;
+                }
+            });
+            match &state.probe {
+                Some(probe) => {
+                    if state.volumes.len() > 1 {
+                        ui.label(format!("channel {}", probe.channel));
+                    }
+                    for line in probe.lines() {
+                        ui.label(line);
+                    }
+                    if let Some(time) = probe_plot(ui, probe, state.render_settings.time) {
+                        state.render_settings.time = time;
+                        state.timeline.playing = false;
+                    }
+                }
+                None => {
+                    ui.label("nothing probed");
+                }
+            }
+        });
+        ui.collapsing("Measure", |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .toggle_value(&mut state.measure.enabled, "Measure Mode")
+                    .on_hover_text("click points on the volume to measure between them")
+                    .changed()
+                {
+                    state.pick_surface = false;
+                    state.probe_mode = false;
+                    state.label_mode = false;
+                }
+                for kind in MeasureKind::ALL {
+                    if ui
+                        .selectable_value(&mut state.measure.kind, kind, kind.name())
+                        .changed()
+                    {
+                        state.measure.points.clear();
+                    }
+                }
+                if ui
+                    .add_enabled(!state.measure.points.is_empty(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    state.measure.points.clear();
+                }
+            });
+            let volume = &state.volumes[state.measure.channel].volume;
+            match state.measure.result(volume, &state.annotations.unit) {
+                Some(result) => ui.label(result),
+                None => ui.label(format!(
+                    "{} of {} points picked",
+                    state.measure.points.len(),
+                    state.measure.kind.num_points()
+                )),
+            };
+        });
+        ui.collapsing("Labels", |ui| labels_ui(ui, state));
+        ui.collapsing("Stereo", |ui| {
+            egui::Grid::new("stereo_settings")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Mode");
+                    egui::ComboBox::new("stereo_mode", "")
+                        .selected_text(state.stereo.mode.name())
+                        .show_ui(ui, |ui| {
+                            for mode in StereoMode::ALL {
+                                ui.selectable_value(&mut state.stereo.mode, mode, mode.name());
                             }
-                            ui.ctx().data_mut(|d| {
-                                d.insert_persisted("selected_cmap".into(), selected_cmap);
-                            });
-                        }
-                        ui.ctx()
-                            .data_mut(|d| d.insert_temp("cmap_search".into(), search_term));
-                        if state.cmap.a.is_none() {
-                            state.cmap.a = Some(vec![(0.0, 1.0, 1.0), (1.0, 1.0, 1.0)]);
+                        });
+                    ui.end_row();
+
+                    ui.label("Eye Separation");
+                    ui.add(
+                        egui::DragValue::new(&mut state.stereo.eye_separation)
+                            .speed(0.001)
+                            .range(0.0..=1.),
+                    )
+                    .on_hover_text("Interocular distance (the smallest side of the volume is 1)");
+                    ui.end_row();
+                });
+        });
+        ui.collapsing("Performance", |ui| {
+            egui::Grid::new("performance")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    let frame_time = state.pacing.frame_time().as_secs_f32();
+                    ui.label("Frame Time");
+                    ui.label(format!(
+                        "{:.1} ms ({:.0} fps)",
+                        frame_time * 1e3,
+                        1. / frame_time.max(1e-6)
+                    ));
+                    ui.end_row();
+
+                    ui.label("Display");
+                    ui.label(state.pacing.refresh_rate().map_or_else(
+                        || "unknown refresh rate".to_string(),
+                        |hz| format!("{hz:.0} Hz"),
+                    ));
+                    ui.end_row();
+
+                    ui.label("Overlay");
+                    ui.checkbox(&mut state.performance_overlay, "Show (F3)")
+                        .on_hover_text(
+                            "Shows frame time, GPU time of the render passes, samples and memory usage",
+                        );
+                    ui.end_row();
+
+                    ui.label("Limit Frame Rate");
+                    let min_wait = state.pacing.min_wait().as_secs_f32() * 1e3;
+                    ui.checkbox(
+                        &mut state.pacing.enabled,
+                        format!("min. {min_wait:.1} ms between frames"),
+                    )
+                    .on_hover_text("Limits the frame rate to the refresh rate of the display");
+                    ui.end_row();
+                });
+        });
+        ui.collapsing("Interface", |ui| {
+            let mut config = state.ui_config;
+            egui::Grid::new("interface")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Scale");
+                    ui.add(
+                        egui::Slider::new(&mut config.scale, 0.5..=3.)
+                            .fixed_decimals(2)
+                            .step_by(0.05),
+                    )
+                    .on_hover_text("Scale of the interface on top of the display scale (Ctrl +/-)");
+                    ui.end_row();
+
+                    ui.label("Theme");
+                    ui.horizontal(|ui| {
+                        ui.selectable_value(&mut config.theme, Theme::Dark, "Dark");
+                        ui.selectable_value(&mut config.theme, Theme::Light, "Light");
+                    });
+                    ui.end_row();
+
+                    ui.label("Font Size");
+                    ui.add(egui::Slider::new(&mut config.font_size, 8.0..=24.).suffix(" pt"));
+                    ui.end_row();
+                });
+            if ui.button("Reset").clicked() {
+                config = UiConfig::default();
+            }
+            if config != state.ui_config {
+                state.set_ui_config(config);
+            }
+        });
+        ui.collapsing("Screenshot", |ui| {
+            ui.checkbox(&mut state.screenshots.include_ui, "Include User Interface");
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Save (F12)")
+                    .on_hover_text("Saves the frame as numbered png file")
+                    .clicked()
+                {
+                    state.screenshots.request(ScreenshotTarget::File);
+                }
+                if ui
+                    .button("Copy (Shift+F12)")
+                    .on_hover_text("Copies the frame to the clipboard")
+                    .clicked()
+                {
+                    state.screenshots.request(ScreenshotTarget::Clipboard);
+                }
+            });
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.horizontal(|ui| {
+                let directory = state
+                    .screenshots
+                    .directory
+                    .as_ref()
+                    .map_or("current directory".to_string(), |d| d.display().to_string());
+                ui.label(format!("Directory: {directory}"));
+                if ui.button("Change…").clicked() {
+                    if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                        state.screenshots.directory = Some(dir);
+                    }
+                }
+            });
+            if let Some(status) = &state.screenshots.status {
+                ui.label(status);
+            }
+        });
+        ui.collapsing("Bookmarks", |ui| bookmarks_ui(ui, state));
+        ui.collapsing("Camera Animation", |ui| keyframes_ui(ui, state));
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.collapsing("Compare", |ui| compare_ui(ui, state));
+        #[cfg(not(target_arch = "wasm32"))]
+        ui.collapsing("Session", |ui| session_ui(ui, state));
+    });
+
+    // let mut cmap = state.cmap.clone();
+
+    if state.colormap_editor_visible {
+        egui::Window::new("Transfer Function")
+            .default_size(vec2(300., 50.))
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("vmin");
+                    let (min_b, max_b) = state
+                        .render_settings
+                        .value_range(&state.volumes[0].volume);
+
+                    let vmin_min = state.volumes[0].volume.min_value.min(min_b);
+                    let vmax_max = state.volumes[0].volume.max_value.max(max_b);
+                    optional_drag(
+                        ui,
+                        &mut state.render_settings.vmin,
+                        Some(vmin_min..=max_b),
+                        Some(0.01),
+                        Some(vmin_min),
+                    );
+                    ui.label("vmax");
+                    optional_drag(
+                        ui,
+                        &mut state.render_settings.vmax,
+                        Some(min_b..=vmax_max),
+                        Some(0.01),
+                        Some(vmax_max),
+                    );
+                    ui.checkbox(&mut state.range_log_scale, "Log")
+                        .on_hover_text("logarithmic scale of the range slider");
+                    if ui
+                        .button("Auto")
+                        .on_hover_text(format!(
+                            "sets vmin and vmax to the {}% and {}% percentiles of the values",
+                            AUTO_RANGE_PERCENTILES.0, AUTO_RANGE_PERCENTILES.1
+                        ))
+                        .clicked()
+                    {
+                        let volume = &state.volumes[state.selected_channel.unwrap_or(0)].volume;
+                        let values = volume
+                            .percentiles(&[AUTO_RANGE_PERCENTILES.0, AUTO_RANGE_PERCENTILES.1]);
+                        state.render_settings.vmin = Some(values[0]);
+                        state.render_settings.vmax = Some(values[1]);
+                    }
+                    ui.checkbox(&mut state.render_settings.per_timestep_range, "per Timestep")
+                        .on_hover_text(
+                            "unset vmin and vmax follow the value range of the current timestep",
+                        );
+                });
+                let volume = &state.volumes[state.selected_channel.unwrap_or(0)].volume;
+                let default = if state.render_settings.per_timestep_range {
+                    volume.range_at(state.render_settings.time)
+                } else {
+                    (volume.min_value, volume.max_value)
+                };
+                let scale = slider_scale(volume, &state.render_settings, state.range_log_scale);
+                let histogram = state
+                    .range_histogram
+                    .enabled
+                    .then(|| state.range_histogram.histogram())
+                    .flatten();
+                range_ui(
+                    ui,
+                    &mut state.render_settings.vmin,
+                    &mut state.render_settings.vmax,
+                    default,
+                    scale,
+                    histogram,
+                )
+                .on_hover_text("Drag the handles to change vmin and vmax.\nDouble-Click to reset.");
+                ui.horizontal(|ui| {
+                    ui.label("Normalization");
+                    let normalization = &mut state.render_settings.normalization;
+                    let exponent = match normalization {
+                        Normalization::Power(exponent) => *exponent,
+                        _ => 2.,
+                    };
+                    egui::ComboBox::new("normalization", "")
+                        .selected_text(normalization.name())
+                        .show_ui(ui, |ui| {
+                            for n in [
+                                Normalization::Linear,
+                                Normalization::Log,
+                                Normalization::Sqrt,
+                                Normalization::Power(exponent),
+                            ] {
+                                ui.selectable_value(normalization, n, n.name());
+                            }
+                        });
+                    if let Normalization::Power(exponent) = normalization {
+                        ui.label("Exponent");
+                        ui.add(
+                            egui::DragValue::new(exponent)
+                                .speed(0.01)
+                                .range(0.01..=10.),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let settings = &mut state.render_settings;
+                    ui.label("NaN");
+                    value_style_ui(ui, "nan_style", &mut settings.nan_style, false)
+                        .on_hover_text("NaN and infinite values");
+                    ui.label("Below");
+                    value_style_ui(ui, "below_style", &mut settings.below_style, true)
+                        .on_hover_text("values below vmin");
+                    ui.label("Above");
+                    value_style_ui(ui, "above_style", &mut settings.above_style, true)
+                        .on_hover_text("values above vmax");
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut state.render_settings.highlight_invalid, "Highlight")
+                        .on_hover_text(
+                            "draw NaN/Inf values and values outside of [vmin, vmax] \
+                            in distinct colors and count them in the current timestep",
+                        );
+                    if let (true, Some((_, counts))) =
+                        (state.render_settings.highlight_invalid, state.invalid_counts)
+                    {
+                        let counts = [
+                            ("NaN/Inf", counts.nan),
+                            ("Below", counts.below),
+                            ("Above", counts.above),
+                        ];
+                        for ((label, count), [r, g, b, _]) in counts.into_iter().zip(HIGHLIGHT_COLORS)
+                        {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(r, g, b),
+                                format!("{label}: {count}"),
+                            );
                         }
-                        if ui.button("↔").clicked() {
-                            state.cmap = (&state.cmap).reverse();
+                    }
+                });
+                #[cfg(feature = "colormaps")]
+                if state.cmap_select_visible {
+                    let cmaps = &COLORMAPS;
+                    let mut selected_cmap: (String, String) = ui.ctx().data_mut(|d| {
+                        d.get_persisted_mut_or(
+                            "selected_cmap".into(),
+                            ("seaborn".to_string(), "icefire".to_string()),
+                        )
+                        .clone()
+                    });
+                    let old_selected_cmap = selected_cmap.clone();
+                    let mut gallery_open: bool = ui
+                        .ctx()
+                        .data_mut(|d| *d.get_temp_mut_or("cmap_gallery_open".into(), false));
+                    ui.horizontal(|ui| {
+                        ui.label("Colormap");
+                        if let Some(cmap) = cmaps
+                            .get(&selected_cmap.0)
+                            .and_then(|group| group.get(&selected_cmap.1))
+                        {
+                            let texture = load_or_create(ui, cmap, COLORMAP_RESOLUTION);
+                            ui.image(egui::ImageSource::Texture(egui::load::SizedTexture {
+                                id: texture,
+                                size: vec2(50., 10.),
+                            }));
                         }
+                        ui.toggle_value(&mut gallery_open, &selected_cmap.1)
+                            .on_hover_text("shows all colormaps");
                     });
+                    if gallery_open {
+                        cmap_gallery(ui, &mut selected_cmap);
+                    }
+                    ui.ctx()
+                        .data_mut(|d| d.insert_temp("cmap_gallery_open".into(), gallery_open));
+                    if old_selected_cmap != selected_cmap {
+                        // only the colors change, the opacity is kept
+                        state.cmap = cmaps[&selected_cmap.0][&selected_cmap.1]
+                            .into_linear_segmented(COLORMAP_RESOLUTION);
+                        state.cmap_sequence = None;
+                        ui.ctx().data_mut(|d| {
+                            d.insert_persisted("selected_cmap".into(), selected_cmap);
+                        });
+                    }
                 }
-                let vmin = state
-                    .render_settings
-                    .vmin
-                    .unwrap_or(state.volumes[0].volume.min_value);
-                let vmax = state
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut state.cmap.reversed, "Reversed");
+                    ui.label("Gamma");
+                    ui.add(
+                        egui::DragValue::new(&mut state.cmap.gamma)
+                            .speed(0.01)
+                            .range(0.1..=10.),
+                    )
+                    .on_hover_text(
+                        "contrast of the colors: values < 1 emphasize low values, > 1 high values",
+                    );
+                    if ui.button("Reset").clicked() {
+                        state.cmap.gamma = 1.;
+                    }
+                });
+                #[cfg(not(target_arch = "wasm32"))]
+                ui.horizontal(|ui| {
+                    ui.label("Preset");
+                    let mut selected = None;
+                    egui::ComboBox::new("cmap_preset", "")
+                        .selected_text("Load...")
+                        .show_ui(ui, |ui| {
+                            if state.colormap_presets.is_empty() {
+                                ui.label("no presets saved yet");
+                            }
+                            for (name, cmap) in &state.colormap_presets {
+                                let texture = load_or_create(ui, cmap, COLORMAP_RESOLUTION);
+                                ui.horizontal(|ui| {
+                                    ui.image(egui::ImageSource::Texture(egui::load::SizedTexture {
+                                        id: texture,
+                                        size: vec2(50., 10.),
+                                    }));
+                                    if ui.selectable_label(false, name).clicked() {
+                                        selected = Some(cmap);
+                                    }
+                                });
+                            }
+                        });
+                    if let Some(cmap) = selected {
+                        // presets include the opacity
+                        state.cmap = cmap.into_linear_segmented(COLORMAP_RESOLUTION);
+                        state.opacity = TransferFunction::from_alpha(&state.cmap);
+                        state.cmap_sequence = None;
+                    }
+                    let mut name: String = ui.ctx().data_mut(|d| {
+                        d.get_temp_mut_or("cmap_preset_name".into(), String::new())
+                            .clone()
+                    });
+                    ui.add(
+                        egui::TextEdit::singleline(&mut name)
+                            .hint_text("name")
+                            .desired_width(80.),
+                    );
+                    let dir = presets::preset_dir()
+                        .map_or("no preset directory".to_string(), |d| d.display().to_string());
+                    let save = ui
+                        .add_enabled(!name.is_empty(), egui::Button::new("Save"))
+                        .on_hover_text(format!("saves colors and opacity to {dir}"));
+                    if save.clicked() {
+                        let cmap = state.cmap.with_opacity(&state.opacity);
+                        match presets::save_preset(&name, &cmap) {
+                            Ok(path) => {
+                                log::info!("saved colormap preset to {}", path.display());
+                                state.colormap_presets = presets::load_presets();
+                            }
+                            Err(err) => log::error!("failed to save colormap preset: {err}"),
+                        }
+                    }
+                    ui.ctx()
+                        .data_mut(|d| d.insert_temp("cmap_preset_name".into(), name));
+                });
+                let (vmin, vmax) = state
                     .render_settings
-                    .vmax
-                    .unwrap_or(state.volumes[0].volume.max_value);
-                show_cmap(ui, egui::Id::new("cmap preview"), &state.cmap, vmin, vmax);
+                    .value_range(&state.volumes[0].volume);
+                let cmap_sequence = state
+                    .cmap_sequence
+                    .as_ref()
+                    .map(|seq| seq.with_opacity(&state.opacity));
+                if let Some(seq) = cmap_sequence {
+                    // colors at the current timestep, quantized to the rows of the texture
+                    let rows = state.cmap_gpu.rows().max(2) - 1;
+                    let time = (state.render_settings.time * rows as f32).round() / rows as f32;
+                    let cmap = seq.at(time, COLORMAP_RESOLUTION);
+                    show_cmap(
+                        ui,
+                        egui::Id::new("cmap preview"),
+                        &cmap,
+                        vmin,
+                        vmax,
+                        &state.reference_values,
+                    );
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "Time-varying colormap ({} keyframes)",
+                            seq.keyframes().count()
+                        ));
+                        if ui
+                            .button("Use single colormap")
+                            .on_hover_text("uses the same colors for all timesteps")
+                            .clicked()
+                        {
+                            state.cmap_sequence = None;
+                        }
+                    });
+                } else {
+                    let cmap = state.cmap.with_opacity(&state.opacity);
+                    show_cmap(
+                        ui,
+                        egui::Id::new("cmap preview"),
+                        &cmap,
+                        vmin,
+                        vmax,
+                        &state.reference_values,
+                    );
+                }
+                egui::CollapsingHeader::new("Reference Values")
+                    .default_open(!state.reference_values.is_empty())
+                    .show(ui, |ui| {
+                        reference_values_ui(ui, &mut state.reference_values, vmin, vmax);
+                        if ui
+                            .toggle_value(&mut state.pick_surface, "Pick Surface")
+                            .on_hover_text(
+                                "click into the volume to set the first surface to the value \
+                                there, Shift+Click adds a new surface",
+                            )
+                            .changed()
+                        {
+                            state.probe_mode = false;
+                            state.measure.enabled = false;
+                            state.label_mode = false;
+                        }
+                    });
 
-                ui.heading("Alpha Channel");
+                ui.heading("Opacity");
                 ui.end_row();
                 ui.horizontal_wrapped(|ui| {
                     ui.label("Presets:");
@@ -275,56 +1264,86 @@ pub(crate) fn ui(state: &mut WindowContext) {
                         .button("\\/")
                         .on_hover_text("double click for smooth version");
                     if v_hack.clicked() {
-                        state.cmap.a = Some(vec![(0.0, 1.0, 1.0), (0.5, 0., 0.), (1.0, 1.0, 1.0)]);
+                        state.opacity.points =
+                            vec![(0.0, 1.0, 1.0), (0.5, 0., 0.), (1.0, 1.0, 1.0)];
                     }
                     if v_hack.double_clicked() {
-                        state.cmap.a =
-                            Some(build_segments(25, |x| ((x * 2. * PI).cos() + 1.) / 2.));
+                        state.opacity.points =
+                            build_segments(25, |x| ((x * 2. * PI).cos() + 1.) / 2.);
                     }
                     let slope_hack = ui
                         .button("/")
                         .on_hover_text("double click for smooth version");
                     if slope_hack.clicked() {
-                        state.cmap.a = Some(build_segments(2, |x| (-(x * PI).cos() + 1.) / 2.));
+                        state.opacity.points = build_segments(2, |x| (-(x * PI).cos() + 1.) / 2.);
                     }
                     if slope_hack.double_clicked() {
-                        state.cmap.a = Some(build_segments(25, |x| (-(x * PI).cos() + 1.) / 2.));
+                        state.opacity.points = build_segments(25, |x| (-(x * PI).cos() + 1.) / 2.);
                     }
                     let double_v_hack = ui
                         .button("/\\/\\")
                         .on_hover_text("double click for smooth version");
                     if double_v_hack.clicked() {
-                        state.cmap.a =
-                            Some(build_segments(5, |x| (-(x * 4. * PI).cos() + 1.) / 2.));
+                        state.opacity.points =
+                            build_segments(5, |x| (-(x * 4. * PI).cos() + 1.) / 2.);
                     }
                     if double_v_hack.double_clicked() {
-                        state.cmap.a =
-                            Some(build_segments(25, |x| (-(x * 4. * PI).cos() + 1.) / 2.));
+                        state.opacity.points =
+                            build_segments(25, |x| (-(x * 4. * PI).cos() + 1.) / 2.);
                     }
                     if ui.button("-").clicked() {
-                        state.cmap.a = Some(vec![(0.0, 1.0, 1.0), (1.0, 1.0, 1.0)]);
+                        state.opacity = TransferFunction::constant(1.);
                     }
                 });
 
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut state.histogram.enabled, "Histogram")
+                        .on_hover_text("distribution of the values between vmin and vmax");
+                    ui.add_enabled(
+                        state.histogram.enabled,
+                        egui::Checkbox::new(&mut state.histogram.per_timestep, "per Timestep"),
+                    )
+                    .on_hover_text("only counts the values of the current timestep");
+                });
+
                 ui.separator();
 
-                if let Some(a) = &mut state.cmap.a {
-                    tf_ui(ui, a)
-                    .on_hover_text("Drag anchor points to change transfer function.\nLeft-Click for new anchor point.\nRight-Click to delete anchor point.");
-                }
+                let histogram = state
+                    .histogram
+                    .enabled
+                    .then(|| state.histogram.histogram())
+                    .flatten();
+                tf_ui(ui, &mut state.opacity.points, histogram)
+                    .on_hover_text("Drag anchor points to change the opacity.\nLeft-Click for new anchor point.\nRight-Click to delete anchor point.");
                 ui.end_row();
-                if ui.button("Save Colormap").clicked(){
-                    let cmap_data = serde_json::to_vec(&state.cmap).unwrap();
+                let mut save = None;
+                ui.horizontal(|ui| {
+                    if ui.button("Save Colormap").clicked() {
+                        let cmap = state.cmap.with_opacity(&state.opacity);
+                        save = Some(("colormap.json", serde_json::to_vec(&cmap).unwrap()));
+                    }
+                    if ui
+                        .button("Export ParaView")
+                        .on_hover_text("Saves colors and opacity as ParaView preset")
+                        .clicked()
+                    {
+                        let cmap = state.cmap.with_opacity(&state.opacity);
+                        let mut data = Vec::new();
+                        cmap.write_paraview_json(&mut data, "vape4d").unwrap();
+                        save = Some(("colormap_paraview.json", data));
+                    }
+                });
+                if let Some((file_name, cmap_data)) = save {
                     #[cfg(target_arch = "wasm32")]
                     wasm_bindgen_futures::spawn_local(async move{
-                        let file = rfd::AsyncFileDialog::new().set_file_name("colormap.json").save_file().await;
+                        let file = rfd::AsyncFileDialog::new().set_file_name(file_name).save_file().await;
                         if let Some(file) = file{
                             file.write(&cmap_data).await.unwrap();
                         }
                     });
                     #[cfg(not(target_arch = "wasm32"))]
                     {
-                        let file = rfd::FileDialog::new().set_file_name("colormap.json").save_file();
+                        let file = rfd::FileDialog::new().set_file_name(file_name).save_file();
                         if let Some(file) = file {
                             std::fs::write(file, cmap_data).unwrap();
                         }
@@ -332,9 +1351,23 @@ pub(crate) fn ui(state: &mut WindowContext) {
                 }
             });
     }
-    state
-        .cmap_gpu
-        .update(&state.wgpu_context.queue, &state.cmap);
+    let cmap_sequence = state
+        .cmap_sequence
+        .as_ref()
+        .map(|seq| seq.with_opacity(&state.opacity));
+    match cmap_sequence {
+        Some(seq) => state
+            .cmap_gpu
+            .update_sequence(&state.wgpu_context.queue, &seq),
+        None => state.cmap_gpu.update(
+            &state.wgpu_context.queue,
+            &state.cmap.with_opacity(&state.opacity),
+        ),
+    };
+
+    if state.performance_overlay {
+        performance_overlay(ctx, state);
+    }
 
     if state.volume_info_visible {
         egui::Window::new("Volume Info").show(ctx, |ui| {
@@ -342,6 +1375,27 @@ pub(crate) fn ui(state: &mut WindowContext) {
                 .num_columns(2)
                 .striped(true)
                 .show(ui, |ui| {
+                    ui.label("file");
+                    ui.horizontal(|ui| {
+                        let file = state.volume_file.as_ref().and_then(|f| f.file_name());
+                        ui.label(file.map_or("none".into(), |f| f.to_string_lossy()));
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if ui
+                            .button("New Window…")
+                            .on_hover_text(
+                                "Opens a volume or session in a new window (or Shift+drop a file)",
+                            )
+                            .clicked()
+                        {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("volume or session", &["npy", "npz", "json"])
+                                .pick_file()
+                            {
+                                state.open_requests.push(path);
+                            }
+                        }
+                    });
+                    ui.end_row();
                     ui.label("timesteps");
                     ui.label(state.volumes[0].volume.timesteps.to_string());
                     ui.end_row();
@@ -352,68 +1406,156 @@ pub(crate) fn ui(state: &mut WindowContext) {
                     let res = state.volumes[0].volume.resolution;
                     ui.label(format!("{}x{}x{} (WxHxD)", res.x, res.y, res.z));
                     ui.end_row();
+                    ui.label("spacing");
+                    let spacing = state.volumes[0].volume.spacing;
+                    ui.label(format!(
+                        "{}x{}x{} {}",
+                        spacing.x, spacing.y, spacing.z, state.annotations.unit
+                    ));
+                    ui.end_row();
                     ui.label("value range");
                     ui.label(format!(
                         "[{} , {}]",
                         state.volumes[0].volume.min_value, state.volumes[0].volume.max_value
                     ));
                     ui.end_row();
+                    ui.label("mask");
+                    ui.horizontal(|ui| {
+                        let mut remove = false;
+                        if state.volumes[0].volume.mask().is_some() {
+                            ui.checkbox(&mut state.render_settings.invert_mask, "Invert")
+                                .on_hover_text("hide the masked voxels instead of the others");
+                            remove = ui.button("Remove").clicked();
+                        } else {
+                            ui.label("none");
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if ui
+                            .button("Load…")
+                            .on_hover_text("Load a mask of shape [D, H, W] (npy or npz)")
+                            .clicked()
+                        {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("numpy", &["npy", "npz"])
+                                .pick_file()
+                            {
+                                if let Err(e) = std::fs::File::open(path)
+                                    .map_err(anyhow::Error::from)
+                                    .and_then(|f| {
+                                        crate::volume::Mask::load_numpy(std::io::BufReader::new(f))
+                                    })
+                                    .and_then(|mask| state.set_mask(Some(mask)))
+                                {
+                                    log::error!("failed to load mask: {:?}", e);
+                                }
+                            }
+                        }
+                        if remove {
+                            state.set_mask(None).unwrap();
+                        }
+                    });
+                    ui.end_row();
                 });
+            egui::CollapsingHeader::new("Statistics")
+                .default_open(false)
+                .show(ui, |ui| statistics_ui(ui, state));
         });
     }
 
+    // probed values next to the clicked point
+    if let (true, Some(probe)) = (state.probe_mode, &state.probe) {
+        let pos = probe.cursor / ctx.pixels_per_point();
+        egui::Area::new(egui::Id::new("probe"))
+            .fixed_pos(Pos2::new(pos.x + 12., pos.y + 12.))
+            .order(Order::Tooltip)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    for line in probe.lines() {
+                        ui.label(line);
+                    }
+                });
+            });
+    }
+
+    crop_box_gizmo(ctx, state);
+
     let frame_rect = ctx.available_rect();
     egui::Area::new(egui::Id::new("orientation"))
         .fixed_pos(Pos2::new(frame_rect.left(), frame_rect.bottom()))
         .anchor(Align2::LEFT_BOTTOM, Vec2::new(0., 0.))
-        .interactable(false)
         .order(Order::Background)
         .show(ctx, |ui| {
-            let (response, painter) = ui.allocate_painter(
-                vec2(100., 100.),
-                Sense {
-                    click: false,
-                    drag: false,
-                    focusable: false,
-                },
-            );
+            let (response, painter) = ui.allocate_painter(vec2(100., 100.), Sense::click());
 
             let to_screen = emath::RectTransform::from_to(
                 Rect::from_two_pos(Pos2::new(-1.2, -1.2), Pos2::new(1.2, 1.2)),
                 response.rect,
             );
-            let x_color = Color32::RED;
-            let y_color = Color32::GREEN;
-            let z_color = Color32::BLUE;
+            let colors = [Color32::RED, Color32::GREEN, Color32::BLUE];
+            let labels = ["X", "Y", "Z"];
 
             let view_matrix = state.camera.view_matrix();
-            let x_axis = view_matrix.transform_vector(Vector3::unit_x());
-            // multiply with -1 because in egui origin is top left
-            let y_axis = -view_matrix.transform_vector(Vector3::unit_y());
-            let z_axis = view_matrix.transform_vector(Vector3::unit_z());
-            let origin = view_matrix.transform_vector(Vector3::zero());
-
-            let axes = [
-                (x_axis, x_color, "X"),
-                (y_axis, y_color, "Y"),
-                (z_axis, z_color, "Z"),
-            ];
-            let depth = vec![-x_axis.z, -y_axis.z, -z_axis.z];
-            let draw_order = argsort(&depth);
-            for i in draw_order.iter() {
-                let (axis, color, label) = axes[*i];
-                let pos = to_screen.transform_pos(pos2(axis.x, axis.y));
-                painter.add(PathShape::line(
-                    vec![to_screen.transform_pos(pos2(origin.x, origin.y)), pos],
-                    Stroke::new(3., color),
-                ));
-                painter.add(TextShape::new(
-                    pos,
-                    painter.layout_no_wrap(label.to_string(), FontId::default(), color),
-                    color,
-                ));
+            let origin = to_screen.transform_pos(Pos2::ZERO);
+            // end points of the positive and negative axes in the order of AXIS_VIEWS
+            let ends: Vec<(Pos2, f32)> = AXIS_VIEWS
+                .iter()
+                .map(|(dir, _)| {
+                    let axis = view_matrix.transform_vector(*dir);
+                    // multiply with -1 because in egui origin is top left
+                    (to_screen.transform_pos(pos2(axis.x, -axis.y)), -axis.z)
+                })
+                .collect();
+
+            let hovered = response.hover_pos().and_then(|pointer| {
+                ends.iter()
+                    .enumerate()
+                    .map(|(i, (pos, _))| (i, pos.distance(pointer)))
+                    .filter(|(_, d)| *d < 10.)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(i, _)| i)
+            });
+            if let Some(i) = hovered {
+                if response.clicked() {
+                    state.controller.snap_to_axis(i);
+                }
+            }
+            response.on_hover_text("click an axis to look along it (keys 1-6)");
+
+            let depth: Vec<f32> = ends.iter().map(|(_, d)| *d).collect();
+            for i in argsort(&depth) {
+                let (pos, _) = ends[i];
+                let axis = i / 2;
+                let positive = i % 2 == 0;
+                let color = colors[axis];
+                let stroke = if hovered == Some(i) {
+                    Stroke::new(2., Color32::WHITE)
+                } else {
+                    Stroke::NONE
+                };
+                if positive {
+                    painter.add(PathShape::line(vec![origin, pos], Stroke::new(3., color)));
+                    painter.circle(pos, 8., color, stroke);
+                    painter.text(
+                        pos,
+                        Align2::CENTER_CENTER,
+                        labels[axis],
+                        FontId::proportional(11.),
+                        Color32::BLACK,
+                    );
+                } else {
+                    painter.circle(pos, 6., color.gamma_multiply(0.5), stroke);
+                }
+            }
+            if hovered.is_some() {
+                ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
             }
         });
+
+    // a drag becomes a single undo step once the pointer is released
+    if !ctx.input(|i| i.pointer.any_down()) {
+        state.history.record(Edits::of(state));
+    }
 }
 
 pub fn argsort<T: PartialOrd>(data: &[T]) -> Vec<usize> {
@@ -422,10 +1564,15 @@ pub fn argsort<T: PartialOrd>(data: &[T]) -> Vec<usize> {
     indices
 }
 
-use cgmath::{Transform, Vector3, Zero};
+use cgmath::Transform;
 use egui::{epaint::PathShape, *};
 
-pub fn tf_ui(ui: &mut Ui, points: &mut Vec<(f32, f32, f32)>) -> egui::Response {
+/// editor for the opacity control `points`, drawn on top of the `histogram` of the values
+pub fn tf_ui(
+    ui: &mut Ui,
+    points: &mut Vec<(f32, f32, f32)>,
+    histogram: Option<&Histogram>,
+) -> egui::Response {
     let (response, painter) = ui.allocate_painter(
         vec2(ui.available_width(), 100.),
         Sense::hover().union(Sense::click()),
@@ -436,6 +1583,10 @@ pub fn tf_ui(ui: &mut Ui, points: &mut Vec<(f32, f32, f32)>) -> egui::Response {
         response.rect,
     );
 
+    if let Some(histogram) = histogram {
+        paint_histogram(ui, &painter, to_screen, histogram);
+    }
+
     let stroke = Stroke::new(1.0, Color32::from_rgb(25, 200, 100));
     if response.clicked() {
         if let Some(pos) = response.interact_pointer_pos() {
@@ -505,46 +1656,232 @@ pub fn tf_ui(ui: &mut Ui, points: &mut Vec<(f32, f32, f32)>) -> egui::Response {
     response
 }
 
-use std::hash::Hash;
-fn load_or_create<C>(ui: &egui::Ui, cmap: C, n: u32) -> egui::TextureId
-where
-    C: ColorMap + Hash,
-{
-    let id = Id::new(&cmap);
-    let tex: Option<egui::TextureHandle> = ui.ctx().data_mut(|d| d.get_temp(id));
-    match tex {
-        Some(tex) => tex.id(),
-        None => {
-            let tex = ui.ctx().load_texture(
-                id.value().to_string(),
-                egui::ColorImage::from_rgba_unmultiplied(
-                    [n as usize, 1],
-                    bytemuck::cast_slice(&cmap.rasterize(n as usize)),
-                ),
-                egui::TextureOptions::LINEAR,
-            );
-            let tex_id = tex.id();
-            ui.ctx().data_mut(|d| d.insert_temp(id, tex));
-            return tex_id;
-        }
+/// draws the bins of the `histogram` as bars over the unit square
+fn paint_histogram(
+    ui: &Ui,
+    painter: &Painter,
+    to_screen: emath::RectTransform,
+    histogram: &Histogram,
+) {
+    let heights = histogram.heights();
+    let width = 1. / heights.len() as f32;
+    let color = ui.visuals().weak_text_color().gamma_multiply(0.5);
+    for (i, h) in heights.iter().enumerate() {
+        let bar = Rect::from_min_max(
+            pos2(i as f32 * width, 1. - h),
+            pos2((i + 1) as f32 * width, 1.),
+        );
+        painter.rect_filled(to_screen.transform_rect(bar), 0., color);
     }
 }
 
-fn optional_drag<T: Numeric>(
-    ui: &mut egui::Ui,
-    opt: &mut Option<T>,
-    range: Option<RangeInclusive<T>>,
-    speed: Option<impl Into<f64>>,
-    default: Option<T>,
-) {
-    let mut placeholder = default.unwrap_or(T::from_f64(0.));
-    let mut drag = if let Some(ref mut val) = opt {
-        egui_winit::egui::DragValue::new(val)
+/// value range and scale of the vmin/vmax slider: the value range of the volume,
+/// extended to include vmin and vmax
+pub(crate) fn slider_scale(
+    volume: &Volume,
+    settings: &RenderSettings,
+    log_scale: bool,
+) -> (f32, f32, Normalization) {
+    let min = settings
+        .vmin
+        .map_or(volume.min_value, |v| v.min(volume.min_value));
+    let max = settings
+        .vmax
+        .map_or(volume.max_value, |v| v.max(volume.max_value));
+    let normalization = if log_scale && max > 0. {
+        Normalization::Log
     } else {
-        egui_winit::egui::DragValue::new(&mut placeholder).custom_formatter(|_, _| {
-            if let Some(v) = default {
-                format!("{:.2}", v.to_f64())
-            } else {
+        Normalization::Linear
+    };
+    (min, max, normalization)
+}
+
+/// slider with two handles for `vmin` and `vmax` (None for `default`), drawn on top of the
+/// `histogram` of the values in the slider's value range `scale` (see [slider_scale])
+fn range_ui(
+    ui: &mut Ui,
+    vmin: &mut Option<f32>,
+    vmax: &mut Option<f32>,
+    default: (f32, f32),
+    scale: (f32, f32, Normalization),
+    histogram: Option<&Histogram>,
+) -> egui::Response {
+    let (min, max, normalization) = scale;
+    let (response, painter) = ui.allocate_painter(
+        vec2(ui.available_width(), 40.),
+        Sense::hover().union(Sense::click()),
+    );
+    let to_screen = emath::RectTransform::from_to(
+        Rect::from_two_pos(Pos2::ZERO, Pos2::new(1., 1.)),
+        response.rect,
+    );
+
+    if let Some(histogram) = histogram {
+        paint_histogram(ui, &painter, to_screen, histogram);
+    }
+    if response.double_clicked() {
+        *vmin = None;
+        *vmax = None;
+    }
+
+    let handle_width = 8.;
+    let mut handles = [vmin.unwrap_or(default.0), vmax.unwrap_or(default.1)]
+        .map(|v| normalization.normalize(v, min, max).clamp(0., 1.));
+    for (i, value) in [vmin, vmax].into_iter().enumerate() {
+        let x = to_screen.transform_pos(pos2(handles[i], 0.)).x;
+        let handle_rect = Rect::from_x_y_ranges(
+            x - handle_width / 2.0..=x + handle_width / 2.,
+            response.rect.y_range(),
+        );
+        let handle_response = ui
+            .interact(handle_rect, response.id.with(i), Sense::drag())
+            .on_hover_cursor(CursorIcon::ResizeHorizontal);
+        if handle_response.dragged() {
+            if let Some(pos) = handle_response.interact_pointer_pos() {
+                let x = to_screen.inverse().transform_pos(pos).x.clamp(0., 1.);
+                // handles cannot move past each other
+                handles[i] = if i == 0 {
+                    x.min(handles[1])
+                } else {
+                    x.max(handles[0])
+                };
+                *value = Some(normalization.denormalize(handles[i], min, max));
+            }
+        }
+        let stroke = ui.style().interact(&handle_response).fg_stroke;
+        painter.vline(x, response.rect.y_range(), Stroke::new(2., stroke.color));
+    }
+
+    // dims the values outside of the selected range
+    let dim = ui.visuals().extreme_bg_color.gamma_multiply(0.7);
+    for (left, right) in [(0., handles[0]), (handles[1], 1.)] {
+        let rect = Rect::from_min_max(pos2(left, 0.), pos2(right, 1.));
+        painter.rect_filled(to_screen.transform_rect(rect), 0., dim);
+    }
+    response
+}
+
+use std::hash::Hash;
+/// searchable previews of all predefined colormaps, grouped by category.
+/// Clicking a preview selects the colormap (group, name).
+#[cfg(feature = "colormaps")]
+fn cmap_gallery(ui: &mut egui::Ui, selected: &mut (String, String)) {
+    let mut search_term: String = ui.ctx().data_mut(|d| {
+        d.get_temp_mut_or("cmap_search".into(), String::new())
+            .clone()
+    });
+    ui.add(egui::TextEdit::singleline(&mut search_term).hint_text("Search..."));
+    let search = search_term.to_lowercase();
+    egui::ScrollArea::vertical()
+        .max_height(300.)
+        .show(ui, |ui| {
+            for (category, names) in COLORMAP_CATEGORIES.iter() {
+                let matches: Vec<_> = names
+                    .iter()
+                    .filter(|(_, name)| name.to_lowercase().contains(&search))
+                    .collect();
+                if matches.is_empty() {
+                    continue;
+                }
+                ui.strong(category.name());
+                egui::Grid::new(("cmap_gallery", category.name()))
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for (i, (group, name)) in matches.into_iter().enumerate() {
+                            let texture =
+                                load_or_create(ui, &COLORMAPS[group][name], COLORMAP_RESOLUTION);
+                            let preview = egui::load::SizedTexture {
+                                id: texture,
+                                size: vec2(50., 10.),
+                            };
+                            let is_selected = selected.0 == *group && selected.1 == *name;
+                            let button =
+                                egui::Button::image_and_text(preview, name).selected(is_selected);
+                            if ui.add(button).on_hover_text(group).clicked() {
+                                *selected = (group.clone(), name.clone());
+                            }
+                            if i % 3 == 2 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+            }
+        });
+    ui.ctx()
+        .data_mut(|d| d.insert_temp("cmap_search".into(), search_term));
+}
+
+fn load_or_create<C>(ui: &egui::Ui, cmap: C, n: u32) -> egui::TextureId
+where
+    C: ColorMap + Hash,
+{
+    let id = Id::new(&cmap);
+    let tex: Option<egui::TextureHandle> = ui.ctx().data_mut(|d| d.get_temp(id));
+    match tex {
+        Some(tex) => tex.id(),
+        None => {
+            let tex = ui.ctx().load_texture(
+                id.value().to_string(),
+                egui::ColorImage::from_rgba_unmultiplied(
+                    [n as usize, 1],
+                    bytemuck::cast_slice(&cmap.rasterize(n as usize)),
+                ),
+                egui::TextureOptions::LINEAR,
+            );
+            let tex_id = tex.id();
+            ui.ctx().data_mut(|d| d.insert_temp(id, tex));
+            return tex_id;
+        }
+    }
+}
+
+/// selects how values without colormap color are rendered.
+/// Clamping is only offered if `clamp` is set.
+fn value_style_ui(
+    ui: &mut egui::Ui,
+    id: &str,
+    style: &mut ValueStyle,
+    clamp: bool,
+) -> egui::Response {
+    let color = match style {
+        ValueStyle::Color(color) => *color,
+        _ => [255, 0, 255, 255],
+    };
+    let styles = [
+        ValueStyle::Clamp,
+        ValueStyle::Transparent,
+        ValueStyle::Color(color),
+    ];
+    let mut response = egui::ComboBox::new(id, "")
+        .selected_text(style.name())
+        .width(90.)
+        .show_ui(ui, |ui| {
+            for s in styles.into_iter().skip(if clamp { 0 } else { 1 }) {
+                ui.selectable_value(style, s, s.name());
+            }
+        })
+        .response;
+    if let ValueStyle::Color(color) = style {
+        response |= ui.color_edit_button_srgba_unmultiplied(color);
+    }
+    response
+}
+
+fn optional_drag<T: Numeric>(
+    ui: &mut egui::Ui,
+    opt: &mut Option<T>,
+    range: Option<RangeInclusive<T>>,
+    speed: Option<impl Into<f64>>,
+    default: Option<T>,
+) {
+    let mut placeholder = default.unwrap_or(T::from_f64(0.));
+    let mut drag = if let Some(ref mut val) = opt {
+        egui_winit::egui::DragValue::new(val)
+    } else {
+        egui_winit::egui::DragValue::new(&mut placeholder).custom_formatter(|_, _| {
+            if let Some(v) = default {
+                format!("{:.2}", v.to_f64())
+            } else {
                 "—".into()
             }
         })
@@ -568,7 +1905,15 @@ fn optional_drag<T: Numeric>(
     }
 }
 
-fn show_cmap(ui: &mut egui::Ui, id: egui::Id, cmap: impl ColorMap + Hash, vmin: f32, vmax: f32) {
+/// colormap legend with markers for the `reference_values` in the value range
+fn show_cmap(
+    ui: &mut egui::Ui,
+    id: egui::Id,
+    cmap: impl ColorMap + Hash,
+    vmin: f32,
+    vmax: f32,
+    reference_values: &[ReferenceValue],
+) {
     let texture = load_or_create(ui, cmap, COLORMAP_RESOLUTION);
     let width = vmax - vmin;
     let height = width / 5.;
@@ -577,7 +1922,11 @@ fn show_cmap(ui: &mut egui::Ui, id: egui::Id, cmap: impl ColorMap + Hash, vmin:
         PlotPoint::new(vmin + width * 0.5, height / 2.),
         vec2(width, height),
     );
-    let plot = Plot::new(id)
+    let markers: Vec<_> = reference_values
+        .iter()
+        .filter(|r| (vmin..=vmax).contains(&r.value))
+        .collect();
+    let mut plot = Plot::new(id)
         .show_x(true)
         .show_y(false)
         .height(100.)
@@ -589,8 +1938,136 @@ fn show_cmap(ui: &mut egui::Ui, id: egui::Id, cmap: impl ColorMap + Hash, vmin:
         .allow_drag(false)
         .allow_scroll(false)
         .allow_zoom(false);
+    if !markers.is_empty() {
+        // room for the labels
+        plot = plot.include_y(height * 1.6);
+    }
     plot.show(ui, |plot_ui| {
         plot_ui.image(image);
+        for r in markers {
+            plot_ui.vline(VLine::new(r.value).color(r.color32()).width(1.5));
+            plot_ui.text(
+                Text::new(PlotPoint::new(r.value, height * 1.05), &r.name)
+                    .anchor(Align2::CENTER_BOTTOM)
+                    .color(r.color32()),
+            );
+        }
+    });
+}
+
+/// values of the probed voxel over time with the current time marked.
+/// Returns the normalized time of the timestep that was clicked.
+fn probe_plot(ui: &mut egui::Ui, probe: &Probe, time: f32) -> Option<f32> {
+    let timesteps = probe.series[0].len() as u32;
+    if timesteps < 2 {
+        return None;
+    }
+    let colors = [
+        Color32::from_rgb(31, 119, 180),
+        Color32::from_rgb(255, 127, 14),
+        Color32::from_rgb(44, 160, 44),
+        Color32::from_rgb(214, 39, 40),
+    ];
+    let marker = ui.visuals().warn_fg_color;
+    let response = Plot::new("probe_series")
+        .height(120.)
+        .width(ui.available_width().max(200.))
+        .legend(egui_plot::Legend::default())
+        .allow_boxed_zoom(false)
+        .allow_drag(false)
+        .allow_scroll(false)
+        .allow_zoom(false)
+        .x_axis_label("timestep")
+        .show(ui, |plot_ui| {
+            for (i, series) in probe.series.iter().enumerate() {
+                let points: egui_plot::PlotPoints = series
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, v)| v.is_finite())
+                    .map(|(t, v)| [t as f64, *v as f64])
+                    .collect();
+                let mut line = egui_plot::Line::new(points).color(colors[i % colors.len()]);
+                if probe.series.len() > 1 {
+                    line = line.name(format!("channel {i}"));
+                }
+                plot_ui.line(line);
+            }
+            plot_ui.vline(VLine::new(timeline::time_index(time, timesteps) as f64).color(marker));
+            plot_ui.pointer_coordinate()
+        });
+    let pointer = response.inner?;
+    response
+        .response
+        .on_hover_text("click to show the timestep")
+        .clicked()
+        .then(|| {
+            let index = pointer.x.round().clamp(0., (timesteps - 1) as f64) as usize;
+            timeline::index_time(index, timesteps)
+        })
+}
+
+/// list of named reference values.
+/// New values are placed in the middle of the value range `vmin..vmax`.
+fn reference_values_ui(ui: &mut Ui, values: &mut Vec<ReferenceValue>, vmin: f32, vmax: f32) {
+    let mut remove = None;
+    egui::Grid::new("reference_values")
+        .num_columns(5)
+        .show(ui, |ui| {
+            for (i, r) in values.iter_mut().enumerate() {
+                ui.add(egui::TextEdit::singleline(&mut r.name).desired_width(100.));
+                ui.add(egui::DragValue::new(&mut r.value).speed((vmax - vmin) / 200.));
+                ui.color_edit_button_srgba_unmultiplied(&mut r.color);
+                ui.checkbox(&mut r.surface, "Surface")
+                    .on_hover_text("draws the surface where the volume crosses the value");
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+    if let Some(i) = remove {
+        values.remove(i);
+    }
+    if values.iter().filter(|r| r.surface).count() > MAX_CONTOURS {
+        ui.label(format!("only the first {MAX_CONTOURS} surfaces are drawn"));
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Add").clicked() {
+            values.push(ReferenceValue::new(
+                format!("value {}", values.len() + 1),
+                (vmin + vmax) / 2.,
+            ));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if ui.button("Save").clicked() {
+                let file = rfd::FileDialog::new()
+                    .set_file_name("reference_values.json")
+                    .save_file();
+                if let Some(file) = file {
+                    if let Err(err) = std::fs::File::create(file)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|f| ReferenceValue::write_json(values, f))
+                    {
+                        log::error!("failed to save reference values: {err}");
+                    }
+                }
+            }
+            if ui.button("Load").clicked() {
+                let file = rfd::FileDialog::new()
+                    .add_filter("json", &["json"])
+                    .pick_file();
+                if let Some(file) = file {
+                    match std::fs::File::open(file)
+                        .map_err(anyhow::Error::from)
+                        .and_then(ReferenceValue::read_json)
+                    {
+                        Ok(loaded) => *values = loaded,
+                        Err(err) => log::error!("failed to load reference values: {err}"),
+                    }
+                }
+            }
+        }
     });
 }
 
@@ -603,3 +2080,949 @@ fn build_segments<F: Fn(f32) -> f32>(n: usize, f: F) -> Vec<(f32, f32, f32)> {
         })
         .collect()
 }
+
+/// settings of the colorbar legend
+fn colorbar_ui(ui: &mut egui::Ui, colorbar: &mut Colorbar) {
+    egui::Grid::new("colorbar_settings")
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("Position");
+            egui::ComboBox::from_id_source("colorbar_position")
+                .selected_text(colorbar.position.name())
+                .show_ui(ui, |ui| {
+                    for position in ColorbarPosition::ALL {
+                        ui.selectable_value(&mut colorbar.position, position, position.name());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Length");
+            ui.add(
+                egui::Slider::new(&mut colorbar.length, 0.1..=1.)
+                    .clamp_to_range(true)
+                    .custom_formatter(|v, _| format!("{:.0}%", v * 100.)),
+            );
+            ui.end_row();
+
+            ui.label("Thickness");
+            ui.add(
+                egui::Slider::new(&mut colorbar.thickness, 4.0..=40.)
+                    .clamp_to_range(true)
+                    .suffix("pt"),
+            );
+            ui.end_row();
+
+            ui.label("Ticks");
+            ui.add(egui::Slider::new(&mut colorbar.ticks, 2..=11).clamp_to_range(true));
+            ui.end_row();
+
+            ui.label("Labels");
+            ui.horizontal(|ui| {
+                let decimals = match colorbar.format {
+                    LabelFormat::Auto => 2,
+                    LabelFormat::Fixed(d) | LabelFormat::Scientific(d) => d,
+                };
+                egui::ComboBox::from_id_source("colorbar_format")
+                    .selected_text(colorbar.format.name())
+                    .show_ui(ui, |ui| {
+                        for format in [
+                            LabelFormat::Auto,
+                            LabelFormat::Fixed(decimals),
+                            LabelFormat::Scientific(decimals),
+                        ] {
+                            ui.selectable_value(&mut colorbar.format, format, format.name());
+                        }
+                    });
+                if let LabelFormat::Fixed(d) | LabelFormat::Scientific(d) = &mut colorbar.format {
+                    ui.add(egui::DragValue::new(d).range(0..=6))
+                        .on_hover_text("Decimals");
+                }
+            });
+            ui.end_row();
+        });
+}
+
+/// settings of the video recording and the record button
+#[cfg(not(target_arch = "wasm32"))]
+fn recording_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    ui.vertical(|ui| {
+        if let Some(recording) = &state.recorder.recording {
+            let (frame, num_frames) = recording.progress();
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::ProgressBar::new(frame as f32 / num_frames as f32)
+                        .desired_width(120.)
+                        .text(format!("{frame}/{num_frames}")),
+                );
+                if ui.button("Stop").clicked() {
+                    state.stop_recording();
+                }
+            });
+            return;
+        }
+        let settings = &mut state.recorder.settings;
+        let preset = &mut state.recorder.preset;
+        let mut path = None;
+        egui::ComboBox::from_id_source("output_preset")
+            .selected_text(preset.map_or("Custom", |p| p.name))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(preset, None, "Custom");
+                for p in OutputPreset::all() {
+                    if ui.selectable_label(*preset == Some(p), p.name).clicked() {
+                        settings.resolution = p.resolution;
+                        *preset = Some(p);
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Sets the resolution, supersampling and scale of the annotations");
+        ui.horizontal(|ui| {
+            let width = ui.add(
+                egui::DragValue::new(&mut settings.resolution.x)
+                    .range(16..=16384)
+                    .suffix("px"),
+            );
+            ui.label("x");
+            let height = ui.add(
+                egui::DragValue::new(&mut settings.resolution.y)
+                    .range(16..=16384)
+                    .suffix("px"),
+            );
+            if width.changed() || height.changed() {
+                *preset = None;
+            }
+            ui.add(
+                egui::DragValue::new(&mut settings.fps)
+                    .range(1..=120)
+                    .suffix(" fps"),
+            );
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("video_format")
+                .selected_text(settings.format.name())
+                .show_ui(ui, |ui| {
+                    for format in VideoFormat::ALL {
+                        ui.selectable_value(&mut settings.format, format, format.name());
+                    }
+                });
+            let format = settings.format;
+            ui.checkbox(&mut state.recorder.turntable, "Turntable")
+                .on_hover_text(
+                    "Orbits the camera once around the volume, \
+                    at the current time unless the animation plays",
+                );
+            if ui
+                .button("⏺ Record")
+                .on_hover_text("Records one loop of the animation (MP4 and WebM with ffmpeg)")
+                .clicked()
+            {
+                path = rfd::FileDialog::new()
+                    .add_filter(format.name(), &[format.extension()])
+                    .set_file_name(format!("vape4d.{}", format.extension()))
+                    .save_file();
+            }
+        });
+        if let Some(path) = path {
+            state.start_recording(&path);
+        }
+        if let Some(status) = &state.recorder.status {
+            ui.label(status);
+        }
+    });
+}
+
+/// list of the camera bookmarks, bookmarks are saved whenever they change
+fn bookmarks_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    let mut recall = None;
+    let mut remove = None;
+    let mut changed = false;
+    egui::Grid::new("bookmarks").num_columns(3).show(ui, |ui| {
+        for (i, bookmark) in state.bookmarks.list.iter_mut().enumerate() {
+            let go = ui.button("Go");
+            let go = if i < NUM_BOOKMARK_KEYS {
+                go.on_hover_text(format!("Shift+{}", i + 1))
+            } else {
+                go
+            };
+            if go.clicked() {
+                recall = Some(i);
+            }
+            changed |= ui
+                .add(egui::TextEdit::singleline(&mut bookmark.name).desired_width(120.))
+                .lost_focus();
+            if ui.button("🗑").clicked() {
+                remove = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(i) = recall {
+        state.recall_bookmark(i);
+    }
+    if let Some(i) = remove {
+        state.bookmarks.list.remove(i);
+        changed = true;
+    }
+    if changed {
+        state.bookmarks.save();
+    }
+    ui.horizontal(|ui| {
+        if ui
+            .button("Add")
+            .on_hover_text("saves the camera, time and surface value")
+            .clicked()
+        {
+            let name = format!("bookmark {}", state.bookmarks.list.len() + 1);
+            state.add_bookmark(name);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if ui.button("Save").clicked() {
+                let file = rfd::FileDialog::new()
+                    .set_file_name("bookmarks.json")
+                    .save_file();
+                if let Some(file) = file {
+                    if let Err(err) = bookmarks::write_bookmarks(&file, &state.bookmarks.list) {
+                        log::error!("failed to save bookmarks: {err}");
+                    }
+                }
+            }
+            if ui.button("Load").clicked() {
+                let file = rfd::FileDialog::new()
+                    .add_filter("json", &["json"])
+                    .pick_file();
+                if let Some(file) = file {
+                    match bookmarks::read_bookmarks(&file) {
+                        Ok(loaded) => {
+                            state.bookmarks.list = loaded;
+                            state.bookmarks.save();
+                        }
+                        Err(err) => log::error!("failed to load bookmarks: {err}"),
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// text labels anchored to the volume: placing, editing, removing, loading and saving
+fn labels_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    ui.horizontal(|ui| {
+        if ui
+            .toggle_value(&mut state.label_mode, "Label Mode")
+            .on_hover_text("click into the volume to place a label there")
+            .changed()
+        {
+            state.pick_surface = false;
+            state.probe_mode = false;
+            state.measure.enabled = false;
+        }
+        if ui
+            .add_enabled(
+                !state.annotations.labels.is_empty(),
+                egui::Button::new("Clear"),
+            )
+            .clicked()
+        {
+            state.annotations.labels.clear();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if ui
+                .add_enabled(
+                    !state.annotations.labels.is_empty(),
+                    egui::Button::new("Save…"),
+                )
+                .clicked()
+            {
+                let file = rfd::FileDialog::new()
+                    .add_filter("json", &["json"])
+                    .set_file_name("labels.json")
+                    .save_file();
+                if let Some(file) = file {
+                    if let Err(err) = annotations::write_labels(&file, &state.annotations.labels) {
+                        log::error!("failed to save labels: {err}");
+                    }
+                }
+            }
+            if ui
+                .button("Load…")
+                .on_hover_text("adds the labels of a json file")
+                .clicked()
+            {
+                let file = rfd::FileDialog::new()
+                    .add_filter("json", &["json"])
+                    .pick_file();
+                if let Some(file) = file {
+                    match annotations::read_labels(&file) {
+                        Ok(labels) => state.annotations.labels.extend(labels),
+                        Err(err) => log::error!("failed to load labels: {err:?}"),
+                    }
+                }
+            }
+        }
+    });
+    if state.annotations.labels.is_empty() {
+        ui.label("no labels");
+        return;
+    }
+    let mut remove = None;
+    egui::Grid::new("labels")
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            for (i, label) in state.annotations.labels.iter_mut().enumerate() {
+                ui.text_edit_singleline(&mut label.text);
+                let [x, y, z] = label.position;
+                ui.label(format!("({x:.3}, {y:.3}, {z:.3})"));
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+    if let Some(i) = remove {
+        state.annotations.labels.remove(i);
+    }
+}
+
+/// saves and restores the whole viewer state (also possible by dropping a session file)
+#[cfg(not(target_arch = "wasm32"))]
+fn session_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    ui.horizontal(|ui| {
+        if ui
+            .button("Save…")
+            .on_hover_text("saves camera, settings, transfer function, playback and bookmarks")
+            .clicked()
+        {
+            let file = rfd::FileDialog::new()
+                .add_filter("json", &["json"])
+                .set_file_name("session.json")
+                .save_file();
+            if let Some(file) = file {
+                if let Err(err) = Session::of(state, &file).write(&file) {
+                    log::error!("failed to save session: {err}");
+                }
+            }
+        }
+        if ui.button("Load…").clicked() {
+            let file = rfd::FileDialog::new()
+                .add_filter("json", &["json"])
+                .pick_file();
+            if let Some(file) = file {
+                if let Err(err) = state.load_session(&file) {
+                    log::error!("failed to load session: {err:?}");
+                }
+            }
+        }
+    });
+    match &state.volume_file {
+        Some(file) => ui.label(format!("volume: {}", file.display())),
+        None => ui.label("the volume has no file, sessions are saved without it"),
+    };
+}
+
+/// frame time, GPU timings, samples and memory usage in the top right corner
+fn performance_overlay(ctx: &egui::Context, state: &WindowContext) {
+    egui::Area::new(egui::Id::new("performance overlay"))
+        .anchor(Align2::RIGHT_TOP, vec2(-8., 8.))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style())
+                .fill(Color32::from_black_alpha(180))
+                .show(ui, |ui| {
+                    egui::Grid::new("performance overlay grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            let frame_time = state.pacing.frame_time().as_secs_f32();
+                            ui.label("frame");
+                            ui.label(format!(
+                                "{:.1} ms ({:.0} fps)",
+                                frame_time * 1e3,
+                                1. / frame_time.max(1e-6)
+                            ));
+                            ui.end_row();
+
+                            if state.gpu_timer.supported() {
+                                let timings = state.gpu_timer.timings();
+                                for (pass, ms) in timings {
+                                    ui.label(format!("GPU {pass}"));
+                                    ui.label(format!("{ms:.2} ms"));
+                                    ui.end_row();
+                                }
+                                ui.label("GPU total");
+                                ui.label(format!(
+                                    "{:.2} ms",
+                                    timings.iter().map(|(_, ms)| ms).sum::<f32>()
+                                ));
+                            } else {
+                                ui.label("GPU");
+                                ui.label("no timestamp queries");
+                            }
+                            ui.end_row();
+
+                            let stats = &state.frame_stats;
+                            ui.label("rays");
+                            ui.label(format!("{:.2} M", stats.rays as f64 / 1e6));
+                            ui.end_row();
+                            ui.label("samples per ray");
+                            ui.label(format!("≤ {}", stats.samples_per_ray));
+                            ui.end_row();
+                            if state.taa.enabled {
+                                ui.label("accumulated");
+                                ui.label(format!(
+                                    "{} / {}",
+                                    state.taa.samples(),
+                                    state.taa.max_samples
+                                ));
+                                ui.end_row();
+                            }
+
+                            let memory: u64 = state.volumes.iter().map(|v| v.memory_size()).sum();
+                            ui.label("volume memory");
+                            ui.label(format!("{:.1} MiB", memory as f64 / (1 << 20) as f64));
+                            ui.end_row();
+                        });
+                });
+        });
+}
+
+/// statistics of the selected channel at the current timestep and of all timesteps.
+/// They are only computed once the section is opened.
+fn statistics_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    let channel = state.selected_channel.unwrap_or(0);
+    let volume = &state.volumes[channel];
+    state.statistics.update(
+        &state.wgpu_context.device,
+        &state.wgpu_context.queue,
+        volume,
+        channel,
+    );
+    let Some(statistics) = state.statistics.get(channel) else {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("computing…");
+        });
+        // keep polling the result
+        ui.ctx().request_repaint();
+        return;
+    };
+    let timesteps = volume.volume.timesteps;
+    let timestep = ((timesteps - 1) as f32 * state.render_settings.time) as usize;
+    let columns = [&statistics.timesteps[timestep], &statistics.global];
+    let number = |v: f64| {
+        if v.is_nan() {
+            "-".to_string()
+        } else {
+            format!("{v:.4}")
+        }
+    };
+    egui::Grid::new("volume_statistics")
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            if state.volumes.len() > 1 {
+                ui.label(format!("channel {}", channel_name(state, channel)));
+            } else {
+                ui.label("");
+            }
+            ui.label(format!("timestep {timestep}"));
+            ui.label("all");
+            ui.end_row();
+            let rows = [
+                ("min", columns.map(|s| s.min as f64)),
+                ("max", columns.map(|s| s.max as f64)),
+                ("mean", columns.map(|s| s.mean)),
+                ("std", columns.map(|s| s.std)),
+            ];
+            for (name, values) in rows {
+                ui.label(name);
+                for v in values {
+                    ui.label(number(v));
+                }
+                ui.end_row();
+            }
+            ui.label("NaN/Inf");
+            for s in columns {
+                ui.label(s.nan.to_string());
+            }
+            ui.end_row();
+            ui.label("histogram").on_hover_text(format!(
+                "values between {} and {}",
+                volume.volume.min_value, volume.volume.max_value
+            ));
+            for s in columns {
+                sparkline(ui, &s.histogram);
+            }
+            ui.end_row();
+        });
+}
+
+/// small bar chart of `bins` on a log scale
+fn sparkline(ui: &mut egui::Ui, bins: &[u32]) {
+    let (rect, _) = ui.allocate_exact_size(vec2(96., 16.), Sense::hover());
+    let max = bins.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let width = rect.width() / bins.len() as f32;
+    let color = ui.visuals().text_color();
+    for (i, c) in bins.iter().enumerate() {
+        let h = (*c as f32).ln_1p() / max.ln_1p() * rect.height();
+        let x = rect.left() + i as f32 * width;
+        let bar = Rect::from_min_max(pos2(x, rect.bottom() - h), pos2(x + width, rect.bottom()));
+        ui.painter().rect_filled(bar, 0., color);
+    }
+}
+
+/// index of the channel, or the name of its view when datasets are compared
+fn channel_name(state: &WindowContext, i: usize) -> String {
+    match &state.comparison {
+        Some(comparison) => comparison.label(state.volume_file.as_deref(), i),
+        None => i.to_string(),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn compare_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    ui.horizontal(|ui| {
+        if ui
+            .button("Open…")
+            .on_hover_text("shows a dataset of the same shape next to the volume")
+            .clicked()
+        {
+            let file = rfd::FileDialog::new()
+                .add_filter("numpy", &["npy", "npz"])
+                .pick_file();
+            if let Some(file) = file {
+                let difference = state.comparison.as_ref().is_some_and(|c| c.difference);
+                if let Err(err) = state.open_comparison(&file, difference) {
+                    log::error!("failed to open {}: {err:?}", file.display());
+                }
+            }
+        }
+        if ui
+            .add_enabled(state.comparison.is_some(), egui::Button::new("Close"))
+            .clicked()
+        {
+            state.close_comparison();
+        }
+    });
+    let Some(comparison) = &state.comparison else {
+        ui.label("no dataset is compared");
+        return;
+    };
+    ui.label(format!("compared with: {}", comparison.file.display()));
+    let mut difference = comparison.difference;
+    if ui
+        .checkbox(&mut difference, "Difference")
+        .on_hover_text("shows the volume minus the compared dataset")
+        .changed()
+    {
+        if let Err(err) = state.set_difference(difference) {
+            log::error!("failed to compute the difference: {err:?}");
+        }
+    }
+}
+
+/// keyframe timeline of the camera animation with playback controls and the keyframe list
+fn keyframes_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    let mut add = false;
+    let mut seek = false;
+    let animation = &mut state.camera_animation;
+    ui.horizontal(|ui| {
+        let play = if animation.playing { "⏸" } else { "▶" };
+        if ui
+            .add_enabled(!animation.keyframes.is_empty(), egui::Button::new(play))
+            .on_hover_text("Plays the camera animation")
+            .clicked()
+        {
+            animation.toggle();
+        }
+        ui.checkbox(&mut animation.looped, "Loop");
+        seek |= ui
+            .add(
+                egui::DragValue::new(&mut animation.position)
+                    .range(0. ..=f32::MAX)
+                    .speed(0.05)
+                    .suffix(" s"),
+            )
+            .changed();
+        add = ui
+            .button("Add Keyframe")
+            .on_hover_text(
+                "adds the camera, time and settings at the playhead \
+                 (replaces the keyframe there)",
+            )
+            .clicked();
+    });
+
+    // keyframes as diamonds, click to select, drag to move the playhead
+    let (_, end) = animation.range();
+    let span = (end.max(animation.position) + 1.).ceil();
+    let (response, painter) = ui.allocate_painter(
+        vec2(ui.available_width().max(100.), 24.),
+        Sense::click_and_drag(),
+    );
+    let rect = response.rect;
+    let x = |t: f32| rect.left() + rect.width() * t / span;
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2., visuals.extreme_bg_color);
+    for second in 0..=span as usize {
+        let px = x(second as f32);
+        painter.line_segment(
+            [pos2(px, rect.bottom() - 4.), pos2(px, rect.bottom())],
+            visuals.widgets.noninteractive.fg_stroke,
+        );
+    }
+    for (i, keyframe) in animation.keyframes.iter().enumerate() {
+        let c = pos2(x(keyframe.at), rect.center().y);
+        let color = if animation.selected == Some(i) {
+            visuals.selection.bg_fill
+        } else {
+            visuals.widgets.inactive.fg_stroke.color
+        };
+        painter.add(Shape::convex_polygon(
+            vec![
+                c + vec2(0., -6.),
+                c + vec2(6., 0.),
+                c + vec2(0., 6.),
+                c + vec2(-6., 0.),
+            ],
+            color,
+            Stroke::NONE,
+        ));
+    }
+    let px = x(animation.position);
+    painter.line_segment(
+        [pos2(px, rect.top()), pos2(px, rect.bottom())],
+        Stroke::new(2., visuals.warn_fg_color),
+    );
+    if let Some(pointer) = response.interact_pointer_pos() {
+        if response.clicked() || response.drag_started() {
+            animation.selected = animation
+                .keyframes
+                .iter()
+                .position(|k| (x(k.at) - pointer.x).abs() < 6.);
+        }
+        animation.position = match animation.selected {
+            Some(i) if response.clicked() => animation.keyframes[i].at,
+            _ => ((pointer.x - rect.left()) / rect.width() * span).clamp(0., span),
+        };
+        seek = true;
+    }
+
+    let mut remove = None;
+    let mut go = None;
+    let mut reorder = false;
+    egui::Grid::new("keyframes").num_columns(3).show(ui, |ui| {
+        for (i, keyframe) in animation.keyframes.iter_mut().enumerate() {
+            if ui
+                .selectable_label(animation.selected == Some(i), format!("#{}", i + 1))
+                .clicked()
+            {
+                go = Some(i);
+            }
+            reorder |= ui
+                .add(
+                    egui::DragValue::new(&mut keyframe.at)
+                        .range(0. ..=f32::MAX)
+                        .speed(0.05)
+                        .suffix(" s"),
+                )
+                .on_hover_text("position of the keyframe")
+                .drag_stopped();
+            if ui.button("🗑").clicked() {
+                remove = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(i) = go {
+        animation.selected = Some(i);
+        animation.position = animation.keyframes[i].at;
+        seek = true;
+    }
+    if let Some(i) = remove {
+        animation.remove(i);
+    }
+    if reorder {
+        animation.sort();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    ui.horizontal(|ui| {
+        if ui
+            .button("Save")
+            .on_hover_text("saves the keyframes as json, usable as \"keyframes\" of a spec")
+            .clicked()
+        {
+            let file = rfd::FileDialog::new()
+                .set_file_name("keyframes.json")
+                .save_file();
+            if let Some(file) = file {
+                let json = serde_json::to_vec_pretty(&animation.keyframes).unwrap();
+                if let Err(err) = std::fs::write(file, json) {
+                    log::error!("failed to save keyframes: {err}");
+                }
+            }
+        }
+        if ui.button("Load").clicked() {
+            let file = rfd::FileDialog::new()
+                .add_filter("json", &["json"])
+                .pick_file();
+            if let Some(file) = file {
+                match std::fs::read(file)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|data| Ok(serde_json::from_slice(&data)?))
+                {
+                    Ok(loaded) => *animation = keyframes::CameraAnimation::new(loaded),
+                    Err(err) => log::error!("failed to load keyframes: {err}"),
+                }
+            }
+        }
+    });
+
+    if add {
+        state.add_keyframe();
+    } else if seek {
+        // shows the animation at the playhead
+        let animation = &mut state.camera_animation;
+        animation.playing = false;
+        if let Some(keyframe) = keyframes::sample(&animation.keyframes, animation.position) {
+            state.apply_keyframe(&keyframe);
+        }
+    }
+}
+
+/// scrubber, play/pause and frame step buttons of the time animation
+fn timeline_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    let timesteps = state.volumes[0].volume.timesteps;
+    let time = &mut state.render_settings.time;
+    let timeline = &mut state.timeline;
+    ui.vertical(|ui| {
+        ui.horizontal(|ui| {
+            if ui.button("⏮").on_hover_text("First timestep").clicked() {
+                timeline.step(time, -(timesteps as i32), timesteps);
+            }
+            if ui.button("⏴").on_hover_text("Previous timestep").clicked() {
+                timeline.step(time, -1, timesteps);
+            }
+            let play = if timeline.playing { "⏸" } else { "▶" };
+            if ui.button(play).clicked() {
+                timeline.toggle(time);
+            }
+            if ui.button("⏵").on_hover_text("Next timestep").clicked() {
+                timeline.step(time, 1, timesteps);
+            }
+            if ui.button("⏭").on_hover_text("Last timestep").clicked() {
+                timeline.step(time, timesteps as i32, timesteps);
+            }
+        });
+        // the scrubber snaps to the timesteps
+        let index = timeline::time_index(*time, timesteps);
+        let mut selected = index.round() as usize;
+        let response = ui.add(
+            egui::Slider::new(&mut selected, 0..=timesteps as usize - 1)
+                .clamp_to_range(true)
+                .show_value(false),
+        );
+        if response.changed() {
+            *time = timeline::index_time(selected, timesteps);
+        }
+        let index = timeline::time_index(*time, timesteps);
+        let physical = format!(
+            "{:.3} {}",
+            timeline.physical_time(index),
+            timeline.time_unit
+        );
+        ui.label(format!(
+            "timestep {index:.1} / {} ({})",
+            timesteps - 1,
+            physical.trim_end()
+        ));
+    });
+}
+
+fn crop_box_ui(ui: &mut egui::Ui, state: &mut WindowContext) {
+    let mut enabled = state.render_settings.clipping_aabb.is_some();
+    if ui.checkbox(&mut enabled, "Enabled").changed() {
+        state.render_settings.clipping_aabb = enabled.then(Aabb::unit);
+        state.crop_box.show = enabled;
+    }
+    let Some(clip) = &mut state.render_settings.clipping_aabb else {
+        return;
+    };
+    ui.add_enabled(
+        state.stereo.mode != StereoMode::SideBySide,
+        egui::Checkbox::new(&mut state.crop_box.show, "Show Box"),
+    )
+    .on_hover_text("drag the handles on the faces of the box to move them");
+    let resolution = state.volumes[state.selected_channel.unwrap_or(0)]
+        .volume
+        .resolution;
+    egui::Grid::new("crop_box")
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("");
+            ui.label("min");
+            ui.label("max");
+            ui.end_row();
+            for (axis, label) in ["x", "y", "z"].into_iter().enumerate() {
+                let (mut min, mut max) = (clip.min[axis], clip.max[axis]);
+                let voxels = resolution[axis] as f32;
+                ui.label(label);
+                let changed = ui
+                    .add(egui::DragValue::new(&mut min).speed(0.002).range(0.0..=1.))
+                    .on_hover_text(format!("voxel {:.0}", min * voxels))
+                    .changed()
+                    | ui.add(egui::DragValue::new(&mut max).speed(0.002).range(0.0..=1.))
+                        .on_hover_text(format!("voxel {:.0}", max * voxels))
+                        .changed();
+                if changed {
+                    crop::set_range(clip, axis, min, max);
+                }
+                ui.end_row();
+            }
+        });
+    let zoom = ui
+        .horizontal(|ui| {
+            if ui.button("Reset").clicked() {
+                *clip = Aabb::unit();
+            }
+            ui.button("Zoom")
+                .on_hover_text("fit the cropped region (key C)")
+                .clicked()
+        })
+        .inner;
+    if zoom {
+        state.zoom_to_clip_region();
+    }
+}
+
+/// box of the clipping region in every cell of the viewport with a handle on each face.
+/// Dragging a handle moves the face along its axis.
+fn crop_box_gizmo(ctx: &egui::Context, state: &mut WindowContext) {
+    state.crop_box.hovered = None;
+    let Some(mut clip) = state.render_settings.clipping_aabb else {
+        state.crop_box.dragged = None;
+        return;
+    };
+    if !state.crop_box.show || state.stereo.mode == StereoMode::SideBySide {
+        state.crop_box.dragged = None;
+        return;
+    }
+    let screen = ctx.screen_rect();
+    let cells: Vec<(Rect, usize)> = match state.selected_channel {
+        Some(i) => vec![(screen, i)],
+        None => {
+            let columns = state.num_columns as usize;
+            let rows = state.volumes.len().div_ceil(columns);
+            let cell_size = vec2(
+                screen.width() / columns as f32,
+                screen.height() / rows as f32,
+            );
+            (0..state.volumes.len())
+                .map(|i| {
+                    let min =
+                        screen.min + vec2((i % columns) as f32, (i / columns) as f32) * cell_size;
+                    (Rect::from_min_size(min, cell_size), i)
+                })
+                .collect()
+        }
+    };
+    let (pointer, pressed, down, delta) = ctx.input(|i| {
+        (
+            i.pointer.hover_pos(),
+            i.pointer.primary_pressed(),
+            i.pointer.primary_down(),
+            i.pointer.delta(),
+        )
+    });
+    let colors = [Color32::RED, Color32::GREEN, Color32::BLUE];
+    let edge_stroke = Stroke::new(1.5, Color32::YELLOW);
+    let painter = ctx.layer_painter(LayerId::background());
+    // screen position of every handle and the screen vector that moves it through the volume
+    let mut handles: Vec<(Rect, usize, Pos2, Vec2)> = Vec::new();
+    for (rect, i) in cells {
+        let volume_aabb = state.volumes[i].volume.aabb;
+        let mut camera = state.camera;
+        camera
+            .projection
+            .resize(rect.width() as u32, rect.height() as u32);
+        let view_proj = camera.view_proj_matrix();
+        let project = |p: cgmath::Point3<f32>| {
+            let clip = view_proj * p.to_homogeneous();
+            pos2(
+                rect.left() + (clip.x / clip.w + 1.) / 2. * rect.width(),
+                rect.top() + (1. - clip.y / clip.w) / 2. * rect.height(),
+            )
+        };
+        let aabb = crop::world_aabb(&clip, &volume_aabb);
+        let corners = aabb.corners();
+        let cell_painter = painter.with_clip_rect(rect);
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    cell_painter.line_segment(
+                        [project(corners[i]), project(corners[i | bit])],
+                        edge_stroke,
+                    );
+                }
+            }
+        }
+        for face in 0..6 {
+            let center = crop::face_center(&aabb, face);
+            let mut extent = cgmath::Vector3::new(0., 0., 0.);
+            extent[face / 2] = volume_aabb.max[face / 2] - volume_aabb.min[face / 2];
+            let pos = project(center);
+            handles.push((rect, face, pos, project(center + extent) - pos));
+        }
+    }
+
+    let dragged = state.crop_box.dragged.filter(|_| down);
+    if let (Some(face), Some(pointer)) = (dragged, pointer) {
+        // the movement is measured in the cell the pointer is in
+        let axis = handles
+            .iter()
+            .find(|(rect, f, _, _)| *f == face && rect.contains(pointer))
+            .map(|(_, _, _, axis)| *axis);
+        if let Some(axis) = axis.filter(|a| a.length_sq() > 1.) {
+            crop::move_face(&mut clip, face, delta.dot(axis) / axis.length_sq());
+            state.render_settings.clipping_aabb = Some(clip);
+        }
+    }
+    let hovered = pointer
+        .filter(|_| dragged.is_none() && !ctx.is_pointer_over_area())
+        .and_then(|pointer| {
+            handles
+                .iter()
+                .filter(|(rect, ..)| rect.contains(pointer))
+                .map(|(_, face, pos, _)| (*face, pos.distance(pointer)))
+                .filter(|(_, d)| *d < 10.)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(face, _)| face)
+        });
+    state.crop_box.dragged = if pressed && hovered.is_some() {
+        hovered
+    } else {
+        dragged
+    };
+    state.crop_box.hovered = hovered.or(state.crop_box.dragged);
+
+    for (rect, face, pos, _) in handles {
+        let active = state.crop_box.hovered == Some(face);
+        let stroke = if active {
+            Stroke::new(2., Color32::WHITE)
+        } else {
+            Stroke::new(1., Color32::BLACK)
+        };
+        painter
+            .with_clip_rect(rect)
+            .circle(pos, 6., colors[face / 2], stroke);
+    }
+    if state.crop_box.dragged.is_some() {
+        ctx.set_cursor_icon(CursorIcon::Grabbing);
+    } else if hovered.is_some() {
+        ctx.set_cursor_icon(CursorIcon::Grab);
+    }
+}
@@ -57,10 +57,10 @@ impl EguiWGPU {
         encoder: &mut wgpu::CommandEncoder,
         output: FullOutput,
     ) -> UIRenderState {
-        let clipped_meshes = self
-            .winit
-            .egui_ctx()
-            .tessellate(output.shapes.clone(), scale_factor);
+        // the interface is zoomed on top of the scale factor of the display
+        let ctx = self.winit.egui_ctx();
+        let clipped_meshes =
+            ctx.tessellate(output.shapes.clone(), scale_factor * ctx.zoom_factor());
 
         // let size = window.inner_size();l
         let screen_descriptor = egui_wgpu::ScreenDescriptor {
@@ -1,15 +1,61 @@
-use clap::Parser;
-use std::{ffi::OsString, fmt::Debug, fs::File, io::BufReader, path::PathBuf};
+use cgmath::{InnerSpace, Vector3};
+use clap::{Args, Parser, Subcommand};
+use image::Rgba;
+use std::{
+    ffi::OsString,
+    fmt::Debug,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use winit::{dpi::PhysicalSize, window::WindowBuilder};
 
-use crate::{cmap, open_window, volume::Volume, RenderConfig};
+use crate::{
+    annotations::{Annotations, Colorbar, ColorbarPosition, ReferenceValue},
+    background::Background,
+    bookmarks::bookmarks_file,
+    camera::{CameraView, ProjectionKind},
+    cmap,
+    datasets::{fetch_example, EXAMPLE_DATASETS},
+    diff::diff_images,
+    offline::{numbered_path, save_image, AlphaMode, AnimationOutput, Aov, AovImages, BitDepth},
+    open_window, open_windows,
+    recording::VideoFormat,
+    renderer::Interpolation,
+    session::Session,
+    spec::{RenderSpec, SpecFiles, VolumeSpec},
+    stereo::{Stereo, StereoMode},
+    volume::{apply_mask, Mask, Volume},
+    RenderConfig, ViewerWindow,
+};
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Opt {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Input file
-    input: PathBuf,
+    #[arg(required_unless_present_any = ["spec", "session"])]
+    input: Option<PathBuf>,
+
+    /// render spec (json) describing volume, colormap, camera, overlays and animation.
+    /// Replaces the input, colormap and all other display options
+    #[arg(long, conflicts_with = "input")]
+    spec: Option<PathBuf>,
+
+    /// session (json) saved in the viewer. Restores the volume, camera, render settings,
+    /// transfer function, playback and bookmarks
+    #[arg(long, conflicts_with_all = ["input", "spec"])]
+    session: Option<PathBuf>,
+
+    /// writes the transfer function (colormap with the opacity as alpha channel, json)
+    /// of the first window to this file when it is closed. The file can be passed as colormap again
+    #[arg(long, value_name = "FILE")]
+    save_transfer_function: Option<PathBuf>,
 
     #[arg(long, default_value_t = false)]
     no_vsync: bool,
@@ -17,59 +63,880 @@ struct Opt {
     #[arg(long, default_value_t = false)]
     channel_first: bool,
 
+    /// fraction of the window resolution the volume is rendered at
+    #[arg(long, default_value_t = 1.0)]
+    render_scale: f32,
+
+    /// equirectangular image shown behind the volume
+    #[arg(long)]
+    environment: Option<PathBuf>,
+
+    /// physical size of a voxel in array order (depth, height, width).
+    /// Overrides the spacing stored in npz files.
+    #[arg(long, num_args = 3, value_names = ["D", "H", "W"])]
+    spacing: Option<Vec<f32>>,
+
+    /// unit of the voxel spacing (e.g. "mm")
+    #[arg(long)]
+    unit: Option<String>,
+
+    /// npy or npz file with a mask of shape [D, H, W] that multiplies the opacity.
+    /// Overrides the mask stored in npz files
+    #[arg(long)]
+    mask: Option<PathBuf>,
+
+    /// hides the masked voxels instead of the others
+    #[arg(long, default_value_t = false)]
+    invert_mask: bool,
+
+    /// second dataset of the same shape (e.g. another simulation run) shown side by side
+    /// with the input, with the same camera and time
+    #[arg(long, value_name = "FILE")]
+    compare: Option<PathBuf>,
+
+    /// also shows the difference of the input and the compared dataset
+    #[arg(long, default_value_t = false, requires = "compare")]
+    difference: bool,
+
+    /// further volumes (or sessions) opened in their own windows with the same colormap.
+    /// Can be given multiple times
+    #[arg(long = "window", value_name = "FILE")]
+    windows: Vec<PathBuf>,
+
+    /// shows a scale bar and axis ticks
+    #[arg(long, default_value_t = false)]
+    scale_bar: bool,
+
+    /// shows a colorbar at the given side of the frame (right, left, top or bottom)
+    #[arg(long, value_name = "POSITION", num_args = 0..=1, default_missing_value = "right")]
+    colorbar: Option<ColorbarPosition>,
+
+    /// seconds it takes to play from the first to the last timestep (default 5)
+    #[arg(long, value_name = "SECONDS", conflicts_with = "fps")]
+    duration: Option<f32>,
+
+    /// playback speed in timesteps per second
+    #[arg(long)]
+    fps: Option<f32>,
+
+    /// plays the animation from the last to the first timestep
+    #[arg(long, default_value_t = false)]
+    reverse: bool,
+
+    /// shows whole timesteps only instead of interpolating between them
+    #[arg(long, default_value_t = false)]
+    snap_timesteps: bool,
+
+    /// stereo output (off, side-by-side or anaglyph)
+    #[arg(long, default_value = "off")]
+    stereo: StereoMode,
+
+    /// interocular distance for stereo rendering (the smallest side of the volume is 1)
+    #[arg(long, default_value_t = 0.1)]
+    eye_separation: f32,
+
+    /// colormaps used over time instead of `colormap` (e.g. one per timestep),
+    /// blended between the keyframes
+    #[arg(long, num_args = 1.., value_name = "FILE")]
+    colormap_sequence: Vec<PathBuf>,
+
+    /// normalized time in [0, 1] of each colormap of the sequence.
+    /// Evenly spaced if omitted
+    #[arg(long, num_args = 1.., value_name = "TIME")]
+    colormap_times: Option<Vec<f32>>,
+
+    /// json file with named reference values (name, value, color, surface)
+    /// marked on the legend and optionally drawn as surfaces
+    #[arg(long)]
+    reference_values: Option<PathBuf>,
+
+    /// sets vmin to this percentile (e.g. 1) of the values instead of their minimum
+    #[arg(long, value_name = "PERCENT")]
+    vmin_percentile: Option<f32>,
+
+    /// sets vmax to this percentile (e.g. 99) of the values instead of their maximum
+    #[arg(long, value_name = "PERCENT")]
+    vmax_percentile: Option<f32>,
+
+    /// comma-separated hex colors (e.g. "#000000,#ff0000,#ffff00") used instead of `colormap`
+    #[arg(long, value_name = "COLORS", conflicts_with = "colormap")]
+    colormap_hex: Option<String>,
+
     #[cfg(not(feature = "colormaps"))]
-    colormap: PathBuf,
+    #[arg(required_unless_present_any = ["spec", "session", "colormap_hex"])]
+    colormap: Option<PathBuf>,
 
     #[cfg(feature = "colormaps")]
     colormap: Option<PathBuf>,
 }
 
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Compares two rendered images (PSNR, SSIM and error heatmap)
+    Diff {
+        image_a: PathBuf,
+        image_b: PathBuf,
+
+        /// writes a heatmap of the per pixel error on top of the first image
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// fails if the SSIM is below this value
+        #[arg(long)]
+        min_ssim: Option<f64>,
+    },
+    /// Renders a volume or the frames of a render spec to images without opening a window
+    Render(Box<RenderArgs>),
+    /// Downloads a small public example dataset into the cache directory and opens it.
+    /// Lists the available datasets if no name is given
+    FetchExample {
+        name: Option<String>,
+
+        /// only downloads the dataset and prints its path
+        #[arg(long, default_value_t = false)]
+        no_open: bool,
+    },
+}
+
+/// frames of a turntable animation if the number is not given, 4s at 30 fps
+const TURNTABLE_FRAMES: usize = 120;
+
+#[derive(Debug, Args)]
+struct RenderArgs {
+    /// render spec (json) or volume (npy or npz).
+    /// With --batch a spec with the settings shared by all volumes
+    #[arg(required_unless_present = "batch")]
+    input: Option<PathBuf>,
+
+    /// image file. Numbered (e.g. frame_0001.png) if there is more than one frame.
+    /// The output directory with --batch
+    #[arg(long, short)]
+    output: PathBuf,
+
+    /// renders every volume matching the patterns (e.g. 'runs/*.npy', * and ? match
+    /// within file names) with the same settings, reusing the GPU. The outputs are named
+    /// like the volumes
+    #[arg(long, num_args = 1.., value_name = "PATTERN")]
+    batch: Option<Vec<String>>,
+
+    /// file extension of the outputs of --batch (e.g. png, exr, mp4, gif or apng)
+    #[arg(long, default_value = "png", requires = "batch")]
+    format: String,
+
+    /// renders all frames with time labels into one grid image with this many columns
+    /// (0 = as square as possible)
+    #[arg(long, value_name = "COLUMNS")]
+    contact_sheet: Option<u32>,
+
+    /// bit depth of the images: 8, 16 (png or tiff) or float (linear, OpenEXR).
+    /// Always float for exr output
+    #[arg(long)]
+    bit_depth: Option<BitDepth>,
+
+    /// transparent background, e.g. to composite the images over slides and figures
+    #[arg(long, default_value_t = false)]
+    transparent: bool,
+
+    /// alpha of transparent images: straight (default, what image viewers expect)
+    /// or premultiplied
+    #[arg(long)]
+    alpha: Option<AlphaMode>,
+
+    /// also writes these outputs next to each image: depth, normal (linear float exr)
+    /// or alpha (e.g. frame_depth.exr)
+    #[arg(long, num_args = 1.., value_name = "OUTPUT")]
+    aov: Option<Vec<Aov>>,
+
+    /// image width in pixels (default 1024)
+    #[arg(long)]
+    width: Option<u32>,
+
+    /// image height in pixels (default 1024)
+    #[arg(long)]
+    height: Option<u32>,
+
+    /// named output format setting the resolution, supersampling and legend scale,
+    /// e.g. "slide-1080p", "slide-4k", "twitter-16:9" or "paper-a4@300dpi"
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["width", "height"])]
+    preset: Option<String>,
+
+    /// samples per pixel along each axis (default: the preset's or 1)
+    #[arg(long)]
+    supersampling: Option<u32>,
+
+    /// normalized time in [0, 1] of each rendered frame (default 0)
+    #[arg(long, num_args = 1.., value_name = "TIME")]
+    time: Option<Vec<f32>>,
+
+    /// renders this many frames sweeping from the first to the last time (the whole time
+    /// range if there is only one) and writes them one by one. Follows the camera animation
+    /// of the spec, if any. Used for videos (mp4, webm, gif or apng output, mp4 and webm
+    /// require ffmpeg), which have as many frames as times otherwise
+    #[arg(long, conflicts_with = "contact_sheet")]
+    frames: Option<usize>,
+
+    /// frame rate of videos
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+
+    /// orbits the camera once around the volume (120 frames unless --frames is given),
+    /// at the first time or from the first to the last time if there are more
+    #[arg(long, default_value_t = false, conflicts_with = "contact_sheet")]
+    turntable: bool,
+
+    /// builtin colormap ("group/name"), comma-separated hex colors or colormap file
+    #[arg(long)]
+    colormap: Option<String>,
+
+    /// camera (json) like the camera section of a spec, replaces the camera of the spec
+    #[arg(long, value_name = "FILE")]
+    view: Option<PathBuf>,
+
+    /// direction from the center of the volume to the camera
+    #[arg(long, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true)]
+    camera: Option<Vec<f32>>,
+
+    /// position of the camera in world space
+    #[arg(long, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true, conflicts_with = "camera")]
+    camera_position: Option<Vec<f32>>,
+
+    /// point the camera looks at in world space (default: center of the volume)
+    #[arg(long, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true)]
+    target: Option<Vec<f32>>,
+
+    /// direction pointing up in the image
+    #[arg(long, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true)]
+    up: Option<Vec<f32>>,
+
+    /// camera angle in degrees around the up vector, 0 looks from +z
+    #[arg(long, allow_negative_numbers = true, conflicts_with_all = ["camera", "camera_position"])]
+    azimuth: Option<f32>,
+
+    /// camera angle in degrees above the plane perpendicular to the up vector
+    #[arg(long, allow_negative_numbers = true, conflicts_with_all = ["camera", "camera_position"])]
+    elevation: Option<f32>,
+
+    /// orthographic, perspective or equirectangular (360° panorama around the camera)
+    #[arg(long)]
+    projection: Option<ProjectionKind>,
+
+    /// vertical field of view of the perspective projection in degrees (default 45)
+    #[arg(long)]
+    fov: Option<f32>,
+
+    /// magnification, 1 fits the volume into the image
+    #[arg(long)]
+    zoom: Option<f32>,
+
+    /// stereo output: side-by-side, anaglyph, top-bottom (each eye half the height) or
+    /// pair (each eye at the full resolution, twice as wide)
+    #[arg(long)]
+    stereo: Option<StereoMode>,
+
+    /// interocular distance for stereo rendering (the smallest side of the volume is 1)
+    #[arg(long)]
+    eye_separation: Option<f32>,
+
+    /// value mapped to the start of the colormap
+    #[arg(long, allow_negative_numbers = true)]
+    vmin: Option<f32>,
+
+    /// value mapped to the end of the colormap
+    #[arg(long, allow_negative_numbers = true)]
+    vmax: Option<f32>,
+
+    /// larger values make everything more opaque
+    #[arg(long)]
+    distance_scale: Option<f32>,
+
+    /// distance between the samples along a ray (smaller is more accurate but slower)
+    #[arg(long)]
+    step_size: Option<f32>,
+
+    /// nearest, linear or cubic interpolation between the voxels
+    #[arg(long)]
+    spatial_interpolation: Option<Interpolation>,
+
+    /// nearest, linear or cubic interpolation between the timesteps
+    #[arg(long)]
+    temporal_interpolation: Option<Interpolation>,
+
+    /// renders only this region of the volume, relative to its size
+    #[arg(long, num_args = 6, value_names = ["MIN_X", "MIN_Y", "MIN_Z", "MAX_X", "MAX_Y", "MAX_Z"])]
+    clip: Option<Vec<f32>>,
+
+    /// draws surfaces where the volume crosses these values
+    #[arg(long, num_args = 1.., value_name = "VALUE", allow_negative_numbers = true)]
+    iso: Option<Vec<f32>>,
+
+    /// the array is stored as [C, T, D, H, W] instead of [T, C, D, H, W]
+    #[arg(long, default_value_t = false)]
+    channel_first: bool,
+}
+
+impl RenderArgs {
+    /// the spec read from the input, or describing the input volume,
+    /// with the options of the command line. `volume` replaces the volume of the spec
+    fn spec(&self, volume: Option<&Path>) -> anyhow::Result<(RenderSpec, SpecFiles)> {
+        let (mut spec, base_dir) = match &self.input {
+            Some(input) if is_spec(input) => {
+                let spec = RenderSpec::read_json(BufReader::new(File::open(input)?))?;
+                (spec, input.parent().unwrap_or(Path::new(".")))
+            }
+            Some(input) => {
+                let spec = RenderSpec {
+                    volume: VolumeSpec {
+                        path: input.to_string_lossy().into_owned(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                (spec, Path::new("."))
+            }
+            None => (RenderSpec::default(), Path::new(".")),
+        };
+        if let Some(volume) = volume {
+            // relative to the working directory, not to the spec
+            spec.volume.path = std::path::absolute(volume)?.to_string_lossy().into_owned();
+        }
+        if self.channel_first {
+            spec.volume.channel_first = true;
+        }
+        if self.width.is_some() || self.height.is_some() {
+            spec.output.preset = None;
+        }
+        if let Some(preset) = &self.preset {
+            spec.output.preset = Some(preset.clone());
+        }
+        if let Some(supersampling) = self.supersampling {
+            spec.output.supersampling = Some(supersampling);
+        }
+        if let Some(width) = self.width {
+            spec.output.width = width;
+        }
+        if let Some(height) = self.height {
+            spec.output.height = height;
+        }
+        if let Some(bit_depth) = self.bit_depth {
+            spec.output.bit_depth = bit_depth;
+        }
+        if self.transparent {
+            spec.render.background = [0.; 4];
+        }
+        if let Some(alpha) = self.alpha {
+            spec.output.alpha = alpha;
+        }
+        if let Some(aovs) = &self.aov {
+            spec.output.aovs = aovs.clone();
+        }
+        if let Some(times) = &self.time {
+            spec.animation.times = times.clone();
+        }
+        if let Some(colormap) = &self.colormap {
+            spec.colormap.name = Some(colormap.clone());
+        }
+        if let Some(view) = &self.view {
+            spec.camera = serde_json::from_reader(BufReader::new(File::open(view)?))
+                .map_err(|err| anyhow::anyhow!("invalid camera {}: {err}", view.display()))?;
+        }
+        let vector = |v: &Vec<f32>| [v[0], v[1], v[2]];
+        if let Some(up) = &self.up {
+            spec.camera.up = vector(up);
+        }
+        if let Some(camera) = &self.camera {
+            spec.camera.direction = vector(camera);
+            spec.camera.position = None;
+            spec.camera.azimuth = None;
+            spec.camera.elevation = None;
+            // looking along the y axis, z points up instead (like the axis views of the viewer)
+            let direction = Vector3::from(spec.camera.direction).normalize();
+            if self.up.is_none()
+                && direction.cross(Vector3::from(spec.camera.up)).magnitude2() < 1e-6
+            {
+                spec.camera.up = [0., 0., -direction.y.signum()];
+            }
+        }
+        if let Some(position) = &self.camera_position {
+            spec.camera.position = Some(vector(position));
+        }
+        if let Some(target) = &self.target {
+            spec.camera.target = Some(vector(target));
+        }
+        if self.azimuth.is_some() || self.elevation.is_some() {
+            spec.camera.position = None;
+            spec.camera.azimuth = self.azimuth.or(spec.camera.azimuth);
+            spec.camera.elevation = self.elevation.or(spec.camera.elevation);
+        }
+        if let Some(projection) = self.projection {
+            spec.camera.projection = projection;
+        }
+        if let Some(fov) = self.fov {
+            spec.camera.fov = fov;
+        }
+        if let Some(zoom) = self.zoom {
+            spec.camera.zoom = zoom;
+        }
+        if let Some(stereo) = self.stereo {
+            spec.render.stereo = stereo.name().to_lowercase();
+        }
+        if let Some(eye_separation) = self.eye_separation {
+            spec.render.eye_separation = eye_separation;
+        }
+        if self.vmin.is_some() {
+            spec.render.vmin = self.vmin;
+        }
+        if self.vmax.is_some() {
+            spec.render.vmax = self.vmax;
+        }
+        if let Some(distance_scale) = self.distance_scale {
+            spec.render.distance_scale = distance_scale;
+        }
+        if self.step_size.is_some() {
+            spec.render.step_size = self.step_size;
+        }
+        if let Some(interpolation) = self.spatial_interpolation {
+            spec.render.spatial_interpolation = interpolation.name().to_lowercase();
+        }
+        if let Some(interpolation) = self.temporal_interpolation {
+            spec.render.temporal_interpolation = interpolation.name().to_lowercase();
+        }
+        if let Some(clip) = &self.clip {
+            spec.render.clipping = Some([[clip[0], clip[1], clip[2]], [clip[3], clip[4], clip[5]]]);
+        }
+        for value in self.iso.iter().flatten() {
+            spec.overlays.reference_values.push(ReferenceValue {
+                surface: true,
+                ..ReferenceValue::new(format!("{value}"), *value)
+            });
+        }
+        if let Some(columns) = self.contact_sheet {
+            let sheet = spec
+                .output
+                .contact_sheet
+                .get_or_insert_with(Default::default);
+            sheet.columns = columns;
+            spec.overlays.time_label = true;
+        }
+        spec.validate()?;
+        let files = spec.load_files(base_dir)?;
+        Ok((spec, files))
+    }
+}
+
+fn diff(
+    image_a: PathBuf,
+    image_b: PathBuf,
+    output: Option<PathBuf>,
+    min_ssim: Option<f64>,
+) -> anyhow::Result<()> {
+    let a = image::open(&image_a)?.to_rgba8();
+    let b = image::open(&image_b)?.to_rgba8();
+    let result = diff_images(&a, &b)?;
+    println!("PSNR: {:.2} dB", result.psnr);
+    println!("SSIM: {:.5}", result.ssim);
+    println!("max error: {}", result.max_error);
+    if let Some(output) = output {
+        result.heatmap.save(output)?;
+    }
+    if let Some(min_ssim) = min_ssim {
+        if result.ssim < min_ssim {
+            anyhow::bail!("SSIM {:.5} is below {}", result.ssim, min_ssim);
+        }
+    }
+    Ok(())
+}
+
+/// reads a render spec and the files it references (relative to the spec file)
+fn read_spec(path: &Path) -> anyhow::Result<(RenderSpec, SpecFiles)> {
+    let spec = RenderSpec::read_json(BufReader::new(File::open(path)?))?;
+    let files = spec.load_files(path.parent().unwrap_or(Path::new(".")))?;
+    Ok((spec, files))
+}
+
+fn is_spec(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+async fn render(args: RenderArgs) -> anyhow::Result<()> {
+    if let Some(patterns) = &args.batch {
+        return render_batch(&args, patterns).await;
+    }
+    let (spec, files) = args.spec(None)?;
+    render_to(&args, &spec, &files, args.output.clone()).await
+}
+
+/// renders every volume matching the `patterns` into the output directory,
+/// the GPU is initialized only once
+async fn render_batch(args: &RenderArgs, patterns: &[String]) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        args.input.as_deref().is_none_or(is_spec),
+        "the input of --batch has to be a render spec (json) with the shared settings"
+    );
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        let matches = expand_pattern(pattern)?;
+        anyhow::ensure!(!matches.is_empty(), "no files match '{pattern}'");
+        inputs.extend(matches);
+    }
+    let mut outputs = Vec::with_capacity(inputs.len());
+    for input in &inputs {
+        let name = input
+            .file_stem()
+            .ok_or_else(|| anyhow::anyhow!("invalid input file {}", input.display()))?
+            .to_string_lossy();
+        let output = args.output.join(format!("{name}.{}", args.format));
+        anyhow::ensure!(
+            !outputs.contains(&output),
+            "several inputs are named {name}"
+        );
+        outputs.push(output);
+    }
+    std::fs::create_dir_all(&args.output)?;
+    let mut failed = 0;
+    for (i, (input, output)) in inputs.iter().zip(outputs).enumerate() {
+        eprintln!("[{}/{}] {}", i + 1, inputs.len(), input.display());
+        let result = match args.spec(Some(input)) {
+            Ok((spec, files)) => render_to(args, &spec, &files, output).await,
+            Err(err) => Err(err),
+        };
+        // the other inputs are still rendered
+        if let Err(err) = result {
+            eprintln!("error: {}: {err:#}", input.display());
+            failed += 1;
+        }
+    }
+    anyhow::ensure!(failed == 0, "{failed} of {} inputs failed", inputs.len());
+    Ok(())
+}
+
+/// files matching a path with * and ? in the file name, sorted by name.
+/// A path without wildcards is returned as it is
+fn expand_pattern(pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    if !name.contains(['*', '?']) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name: Vec<char> = name.chars().collect();
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("cannot read directory {}: {e}", dir.display()))?
+    {
+        let entry = entry?;
+        let file_name: Vec<char> = entry.file_name().to_string_lossy().chars().collect();
+        if entry.file_type()?.is_file() && wildcard_match(&name, &file_name) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// true if `text` matches `pattern`, where * matches any characters and ? a single one
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| wildcard_match(rest, &text[i..])),
+        Some((p, rest)) => text
+            .split_first()
+            .is_some_and(|(t, text)| (*p == '?' || p == t) && wildcard_match(rest, text)),
+    }
+}
+
+/// renders the spec to `output`, an animation with --frames, --turntable or a video output
+async fn render_to(
+    args: &RenderArgs,
+    spec: &RenderSpec,
+    files: &SpecFiles,
+    output: PathBuf,
+) -> anyhow::Result<()> {
+    let video = VideoFormat::from_path(&output);
+    if args.frames.is_some() || video.is_some() || args.turntable {
+        let num_frames = match args.frames {
+            Some(frames) => frames,
+            None if args.turntable => TURNTABLE_FRAMES,
+            None => spec.frames().len(),
+        };
+        let output = match video {
+            Some(format) => AnimationOutput::Video {
+                path: output,
+                format,
+                fps: args.fps,
+            },
+            None => AnimationOutput::Images(output),
+        };
+        spec.render_animation(
+            spec.volumes(files)?,
+            files,
+            num_frames,
+            args.turntable,
+            &output,
+            |frame, total| eprint!("\rframe {frame}/{total}"),
+        )
+        .await?;
+        eprintln!();
+        return Ok(());
+    }
+    let volumes = spec.volumes(files)?;
+    let bit_depth = spec.output.bit_depth.for_path(&output);
+    let save_aovs = |aovs: &AovImages, path: &Path| {
+        for (aov, img) in aovs {
+            aov.save(img, path, bit_depth)?;
+        }
+        anyhow::Ok(())
+    };
+    if bit_depth == BitDepth::Eight {
+        let frames = spec.render_with_aovs::<Rgba<u8>>(volumes, files).await?;
+        return save_frames(&frames, &output, |(frame, aovs), path| {
+            frame.save(path)?;
+            save_aovs(aovs, path)
+        });
+    }
+    let frames = spec.render_with_aovs::<Rgba<f32>>(volumes, files).await?;
+    save_frames(&frames, &output, |(frame, aovs), path| {
+        save_image(frame, path, bit_depth)?;
+        save_aovs(aovs, path)
+    })
+}
+
+/// writes a single frame to `output`, numbered images if there are more
+fn save_frames<T>(
+    frames: &[T],
+    output: &Path,
+    save: impl Fn(&T, &Path) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if let [frame] = frames {
+        return save(frame, output);
+    }
+    for (i, frame) in frames.iter().enumerate() {
+        save(frame, &numbered_path(output, i)?)?;
+    }
+    Ok(())
+}
+
 pub async fn viewer<I, T>(args: I) -> anyhow::Result<()>
 where
     I: IntoIterator<Item = T>,
     T: Into<OsString> + Clone,
 {
     env_logger::init();
-    let opt = Opt::try_parse_from(args)?;
+    let mut opt = Opt::try_parse_from(args)?;
 
-    let data_file = File::open(&opt.input)?;
+    match opt.command.take() {
+        Some(Command::Diff {
+            image_a,
+            image_b,
+            output,
+            min_ssim,
+        }) => return diff(image_a, image_b, output, min_ssim),
+        Some(Command::Render(args)) => return render(*args).await,
+        Some(Command::FetchExample { name: None, .. }) => {
+            for dataset in EXAMPLE_DATASETS {
+                println!("{:<12} {}", dataset.name, dataset.description);
+            }
+            return Ok(());
+        }
+        Some(Command::FetchExample {
+            name: Some(name),
+            no_open,
+        }) => {
+            let path = fetch_example(&name)?;
+            println!("{}", path.display());
+            if no_open {
+                return Ok(());
+            }
+            opt.input = Some(path);
+        }
+        None => {}
+    }
 
     let window_builder = WindowBuilder::new().with_inner_size(PhysicalSize::new(800, 600));
 
-    let volumes = Volume::load_numpy(BufReader::new(data_file), !opt.channel_first)
+    if let Some(path) = opt.spec {
+        let (spec, files) = read_spec(&path)?;
+        let mut render_config = spec.render_config(&files)?;
+        render_config.no_vsync = opt.no_vsync;
+        render_config.render_scale = opt.render_scale;
+        render_config.bookmarks_file = Some(bookmarks_file(&path));
+        render_config.transfer_function_file = opt.save_transfer_function;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        render_config.volume_file = Some(base_dir.join(&spec.volume.path));
+        render_config.mask_file = spec.volume.mask.as_ref().map(|mask| base_dir.join(mask));
+        open_window(
+            window_builder,
+            spec.volumes(&files)?,
+            spec.colormap.colormap(&files)?,
+            render_config,
+        )
+        .await;
+        return Ok(());
+    }
+
+    if let Some(path) = opt.session {
+        let session = Session::read(&path)?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        let file = session
+            .volume_file(base_dir)
+            .ok_or_else(|| anyhow::anyhow!("the session has no volume file"))?;
+        let mut volumes = Volume::load_numpy(BufReader::new(File::open(&file)?), true)?;
+        volumes.iter_mut().for_each(|v| session.apply_spacing(v));
+        session.load_mask(base_dir, &mut volumes)?;
+        let mut render_config = session.render_config(base_dir);
+        render_config.no_vsync = opt.no_vsync;
+        render_config.render_scale = opt.render_scale;
+        render_config.transfer_function_file = opt.save_transfer_function;
+        render_config.volume_file = Some(file);
+        open_window(
+            window_builder,
+            volumes,
+            session.colormap.clone(),
+            render_config,
+        )
+        .await;
+        return Ok(());
+    }
+
+    let input = opt.input.unwrap();
+    let data_file = File::open(&input)?;
+
+    let mut volumes = Volume::load_numpy(BufReader::new(data_file), !opt.channel_first)
         .expect("Failed to load volume");
+    if let Some(spacing) = opt.spacing {
+        anyhow::ensure!(spacing.iter().all(|s| *s > 0.), "spacing must be positive");
+        for v in volumes.iter_mut() {
+            v.set_spacing(Vector3::new(spacing[2], spacing[1], spacing[0]));
+        }
+    }
+    let mask = opt
+        .mask
+        .as_ref()
+        .map(|path| Mask::load_numpy(BufReader::new(File::open(path)?)))
+        .transpose()?;
+    apply_mask(&mut volumes, mask, opt.invert_mask)?;
 
-    #[cfg(feature = "colormaps")]
-    let cmap = opt.colormap.map_or(
-        Ok(cmap::COLORMAPS["seaborn"]["icefire"].clone()),
-        |path| {
-            let reader = File::open(path)?;
-            cmap::GenericColorMap::read(reader)
-        },
-    )?;
-    #[cfg(not(feature = "colormaps"))]
-    let cmap = {
-        let reader = File::open(&opt.colormap)?;
-        cmap::GenericColorMap::read(reader)?
+    let percentiles = [opt.vmin_percentile, opt.vmax_percentile];
+    for p in percentiles.iter().flatten() {
+        anyhow::ensure!((0. ..=100.).contains(p), "percentiles must be in [0, 100]");
+    }
+    let (vmin, vmax) = match percentiles {
+        [None, None] => (None, None),
+        [lower, upper] => {
+            let values = volumes[0].percentiles(&[lower.unwrap_or(0.), upper.unwrap_or(100.)]);
+            (lower.map(|_| values[0]), upper.map(|_| values[1]))
+        }
+    };
+
+    let cmap = match (opt.colormap_hex, opt.colormap) {
+        (Some(colors), _) => cmap::GenericColorMap::LinearSegmented(
+            cmap::LinearSegmentedColorMap::from_hex_list(&colors)?,
+        ),
+        (None, Some(path)) => cmap::GenericColorMap::read(File::open(path)?)?,
+        #[cfg(feature = "colormaps")]
+        (None, None) => cmap::COLORMAPS["seaborn"]["icefire"].clone(),
+        // only for example datasets, the colormap is required otherwise
+        #[cfg(not(feature = "colormaps"))]
+        (None, None) => cmap::GenericColorMap::LinearSegmented(
+            cmap::LinearSegmentedColorMap::from_hex_list("#000000,#ffffff")?,
+        ),
+    };
+
+    let cmap_sequence = if opt.colormap_sequence.is_empty() {
+        None
+    } else {
+        let cmaps = opt
+            .colormap_sequence
+            .iter()
+            .map(|path| cmap::GenericColorMap::read(File::open(path)?))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Some(match opt.colormap_times {
+            Some(times) => {
+                anyhow::ensure!(
+                    times.len() == cmaps.len(),
+                    "expected {} colormap times, got {}",
+                    cmaps.len(),
+                    times.len()
+                );
+                cmap::ColorMapSequence::new(times.into_iter().zip(cmaps).collect())?
+            }
+            None => cmap::ColorMapSequence::uniform(cmaps)?,
+        })
+    };
+
+    let reference_values = match opt.reference_values {
+        Some(path) => ReferenceValue::read_json(BufReader::new(File::open(path)?))?,
+        None => Vec::new(),
     };
 
-    open_window(
-        window_builder,
+    let cmap = cmap.into_linear_segmented(cmap::COLORMAP_RESOLUTION);
+    let mut windows = vec![ViewerWindow {
         volumes,
-        cmap.into_linear_segmented(cmap::COLORMAP_RESOLUTION),
-        RenderConfig {
+        cmap: cmap.clone(),
+        config: RenderConfig {
             no_vsync: opt.no_vsync,
             background_color: wgpu::Color::BLACK,
+            background: if opt.environment.is_some() {
+                Background::Environment
+            } else {
+                Background::Solid
+            },
+            environment_map: opt.environment,
+            annotations: Annotations {
+                scale_bar: opt.scale_bar,
+                ticks: opt.scale_bar,
+                unit: opt.unit.unwrap_or_default(),
+                ..Default::default()
+            },
+            stereo: Stereo {
+                mode: opt.stereo,
+                eye_separation: opt.eye_separation,
+            },
+            cmap_sequence,
+            reference_values,
+            colorbar: opt.colorbar.map(|position| Colorbar {
+                position,
+                ..Default::default()
+            }),
             show_colormap_editor: true,
             show_volume_info: true,
-            vmin: None,
-            vmax: None,
+            vmin,
+            vmax,
             #[cfg(feature = "colormaps")]
             show_cmap_select: true,
-            duration: None,
+            duration: opt.duration.map(Duration::from_secs_f32),
+            fps: opt.fps,
+            reverse_playback: opt.reverse,
+            snap_timesteps: opt.snap_timesteps,
+            bookmarks_file: Some(bookmarks_file(&input)),
+            keyframes: Vec::new(),
+            volume_file: Some(input),
+            mask_file: opt.mask,
+            invert_mask: opt.invert_mask,
+            render_settings: None,
+            transfer_function_file: opt.save_transfer_function,
+            session: None,
+            compare_file: opt.compare,
+            difference: opt.difference,
             distance_scale: 1.0,
+            spatial_interpolation: Interpolation::Linear,
+            temporal_interpolation: Interpolation::Linear,
+            camera: CameraView::default(),
+            render_scale: opt.render_scale,
         },
-    )
-    .await;
+    }];
+    for path in opt.windows {
+        let mut window = ViewerWindow::load(&path, cmap.clone())?;
+        window.config.no_vsync = opt.no_vsync;
+        window.config.render_scale = opt.render_scale;
+        windows.push(window);
+    }
+    open_windows(window_builder, windows).await;
     Ok(())
 }
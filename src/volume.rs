@@ -1,5 +1,5 @@
 use bytemuck::Zeroable;
-use cgmath::{BaseNum, EuclideanSpace, MetricSpace, Point3, Vector3, Zero};
+use cgmath::{BaseNum, ElementWise, EuclideanSpace, MetricSpace, Point3, Vector3, Zero};
 use half::f16;
 #[cfg(target_arch = "wasm32")]
 use instant::Instant;
@@ -7,21 +7,270 @@ use npyz::{npz, Deserialize, NpyFile};
 use num_traits::Float;
 #[cfg(feature = "python")]
 use numpy::ndarray::ArrayViewD;
-use std::io::{Read, Seek};
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
+use std::{
+    io::{Read, Seek},
+    sync::OnceLock,
+};
 use wgpu::util::{DeviceExt, TextureDataOrder};
 
+/// name of the optional array in npz files holding the voxel spacing
+pub const SPACING_ARRAY: &str = "spacing";
+/// name of the optional array in npz files holding the [Mask]
+pub const MASK_ARRAY: &str = "mask";
+
+/// number of values without a colormap color (see [Volume::invalid_counts])
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InvalidCounts {
+    /// NaN and infinite values
+    pub nan: usize,
+    pub below: usize,
+    pub above: usize,
+}
+
 pub struct Volume {
     pub timesteps: u32,
     pub resolution: Vector3<u32>,
     pub aabb: Aabb<f32>,
     pub min_value: f32,
     pub max_value: f32,
+    /// physical size of a voxel along x, y and z
+    pub spacing: Vector3<f32>,
     data: Vec<f16>,
+    /// value range (min, max) of each timestep, computed on first use
+    timestep_ranges: OnceLock<Vec<(f32, f32)>>,
+    mask: Option<Mask>,
 }
 
 impl Volume {
+    /// values of a single timestep in z, y, x order
+    pub(crate) fn timestep(&self, i: usize) -> &[f16] {
+        let numel =
+            self.resolution.x as usize * self.resolution.y as usize * self.resolution.z as usize;
+        &self.data[i * numel..(i + 1) * numel]
+    }
+
+    pub fn mask(&self) -> Option<&Mask> {
+        self.mask.as_ref()
+    }
+
+    /// sets the mask whose values multiply the opacity, it must have the volume's resolution
+    pub fn set_mask(&mut self, mask: Option<Mask>) -> anyhow::Result<()> {
+        if let Some(mask) = &mask {
+            anyhow::ensure!(
+                mask.resolution == self.resolution,
+                "mask resolution {:?} does not match the volume resolution {:?}",
+                mask.resolution,
+                self.resolution
+            );
+        }
+        self.mask = mask;
+        Ok(())
+    }
+
+    /// sets the physical size of a voxel (x, y, z) and stretches the bounding box accordingly
+    pub fn set_spacing(&mut self, spacing: Vector3<f32>) {
+        let extent = Vector3::new(
+            self.resolution[2] as f32 * spacing.x,
+            self.resolution[1] as f32 * spacing.y,
+            self.resolution[0] as f32 * spacing.z,
+        );
+        let min_extent = extent.x.min(extent.y).min(extent.z);
+        self.aabb = Aabb {
+            min: Point3::origin(),
+            max: Point3::from_vec(extent / min_extent),
+        };
+        self.spacing = spacing;
+    }
+
+    /// voxel-wise difference `self - other` of two volumes of the same shape.
+    /// The value range is symmetric around zero, so that zero is in the middle of the colormap.
+    pub fn difference(&self, other: &Self) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            self.resolution == other.resolution && self.timesteps == other.timesteps,
+            "volume shapes do not match ({} x {:?} vs {} x {:?})",
+            self.timesteps,
+            self.resolution,
+            other.timesteps,
+            other.resolution
+        );
+        let data: Vec<f16> = self
+            .data
+            .iter()
+            .zip(&other.data)
+            .map(|(a, b)| f16::from_f32(a.to_f32() - b.to_f32()))
+            .collect();
+        let max = data
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold(0f32, |max, v| max.max(v.to_f32().abs()));
+        let max = if max > 0. { max } else { 1. };
+        Ok(Self {
+            timesteps: self.timesteps,
+            resolution: self.resolution,
+            aabb: self.aabb,
+            min_value: -max,
+            max_value: max,
+            spacing: self.spacing,
+            data,
+            timestep_ranges: OnceLock::new(),
+            mask: self.mask.clone(),
+        })
+    }
+
+    /// joins volumes of the same resolution (e.g. read from one file per timestep)
+    /// into one time-varying volume, in the given order.
+    /// Spacing and mask are taken from the first volume.
+    pub fn concat_timesteps(volumes: Vec<Self>) -> anyhow::Result<Self> {
+        let mut volumes = volumes.into_iter();
+        let mut series = volumes
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no volumes to join"))?;
+        for v in volumes {
+            anyhow::ensure!(
+                v.resolution == series.resolution,
+                "resolution {:?} does not match the first volume ({:?})",
+                v.resolution,
+                series.resolution
+            );
+            series.timesteps += v.timesteps;
+            series.min_value = series.min_value.min(v.min_value);
+            series.max_value = series.max_value.max(v.max_value);
+            series.data.extend_from_slice(&v.data);
+        }
+        series.timestep_ranges = OnceLock::new();
+        Ok(series)
+    }
+
+    /// value range (min, max) of each timestep, NaN and infinite values are ignored
+    pub fn timestep_ranges(&self) -> &[(f32, f32)] {
+        self.timestep_ranges.get_or_init(|| {
+            (0..self.timesteps as usize)
+                .map(|t| {
+                    let (min, max) = self
+                        .timestep(t)
+                        .iter()
+                        .filter(|v| v.is_finite())
+                        .fold((f32::MAX, f32::MIN), |(min, max), v| {
+                            (min.min(v.to_f32()), max.max(v.to_f32()))
+                        });
+                    if min < max {
+                        (min, max)
+                    } else {
+                        (min, min + 1.)
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// value range at the normalized `time`, linearly interpolated between the timesteps
+    pub fn range_at(&self, time: f32) -> (f32, f32) {
+        let ranges = self.timestep_ranges();
+        let last = ranges.len() - 1;
+        let t = time.clamp(0., 1.) * last as f32;
+        let i = (t as usize).min(last);
+        let f = t - i as f32;
+        let (a, b) = (ranges[i], ranges[(i + 1).min(last)]);
+        (a.0 + (b.0 - a.0) * f, a.1 + (b.1 - a.1) * f)
+    }
+
+    /// number of NaN/Inf values and values outside of [vmin, vmax] in `timestep`
+    pub fn invalid_counts(&self, timestep: usize, vmin: f32, vmax: f32) -> InvalidCounts {
+        let mut counts = InvalidCounts::default();
+        for v in self.timestep(timestep) {
+            let v = v.to_f32();
+            if !v.is_finite() {
+                counts.nan += 1;
+            } else if v < vmin {
+                counts.below += 1;
+            } else if v > vmax {
+                counts.above += 1;
+            }
+        }
+        counts
+    }
+
+    /// values at the `percentiles` (in [0, 100]) of all timesteps, NaNs are ignored.
+    /// Exact, since every f16 value has its own bin.
+    pub fn percentiles(&self, percentiles: &[f32]) -> Vec<f32> {
+        // bins ordered by value: negative values in reverse bit order first
+        let key = |bits: u16| {
+            if bits & 0x8000 != 0 {
+                !bits as usize
+            } else {
+                (bits | 0x8000) as usize
+            }
+        };
+        let mut counts = vec![0u64; 1 << 16];
+        for v in self.data.iter().filter(|v| !v.is_nan()) {
+            counts[key(v.to_bits())] += 1;
+        }
+        let total: u64 = counts.iter().sum();
+        let value = |k: usize| {
+            let bits = if k & 0x8000 != 0 {
+                k as u16 & 0x7fff
+            } else {
+                !(k as u16)
+            };
+            f16::from_bits(bits).to_f32()
+        };
+        percentiles
+            .iter()
+            .map(|p| {
+                let rank = (p.clamp(0., 100.) as f64 / 100. * total as f64) as u64;
+                let rank = rank.min(total.saturating_sub(1));
+                let mut seen = 0;
+                let k = counts
+                    .iter()
+                    .position(|c| {
+                        seen += c;
+                        seen > rank
+                    })
+                    .unwrap_or(0);
+                value(k)
+            })
+            .collect()
+    }
+
+    /// length of the shortest voxel side in world space
+    /// index (depth, height, width) of the voxel containing the world space `position`,
+    /// positions outside of the volume are clamped to it
+    pub fn voxel_index(&self, position: Point3<f32>) -> [usize; 3] {
+        let p = (position - self.aabb.min).div_element_wise(self.aabb.max - self.aabb.min);
+        let index = |v: f32, n: u32| ((v.clamp(0., 1.) * n as f32) as usize).min(n as usize - 1);
+        // the y axis of the array points down
+        [
+            index(p.z, self.resolution[0]),
+            index(1. - p.y, self.resolution[1]),
+            index(p.x, self.resolution[2]),
+        ]
+    }
+
+    /// value of the voxel with `index` (depth, height, width) at `timestep`
+    pub fn voxel_value(&self, timestep: usize, [d, h, w]: [usize; 3]) -> f32 {
+        let (height, width) = (self.resolution[1] as usize, self.resolution[2] as usize);
+        self.timestep(timestep)[(d * height + h) * width + w].to_f32()
+    }
+
+    pub fn voxel_size(&self) -> f32 {
+        let size = self.aabb.max - self.aabb.min;
+        (size.x / self.resolution[2] as f32)
+            .min(size.y / self.resolution[1] as f32)
+            .min(size.z / self.resolution[0] as f32)
+    }
+
+    /// raymarching step size of half a voxel, so that no voxel is skipped
+    pub fn default_step_size(&self) -> f32 {
+        self.voxel_size() / 2.
+    }
+
+    /// physical length of one unit in world space
+    pub fn physical_scale(&self) -> f32 {
+        self.resolution[2] as f32 * self.spacing.x / (self.aabb.max.x - self.aabb.min.x)
+    }
+
     #[cfg(feature = "python")]
     pub fn from_array(data: ArrayViewD<f16>) -> Self {
         let shape = data.shape().to_vec();
@@ -37,15 +286,25 @@ impl Volume {
                 resolution[0] as f32 / *res_min as f32,
             ),
         };
-        let vmin = *vec_data.iter().min_by(|a, b| a.total_cmp(b)).unwrap();
-        let vmax = *vec_data.iter().max_by(|a, b| a.total_cmp(b)).unwrap();
+        let finite = || vec_data.iter().filter(|v| v.is_finite());
+        let vmin = finite()
+            .min_by(|a, b| a.total_cmp(b))
+            .copied()
+            .unwrap_or(f16::ZERO);
+        let vmax = finite()
+            .max_by(|a, b| a.total_cmp(b))
+            .copied()
+            .unwrap_or(f16::ONE);
         Self {
             timesteps: shape[0] as u32,
             resolution: resolution.into(),
             aabb,
             min_value: vmin.to_f32(),
             max_value: vmax.to_f32(),
+            spacing: Vector3::new(1., 1., 1.),
             data: vec_data,
+            timestep_ranges: OnceLock::new(),
+            mask: None,
         }
     }
 
@@ -87,11 +346,10 @@ impl Volume {
             let v64: f64 = v.unwrap().into();
             let v32: f32 = v64 as f32;
             let v = f16::from_f32(v32);
-            if v32 > max_value {
-                max_value = v32;
-            }
-            if v32 < min_value {
-                min_value = v32;
+            // NaN and infinite values don't count towards the value range
+            if v32.is_finite() {
+                max_value = max_value.max(v32);
+                min_value = min_value.min(v32);
             }
             let (c, idx) = if time_first {
                 let t = i / strides[0] as usize;
@@ -107,6 +365,10 @@ impl Volume {
             volumes[c][idx] = v;
         }
 
+        if min_value > max_value {
+            // no finite values
+            (min_value, max_value) = (0., 1.);
+        }
         if min_value == max_value {
             max_value = min_value + 1.0;
         }
@@ -128,7 +390,10 @@ impl Volume {
                 aabb,
                 max_value,
                 min_value,
+                spacing: Vector3::new(1., 1., 1.),
                 data: volumes[c].clone(),
+                timestep_ranges: OnceLock::new(),
+                mask: None,
             })
             .collect();
         log::info!("read volume in {:?}", start.elapsed());
@@ -185,20 +450,179 @@ impl Volume {
         let mut reader = npz::NpzArchive::new(reader)?;
         let arr_name = reader
             .array_names()
-            .next()
+            .find(|name| *name != SPACING_ARRAY && *name != MASK_ARRAY)
             .ok_or(anyhow::format_err!("no array present"))?
             .to_string();
         let array = reader.by_name(arr_name.as_str())?.unwrap();
-        Self::read(array, time_first)
+        let mut volumes = Self::read(array, time_first)?;
+        if let Some(spacing) = reader.by_name(SPACING_ARRAY)? {
+            let spacing = Self::read_spacing(spacing)?;
+            for v in volumes.iter_mut() {
+                v.set_spacing(spacing);
+            }
+        }
+        if let Some(mask) = reader.by_name(MASK_ARRAY)? {
+            let mask = Mask::read(mask)?;
+            for v in volumes.iter_mut() {
+                v.set_mask(Some(mask.clone()))?;
+            }
+        }
+        Ok(volumes)
+    }
+
+    /// reads the voxel spacing given in array order (depth, height, width)
+    fn read_spacing<R: Read>(array: NpyFile<R>) -> anyhow::Result<Vector3<f32>> {
+        let values: Vec<f64> = match array.dtype() {
+            npyz::DType::Plain(d)
+                if d.type_char() == npyz::TypeChar::Float && d.num_bytes() == Some(4) =>
+            {
+                array
+                    .into_vec::<f32>()?
+                    .into_iter()
+                    .map(f64::from)
+                    .collect()
+            }
+            _ => array.into_vec::<f64>()?,
+        };
+        if values.len() != 3 || values.iter().any(|v| *v <= 0.) {
+            anyhow::bail!(
+                "spacing must contain three positive values, got {:?}",
+                values
+            );
+        }
+        Ok(Vector3::new(values[2], values[1], values[0])
+            .cast()
+            .unwrap())
+    }
+}
+
+/// opacity multiplier in [0, 1] for each voxel, shared by all timesteps.
+/// Used to hide regions like padding or out-of-domain areas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mask {
+    /// size in array order (depth, height, width)
+    pub resolution: Vector3<u32>,
+    data: Vec<f16>,
+}
+
+impl Mask {
+    /// values in z, y, x order
+    pub fn data(&self) -> &[f16] {
+        &self.data
+    }
+
+    /// mask that hides what was visible before and vice versa
+    pub fn inverted(mut self) -> Self {
+        for v in self.data.iter_mut() {
+            *v = f16::ONE - *v;
+        }
+        self
+    }
+
+    /// reads a mask from a npy file or the [MASK_ARRAY] (or else the first array) of a npz file
+    pub fn load_numpy<R: Read + Seek>(mut reader: R) -> anyhow::Result<Self> {
+        let mut buffer = [0; 4];
+        reader.read_exact(&mut buffer)?;
+        reader.seek(std::io::SeekFrom::Current(-4))?;
+        if buffer != *b"\x50\x4B\x03\x04" {
+            return Self::read(NpyFile::new(reader)?);
+        }
+        let mut reader = npz::NpzArchive::new(reader)?;
+        let name = reader
+            .array_names()
+            .find(|name| *name == MASK_ARRAY)
+            .or_else(|| reader.array_names().next())
+            .ok_or(anyhow::format_err!("no array present"))?
+            .to_string();
+        let array = reader.by_name(&name)?.unwrap();
+        Self::read(array)
+    }
+
+    /// reads a bool, integer or float array of shape [D, H, W] (or [1, 1, D, H, W]).
+    /// Values are divided by the maximum if it is larger than one (e.g. for 0/255 masks)
+    /// and clamped to [0, 1].
+    pub fn read<R: Read>(array: NpyFile<R>) -> anyhow::Result<Self> {
+        let shape = array.shape().to_vec();
+        anyhow::ensure!(
+            shape.len() >= 3 && shape[..shape.len() - 3].iter().all(|s| *s == 1),
+            "unsupported mask shape: {:?}",
+            shape
+        );
+        let values = match array.dtype() {
+            npyz::DType::Plain(d) => match (d.type_char(), d.num_bytes().unwrap()) {
+                (npyz::TypeChar::Bool, _) => array
+                    .into_vec::<bool>()?
+                    .into_iter()
+                    .map(|v| v as u8 as f32)
+                    .collect(),
+                (npyz::TypeChar::Uint, 1) => Self::values::<_, u8>(array)?,
+                (npyz::TypeChar::Uint, 2) => Self::values::<_, u16>(array)?,
+                (npyz::TypeChar::Int, 1) => Self::values::<_, i8>(array)?,
+                (npyz::TypeChar::Int, 2) => Self::values::<_, i16>(array)?,
+                (npyz::TypeChar::Float, 2) => Self::values::<_, f16>(array)?,
+                (npyz::TypeChar::Float, 4) => Self::values::<_, f32>(array)?,
+                (npyz::TypeChar::Float, 8) => Self::values::<_, f64>(array)?,
+                _ => anyhow::bail!("unsupported mask type {:}", d),
+            },
+            d => anyhow::bail!("unsupported mask type {:}", d.descr()),
+        };
+        let max = values.iter().copied().fold(1f32, f32::max);
+        let [d, h, w] = [3, 2, 1].map(|i| shape[shape.len() - i] as u32);
+        Ok(Self {
+            resolution: Vector3::new(d, h, w),
+            data: values
+                .into_iter()
+                .map(|v| f16::from_f32((v / max).clamp(0., 1.)))
+                .collect(),
+        })
     }
+
+    fn values<R: Read, P: Into<f64> + Deserialize>(array: NpyFile<R>) -> anyhow::Result<Vec<f32>> {
+        Ok(array
+            .into_vec::<P>()?
+            .into_iter()
+            .map(|v| v.into() as f32)
+            .collect())
+    }
+}
+
+/// sets `mask` (or else the mask stored with the volumes) for all channels,
+/// inverted if `invert` is set
+pub fn apply_mask(volumes: &mut [Volume], mask: Option<Mask>, invert: bool) -> anyhow::Result<()> {
+    let Some(mask) = mask.or_else(|| volumes[0].mask().cloned()) else {
+        return Ok(());
+    };
+    let mask = if invert { mask.inverted() } else { mask };
+    for v in volumes.iter_mut() {
+        v.set_mask(Some(mask.clone()))?;
+    }
+    Ok(())
 }
 
 pub struct VolumeGPU {
     pub(crate) textures: Vec<wgpu::Texture>,
+    /// [Mask] of the volume, a single voxel with value one if it has none
+    pub(crate) mask: wgpu::Texture,
     pub(crate) volume: Volume,
 }
 
 impl VolumeGPU {
+    /// GPU memory of the textures of all timesteps and the mask in bytes
+    pub fn memory_size(&self) -> u64 {
+        self.textures
+            .iter()
+            .chain(std::iter::once(&self.mask))
+            .map(|t| {
+                let size = t.size();
+                let texel = t.format().block_copy_size(None).unwrap_or(0);
+                size.width as u64
+                    * size.height as u64
+                    * size.depth_or_array_layers as u64
+                    * texel as u64
+            })
+            .sum()
+    }
+
     pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, volume: Volume) -> Self {
         let textures = (0..volume.timesteps)
             .map(|i| {
@@ -232,12 +656,59 @@ impl VolumeGPU {
                 )
             })
             .collect();
-        Self { textures, volume }
+        let mask = Self::mask_texture(device, queue, volume.mask());
+        Self {
+            textures,
+            mask,
+            volume,
+        }
+    }
+
+    /// replaces the mask of the volume
+    pub fn set_mask(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mask: Option<Mask>,
+    ) -> anyhow::Result<()> {
+        self.volume.set_mask(mask)?;
+        self.mask = Self::mask_texture(device, queue, self.volume.mask());
+        Ok(())
+    }
+
+    fn mask_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mask: Option<&Mask>,
+    ) -> wgpu::Texture {
+        let (resolution, data) = match mask {
+            Some(mask) => (mask.resolution, mask.data()),
+            None => (Vector3::new(1, 1, 1), [f16::ONE].as_slice()),
+        };
+        device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("mask texture"),
+                size: wgpu::Extent3d {
+                    width: resolution[2],
+                    height: resolution[1],
+                    depth_or_array_layers: resolution[0],
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::R16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(data),
+        )
     }
 }
 
 #[repr(C)]
-#[derive(Zeroable, Clone, Copy, Debug)]
+#[derive(Zeroable, Clone, Copy, Debug, PartialEq)]
 pub struct Aabb<F: Float + BaseNum> {
     pub min: Point3<F>,
     pub max: Point3<F>,
@@ -259,4 +730,18 @@ impl<F: Float + BaseNum> Aabb<F> {
     pub fn radius(&self) -> F {
         self.min.distance(self.max) / (F::one() + F::one())
     }
+
+    pub fn corners(&self) -> [Point3<F>; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point3::new(a.x, a.y, a.z),
+            Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z),
+            Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z),
+            Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z),
+            Point3::new(b.x, b.y, b.z),
+        ]
+    }
 }
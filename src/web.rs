@@ -1,17 +1,31 @@
+use std::cell::RefCell;
+use std::future::Future;
 use std::io::Cursor;
+use std::rc::Rc;
 
-use instant::Duration;
+use cgmath::Vector2;
+use image::Rgba;
+use instant::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::closure::Closure;
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::{JsCast, JsError, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::js_sys::{ArrayBuffer, Uint8Array};
-use web_sys::{Request, RequestInit, RequestMode, Response};
+use web_sys::js_sys::{ArrayBuffer, Promise, Uint8Array};
+use web_sys::{MessageEvent, Request, RequestInit, RequestMode, Response};
 use winit::platform::web::WindowBuilderExtWebSys;
 use winit::window::WindowBuilder;
 
-use crate::cmap::{self, GenericColorMap, COLORMAP_RESOLUTION};
+use crate::camera::CameraView;
+use crate::cmap::{
+    self, GenericColorMap, LinearSegmentedColorMap, TransferFunction, COLORMAP_RESOLUTION,
+};
+use crate::offline::{OfflineRenderer, OfflineSettings};
+use crate::session::SessionCamera;
+use crate::spec::{RenderSpec, SpecFiles};
+use crate::stereo::Stereo;
 use crate::volume::Volume;
-use crate::{open_window, RenderConfig};
+use crate::{open_window, RenderConfig, WindowContext};
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, Copy)]
@@ -53,6 +67,10 @@ pub struct InlineViewerConfig {
     pub vmax: Option<f32>,
     pub distance_scale: f32,
     pub duration: Option<f32>,
+    // camera section of a render spec (json)
+    camera: Option<String>,
+    // render section of a session (json)
+    render: Option<String>,
 }
 
 #[wasm_bindgen]
@@ -67,6 +85,8 @@ impl InlineViewerConfig {
         vmax: Option<f32>,
         distance_scale: f32,
         duration: Option<f32>,
+        camera: Option<String>,
+        render: Option<String>,
     ) -> Self {
         Self {
             background_color,
@@ -77,6 +97,8 @@ impl InlineViewerConfig {
             vmax,
             distance_scale,
             duration,
+            camera,
+            render,
         }
     }
 }
@@ -96,35 +118,51 @@ pub async fn viewer_wasm(
     colormap: Option<Vec<u8>>,
     settings: Option<InlineViewerConfig>,
 ) -> Result<(), JsValue> {
+    let inline = settings.is_some();
     let render_config = match settings {
         Some(settings) => RenderConfig {
-            no_vsync: false,
             background_color: settings.background_color.into(),
             show_colormap_editor: settings.show_colormap_editor,
             show_volume_info: settings.show_volume_info,
             vmin: settings.vmin,
             vmax: settings.vmax,
             distance_scale: settings.distance_scale,
+            camera: match &settings.camera {
+                Some(json) => parse_camera(json)?,
+                None => CameraView::default(),
+            },
             #[cfg(feature = "colormaps")]
             show_cmap_select: settings.show_cmap_select,
             duration: settings.duration.map(Duration::from_secs_f32),
+            render_settings: match &settings.render {
+                Some(json) => Some(
+                    serde_json::from_str(json)
+                        .map_err(|e| JsError::new(&format!("Invalid render settings: {}", e)))?,
+                ),
+                None => None,
+            },
+            ..Default::default()
         },
-        None => RenderConfig {
-            no_vsync: false,
-            background_color: wgpu::Color::BLACK,
-            show_colormap_editor: true,
-            show_volume_info: true,
-            show_cmap_select: true,
-            vmin: None,
-            vmax: None,
-            duration: None,
-            distance_scale: 1.0,
-        },
+        None => RenderConfig::default(),
     };
 
+    if inline {
+        // embedded by the Python viewer or the Jupyter widget
+        listen_widget()?;
+    }
     start_viewer(canvas_id, render_config, volume_data, colormap).await
 }
 
+/// reads a camera given like the camera section of a render spec
+fn parse_camera(json: &str) -> Result<CameraView, JsValue> {
+    let camera: CameraView = serde_json::from_str(json)
+        .map_err(|e| JsError::new(&format!("Invalid camera: {}", e)))?;
+    camera
+        .validate()
+        .map_err(|e| JsError::new(&format!("Invalid camera: {}", e)))?;
+    Ok(camera)
+}
+
 /// Download a file from a given url
 /// returns body bytes
 pub async fn download_file(window: web_sys::Window, url: String) -> Result<Vec<u8>, JsValue> {
@@ -204,13 +242,67 @@ async fn start_viewer(
     render_config: RenderConfig,
     volume_data: Option<Vec<u8>>,
     colormap: Option<Vec<u8>>,
+) -> Result<(), JsValue> {
+    open_canvas(&canvas_id, async move {
+        let volume_data = match volume_data {
+            Some(data) => data,
+            None => load_data().await?,
+        };
+        // load colormap from url if present
+        let colormap = match colormap {
+            Some(data) => Some(data),
+            None => load_colormap().await?,
+        };
+        let colormap = match colormap {
+            Some(data) => GenericColorMap::read(Cursor::new(data))
+                .map_err(|e| JsError::new(&format!("Failed to load colormap: {}", e)))?
+                .into_linear_segmented(COLORMAP_RESOLUTION),
+            None => cmap::COLORMAPS["seaborn"]["icefire"]
+                .clone()
+                .into_linear_segmented(COLORMAP_RESOLUTION),
+        };
+        let volumes = Volume::load_numpy(Cursor::new(volume_data), true)
+            .map_err(|e| JsError::new(&format!("Failed to load volume: {}", e)))?;
+        Ok((volumes, colormap, render_config))
+    })
+    .await
+}
+
+/// Start the viewer with a render spec (json).
+/// The files referenced by the spec are downloaded, relative urls are relative to the page.
+#[wasm_bindgen]
+pub async fn viewer_wasm_spec(canvas_id: String, spec: String) -> Result<(), JsValue> {
+    let spec = RenderSpec::from_json(&spec)
+        .map_err(|e| JsError::new(&format!("Invalid render spec: {}", e)))?;
+    open_canvas(&canvas_id, async move {
+        let window = web_sys::window().ok_or(JsError::new("cannot access window"))?;
+        let mut files = SpecFiles::new();
+        for url in spec.files() {
+            let data = download_file(window.clone(), url.to_string()).await?;
+            files.insert(url.to_string(), data);
+        }
+        let to_js = |e: anyhow::Error| JsValue::from(JsError::new(&e.to_string()));
+        let volumes = spec.volumes(&files).map_err(to_js)?;
+        let colormap = spec.colormap.colormap(&files).map_err(to_js)?;
+        let render_config = spec.render_config(&files).map_err(to_js)?;
+        Ok((volumes, colormap, render_config))
+    })
+    .await
+}
+
+/// Shows the loading spinner while `load` runs and then opens the viewer on the canvas.
+async fn open_canvas(
+    canvas_id: &str,
+    load: impl Future<
+        Output = Result<(Vec<Volume>, LinearSegmentedColorMap, RenderConfig), JsValue>,
+    >,
 ) -> Result<(), JsValue> {
     let window = web_sys::window().ok_or(JsError::new("cannot access window"))?;
     let document = window
         .document()
         .ok_or(JsError::new("cannot access document"))?;
     let canvas = document
-        .get_element_by_id(&canvas_id)
+        .get_element_by_id(canvas_id)
         .ok_or(JsError::new("cannot find canvas"))?
         .dyn_into::<web_sys::HtmlCanvasElement>()?;
 
@@ -227,29 +319,187 @@ async fn start_viewer(
         .dyn_into::<web_sys::HtmlElement>()?;
 
     spinner.set_attribute("style", "display:flex;")?;
-    let volume_data = match volume_data {
-        Some(data) => data,
-        None => load_data().await?
-    };
-    // load colormap from url if present
-    let colormap = match colormap {
-        Some(data) => Some(data),
-        None => load_colormap().await?
-    };
-    let colormap = match colormap {
-        Some(data) => GenericColorMap::read(Cursor::new(data)).map_err(|e| JsError::new(&format!("Failed to load colormap: {}", e)))?.into_linear_segmented(COLORMAP_RESOLUTION),
-        None => cmap::COLORMAPS["seaborn"]["icefire"]
-            .clone()
-            .into_linear_segmented(COLORMAP_RESOLUTION),
-    };
+    let (volumes, colormap, render_config) = load.await?;
 
     wasm_bindgen_futures::spawn_local(async move {
-    
-        let reader_v = Cursor::new(volume_data);
-        let volumes: Vec<Volume> = Volume::load_numpy(reader_v, true).unwrap();
         overlay.set_attribute("style", "display:none;").ok();
 
         open_window(window_builder, volumes, colormap, render_config).await
     });
     Ok(())
 }
+
+/// Renders images of a volume without a canvas, e.g. thumbnails or frames of an export.
+/// The device and the uploaded volume are kept between the renders.
+#[wasm_bindgen]
+pub struct ImageRenderer {
+    renderer: Rc<OfflineRenderer>,
+}
+
+#[wasm_bindgen]
+impl ImageRenderer {
+    /// uploads the volume (npy or npz) and the colormap (default: seaborn/icefire)
+    pub async fn create(
+        volume_data: Vec<u8>,
+        colormap: Option<Vec<u8>>,
+    ) -> Result<ImageRenderer, JsValue> {
+        let colormap = match colormap {
+            Some(data) => GenericColorMap::read(Cursor::new(data))
+                .map_err(|e| JsError::new(&format!("Failed to load colormap: {}", e)))?,
+            None => cmap::COLORMAPS["seaborn"]["icefire"].clone(),
+        };
+        let volumes = Volume::load_numpy(Cursor::new(volume_data), true)
+            .map_err(|e| JsError::new(&format!("Failed to load volume: {}", e)))?;
+        Ok(Self {
+            renderer: Rc::new(OfflineRenderer::new(volumes, colormap).await),
+        })
+    }
+
+    /// renders the volume at the normalized `time` and resolves to the RGBA pixels
+    /// (premultiplied alpha) of the image row by row.
+    /// `camera` is given like the camera section of a render spec (json)
+    pub fn render(
+        &self,
+        time: f32,
+        width: u32,
+        height: u32,
+        background: Color,
+        camera: Option<String>,
+    ) -> Result<Promise, JsValue> {
+        let settings = OfflineSettings {
+            view: match &camera {
+                Some(json) => parse_camera(json)?,
+                None => CameraView::default(),
+            },
+            background: background.into(),
+            ..OfflineSettings::new(Vector2::new(width, height))
+        };
+        let renderer = self.renderer.clone();
+        Ok(wasm_bindgen_futures::future_to_promise(async move {
+            let img = renderer
+                .render::<Rgba<u8>>(time, &settings, None, &Stereo::default())
+                .await
+                .map_err(|e| JsError::new(&format!("Failed to render: {}", e)))?;
+            Ok(Uint8Array::from(img.as_raw().as_slice()).into())
+        }))
+    }
+}
+
+/// state of the viewer synced with the Jupyter widget (`vape4d.ViewerWidget`)
+/// through messages to and from the parent window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct WidgetState {
+    camera: SessionCamera,
+    colormap: LinearSegmentedColorMap,
+    opacity: TransferFunction,
+    time: f32,
+}
+
+impl WidgetState {
+    fn of(state: &WindowContext) -> Self {
+        Self {
+            camera: SessionCamera::new(&state.camera, state.controller.center),
+            colormap: state.cmap.clone(),
+            opacity: state.opacity.clone(),
+            time: state.render_settings.time,
+        }
+    }
+
+    fn apply(&self, state: &mut WindowContext) {
+        state.controller.stop();
+        state.camera = self.camera.camera();
+        state
+            .camera
+            .projection
+            .resize(state.config.width, state.config.height);
+        state.controller.center = self.camera.center();
+        state.cmap = self.colormap.clone();
+        state.opacity = self.opacity.clone();
+        state.render_settings.time = self.time.clamp(0., 1.);
+        state.last_frame = None;
+        state.taa.reset();
+    }
+}
+
+/// messages exchanged with the widget as json strings
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum WidgetMessage {
+    State(WidgetState),
+}
+
+/// minimum time between two states sent to the widget, e.g. while the camera is dragged
+const WIDGET_SYNC_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct WidgetSync {
+    /// last state sent to or received from the widget
+    synced: Option<WidgetState>,
+    last_sent: Option<Instant>,
+    received: Option<WidgetState>,
+}
+
+thread_local! {
+    /// set once the viewer listens to the widget
+    static WIDGET: RefCell<Option<WidgetSync>> = const { RefCell::new(None) };
+}
+
+/// receives the states set in Python, they are applied by [sync_widget]
+fn listen_widget() -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or(JsError::new("cannot access window"))?;
+    WIDGET.with(|widget| widget.replace(Some(WidgetSync::default())));
+    let listener = Closure::<dyn FnMut(MessageEvent)>::new(|event: MessageEvent| {
+        let Some(json) = event.data().as_string() else {
+            return;
+        };
+        match serde_json::from_str(&json) {
+            Ok(WidgetMessage::State(state)) => WIDGET.with(|widget| {
+                if let Some(widget) = widget.borrow_mut().as_mut() {
+                    widget.received = Some(state);
+                }
+            }),
+            Err(e) => log::warn!("invalid widget message: {e}"),
+        }
+    });
+    window.add_event_listener_with_callback("message", listener.as_ref().unchecked_ref())?;
+    listener.forget();
+    Ok(())
+}
+
+/// applies the state received from the widget and sends the state of the viewer
+/// to the parent window when it changed
+pub(crate) fn sync_widget(state: &mut WindowContext) {
+    WIDGET.with(|widget| {
+        let mut widget = widget.borrow_mut();
+        let Some(widget) = widget.as_mut() else {
+            return;
+        };
+        if let Some(received) = widget.received.take() {
+            received.apply(state);
+            widget.synced = Some(received);
+            return;
+        }
+        let now = Instant::now();
+        if widget
+            .last_sent
+            .is_some_and(|last| now - last < WIDGET_SYNC_INTERVAL)
+        {
+            return;
+        }
+        let current = WidgetState::of(state);
+        if widget.synced.as_ref() == Some(&current) {
+            return;
+        }
+        let Some(parent) = web_sys::window().and_then(|w| w.parent().ok().flatten()) else {
+            return;
+        };
+        let message = serde_json::to_string(&WidgetMessage::State(current.clone())).unwrap();
+        if parent
+            .post_message(&JsValue::from_str(&message), "*")
+            .is_ok()
+        {
+            widget.synced = Some(current);
+            widget.last_sent = Some(now);
+        }
+    });
+}
@@ -1,4 +1,7 @@
-from .render import render
+from .render import render, render_spec, render_session, output_presets, diff, compare_renders, Renderer, save_animation, render_batch, render_video
+from .options import RenderOptions, CameraOptions
+from .transfer_function import TransferFunction, edit_transfer_function
+from .utils import stats
 from . import utils
 
 try:
@@ -8,6 +11,12 @@ try:
 except ImportError:
     pass
 
+try:
+    # check if anywidget is available
+    from .widget import ViewerWidget
+except ImportError:
+    pass
+
 from . import vape4d as vape_py
 
 __doc__ = vape_py.__doc__
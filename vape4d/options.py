@@ -0,0 +1,147 @@
+from dataclasses import dataclass, field
+from typing import Optional, Union
+
+
+@dataclass
+class CameraOptions:
+    """camera pose, the same as the camera section of a render spec
+
+    The position is taken from `position`, `azimuth`/`elevation` or `direction` (in this order).
+
+    Args:
+        direction (tuple[float, float, float]): direction from the target to the camera. Defaults to (1, -1, 1).
+        up (tuple[float, float, float]): up vector of the camera. Defaults to (0, 1, 0).
+        zoom (float): magnification, 1 fits the volume into the image. Defaults to 1.
+        position (Optional[tuple[float, float, float]]): position of the camera in world space. Defaults to None.
+        target (Optional[tuple[float, float, float]]): point in world space the camera looks at. Defaults to None (center of the volume).
+        azimuth (Optional[float]): angle in degrees around the up vector. Defaults to None.
+        elevation (Optional[float]): angle in degrees above the plane perpendicular to the up vector. Defaults to None.
+        projection (str): "orthographic", "perspective" or "equirectangular" (offline rendering only). The viewer always uses an orthographic projection. Defaults to "orthographic".
+        fov (float): vertical field of view of the perspective projection in degrees. Defaults to 45.
+    """
+
+    direction: tuple[float, float, float] = (1.0, -1.0, 1.0)
+    up: tuple[float, float, float] = (0.0, 1.0, 0.0)
+    zoom: float = 1.0
+    position: Optional[tuple[float, float, float]] = None
+    target: Optional[tuple[float, float, float]] = None
+    azimuth: Optional[float] = None
+    elevation: Optional[float] = None
+    projection: str = "orthographic"
+    fov: float = 45.0
+
+    def to_dict(self) -> dict:
+        """camera section of a render spec"""
+        camera = dict(
+            direction=[float(c) for c in self.direction],
+            up=[float(c) for c in self.up],
+            zoom=float(self.zoom),
+            projection=self.projection.lower(),
+            fov=float(self.fov),
+        )
+        if self.position is not None:
+            camera["position"] = [float(c) for c in self.position]
+        if self.target is not None:
+            camera["target"] = [float(c) for c in self.target]
+        if self.azimuth is not None:
+            camera["azimuth"] = float(self.azimuth)
+        if self.elevation is not None:
+            camera["elevation"] = float(self.elevation)
+        return camera
+
+
+@dataclass
+class RenderOptions:
+    """render settings of the viewer and of offline renders, the same as the render section of a session saved in the viewer
+
+    Args:
+        step_size (Optional[float]): distance between two samples along a ray. Defaults to None (derived from the volume resolution).
+        spatial_interpolation (str): "nearest", "linear" or "cubic". Defaults to "linear".
+        temporal_interpolation (str): "nearest", "linear" or "cubic". Defaults to "linear".
+        distance_scale (float): a larger value makes everything more opaque. Defaults to 1.
+        vmin (Optional[float]): value mapped to the start of the colormap. Defaults to None (minimum of the volume).
+        vmax (Optional[float]): value mapped to the end of the colormap. Defaults to None (maximum of the volume).
+        per_timestep_range (bool): vmin and vmax default to the value range of the current timestep. Defaults to False.
+        normalization (Union[str, float]): "linear", "log", "sqrt" or an exponent for a power normalization. Defaults to "linear".
+        clipping (Optional[tuple[tuple[float, float, float], tuple[float, float, float]]]): rendered region of the volume relative to its size as (min, max). Defaults to None (whole volume).
+        iso_surfaces (list[tuple[float, tuple[float, float, float, float]]]): surfaces drawn where the volume crosses a value, as (value, rgba color). At most 8 are used. Defaults to none.
+        boundary_enhancement (float): strength of the gradient magnitude based opacity modulation. Defaults to 0 (off).
+        silhouette (float): strength of the view angle based opacity modulation and darkening. Defaults to 0 (off).
+        gamma_correction (bool): Defaults to False.
+        nan_style (Union[str, tuple[int, int, int, int]]): rendering of NaN and infinite values, "clamp", "transparent" or an RGBA color (0-255). Defaults to "transparent".
+        below_style (Union[str, tuple[int, int, int, int]]): rendering of values below vmin. Defaults to "clamp".
+        above_style (Union[str, tuple[int, int, int, int]]): rendering of values above vmax. Defaults to "clamp".
+        invert_mask (bool): hides the masked voxels instead of the others. Defaults to False.
+        background (Optional[tuple[float, float, float, float]]): background color. Defaults to None (black).
+        camera (Optional[CameraOptions]): camera pose. Defaults to None (isometric view).
+    """
+
+    step_size: Optional[float] = None
+    spatial_interpolation: str = "linear"
+    temporal_interpolation: str = "linear"
+    distance_scale: float = 1.0
+    vmin: Optional[float] = None
+    vmax: Optional[float] = None
+    per_timestep_range: bool = False
+    normalization: Union[str, float] = "linear"
+    clipping: Optional[
+        tuple[tuple[float, float, float], tuple[float, float, float]]
+    ] = None
+    iso_surfaces: list[tuple[float, tuple[float, float, float, float]]] = field(
+        default_factory=list
+    )
+    boundary_enhancement: float = 0.0
+    silhouette: float = 0.0
+    gamma_correction: bool = False
+    nan_style: Union[str, tuple[int, int, int, int]] = "transparent"
+    below_style: Union[str, tuple[int, int, int, int]] = "clamp"
+    above_style: Union[str, tuple[int, int, int, int]] = "clamp"
+    invert_mask: bool = False
+    background: Optional[tuple[float, float, float, float]] = None
+    camera: Optional[CameraOptions] = None
+
+    def settings_dict(self) -> dict:
+        """render section of a session"""
+        if len(self.iso_surfaces) > 8:
+            raise ValueError("at most 8 iso surfaces are supported")
+        settings = dict(
+            spatial_interpolation=self.spatial_interpolation.lower(),
+            temporal_interpolation=self.temporal_interpolation.lower(),
+            distance_scale=float(self.distance_scale),
+            vmin=None if self.vmin is None else float(self.vmin),
+            vmax=None if self.vmax is None else float(self.vmax),
+            per_timestep_range=self.per_timestep_range,
+            normalization=_normalization(self.normalization),
+            nan_style=_value_style(self.nan_style),
+            below_style=_value_style(self.below_style),
+            above_style=_value_style(self.above_style),
+            invert_mask=self.invert_mask,
+            gamma_correction=self.gamma_correction,
+            boundary_enhancement=float(self.boundary_enhancement),
+            silhouette=float(self.silhouette),
+            contours=[
+                [float(value), [float(c) for c in color]]
+                for value, color in self.iso_surfaces
+            ],
+        )
+        if self.step_size is not None:
+            settings["step_size"] = float(self.step_size)
+        if self.clipping is not None:
+            settings["clipping"] = [[float(c) for c in corner] for corner in self.clipping]
+        return settings
+
+    def camera_dict(self) -> Optional[dict]:
+        """camera section of a render spec"""
+        return None if self.camera is None else self.camera.to_dict()
+
+
+def _normalization(normalization: Union[str, float]):
+    if isinstance(normalization, str):
+        return normalization.lower()
+    return {"power": float(normalization)}
+
+
+def _value_style(style: Union[str, tuple[int, int, int, int]]):
+    if isinstance(style, str):
+        return style.lower()
+    return {"color": [int(c) for c in style]}
@@ -1,67 +1,182 @@
+import json
+import os
 from typing import Optional, Union
-from matplotlib import pyplot as plt
 from matplotlib.colors import Colormap
 import numpy as np
 from . import vape4d
+from .options import CameraOptions, RenderOptions
+from .utils import _as_tdhw, apply_affine, from_xarray, to_colormap
 
 
 def render(
-    volume: np.ndarray,
-    cmap: Optional[Colormap] = None,
+    volume: Union[np.ndarray, "xarray.DataArray"],
+    cmap: Optional[Union[str, Colormap, list[Union[str, Colormap]]]] = None,
     time: Optional[Union[float, list[float]]] = 0.0,
     width: int = 1024,
     height: int = 1024,
-    background: tuple[float, float, float, float] = (0, 0, 0, 1),
+    background: Optional[tuple[float, float, float, float]] = None,
     vmin: Optional[float] = None,
     vmax: Optional[float] = None,
-    distance_scale: float = 1.0,
-    spatial_interpolation: str = "linear",
-    temporal_interpolation: str = "linear",
+    distance_scale: Optional[float] = None,
+    spatial_interpolation: Optional[str] = None,
+    temporal_interpolation: Optional[str] = None,
+    frame_interpolation: int = 1,
+    motion_blur_samples: int = 1,
+    shutter: float = 0.5,
+    preset: Optional[str] = None,
+    supersampling: Optional[int] = None,
+    shadow_color: Optional[tuple[float, float, float, float]] = None,
+    shadow_offset: tuple[int, int] = (8, 8),
+    shadow_blur: float = 8.0,
+    spacing: Optional[tuple[float, float, float]] = None,
+    affine: Optional[np.ndarray] = None,
+    unit: str = "",
+    scale_bar: bool = False,
+    axis_ticks: bool = False,
+    stereo: Optional[str] = None,
+    eye_separation: float = 0.1,
+    opacity: Optional[list[tuple[float, float]]] = None,
+    cmap_times: Optional[list[float]] = None,
+    time_label: bool = False,
+    contact_sheet: Optional[int] = None,
+    camera: Optional[dict] = None,
+    settings: Optional[dict] = None,
+    alpha: str = "premultiplied",
+    options: Optional[RenderOptions] = None,
 ) -> np.ndarray:
     """renders a single or multiple images of a volume
 
     Args:
-        volume (np.ndarray): volume data of shape [N, D, H, W]
-        cmap (Colormap): colormap to use for rendering. Defaults to matplotlib's default colormap.
+        volume (Union[np.ndarray, xarray.DataArray]): volume data of shape [N, D, H, W]. For a DataArray, the time dimension is found by its name (e.g. "time") and spacing, unit, vmin and vmax default to its evenly spaced coordinates and attributes (see `utils.from_xarray`).
+        cmap (Union[str, Colormap, list[Union[str, Colormap]]]): colormap to use for rendering. A matplotlib colormap, the name of one (e.g. "viridis" or "magma_r") or a comma-separated list of hex colors (e.g. "#000000,#ff0000,#ffff00"). A list of colormaps gives a time-varying colormap that is blended between its keyframes (e.g. one colormap per timestep). Defaults to matplotlib's default colormap.
         time (Union[float, list[float]]): if a single value is given, a single image is rendered at that time. If a list of values is given, a video is rendered with the given times.
         width (int, optional): image width. Defaults to 1024.
         height (int, optional): image height. Defaults to 1024.
-        background (tuple[float, float, float, float], optional): background color. Defaults to (0, 0, 0, 1).
+        background (Optional[tuple[float, float, float, float]], optional): background color. Defaults to the background of options or (0, 0, 0, 1).
         vmin (Optional[float], optional): minimum value for colormap. defaults to minimum value in volume.
         vmax (Optional[float], optional): maximum value for colormap. defaults to maximum value in volume.
-        distance_scale (float, optional): distance scale for rendering. A larger value makes everything more opaque. Defaults to 1.0.
-        spatial_interpolation (str, optional): interpolation in space. Linear or Nearest. Defaults to "linear".
-        temporal_interpolation (str, optional): interpolation in time. Linear or Nearest. Defaults to "linear".
+        distance_scale (Optional[float], optional): distance scale for rendering. A larger value makes everything more opaque. Defaults to 1.0.
+        spatial_interpolation (Optional[str], optional): interpolation in space. Nearest, Linear or Cubic (tricubic B-spline). Defaults to "linear".
+        temporal_interpolation (Optional[str], optional): interpolation in time. Nearest, Linear or Cubic (Catmull-Rom over four timesteps). Defaults to "linear".
+        frame_interpolation (int, optional): number of frames rendered per given time step. The additional frames are rendered at intermediate times using linear or cubic temporal interpolation (slow motion). Defaults to 1.
+        motion_blur_samples (int, optional): number of time samples averaged per frame for motion blur along the time axis. Defaults to 1 (no motion blur).
+        shutter (float, optional): exposure time used for motion blur as a fraction of the time between two frames. Defaults to 0.5.
+        preset (Optional[str], optional): named output preset (e.g. "Slide 1080p", "Poster A0 @300dpi", "Twitter 16:9") that sets the resolution and supersampling. Overrides width and height. See `output_presets()` for all available presets.
+        supersampling (Optional[int], optional): number of samples per pixel along each axis. Defaults to the preset's value or 1.
+        shadow_color (Optional[tuple[float, float, float, float]], optional): color of a drop shadow composited behind the volume. Use a transparent background (e.g. (0, 0, 0, 0)) for slide-ready cutouts. A zero offset and a bright color gives a glow. Defaults to None (no shadow).
+        shadow_offset (tuple[int, int], optional): shadow offset in pixels (x, y). Defaults to (8, 8).
+        shadow_blur (float, optional): standard deviation of the shadow blur in pixels. Defaults to 8.
+        spacing (Optional[tuple[float, float, float]], optional): physical size of a voxel along (D, H, W). Stretches the volume accordingly and is used for the scale bar and axis ticks. Defaults to None (isotropic voxels of size 1).
+        affine (Optional[np.ndarray], optional): 4x4 matrix mapping voxel indices (d, h, w, 1) to world coordinates (e.g. of a NIfTI file), instead of spacing. Only scaling, flips and axis permutations are supported, see `utils.apply_affine`. Defaults to None.
+        unit (str, optional): unit of the spacing shown in the labels (e.g. "mm"). Defaults to "".
+        scale_bar (bool, optional): draws a scale bar with a round physical length in the lower right corner. Defaults to False.
+        axis_ticks (bool, optional): draws labeled ticks along the axes of the bounding box. Defaults to False.
+        stereo (Optional[str], optional): renders a stereo image. "side-by-side" puts the left eye into the left and the right eye into the right half (each squeezed to half the width), "anaglyph" gives a red/cyan image. "top-bottom" puts the left eye above the right eye (each squeezed to half the height), "pair" puts both eyes next to each other at the full resolution (the image is twice as wide). Defaults to None (mono).
+        eye_separation (float, optional): interocular distance for stereo rendering, relative to the smallest side of the volume. Defaults to 0.1.
+        opacity (Optional[list[tuple[float, float]]], optional): piecewise-linear opacity given as (value, opacity) control points with ascending values in [0, 1] (relative to vmin and vmax). Replaces the alpha channel of the colormap, so structures can be isolated without changing the colors. Defaults to None (alpha channel of the colormap).
+        cmap_times (Optional[list[float]], optional): normalized time in [0, 1] (ascending) of each colormap if a list of colormaps is given. Defaults to None (evenly spaced).
+        time_label (bool, optional): draws the timestep in the upper left corner. Defaults to False.
+        contact_sheet (Optional[int], optional): tiles all frames into a single image with this many columns (0 = as square as possible), e.g. to show the temporal evolution side by side. Usually combined with time_label. Defaults to None (one image per frame).
+        camera (Optional[dict], optional): camera like the camera section of a render spec, e.g. dict(position=(0.5, 0.5, 3), projection="perspective", fov=30) or dict(azimuth=30, elevation=20, zoom=1.5). Keys: direction, up, zoom, position, target, azimuth, elevation (degrees), projection ("orthographic", "perspective" or "equirectangular" for a 360° panorama around the camera) and fov (degrees). Defaults to None (isometric view).
+        settings (Optional[dict], optional): render settings like the "render" section of a session saved in the viewer, e.g. dict(step_size=5e-4, clipping=[[0, 0, 0], [0.5, 1, 1]], normalization="log", contours=[[0.5, [1, 1, 1, 0.5]]]) for the same image as in the viewer. vmin, vmax, distance_scale and the interpolations given as arguments take precedence. Defaults to None (default settings).
+        alpha (str, optional): alpha of images with a transparent background. "premultiplied" (color multiplied by alpha, as rendered) or "straight" (what e.g. PIL and matplotlib expect when saving PNG files). Defaults to "premultiplied".
+        options (Optional[RenderOptions], optional): render settings, camera and background, e.g. the same options passed to `viewer`. camera, settings, background and the settings given as arguments take precedence. Defaults to None.
 
     Returns:
-        np.ndarray: [T, H, W, 4] if time is a list, [H, W, 4] if time is a single value
+        np.ndarray: uint8 RGBA, e.g. for plt.imshow or imageio. [T, H, W, 4] if time is a list or array (also of a single time), [H, W, 4] if time is a single value. With frame_interpolation > 1, T = (len(time) - 1) * frame_interpolation + 1. The contact sheet is a single [H, W, 4] image.
     """
 
-    if cmap is None:
-        cmap = plt.get_cmap()
-
-    if volume.ndim == 5:
-        # check if we have a single channel
-        if volume.shape[1] != 1:
-            raise ValueError("only one channel supported")
-    elif volume.ndim == 4:
-        pass
-    elif volume.ndim == 3:
-        # add one channel
-        volume = volume[:, None]
+    background, camera, settings = _apply_options(options, background, camera, settings)
+
+    volume, info = from_xarray(volume)
+    if affine is not None:
+        if spacing is not None:
+            raise ValueError("spacing and affine can not be combined")
+        volume, spacing = apply_affine(volume, affine)
+    if spacing is None:
+        spacing = info.get("spacing")
+    if unit == "":
+        unit = info.get("unit", "")
+    if vmin is None and (settings or {}).get("vmin") is None:
+        vmin = info.get("vmin")
+    if vmax is None and (settings or {}).get("vmax") is None:
+        vmax = info.get("vmax")
+
+    if isinstance(cmap, (list, tuple)):
+        cmap = [to_colormap(c) for c in cmap]
     else:
-        raise ValueError(
-            "volume must have shape [T,1, D, H, W], [T, D, H, W] or [D,H,W] "
-        )
+        cmap = to_colormap(cmap)
 
-    colormap_data = cmap(np.linspace(0, 1, 256)).astype(np.float32)
+    volume = _as_tdhw(volume)
 
-    if isinstance(time, np.ndarray):
-        time = time.tolist()
+    if isinstance(cmap, (list, tuple)):
+        if len(cmap) == 0:
+            raise ValueError("cmap must contain at least one colormap")
+        colormap_data = np.stack(
+            [c(np.linspace(0, 1, 256)) for c in cmap]
+        ).astype(np.float32)
+    else:
+        colormap_data = cmap(np.linspace(0, 1, 256)).astype(np.float32)
+    if cmap_times is not None and (
+        colormap_data.ndim != 3 or len(cmap_times) != colormap_data.shape[0]
+    ):
+        raise ValueError("cmap_times must contain one time per colormap")
 
-    if not isinstance(time, list):
-        time = [time]
+    # a list of times gives [T, H, W, 4] even for a single time
+    batched = isinstance(time, (list, tuple, np.ndarray))
+    time = [float(t) for t in np.ravel(time)]
+    if len(time) == 0:
+        raise ValueError("time must contain at least one value")
 
+    if frame_interpolation < 1:
+        raise ValueError("frame_interpolation must be at least 1")
+    if (
+        frame_interpolation > 1
+        and temporal_interpolation is not None
+        and temporal_interpolation.lower() == "nearest"
+    ):
+        raise ValueError(
+            "frame_interpolation requires linear or cubic temporal interpolation"
+        )
+    if motion_blur_samples < 1:
+        raise ValueError("motion_blur_samples must be at least 1")
+    if not 0 <= shutter <= 1:
+        raise ValueError("shutter must be in [0, 1]")
+    if supersampling is not None and supersampling < 1:
+        raise ValueError("supersampling must be at least 1")
+    if spacing is not None and (len(spacing) != 3 or min(spacing) <= 0):
+        raise ValueError("spacing must contain three positive values")
+    if contact_sheet is not None and contact_sheet < 0:
+        raise ValueError("contact_sheet must not be negative")
+
+    video_options = dict(
+        distance_scale=distance_scale,
+        vmin=vmin,
+        vmax=vmax,
+        spatial_interpolation=spatial_interpolation,
+        temporal_interpolation=temporal_interpolation,
+        frame_interpolation=frame_interpolation,
+        motion_blur_samples=motion_blur_samples,
+        shutter=shutter,
+        preset=preset,
+        supersampling=supersampling,
+        shadow_color=shadow_color,
+        shadow_offset=shadow_offset,
+        shadow_blur=shadow_blur,
+        spacing=None if spacing is None else [float(s) for s in spacing],
+        unit=unit,
+        scale_bar=scale_bar,
+        axis_ticks=axis_ticks,
+        stereo=stereo,
+        eye_separation=eye_separation,
+        opacity=None if opacity is None else [(float(x), float(a)) for x, a in opacity],
+        cmap_times=None if cmap_times is None else [float(t) for t in cmap_times],
+        time_label=time_label,
+        contact_sheet=contact_sheet,
+        camera=camera,
+        settings=settings,
+        alpha=alpha,
+    )
     frames = vape4d.render_video(
         np.ascontiguousarray(volume).astype(np.float16),
         colormap_data,
@@ -69,12 +184,406 @@ def render(
         height,
         time,
         background,
-        distance_scale,
-        vmin,
-        vmax,
-        spatial_interpolation,
-        temporal_interpolation,
+        # numpy scalars (e.g. vmin of a DataArray) are converted to python numbers
+        json.dumps(
+            {k: v for k, v in video_options.items() if v is not None},
+            default=lambda x: x.item(),
+        ),
     )
-    if len(time) == 1:
+    if not batched or contact_sheet is not None:
         return frames[0]
     return frames
+
+
+def _apply_options(
+    options: Optional[RenderOptions],
+    background: Optional[tuple[float, float, float, float]],
+    camera: Optional[dict],
+    settings: Optional[dict],
+) -> tuple[tuple[float, float, float, float], Optional[dict], Optional[dict]]:
+    """fills the arguments that are not given from the options"""
+    if options is not None:
+        if background is None:
+            background = options.background
+        if camera is None:
+            camera = options.camera_dict()
+        settings = {**options.settings_dict(), **(settings or {})}
+    if background is None:
+        background = (0, 0, 0, 1)
+    return tuple(float(c) for c in background), camera, settings
+
+
+def render_spec(
+    spec: Union[str, os.PathLike, dict], volume: Optional[np.ndarray] = None
+) -> np.ndarray:
+    """renders the frames described by a render spec (the same json file the viewer reads with --spec)
+
+    Args:
+        spec (Union[str, os.PathLike, dict]): path of a render spec (json) or the spec as a dict. Paths in the spec are relative to the spec file (or the working directory for a dict).
+        volume (Optional[np.ndarray], optional): volume data of shape [T, D, H, W] used instead of the spec's volume file. Defaults to None.
+
+    Returns:
+        np.ndarray: [T, H, W, 4] with one image per frame of the spec's animation
+    """
+    if isinstance(spec, dict):
+        spec_json = json.dumps(spec)
+        base_dir = None
+    else:
+        with open(spec) as f:
+            spec_json = f.read()
+        base_dir = os.path.dirname(os.path.abspath(spec))
+
+    if volume is not None:
+        volume = _as_tdhw(volume)
+        volume = np.ascontiguousarray(volume).astype(np.float16)
+
+    return vape4d.render_spec(spec_json, base_dir, volume)
+
+
+def render_session(
+    session: Union[str, os.PathLike, dict],
+    width: int = 1024,
+    height: int = 1024,
+    volume: Optional[np.ndarray] = None,
+) -> np.ndarray:
+    """renders an image of a session saved in the viewer (Session section or --session) with its camera, render settings and transfer function
+
+    Args:
+        session (Union[str, os.PathLike, dict]): path of a session file (json) or the session as a dict. The volume file of the session is relative to the session file (or the working directory for a dict).
+        width (int, optional): image width. Defaults to 1024.
+        height (int, optional): image height. Defaults to 1024.
+        volume (Optional[np.ndarray], optional): volume data of shape [T, D, H, W] used instead of the session's volume file. Defaults to None.
+
+    Returns:
+        np.ndarray: [H, W, 4] image
+    """
+    if isinstance(session, dict):
+        session_json = json.dumps(session)
+        base_dir = None
+    else:
+        with open(session) as f:
+            session_json = f.read()
+        base_dir = os.path.dirname(os.path.abspath(session))
+
+    if volume is not None:
+        volume = _as_tdhw(volume)
+        volume = np.ascontiguousarray(volume).astype(np.float16)
+
+    return vape4d.render_session(session_json, width, height, base_dir, volume)
+
+
+def output_presets() -> dict[str, dict]:
+    """returns the available output presets for `render`
+
+    Returns:
+        dict[str, dict]: preset name -> dict with width, height, supersampling and dpi
+    """
+    return {
+        name: dict(width=width, height=height, supersampling=supersampling, dpi=dpi)
+        for name, width, height, supersampling, dpi in vape4d.output_presets()
+    }
+
+
+def diff(a: np.ndarray, b: np.ndarray) -> tuple[float, float, np.ndarray]:
+    """compares two rendered images
+
+    Args:
+        a (np.ndarray): first image of shape [H, W, 4] (uint8)
+        b (np.ndarray): second image of shape [H, W, 4] (uint8)
+
+    Returns:
+        tuple[float, float, np.ndarray]: PSNR in dB, SSIM and a heatmap of the per pixel error on top of the first image
+    """
+    return vape4d.diff_images(
+        np.ascontiguousarray(a, dtype=np.uint8), np.ascontiguousarray(b, dtype=np.uint8)
+    )
+
+
+def save_animation(frames: np.ndarray, path: str, fps: int = 10):
+    """writes rendered frames to a looping animated GIF or APNG, e.g. to share a short animation of the time dimension
+
+    Args:
+        frames (np.ndarray): frames of shape [T, H, W, 4] (uint8), e.g. rendered by `render` with a list of times
+        path (str): output file, the format is chosen by the extension (.gif or .apng). APNG is lossless and keeps the transparency (render with alpha="straight")
+        fps (int, optional): frames per second. Defaults to 10.
+    """
+    if frames.ndim != 4 or frames.shape[-1] != 4:
+        raise ValueError("frames must have shape [T, H, W, 4]")
+    if fps < 1:
+        raise ValueError("fps must be at least 1")
+    vape4d.save_animation(np.ascontiguousarray(frames, dtype=np.uint8), str(path), fps)
+
+
+def compare_renders(
+    volume: np.ndarray, settings_a: dict, settings_b: dict, **kwargs
+) -> tuple[float, float, np.ndarray]:
+    """renders the same volume with two different settings and compares the results
+
+    Args:
+        volume (np.ndarray): volume data, see `render`
+        settings_a (dict): arguments passed to `render` for the first image
+        settings_b (dict): arguments passed to `render` for the second image
+        **kwargs: arguments shared by both renders
+
+    Returns:
+        tuple[float, float, np.ndarray]: PSNR in dB, SSIM and error heatmap, see `diff`
+    """
+    a = render(volume, **{**kwargs, **settings_a})
+    b = render(volume, **{**kwargs, **settings_b})
+    if a.ndim != 3 or b.ndim != 3:
+        raise ValueError("compare_renders only supports single images")
+    return diff(a, b)
+
+
+class Renderer:
+    """renders many images of the same volume, e.g. the frames of an animation computed one by one.
+    Unlike `render`, the GPU device and the uploaded volume are reused for every image.
+    """
+
+    def __init__(
+        self,
+        volume: Union[np.ndarray, "xarray.DataArray"],
+        cmap: Optional[Union[str, Colormap]] = None,
+        spacing: Optional[tuple[float, float, float]] = None,
+        affine: Optional[np.ndarray] = None,
+    ):
+        """
+        Args:
+            volume (Union[np.ndarray, xarray.DataArray]): volume data of shape [T, D, H, W] or [D, H, W], see `render`
+            cmap (Optional[Union[str, Colormap]], optional): colormap, see `render`. Defaults to matplotlib's default colormap.
+            spacing (Optional[tuple[float, float, float]], optional): physical size of a voxel along (D, H, W). Defaults to None (the coordinates of a DataArray or isotropic voxels of size 1).
+            affine (Optional[np.ndarray], optional): 4x4 matrix mapping voxel indices to world coordinates instead of spacing, see `render`. Defaults to None.
+        """
+        cmap = to_colormap(cmap)
+        volume, info = from_xarray(volume)
+        if affine is not None:
+            if spacing is not None:
+                raise ValueError("spacing and affine can not be combined")
+            volume, spacing = apply_affine(volume, affine)
+        if spacing is None:
+            spacing = info.get("spacing")
+        # value range of a DataArray, used unless the settings have one
+        self._value_range = {k: info[k] for k in ("vmin", "vmax") if k in info}
+        volume = _as_tdhw(volume)
+        if spacing is not None and (len(spacing) != 3 or min(spacing) <= 0):
+            raise ValueError("spacing must contain three positive values")
+        self._renderer = vape4d.Renderer(
+            np.ascontiguousarray(volume).astype(np.float16),
+            cmap(np.linspace(0, 1, 256)).astype(np.float32),
+            None if spacing is None else tuple(float(s) for s in spacing),
+        )
+
+    def render(
+        self,
+        time: Union[float, list[float], np.ndarray] = 0.0,
+        width: int = 1024,
+        height: int = 1024,
+        background: Optional[tuple[float, float, float, float]] = None,
+        supersampling: int = 1,
+        camera: Optional[dict] = None,
+        settings: Optional[dict] = None,
+        alpha: str = "premultiplied",
+        options: Optional[RenderOptions] = None,
+    ) -> np.ndarray:
+        """renders the volume at one or more normalized times in [0, 1]
+
+        Args:
+            time (Union[float, list[float], np.ndarray], optional): normalized time, or one time per image. Defaults to 0.
+            width (int, optional): image width. Defaults to 1024.
+            height (int, optional): image height. Defaults to 1024.
+            background (Optional[tuple[float, float, float, float]], optional): background color. Defaults to the background of options or (0, 0, 0, 1).
+            supersampling (int, optional): number of samples per pixel along each axis. Defaults to 1.
+            camera (Optional[dict], optional): camera like the camera section of a render spec, see `render`. Defaults to None (isometric view).
+            settings (Optional[dict], optional): render settings like the "render" section of a session, see `render`. Defaults to None (default settings).
+            alpha (str, optional): "premultiplied" or "straight", see `render`. Defaults to "premultiplied".
+            options (Optional[RenderOptions], optional): render settings, camera and background, see `render`. Defaults to None.
+
+        Returns:
+            np.ndarray: uint8 RGBA images of shape [T, H, W, 4] if time is a list or array, [H, W, 4] if time is a single value
+        """
+        if supersampling < 1:
+            raise ValueError("supersampling must be at least 1")
+        batched = isinstance(time, (list, tuple, np.ndarray))
+        background, camera, settings = _apply_options(
+            options, background, camera, settings
+        )
+        if self._value_range:
+            settings = dict(settings or {})
+            for key, value in self._value_range.items():
+                if settings.get(key) is None:
+                    settings[key] = value
+        frames = self._renderer.render(
+            [float(t) for t in np.ravel(time)],
+            width,
+            height,
+            background,
+            supersampling,
+            None if camera is None else json.dumps(camera),
+            None if settings is None else json.dumps(settings),
+            alpha,
+        )
+        return frames if batched else frames[0]
+
+
+def render_batch(
+    volume: Union[np.ndarray, "xarray.DataArray"],
+    times: Union[list[float], np.ndarray] = (0.0,),
+    cameras: Optional[list[Union[dict, CameraOptions]]] = None,
+    cmap: Optional[Union[str, Colormap]] = None,
+    width: int = 512,
+    height: int = 512,
+    background: Optional[tuple[float, float, float, float]] = None,
+    supersampling: int = 1,
+    spacing: Optional[tuple[float, float, float]] = None,
+    affine: Optional[np.ndarray] = None,
+    settings: Optional[dict] = None,
+    alpha: str = "premultiplied",
+    options: Optional[RenderOptions] = None,
+) -> np.ndarray:
+    """renders every combination of times and cameras, e.g. for training data or figure grids.
+    The volume is uploaded to the GPU once for all images.
+
+    Args:
+        volume (Union[np.ndarray, xarray.DataArray]): volume data of shape [T, D, H, W] or [D, H, W], see `render`
+        times (Union[list[float], np.ndarray], optional): normalized times in [0, 1]. Defaults to (0,).
+        cameras (Optional[list[Union[dict, CameraOptions]]], optional): cameras like the camera section of a render spec (see `render`) or CameraOptions. Defaults to None (the camera of options or the isometric view).
+        cmap (Optional[Union[str, Colormap]], optional): colormap, see `render`. Defaults to matplotlib's default colormap.
+        width (int, optional): image width. Defaults to 512.
+        height (int, optional): image height. Defaults to 512.
+        background (Optional[tuple[float, float, float, float]], optional): background color. Defaults to the background of options or (0, 0, 0, 1).
+        supersampling (int, optional): number of samples per pixel along each axis. Defaults to 1.
+        spacing (Optional[tuple[float, float, float]], optional): physical size of a voxel along (D, H, W). Defaults to None (isotropic voxels of size 1).
+        affine (Optional[np.ndarray], optional): 4x4 matrix mapping voxel indices to world coordinates instead of spacing, see `render`. Defaults to None.
+        settings (Optional[dict], optional): render settings like the "render" section of a session, see `render`. Defaults to None.
+        alpha (str, optional): "premultiplied" or "straight", see `render`. Defaults to "premultiplied".
+        options (Optional[RenderOptions], optional): render settings, camera and background, see `render`. Defaults to None.
+
+    Returns:
+        np.ndarray: uint8 RGBA images of shape [C, T, H, W, 4] with C cameras and T times
+    """
+    times = [float(t) for t in np.ravel(times)]
+    if len(times) == 0:
+        raise ValueError("times must contain at least one time")
+    if cameras is None:
+        cameras = [None]
+    elif len(cameras) == 0:
+        raise ValueError("cameras must contain at least one camera")
+    renderer = Renderer(volume, cmap, spacing, affine)
+    frames = np.empty((len(cameras), len(times), height, width, 4), dtype=np.uint8)
+    for i, camera in enumerate(cameras):
+        if isinstance(camera, CameraOptions):
+            camera = camera.to_dict()
+        frames[i] = renderer.render(
+            times,
+            width,
+            height,
+            background,
+            supersampling,
+            camera,
+            settings,
+            alpha,
+            options,
+        )
+    return frames
+
+
+def render_video(
+    volume: Union[np.ndarray, "xarray.DataArray"],
+    path: Union[str, os.PathLike],
+    fps: int = 30,
+    duration: float = 5.0,
+    camera_path: Optional[Union[str, list[Union[dict, CameraOptions]]]] = None,
+    cmap: Optional[Union[str, Colormap, "TransferFunction"]] = None,
+    width: int = 1920,
+    height: int = 1080,
+    time_range: tuple[float, float] = (0.0, 1.0),
+    background: Optional[tuple[float, float, float, float]] = None,
+    supersampling: int = 1,
+    spacing: Optional[tuple[float, float, float]] = None,
+    affine: Optional[np.ndarray] = None,
+    camera: Optional[dict] = None,
+    settings: Optional[dict] = None,
+    options: Optional[RenderOptions] = None,
+):
+    """renders an animation of the volume over time into a video file frame by frame, so long videos do not have to fit into memory
+
+    Args:
+        volume (Union[np.ndarray, xarray.DataArray]): volume data of shape [T, D, H, W] or [D, H, W], see `render`
+        path (Union[str, os.PathLike]): output file. The format is chosen by the extension: .mp4 and .webm are encoded by ffmpeg (which has to be installed), .gif and .apng are written directly. Other extensions (e.g. .png) give numbered images.
+        fps (int, optional): frames per second. Defaults to 30.
+        duration (float, optional): length of the video in seconds. Defaults to 5.
+        camera_path (Optional[Union[str, list[Union[dict, CameraOptions]]]], optional): "turntable" orbits the camera once around the volume. A list of keyframes (dicts with direction, up, zoom, center relative to the volume center and optionally at in seconds, vmin, vmax and distance_scale, like the keyframes of a session, or CameraOptions with direction, up and zoom) is spread over the video, evenly if at is not given. Defaults to None (the camera stays at camera).
+        cmap (Optional[Union[str, Colormap, TransferFunction]], optional): colormap, see `render`. Defaults to matplotlib's default colormap.
+        width (int, optional): video width. Defaults to 1920.
+        height (int, optional): video height. Defaults to 1080.
+        time_range (tuple[float, float], optional): normalized time of the first and the last frame. Defaults to (0, 1).
+        background (Optional[tuple[float, float, float, float]], optional): background color. Defaults to the background of options or (0, 0, 0, 1).
+        supersampling (int, optional): number of samples per pixel along each axis. Defaults to 1.
+        spacing (Optional[tuple[float, float, float]], optional): physical size of a voxel along (D, H, W). Defaults to None (the coordinates of a DataArray or isotropic voxels of size 1).
+        affine (Optional[np.ndarray], optional): 4x4 matrix mapping voxel indices to world coordinates instead of spacing, see `render`. Defaults to None.
+        camera (Optional[dict], optional): camera like the camera section of a render spec, see `render`. Defaults to the camera of options or the isometric view.
+        settings (Optional[dict], optional): render settings like the "render" section of a session, see `render`. Defaults to None.
+        options (Optional[RenderOptions], optional): render settings, camera and background, see `render`. Defaults to None.
+    """
+    if fps < 1:
+        raise ValueError("fps must be at least 1")
+    num_frames = max(int(round(duration * fps)), 1)
+    if supersampling < 1:
+        raise ValueError("supersampling must be at least 1")
+    background, camera, settings = _apply_options(options, background, camera, settings)
+
+    cmap = to_colormap(cmap)
+    volume, info = from_xarray(volume)
+    if affine is not None:
+        if spacing is not None:
+            raise ValueError("spacing and affine can not be combined")
+        volume, spacing = apply_affine(volume, affine)
+    if spacing is None:
+        spacing = info.get("spacing")
+    settings = dict(settings or {})
+    for key in ("vmin", "vmax"):
+        if settings.get(key) is None and key in info:
+            settings[key] = info[key]
+    volume = _as_tdhw(volume)
+    if spacing is not None and (len(spacing) != 3 or min(spacing) <= 0):
+        raise ValueError("spacing must contain three positive values")
+
+    turntable = False
+    keyframes = None
+    if isinstance(camera_path, str):
+        if camera_path != "turntable":
+            raise ValueError(f"unknown camera path '{camera_path}'")
+        turntable = True
+    elif camera_path is not None:
+        keyframes = [_keyframe(k, i) for i, k in enumerate(camera_path)]
+        if len(keyframes) == 0:
+            raise ValueError("camera_path must contain at least one keyframe")
+
+    vape4d.render_animation_file(
+        np.ascontiguousarray(volume).astype(np.float16),
+        cmap(np.linspace(0, 1, 256)).astype(np.float32),
+        str(path),
+        fps,
+        num_frames,
+        width,
+        height,
+        (float(time_range[0]), float(time_range[1])),
+        background,
+        supersampling,
+        None if spacing is None else tuple(float(s) for s in spacing),
+        None if camera is None else json.dumps(camera),
+        None if keyframes is None else json.dumps(keyframes),
+        turntable,
+        json.dumps(settings),
+    )
+
+
+def _keyframe(keyframe: Union[dict, CameraOptions], index: int) -> dict:
+    """keyframe of a camera path, at `index` seconds if it has no time"""
+    if isinstance(keyframe, CameraOptions):
+        if keyframe.position is not None or keyframe.azimuth is not None:
+            raise ValueError("keyframes are given by direction, up and zoom")
+        keyframe = dict(direction=keyframe.direction, up=keyframe.up, zoom=keyframe.zoom)
+    keyframe = dict(keyframe)
+    keyframe.setdefault("at", float(index))
+    return keyframe
@@ -0,0 +1,199 @@
+import json
+import os
+import subprocess
+import sys
+import tempfile
+from typing import Optional, Union
+
+import numpy as np
+from matplotlib.colors import Colormap, LinearSegmentedColormap
+
+Points = list[tuple[float, float, float]]
+
+
+class TransferFunction:
+    """colors and opacity as piecewise-linear control points, the same as the transfer function edited in the viewer.
+
+    Every channel is a list of control points (x, y0, y1) with ascending x in [0, 1] and the value y0 left and y1 right of x (like the segments of a matplotlib LinearSegmentedColormap).
+    It can be passed as colormap to `viewer`, `render` and `Renderer`.
+    """
+
+    def __init__(
+        self,
+        red: Points,
+        green: Points,
+        blue: Points,
+        opacity: Optional[Points] = None,
+    ):
+        """
+        Args:
+            red (Points): control points of the red channel
+            green (Points): control points of the green channel
+            blue (Points): control points of the blue channel
+            opacity (Optional[Points], optional): control points of the opacity. Defaults to None (opaque).
+        """
+        self.red = _points(red, "red")
+        self.green = _points(green, "green")
+        self.blue = _points(blue, "blue")
+        self.opacity = _points(
+            opacity if opacity is not None else [(0, 1, 1), (1, 1, 1)], "opacity"
+        )
+
+    @classmethod
+    def from_colormap(
+        cls,
+        cmap: Optional[Union[str, Colormap]] = None,
+        opacity: Optional[list[tuple[float, ...]]] = None,
+    ) -> "TransferFunction":
+        """control points of a matplotlib colormap
+
+        Args:
+            cmap (Optional[Union[str, Colormap]], optional): colormap or its name, see `render`. Defaults to matplotlib's default colormap.
+            opacity (Optional[list[tuple[float, ...]]], optional): control points (x, opacity) or (x, y0, y1). Defaults to None (alpha channel of the colormap).
+
+        Returns:
+            TransferFunction: the transfer function
+        """
+        from .utils import to_colormap
+
+        cmap = to_colormap(cmap)
+        segments = getattr(cmap, "_segmentdata", None)
+        if isinstance(cmap, LinearSegmentedColormap) and all(
+            not callable(segments.get(key)) for key in ("red", "green", "blue")
+        ):
+            channels = [segments[key] for key in ("red", "green", "blue")]
+            alpha = segments.get("alpha")
+            if callable(alpha):
+                alpha = None
+        else:
+            # sampled like the colormaps passed to render
+            x = np.linspace(0, 1, 256)
+            colors = cmap(x)
+            channels = [list(zip(x, colors[:, i], colors[:, i])) for i in range(3)]
+            alpha = list(zip(x, colors[:, 3], colors[:, 3]))
+        tf = cls(*channels, opacity=alpha)
+        if opacity is not None:
+            tf.set_opacity(opacity)
+        return tf
+
+    @classmethod
+    def from_dict(cls, data: dict) -> "TransferFunction":
+        """reads the json format of colormaps (r, g, b and a as opacity), e.g. written by the viewer with --save-transfer-function"""
+        return cls(
+            data.get("r", data.get("red")),
+            data.get("g", data.get("green")),
+            data.get("b", data.get("blue")),
+            data.get("a", data.get("alpha")),
+        )
+
+    @classmethod
+    def load(cls, path: Union[str, os.PathLike]) -> "TransferFunction":
+        """reads a transfer function saved with `save` or by the viewer"""
+        with open(path) as f:
+            return cls.from_dict(json.load(f))
+
+    def to_dict(self) -> dict:
+        """json format of colormaps with the opacity as alpha channel, read by the viewer as colormap"""
+        return dict(r=self.red, g=self.green, b=self.blue, a=self.opacity)
+
+    def save(self, path: Union[str, os.PathLike]):
+        """writes the transfer function as json, it can be opened in the viewer as colormap"""
+        with open(path, "w") as f:
+            json.dump(self.to_dict(), f, indent=2)
+
+    def set_opacity(self, points: list[tuple[float, ...]]):
+        """replaces the opacity by control points (x, opacity) or (x, y0, y1)"""
+        self.opacity = _points([(p[0], p[1], p[-1]) for p in points], "opacity")
+
+    def add_opacity_point(self, x: float, opacity: float):
+        """inserts a control point into the opacity, an existing point at x is replaced"""
+        points = [p for p in self.opacity if p[0] != x]
+        points.append((x, opacity, opacity))
+        self.opacity = _points(sorted(points), "opacity")
+
+    def remove_opacity_point(self, index: int):
+        """removes the control point at index from the opacity, the first and last points are kept"""
+        if index in (0, -1, len(self.opacity) - 1):
+            raise ValueError("the first and last control points can not be removed")
+        del self.opacity[index]
+
+    def as_colormap(self, name: str = "vape4d") -> Colormap:
+        """matplotlib colormap with the opacity as alpha channel"""
+        return LinearSegmentedColormap(
+            name,
+            dict(red=self.red, green=self.green, blue=self.blue, alpha=self.opacity),
+        )
+
+    def __call__(self, x, *args, **kwargs) -> np.ndarray:
+        """RGBA colors at x, like a matplotlib colormap"""
+        return self.as_colormap()(x, *args, **kwargs)
+
+    def __eq__(self, other) -> bool:
+        return isinstance(other, TransferFunction) and self.to_dict() == other.to_dict()
+
+    def __repr__(self) -> str:
+        return (
+            f"TransferFunction({len(self.red)}, {len(self.green)}, {len(self.blue)} color "
+            f"and {len(self.opacity)} opacity control points)"
+        )
+
+
+def _points(points, name: str) -> Points:
+    points = [tuple(float(v) for v in p) for p in points]
+    if len(points) < 2 or any(len(p) != 3 for p in points):
+        raise ValueError(f"{name} needs at least two control points (x, y0, y1)")
+    x = [p[0] for p in points]
+    if x[0] != 0 or x[-1] != 1 or any(a > b for a, b in zip(x, x[1:])):
+        raise ValueError(f"x values of {name} must ascend from 0 to 1")
+    return points
+
+
+def edit_transfer_function(
+    volume: np.ndarray,
+    tf: Optional[Union[str, Colormap, TransferFunction]] = None,
+) -> TransferFunction:
+    """opens the volume in the viewer and returns the transfer function edited there once the window is closed
+
+    Args:
+        volume (np.ndarray): volume data of shape [T, C, D, H, W], [T, D, H, W] or [D, H, W]
+        tf (Optional[Union[str, Colormap, TransferFunction]], optional): initial transfer function or colormap. Defaults to matplotlib's default colormap.
+
+    Returns:
+        TransferFunction: the edited transfer function, e.g. to render figures with it
+    """
+    from .utils import from_xarray
+
+    if not isinstance(tf, TransferFunction):
+        tf = TransferFunction.from_colormap(tf)
+    volume, _ = from_xarray(volume)
+    volume = np.asarray(volume, dtype=np.float32)
+    if volume.ndim == 3:
+        volume = volume[None, None]
+    elif volume.ndim == 4:
+        volume = volume[:, None]
+    elif volume.ndim != 5:
+        raise ValueError(
+            "volume must have shape [T,C, D, H, W], [T, D, H, W] or [D,H,W] "
+        )
+    with tempfile.TemporaryDirectory() as tmp:
+        volume_file = os.path.join(tmp, "volume.npy")
+        colormap_file = os.path.join(tmp, "colormap.json")
+        output_file = os.path.join(tmp, "edited.json")
+        np.save(volume_file, volume)
+        tf.save(colormap_file)
+        # the event loop of the viewer can only run once per process
+        subprocess.run(
+            [
+                sys.executable,
+                "-c",
+                "import sys; from vape4d import vape4d; vape4d.run_viewer(sys.argv[1:])",
+                volume_file,
+                colormap_file,
+                "--save-transfer-function",
+                output_file,
+            ],
+            check=True,
+        )
+        if not os.path.exists(output_file):
+            raise RuntimeError("the viewer was not closed normally")
+        return TransferFunction.load(output_file)
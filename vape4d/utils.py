@@ -1,6 +1,10 @@
 from copy import deepcopy
+from typing import Optional, Union
+import matplotlib
+from matplotlib import pyplot as plt
 from matplotlib.colors import Colormap, LinearSegmentedColormap, ListedColormap
 import numpy as np
+from . import vape4d
 
 
 def diverging_alpha(cmap: Colormap) -> Colormap:
@@ -49,3 +53,242 @@ def linear_increasing_alpha(cmap: Colormap) -> Colormap:
             "cmap must be either a ListedColormap or a LinearSegmentedColormap"
         )
     return cmap
+
+
+def from_hex_list(colors: str, name: str = "hex") -> Colormap:
+    """creates a colormap from a comma-separated list of hex colors (e.g. "#000000,#ff0000,#ffff00")
+
+    Args:
+        colors (str): evenly spaced colors as #rgb, #rgba, #rrggbb or #rrggbbaa (the # is optional)
+        name (str, optional): name of the colormap. Defaults to "hex".
+
+    Returns:
+        Colormap: new colormap
+    """
+    return ListedColormap(vape4d.colormap_from_hex(colors), name=name)
+
+
+def to_colormap(cmap: Optional[Union[str, Colormap, "TransferFunction"]]) -> Colormap:
+    """resolves the colormap arguments of the Python API
+
+    Args:
+        cmap (Optional[Union[str, Colormap, TransferFunction]]): transfer function, matplotlib colormap, name of a matplotlib colormap (e.g. "viridis" or "magma_r") or comma-separated list of hex colors (e.g. "#000000,#ff0000,#ffff00"). None is matplotlib's default colormap.
+
+    Returns:
+        Colormap: the colormap
+    """
+    if cmap is None:
+        return plt.get_cmap()
+    if isinstance(cmap, Colormap):
+        return cmap
+    from .transfer_function import TransferFunction
+
+    if isinstance(cmap, TransferFunction):
+        return cmap.as_colormap()
+    if not isinstance(cmap, str):
+        raise TypeError(f"cmap must be a matplotlib colormap or a string, not {type(cmap).__name__}")
+    if cmap in matplotlib.colormaps:
+        return matplotlib.colormaps[cmap]
+    try:
+        return from_hex_list(cmap)
+    except Exception:
+        raise ValueError(
+            f"unknown colormap '{cmap}' (expected a matplotlib colormap name or comma-separated hex colors)"
+        ) from None
+
+
+def fetch_example(name: str) -> np.ndarray:
+    """downloads a small public example dataset (if it is not cached yet) and loads it.
+    The datasets are stored in $VAPE4D_CACHE_DIR or the user's cache directory.
+    Requires curl.
+
+    Args:
+        name (str): name of the dataset (see `example_datasets()`), e.g. "ct-head", "turbulence" or "fuel"
+
+    Returns:
+        np.ndarray: volume of shape [1, D, H, W]
+    """
+    return np.load(vape4d.fetch_example(name))[:, 0]
+
+
+def example_datasets() -> list[tuple[str, str]]:
+    """returns the names and descriptions of the available example datasets"""
+    return vape4d.example_datasets()
+
+
+# dimension names of xarray DataArrays that are mapped to time and channels
+TIME_DIMS = ("time", "t", "timestep", "step")
+CHANNEL_DIMS = ("channel", "channels", "c", "band", "variable", "component")
+
+
+def is_xarray(data) -> bool:
+    """checks for an xarray.DataArray without importing xarray"""
+    return type(data).__module__.startswith("xarray") and hasattr(data, "dims")
+
+
+def from_xarray(data) -> tuple[np.ndarray, dict]:
+    """converts an xarray.DataArray to a volume of shape [T, C, D, H, W].
+
+    The time and channel dimensions are found by their names (e.g. "time" or "band") or,
+    for time, by datetime coordinates. The remaining three dimensions are the spatial ones in
+    their order. Arrays that are not DataArrays are returned unchanged.
+
+    Args:
+        data (xarray.DataArray): volume with two to five dimensions
+
+    Returns:
+        tuple[np.ndarray, dict]: the volume and what is known about it: spacing (D, H, W) of evenly spaced numeric coordinates, unit of these coordinates ("units" attribute) and vmin/vmax from the "valid_min", "valid_max", "valid_range" or "actual_range" attributes
+    """
+    if not is_xarray(data):
+        return data, {}
+
+    def find(names, exclude):
+        for dim in data.dims:
+            if dim not in exclude and str(dim).lower() in names:
+                return dim
+        return None
+
+    time_dim = find(TIME_DIMS, ())
+    if time_dim is None:
+        time_dim = next(
+            (
+                dim
+                for dim in data.dims
+                if dim in data.coords
+                and np.issubdtype(data.coords[dim].dtype, np.datetime64)
+            ),
+            None,
+        )
+    channel_dim = find(CHANNEL_DIMS, (time_dim,))
+    spatial_dims = [d for d in data.dims if d not in (time_dim, channel_dim)]
+    if len(spatial_dims) != 3:
+        raise ValueError(
+            f"expected three spatial dimensions besides time and channel, got {spatial_dims}"
+        )
+
+    volume = data.transpose(
+        *[d for d in (time_dim, channel_dim) if d is not None], *spatial_dims
+    ).values
+    if time_dim is None:
+        volume = volume[None]
+    if channel_dim is None:
+        volume = volume[:, None]
+
+    info = {}
+    spacing = []
+    units = set()
+    for dim in spatial_dims:
+        coord = data.coords.get(dim)
+        if coord is None or coord.size < 2 or not np.issubdtype(coord.dtype, np.number):
+            break
+        steps = np.diff(coord.values.astype(np.float64))
+        if not np.allclose(steps, steps[0], rtol=1e-3) or steps[0] == 0:
+            break
+        spacing.append(abs(float(steps[0])))
+        units.add(coord.attrs.get("units", ""))
+    else:
+        info["spacing"] = tuple(spacing)
+        if len(units) == 1 and "" not in units:
+            info["unit"] = units.pop()
+
+    attrs = data.attrs
+    value_range = attrs.get("valid_range", attrs.get("actual_range"))
+    if value_range is not None and len(value_range) == 2:
+        info["vmin"], info["vmax"] = float(value_range[0]), float(value_range[1])
+    if "valid_min" in attrs:
+        info["vmin"] = float(attrs["valid_min"])
+    if "valid_max" in attrs:
+        info["vmax"] = float(attrs["valid_max"])
+    return volume, info
+
+
+def _as_tdhw(volume: np.ndarray) -> np.ndarray:
+    """volume of shape [T, 1, D, H, W], [T, D, H, W] or [D, H, W] as [T, D, H, W]"""
+    if volume.ndim == 5:
+        if volume.shape[1] != 1:
+            raise ValueError("only one channel supported")
+        return volume[:, 0]
+    if volume.ndim == 3:
+        return volume[None]
+    if volume.ndim != 4:
+        raise ValueError(
+            "volume must have shape [T,1, D, H, W], [T, D, H, W] or [D,H,W] "
+        )
+    return volume
+
+
+def apply_affine(
+    volume: np.ndarray, affine: np.ndarray
+) -> tuple[np.ndarray, tuple[float, float, float]]:
+    """reorients a volume by an axis-aligned affine, e.g. of a NIfTI file
+
+    The renderer only knows axis-aligned bounding boxes, so the affine may scale, flip and permute the axes but not rotate or shear them.
+    Flipped and permuted axes are applied to the data, the translation is ignored (the volume is always centered).
+
+    Args:
+        volume (np.ndarray): volume of shape [..., D, H, W]
+        affine (np.ndarray): 4x4 matrix mapping voxel indices (d, h, w, 1) to world coordinates in the same axis order
+
+    Returns:
+        tuple[np.ndarray, tuple[float, float, float]]: the reoriented volume and its spacing along (D, H, W)
+    """
+    affine = np.asarray(affine, dtype=np.float64)
+    if affine.shape != (4, 4):
+        raise ValueError(f"affine must be a 4x4 matrix, got shape {affine.shape}")
+    linear = affine[:3, :3]
+    # world axis of every array axis
+    axes = np.argmax(np.abs(linear), axis=0)
+    scale = linear[axes, np.arange(3)]
+    off_axis = np.abs(linear).sum(axis=0) - np.abs(scale)
+    if (
+        sorted(axes) != [0, 1, 2]
+        or np.any(scale == 0)
+        or np.any(off_axis > 1e-6 * np.abs(scale))
+    ):
+        raise ValueError(
+            "only axis-aligned affines are supported (scaling, flips, axis permutations and translation)"
+        )
+    lead = volume.ndim - 3
+    flipped = [lead + i for i in range(3) if scale[i] < 0]
+    if len(flipped) > 0:
+        volume = np.flip(volume, flipped)
+    # array axis of every world axis
+    order = np.argsort(axes)
+    volume = volume.transpose(*range(lead), *(lead + order))
+    return volume, tuple(float(abs(scale[i])) for i in order)
+
+
+def stats(
+    volume: Union[np.ndarray, "xarray.DataArray"],
+    percentiles: list[float] = (1, 5, 50, 95, 99),
+    timestep: Optional[int] = None,
+) -> dict:
+    """computes statistics of the finite values of a volume on the GPU with the same kernels as the volume info window of the viewer.
+    Much faster than numpy for large volumes, e.g. to pick vmin and vmax before rendering.
+
+    The values are converted to float16 like for rendering.
+
+    Args:
+        volume (Union[np.ndarray, xarray.DataArray]): volume data of shape [T, D, H, W] or [D, H, W]
+        percentiles (list[float], optional): percentiles in [0, 100]. They are accurate to about (max - min) / 128². Defaults to (1, 5, 50, 95, 99).
+        timestep (Optional[int], optional): only this timestep instead of all. Defaults to None.
+
+    Returns:
+        dict: count (finite values), nan (NaN and infinite values), min, max, mean, std, percentiles (dict of percentile -> value) and histogram (counts of 128 bins between min and max and the bin edges, like np.histogram)
+    """
+    volume, _ = from_xarray(volume)
+    volume = _as_tdhw(volume)
+    percentiles = [float(p) for p in percentiles]
+    count, nan, vmin, vmax, mean, std, values, bins = vape4d.volume_statistics(
+        np.ascontiguousarray(volume).astype(np.float16), percentiles, timestep
+    )
+    return dict(
+        count=count,
+        nan=nan,
+        min=vmin,
+        max=vmax,
+        mean=mean,
+        std=std,
+        percentiles=dict(zip(percentiles, values)),
+        histogram=(np.array(bins), np.linspace(vmin, vmax, len(bins) + 1)),
+    )
@@ -5,13 +5,15 @@ import json
 import os
 import random
 from dataclasses import dataclass
-from typing import Optional
+from typing import Optional, Union
 
-from matplotlib import pyplot as plt
 from matplotlib.colors import Colormap
 import numpy as np
 from IPython.display import DisplayObject
 
+from .options import RenderOptions
+from .utils import apply_affine, from_xarray, to_colormap
+
 VAPE_URL = os.environ.get("VAPE_URL", "https://keksboter.github.io/vape4d")
 
 
@@ -56,38 +58,80 @@ class ViewerSettings:
     vmax: Optional[float]
     distance_scale: float
     duration: Optional[float] = None
+    # camera section of a render spec as json
+    camera: Optional[str] = None
+    # render section of a session as json
+    render: Optional[str] = None
 
 
 def viewer(
-    data: np.ndarray,
-    colormap: Optional[Colormap] = None,
+    data: Union[np.ndarray, "xarray.DataArray"],
+    colormap: Optional[Union[str, Colormap]] = None,
     width: int = 800,
     height: int = 600,
-    background_color=(0.0, 0.0, 0.0, 1.0),
+    background_color=None,
     show_colormap_editor=False,
     show_volume_info=False,
     vmin=None,
     vmax=None,
-    distance_scale=1.0,
+    distance_scale=None,
     duration=None,
+    camera: Optional[dict] = None,
+    options: Optional[RenderOptions] = None,
+    spacing: Optional[tuple[float, float, float]] = None,
+    affine: Optional[np.ndarray] = None,
 ):
     """_summary_
 
     Args:
-        data (np.ndarray): volume data of shape [T,C, D, H, W]
-        colormap (Optional[Colormap], optional): _description_. Defaults to matplotlib default colormap.
+        data (Union[np.ndarray, xarray.DataArray]): volume data of shape [T,C, D, H, W]. For a DataArray, time and channel dimensions are found by their names (e.g. "time" or "band") and vmin and vmax default to its attributes (see `utils.from_xarray`).
+        colormap (Optional[Union[str, Colormap]], optional): matplotlib colormap, the name of one (e.g. "viridis") or comma-separated list of hex colors (e.g. "#000000,#ff0000,#ffff00"). Defaults to matplotlib default colormap.
         width (int, optional): viewer width. Defaults to 800.
         height (int, optional): viewer height. Defaults to 600.
-        background_color (tuple, optional): background color in renderer. Defaults to black.
+        background_color (tuple, optional): background color in renderer. Defaults to the background of options or black.
         show_colormap_editor (bool, optional): show the transfer function editor. Defaults to False.
         show_volume_info (bool, optional): show the volume info window. Defaults to False.
         vmin (float, optional): all values in data are clamped to this value. Defaults to minimum value in data.
         vmax (float, optional):  all values in data are clamped to this value. Defaults to maximum value in data.
-        distance_scale (float, optional): distance scale used for rendering. Defaults to 1.0.
+        distance_scale (float, optional): distance scale used for rendering. Defaults to the distance scale of options or 1.0.
         duration (_type_, optional): duration of one animation cycle. Defaults to 5 seconds.
+        camera (Optional[dict], optional): initial camera like the camera section of a render spec, e.g. dict(azimuth=30, elevation=20, zoom=1.5). The viewer always uses an orthographic projection. Defaults to the camera of options or the isometric view.
+        options (Optional[RenderOptions], optional): initial render settings, camera and background, e.g. the same options passed to `render`. The other arguments take precedence. Defaults to None.
+        spacing (Optional[tuple[float, float, float]], optional): physical size of a voxel along (D, H, W). Stretches the volume accordingly. Defaults to None (the coordinates of a DataArray or isotropic voxels of size 1).
+        affine (Optional[np.ndarray], optional): 4x4 matrix mapping voxel indices to world coordinates instead of spacing, see `render`. Defaults to None.
     """
-    if colormap is None:
-        colormap = plt.get_cmap()
+    colormap = to_colormap(colormap)
+    data, info = from_xarray(data)
+    if affine is not None:
+        if spacing is not None:
+            raise ValueError("spacing and affine can not be combined")
+        data, spacing = apply_affine(data, affine)
+    if spacing is None:
+        spacing = info.get("spacing")
+    if spacing is not None and (len(spacing) != 3 or min(spacing) <= 0):
+        raise ValueError("spacing must contain three positive values")
+    if vmin is None and (options is None or options.vmin is None):
+        vmin = info.get("vmin")
+    if vmax is None and (options is None or options.vmax is None):
+        vmax = info.get("vmax")
+    render = None
+    if options is not None:
+        if background_color is None:
+            background_color = options.background
+        if camera is None:
+            camera = options.camera_dict()
+        if vmin is None:
+            vmin = options.vmin
+        if vmax is None:
+            vmax = options.vmax
+        if distance_scale is None:
+            distance_scale = options.distance_scale
+        render = options.settings_dict()
+        render.update(vmin=vmin, vmax=vmax, distance_scale=float(distance_scale))
+    if background_color is None:
+        background_color = (0.0, 0.0, 0.0, 1.0)
+    if distance_scale is None:
+        distance_scale = 1.0
     return VolumeViewer(
         data,
         colormap,
@@ -101,14 +145,42 @@ def viewer(
             vmax,
             distance_scale,
             duration,
+            None if camera is None else json.dumps(camera),
+            None if render is None else json.dumps(render),
         ),
+        spacing,
     )
 
 
+def volume_file(data: np.ndarray, spacing: Optional[tuple] = None) -> bytes:
+    """volume as npy file, or as npz file with the spacing array read by the viewer"""
+    buffer = io.BytesIO()
+    if spacing is None:
+        np.save(buffer, data.astype(np.float32))
+    else:
+        np.savez(
+            buffer,
+            volume=data.astype(np.float32),
+            spacing=np.array(spacing, dtype=np.float32),
+        )
+    return buffer.getvalue()
+
+
 class VolumeViewer(DisplayObject):
-    def __init__(self, data: np.ndarray, colormap: Colormap, settings: ViewerSettings):
+    def __init__(
+        self,
+        data: np.ndarray,
+        colormap: Colormap,
+        settings: ViewerSettings,
+        spacing: Optional[tuple] = None,
+    ):
         super(VolumeViewer, self).__init__(
-            data={"volume": data, "cmap": colormap, "settings": settings}
+            data={
+                "volume": data,
+                "cmap": colormap,
+                "settings": settings,
+                "spacing": spacing,
+            }
         )
 
     def _repr_html_(self):
@@ -116,9 +188,7 @@ class VolumeViewer(DisplayObject):
         data = self.data["volume"]
         colormap = self.data["cmap"]
         settings = self.data["settings"]
-        buffer = io.BytesIO()
-        np.save(buffer, data.astype(np.float32))
-        data_code = base64.b64encode(buffer.getvalue())
+        data_code = base64.b64encode(volume_file(data, self.data["spacing"]))
 
         buffer2 = io.BytesIO()
         colormap_data = colormap(np.linspace(0, 1, 256)).astype(np.float32)
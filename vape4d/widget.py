@@ -0,0 +1,190 @@
+import base64
+import dataclasses
+import io
+from typing import Optional, Union
+
+import anywidget
+import numpy as np
+import traitlets
+from matplotlib.colors import Colormap, LinearSegmentedColormap
+
+from .transfer_function import TransferFunction
+from .utils import to_colormap
+from .viewer import VAPE_URL, viewer, volume_file
+
+_ESM = """
+function render({ model, el }) {
+    const iframe = document.createElement("iframe");
+    iframe.src = `${model.get("viewer_url")}/index.html?inline`;
+    iframe.width = model.get("width");
+    iframe.height = model.get("height");
+    iframe.frameBorder = "0";
+    iframe.sandbox = "allow-same-origin allow-scripts";
+    el.appendChild(iframe);
+
+    const bytes = (data) => Uint8Array.from(atob(data), (c) => c.charCodeAt(0));
+    // last state received from the viewer, not sent back to it
+    let received = null;
+    const onMessage = (event) => {
+        if (event.source !== iframe.contentWindow) {
+            return;
+        }
+        if (event.data === "ready") {
+            iframe.contentWindow.postMessage(
+                {
+                    volume: bytes(model.get("volume")),
+                    cmap: bytes(model.get("cmap")),
+                    settings: model.get("settings"),
+                },
+                "*"
+            );
+            return;
+        }
+        if (typeof event.data !== "string") {
+            return;
+        }
+        const message = JSON.parse(event.data);
+        if (message.state !== undefined) {
+            received = JSON.stringify(message.state);
+            model.set("state", message.state);
+            model.save_changes();
+        }
+    };
+    const onState = () => {
+        const state = model.get("state");
+        if (state === null || JSON.stringify(state) === received) {
+            return;
+        }
+        iframe.contentWindow?.postMessage(JSON.stringify({ state: state }), "*");
+    };
+    window.addEventListener("message", onMessage);
+    model.on("change:state", onState);
+    return () => {
+        window.removeEventListener("message", onMessage);
+        model.off("change:state", onState);
+    };
+}
+export default { render };
+"""
+
+
+class ViewerWidget(anywidget.AnyWidget):
+    """interactive viewer embedded in a notebook cell. Unlike `viewer`, the camera, colormap,
+    opacity and time changed in the viewer are synced back to Python and can be set from Python.
+
+    The state is available once the widget is displayed.
+    """
+
+    _esm = _ESM
+    viewer_url = traitlets.Unicode(VAPE_URL).tag(sync=True)
+    width = traitlets.Int(800).tag(sync=True)
+    height = traitlets.Int(600).tag(sync=True)
+    # npy or npz files (base64)
+    volume = traitlets.Unicode().tag(sync=True)
+    cmap = traitlets.Unicode().tag(sync=True)
+    settings = traitlets.Dict().tag(sync=True)
+    # camera, colormap, opacity and time of the viewer
+    state = traitlets.Dict(allow_none=True, default_value=None).tag(sync=True)
+
+    def __init__(
+        self,
+        data: np.ndarray,
+        colormap: Optional[Union[str, Colormap]] = None,
+        **kwargs,
+    ):
+        """
+        Args:
+            data (Union[np.ndarray, xarray.DataArray]): volume data of shape [T,C, D, H, W], see `viewer`
+            colormap (Optional[Union[str, Colormap]], optional): colormap, see `viewer`. Defaults to matplotlib default colormap.
+            **kwargs: settings of the viewer (width, height, background_color, vmin, vmax, camera, options, spacing, affine, ...), see `viewer`
+        """
+        volume_viewer = viewer(data, colormap, **kwargs)
+        data = volume_viewer.data["volume"]
+        settings = volume_viewer.data["settings"]
+        colormap = volume_viewer.data["cmap"]
+
+        buffer2 = io.BytesIO()
+        np.save(buffer2, colormap(np.linspace(0, 1, 256)).astype(np.float32))
+        super().__init__(
+            width=settings.width,
+            height=settings.height,
+            volume=base64.b64encode(
+                volume_file(data, volume_viewer.data["spacing"])
+            ).decode("utf-8"),
+            cmap=base64.b64encode(buffer2.getvalue()).decode("utf-8"),
+            settings=dataclasses.asdict(settings),
+        )
+
+    def _state(self) -> dict:
+        if self.state is None:
+            raise RuntimeError("the viewer has not been displayed yet")
+        return self.state
+
+    def _update(self, **changes):
+        self.state = {**self._state(), **changes}
+
+    @property
+    def camera(self) -> dict:
+        """orthographic camera of the viewer like the camera of a saved session (position, rotation as quaternion (w, x, y, z), center and viewport)"""
+        return self._state()["camera"]
+
+    @camera.setter
+    def camera(self, camera: dict):
+        self._update(camera=dict(camera))
+
+    @property
+    def colormap(self) -> Colormap:
+        """colormap edited in the viewer"""
+        segments = self._state()["colormap"]
+        segmentdata = {
+            name: segments[key]
+            for name, key in [("red", "r"), ("green", "g"), ("blue", "b"), ("alpha", "a")]
+            if segments.get(key) is not None
+        }
+        return LinearSegmentedColormap("vape4d", segmentdata)
+
+    @colormap.setter
+    def colormap(self, colormap: Union[str, Colormap]):
+        colormap = to_colormap(colormap)
+        x = np.linspace(0, 1, 256)
+        colors = colormap(x)
+        self._update(
+            colormap={
+                key: [(float(x), float(c), float(c)) for x, c in zip(x, colors[:, i])]
+                for i, key in enumerate(["r", "g", "b", "a"])
+            }
+        )
+
+    @property
+    def opacity(self) -> list[tuple[float, float, float]]:
+        """opacity control points (x, y0, y1) with opacity y0 left and y1 right of x"""
+        return [tuple(p) for p in self._state()["opacity"]]
+
+    @opacity.setter
+    def opacity(self, points: list[tuple[float, ...]]):
+        """control points (x, opacity) or (x, y0, y1) with ascending x in [0, 1]"""
+        points = [(float(p[0]), float(p[1]), float(p[-1])) for p in points]
+        if len(points) < 2:
+            raise ValueError("opacity needs at least two control points")
+        self._update(opacity=points)
+
+    @property
+    def transfer_function(self) -> TransferFunction:
+        """colormap and opacity edited in the viewer"""
+        state = self._state()
+        return TransferFunction.from_dict({**state["colormap"], "a": state["opacity"]})
+
+    @transfer_function.setter
+    def transfer_function(self, tf: TransferFunction):
+        colormap = tf.to_dict()
+        opacity = colormap.pop("a")
+        self._update(colormap=colormap, opacity=opacity)
+
+    @property
+    def time(self) -> float:
+        """normalized time in [0, 1]"""
+        return self._state()["time"]
+
+    @time.setter
+    def time(self, time: float):
+        self._update(time=float(time))